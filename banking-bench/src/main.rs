@@ -369,6 +369,7 @@ fn main() {
             bank_forks.clone(),
             HashSet::default(),
             BundleAccountLocker::default(),
+            None,
         );
         poh_recorder.write().unwrap().set_bank(&bank, false);
 
@@ -300,6 +300,7 @@ fn bench_banking(bencher: &mut Bencher, tx_type: TransactionType) {
             bank_forks,
             HashSet::new(),
             BundleAccountLocker::default(),
+            None,
         );
         poh_recorder.write().unwrap().set_bank(&bank, false);
 
@@ -4,7 +4,7 @@
 
 use {
     crate::{
-        bundle_account_locker::BundleAccountLocker,
+        bundle_account_locker::{BundleAccountLocker, BundleAccountLocks},
         forward_packet_batches_by_accounts::ForwardPacketBatchesByAccounts,
         immutable_deserialized_packet::ImmutableDeserializedPacket,
         leader_slot_banking_stage_metrics::{LeaderSlotMetricsTracker, ProcessTransactionsSummary},
@@ -104,6 +104,8 @@ pub struct ProcessTransactionBatchOutput {
     cost_model_throttled_transactions_count: usize,
     // Amount of time spent running the cost model
     cost_model_us: u64,
+    // The number of transactions deferred because they conflict with accounts held by bundles
+    bundle_locked_transactions_count: usize,
     execute_and_commit_transactions_output: ExecuteAndCommitTransactionsOutput,
 }
 
@@ -406,6 +408,7 @@ impl BankingStage {
         bank_forks: Arc<RwLock<BankForks>>,
         blacklisted_accounts: HashSet<Pubkey>,
         bundle_account_locker: BundleAccountLocker,
+        relayer_priority_percent: Option<u64>,
     ) -> Self {
         Self::new_num_threads(
             cluster_info,
@@ -422,6 +425,7 @@ impl BankingStage {
             bank_forks,
             blacklisted_accounts,
             bundle_account_locker,
+            relayer_priority_percent,
         )
     }
 
@@ -441,6 +445,7 @@ impl BankingStage {
         bank_forks: Arc<RwLock<BankForks>>,
         blacklisted_accounts: HashSet<Pubkey>,
         bundle_account_locker: BundleAccountLocker,
+        relayer_priority_percent: Option<u64>,
     ) -> Self {
         assert!(num_threads >= MIN_TOTAL_THREADS);
         // Single thread to generate entries from many banks.
@@ -465,7 +470,8 @@ impl BankingStage {
                     _ => (verified_receiver.clone(), ForwardOption::ForwardTransaction),
                 };
 
-                let mut packet_deserializer = PacketDeserializer::new(verified_receiver);
+                let mut packet_deserializer =
+                    PacketDeserializer::new(verified_receiver, relayer_priority_percent);
                 let poh_recorder = poh_recorder.clone();
                 let cluster_info = cluster_info.clone();
                 let mut recv_start = Instant::now();
@@ -1767,16 +1773,34 @@ impl BankingStage {
         // same account state.
         let mut lock_time = Measure::start("lock_time");
 
-        let batch = {
+        let (batch, bundle_locked_transactions_count) = {
             // BundleStage locks ALL accounts in ALL transactions in a bundle to avoid race
-            // conditions with BankingStage
+            // conditions with BankingStage. Transactions using one of them are rejected before the
+            // bank locks anything, and the bundle locks are held until the bank has locked the
+            // rest so BundleStage can't take an account in between.
             let account_locks = bundle_account_locker.account_locks();
-            bank.prepare_sanitized_batch_with_results(
+            let mut bundle_locked_transactions_count = 0;
+            let lock_results: Vec<transaction::Result<()>> = txs
+                .iter()
+                .zip(transactions_qos_results.iter())
+                .map(|(tx, qos_result)| {
+                    qos_result.clone().and_then(|_| {
+                        if Self::is_bundle_locked(tx, &account_locks) {
+                            bundle_locked_transactions_count += 1;
+                            Err(TransactionError::AccountInUse)
+                        } else {
+                            Ok(())
+                        }
+                    })
+                })
+                .collect();
+            let batch = bank.prepare_sanitized_batch_with_results(
                 txs,
-                transactions_qos_results.iter(),
-                &account_locks.read_locks(),
-                &account_locks.write_locks(),
-            )
+                lock_results.iter(),
+                &HashSet::default(),
+                &HashSet::default(),
+            );
+            (batch, bundle_locked_transactions_count)
         };
         lock_time.stop();
 
@@ -1835,10 +1859,27 @@ impl BankingStage {
         ProcessTransactionBatchOutput {
             cost_model_throttled_transactions_count,
             cost_model_us: cost_model_time.as_us(),
+            bundle_locked_transactions_count,
             execute_and_commit_transactions_output,
         }
     }
 
+    /// Returns true if a bundle holds one of the transaction's accounts, in which case it can't be
+    /// locked. It's retried once the bundle executes or is dropped.
+    fn is_bundle_locked(
+        tx: &SanitizedTransaction,
+        bundle_account_locks: &BundleAccountLocks,
+    ) -> bool {
+        let account_locks = tx.get_account_locks_unchecked();
+        account_locks.writable.iter().any(|account| {
+            bundle_account_locks.is_write_locked(account)
+                || bundle_account_locks.is_read_locked(account)
+        }) || account_locks
+            .readonly
+            .iter()
+            .any(|account| bundle_account_locks.is_write_locked(account))
+    }
+
     // rollup transaction cost details, eg signature_cost, write_lock_cost, data_bytes_cost and
     // execution_cost from the batch of transactions selected for block.
     fn accumulate_batched_transaction_costs<'a>(
@@ -1964,6 +2005,7 @@ impl BankingStage {
         let mut total_failed_commit_count: usize = 0;
         let mut total_cost_model_throttled_transactions_count: usize = 0;
         let mut total_cost_model_us: u64 = 0;
+        let mut total_bundle_locked_transactions_count: usize = 0;
         let mut total_execute_and_commit_timings = LeaderExecuteAndCommitTimings::default();
         let mut total_error_counters = TransactionErrorMetrics::default();
         let mut reached_max_poh_height = false;
@@ -1987,12 +2029,15 @@ impl BankingStage {
             let ProcessTransactionBatchOutput {
                 cost_model_throttled_transactions_count: new_cost_model_throttled_transactions_count,
                 cost_model_us: new_cost_model_us,
+                bundle_locked_transactions_count: new_bundle_locked_transactions_count,
                 execute_and_commit_transactions_output,
             } = process_transaction_batch_output;
             total_cost_model_throttled_transactions_count =
                 total_cost_model_throttled_transactions_count
                     .saturating_add(new_cost_model_throttled_transactions_count);
             total_cost_model_us = total_cost_model_us.saturating_add(new_cost_model_us);
+            total_bundle_locked_transactions_count = total_bundle_locked_transactions_count
+                .saturating_add(new_bundle_locked_transactions_count);
 
             let ExecuteAndCommitTransactionsOutput {
                 transactions_attempted_execution_count: new_transactions_attempted_execution_count,
@@ -2068,6 +2113,7 @@ impl BankingStage {
             retryable_transaction_indexes: all_retryable_tx_indexes,
             cost_model_throttled_transactions_count: total_cost_model_throttled_transactions_count,
             cost_model_us: total_cost_model_us,
+            bundle_locked_transactions_count: total_bundle_locked_transactions_count,
             execute_and_commit_timings: total_execute_and_commit_timings,
             error_counters: total_error_counters,
         }
@@ -2464,6 +2510,7 @@ mod tests {
         solana_runtime::bank_forks::BankForks,
         solana_sdk::{
             account::AccountSharedData,
+            bundle::sanitized::SanitizedBundle,
             hash::Hash,
             instruction::InstructionError,
             message::{
@@ -2532,6 +2579,7 @@ mod tests {
                 bank_forks,
                 HashSet::default(),
                 bundle_locker,
+                None,
             );
             drop(verified_sender);
             drop(gossip_verified_vote_sender);
@@ -2591,6 +2639,7 @@ mod tests {
                 bank_forks,
                 HashSet::default(),
                 bundle_locker,
+                None,
             );
             trace!("sending bank");
             drop(verified_sender);
@@ -2679,6 +2728,7 @@ mod tests {
                 bank_forks,
                 HashSet::default(),
                 bundle_locker,
+                None,
             );
 
             // fund another account so we can send 2 good transactions in a single batch.
@@ -2846,6 +2896,7 @@ mod tests {
                     bank_forks,
                     HashSet::default(),
                     bundle_locker,
+                    None,
                 );
 
                 // wait for banking_stage to eat the packets
@@ -3262,6 +3313,144 @@ mod tests {
         Blockstore::destroy(ledger_path.path()).unwrap();
     }
 
+    #[test]
+    fn test_bank_process_and_record_transactions_bundle_locked() {
+        solana_logger::setup();
+
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_slow_genesis_config(10_000);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+        let pubkey = solana_sdk::pubkey::new_rand();
+
+        let transactions = sanitize_transactions(vec![system_transaction::transfer(
+            &mint_keypair,
+            &pubkey,
+            1,
+            genesis_config.hash(),
+        )]);
+        let sanitized_bundle = SanitizedBundle {
+            transactions: sanitize_transactions(vec![system_transaction::transfer(
+                &mint_keypair,
+                &solana_sdk::pubkey::new_rand(),
+                1,
+                genesis_config.hash(),
+            )]),
+            expiry_slot: Slot::MAX,
+        };
+
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        {
+            let blockstore = Arc::new(
+                Blockstore::open(ledger_path.path())
+                    .expect("Expected to be able to open database ledger"),
+            );
+            let (exit, poh_recorder, poh_service, _entry_receiver) =
+                create_test_recorder(&bank, &blockstore, None, None);
+            let recorder = poh_recorder.read().unwrap().recorder();
+            let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+            let qos_service = QosService::new(Arc::new(RwLock::new(CostModel::default())), 1);
+            let bundle_locker = BundleAccountLocker::default();
+
+            // the bundle holding the mint account defers the transaction instead of failing it
+            let reservation = bundle_locker
+                .reserve_bundle_accounts(&sanitized_bundle, &bank)
+                .unwrap();
+            let process_transactions_batch_output = BankingStage::process_and_record_transactions(
+                &bank,
+                &transactions,
+                &recorder,
+                0,
+                None,
+                &gossip_vote_sender,
+                &qos_service,
+                None,
+                &bundle_locker,
+            );
+            assert_eq!(
+                process_transactions_batch_output.bundle_locked_transactions_count,
+                1
+            );
+            let ExecuteAndCommitTransactionsOutput {
+                executed_transactions_count,
+                retryable_transaction_indexes,
+                ..
+            } = process_transactions_batch_output.execute_and_commit_transactions_output;
+            assert_eq!(executed_transactions_count, 0);
+            assert_eq!(retryable_transaction_indexes, vec![0]);
+
+            drop(reservation);
+
+            // the mint account being in use by another batch isn't counted against bundles, even
+            // while a bundle holds other accounts
+            let other_bundle = SanitizedBundle {
+                transactions: sanitize_transactions(vec![system_transaction::transfer(
+                    &Keypair::new(),
+                    &solana_sdk::pubkey::new_rand(),
+                    1,
+                    genesis_config.hash(),
+                )]),
+                expiry_slot: Slot::MAX,
+            };
+            let other_reservation = bundle_locker
+                .reserve_bundle_accounts(&other_bundle, &bank)
+                .unwrap();
+            let other_batch = bank.prepare_sanitized_batch(&sanitized_bundle.transactions);
+            let process_transactions_batch_output = BankingStage::process_and_record_transactions(
+                &bank,
+                &transactions,
+                &recorder,
+                0,
+                None,
+                &gossip_vote_sender,
+                &qos_service,
+                None,
+                &bundle_locker,
+            );
+            assert_eq!(
+                process_transactions_batch_output.bundle_locked_transactions_count,
+                0
+            );
+            assert_eq!(
+                process_transactions_batch_output
+                    .execute_and_commit_transactions_output
+                    .retryable_transaction_indexes,
+                vec![0]
+            );
+            drop(other_batch);
+            drop(other_reservation);
+
+            let process_transactions_batch_output = BankingStage::process_and_record_transactions(
+                &bank,
+                &transactions,
+                &recorder,
+                0,
+                None,
+                &gossip_vote_sender,
+                &qos_service,
+                None,
+                &bundle_locker,
+            );
+            assert_eq!(
+                process_transactions_batch_output.bundle_locked_transactions_count,
+                0
+            );
+            assert_eq!(
+                process_transactions_batch_output
+                    .execute_and_commit_transactions_output
+                    .executed_transactions_count,
+                1
+            );
+            assert_eq!(bank.get_balance(&pubkey), 1);
+
+            exit.store(true, Ordering::Relaxed);
+            poh_service.join().unwrap();
+        }
+        Blockstore::destroy(ledger_path.path()).unwrap();
+    }
+
     #[test]
     fn test_bank_process_and_record_transactions_all_unexecuted() {
         solana_logger::setup();
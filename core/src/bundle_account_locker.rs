@@ -9,13 +9,27 @@
 /// and commit the results before the bundle completes. By the time the bundle commits the new account
 /// state for {A, B, C}, A and B would be incorrect and the entries containing the bundle would be
 /// replayed improperly and that leader would have produced an invalid block.
+///
+/// Accounts are reserved from the moment a bundle is admitted to BundleStage's queue while the
+/// validator has a working bank, so BankingStage can't change the state the bundle is simulated
+/// against before it's locked for execution. The reservation is released once the bundle is
+/// locked, or when it's dropped, evicted or expires. Bundles admitted outside of a leader slot are
+/// reserved when the execution pass sanitizes them instead.
+///
+/// The locks a bundle took are remembered by its LockedBundle and released when it's dropped,
+/// whether the bundle executed, failed or BundleStage unwound from a panic. A poisoned mutex is
+/// recovered from rather than propagated; the lock counts are only changed through plain map
+/// updates so a panic elsewhere can't leave them half-updated.
 use {
     solana_runtime::bank::Bank,
     solana_sdk::{
-        bundle::sanitized::SanitizedBundle, pubkey::Pubkey, transaction::TransactionAccountLocks,
+        bundle::sanitized::SanitizedBundle,
+        pubkey::Pubkey,
+        transaction::{SanitizedTransaction, TransactionAccountLocks},
     },
     std::collections::{hash_map::Entry, HashMap, HashSet},
-    std::sync::{Arc, Mutex, MutexGuard},
+    std::sync::{Arc, Mutex, MutexGuard, PoisonError},
+    std::time::{Duration, Instant},
 };
 
 #[derive(Debug)]
@@ -28,33 +42,75 @@ pub type BundleAccountLockerResult<T> = Result<T, BundleAccountLockerError>;
 pub struct LockedBundle<'a, 'b> {
     bundle_account_locker: &'a BundleAccountLocker,
     sanitized_bundle: &'b SanitizedBundle,
-    bank: Arc<Bank>,
+    // the locks taken for this bundle, released on drop
+    read_locks: HashMap<Pubkey, u64>,
+    write_locks: HashMap<Pubkey, u64>,
 }
 
 impl<'a, 'b> LockedBundle<'a, 'b> {
-    pub fn new(
+    fn new(
         bundle_account_locker: &'a BundleAccountLocker,
         sanitized_bundle: &'b SanitizedBundle,
-        bank: &Arc<Bank>,
+        read_locks: HashMap<Pubkey, u64>,
+        write_locks: HashMap<Pubkey, u64>,
     ) -> Self {
         Self {
             bundle_account_locker,
             sanitized_bundle,
-            bank: bank.clone(),
+            read_locks,
+            write_locks,
         }
     }
 
     pub fn sanitized_bundle(&self) -> &SanitizedBundle {
         self.sanitized_bundle
     }
+
+    /// Returns true if either bundle writes an account the other one reads or writes, in which
+    /// case they can't be executed at the same time.
+    pub fn conflicts_with(&self, other: &LockedBundle) -> bool {
+        self.write_locks.keys().any(|account| {
+            other.write_locks.contains_key(account) || other.read_locks.contains_key(account)
+        }) || other
+            .write_locks
+            .keys()
+            .any(|account| self.read_locks.contains_key(account))
+    }
 }
 
 // Automatically unlock bundle accounts when destructed
 impl<'a, 'b> Drop for LockedBundle<'a, 'b> {
     fn drop(&mut self) {
-        let _ = self
-            .bundle_account_locker
-            .unlock_bundle_accounts(self.sanitized_bundle, &self.bank);
+        self.bundle_account_locker.unlock_bundle_accounts(
+            std::mem::take(&mut self.read_locks),
+            std::mem::take(&mut self.write_locks),
+        );
+    }
+}
+
+/// Accounts held for a bundle from its admission until it's locked for execution. Unlike
+/// LockedBundle, the reservation doesn't borrow the bundle or the locker so it can be queued
+/// along with the bundle.
+pub struct BundleAccountReservation {
+    bundle_account_locker: BundleAccountLocker,
+    read_locks: HashMap<Pubkey, u64>,
+    write_locks: HashMap<Pubkey, u64>,
+    reserved_at: Instant,
+}
+
+impl BundleAccountReservation {
+    /// How long the accounts have been reserved.
+    pub fn held_for(&self) -> Duration {
+        self.reserved_at.elapsed()
+    }
+}
+
+impl Drop for BundleAccountReservation {
+    fn drop(&mut self) {
+        self.bundle_account_locker.unlock_bundle_accounts(
+            std::mem::take(&mut self.read_locks),
+            std::mem::take(&mut self.write_locks),
+        );
     }
 }
 
@@ -73,6 +129,14 @@ impl BundleAccountLocks {
         self.write_locks.keys().cloned().collect()
     }
 
+    pub fn is_read_locked(&self, account: &Pubkey) -> bool {
+        self.read_locks.contains_key(account)
+    }
+
+    pub fn is_write_locked(&self, account: &Pubkey) -> bool {
+        self.write_locks.contains_key(account)
+    }
+
     pub fn lock_accounts(
         &mut self,
         read_locks: HashMap<Pubkey, u64>,
@@ -125,19 +189,21 @@ impl BundleAccountLocker {
     /// used in BankingStage during TransactionBatch construction to ensure that BankingStage
     /// doesn't lock anything currently locked in the BundleAccountLocker
     pub fn read_locks(&self) -> HashSet<Pubkey> {
-        self.account_locks.lock().unwrap().read_locks()
+        self.account_locks().read_locks()
     }
 
     /// used in BankingStage during TransactionBatch construction to ensure that BankingStage
     /// doesn't lock anything currently locked in the BundleAccountLocker
     pub fn write_locks(&self) -> HashSet<Pubkey> {
-        self.account_locks.lock().unwrap().write_locks()
+        self.account_locks().write_locks()
     }
 
     /// used in BankingStage during TransactionBatch construction to ensure that BankingStage
     /// doesn't lock anything currently locked in the BundleAccountLocker
     pub fn account_locks(&self) -> MutexGuard<BundleAccountLocks> {
-        self.account_locks.lock().unwrap()
+        self.account_locks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
     }
 
     /// Prepares a locked bundle and returns a LockedBundle containing locked accounts.
@@ -147,38 +213,65 @@ impl BundleAccountLocker {
         sanitized_bundle: &'b SanitizedBundle,
         bank: &Arc<Bank>,
     ) -> BundleAccountLockerResult<LockedBundle<'a, 'b>> {
-        let (read_locks, write_locks) = Self::get_read_write_locks(sanitized_bundle, bank)?;
+        let (read_locks, write_locks) =
+            Self::get_read_write_locks(&sanitized_bundle.transactions, bank)?;
 
-        self.account_locks
-            .lock()
-            .unwrap()
-            .lock_accounts(read_locks, write_locks);
-        Ok(LockedBundle::new(self, sanitized_bundle, bank))
+        self.account_locks()
+            .lock_accounts(read_locks.clone(), write_locks.clone());
+        Ok(LockedBundle::new(
+            self,
+            sanitized_bundle,
+            read_locks,
+            write_locks,
+        ))
     }
 
-    /// Unlocks bundle accounts. Note that LockedBundle::drop will auto-drop the bundle account locks
-    fn unlock_bundle_accounts(
+    /// Reserves the bundle's accounts so BankingStage can't lock them until the returned
+    /// reservation is dropped.
+    pub fn reserve_bundle_accounts(
         &self,
         sanitized_bundle: &SanitizedBundle,
         bank: &Bank,
-    ) -> BundleAccountLockerResult<()> {
-        let (read_locks, write_locks) = Self::get_read_write_locks(sanitized_bundle, bank)?;
+    ) -> BundleAccountLockerResult<BundleAccountReservation> {
+        self.reserve_transaction_accounts(&sanitized_bundle.transactions, bank)
+    }
 
-        self.account_locks
-            .lock()
-            .unwrap()
+    /// Reserves the accounts of a bundle's transactions before the bundle is sanitized, see
+    /// [Self::reserve_bundle_accounts].
+    pub fn reserve_transaction_accounts(
+        &self,
+        transactions: &[SanitizedTransaction],
+        bank: &Bank,
+    ) -> BundleAccountLockerResult<BundleAccountReservation> {
+        let (read_locks, write_locks) = Self::get_read_write_locks(transactions, bank)?;
+
+        self.account_locks()
+            .lock_accounts(read_locks.clone(), write_locks.clone());
+        Ok(BundleAccountReservation {
+            bundle_account_locker: self.clone(),
+            read_locks,
+            write_locks,
+            reserved_at: Instant::now(),
+        })
+    }
+
+    /// Unlocks bundle accounts. Note that LockedBundle::drop will auto-drop the bundle account locks
+    fn unlock_bundle_accounts(
+        &self,
+        read_locks: HashMap<Pubkey, u64>,
+        write_locks: HashMap<Pubkey, u64>,
+    ) {
+        self.account_locks()
             .unlock_accounts(read_locks, write_locks);
-        Ok(())
     }
 
-    /// Returns the read and write locks for this bundle
+    /// Returns the read and write locks for this bundle's transactions
     /// Each lock type contains a HashMap which maps Pubkey to number of locks held
     fn get_read_write_locks(
-        bundle: &SanitizedBundle,
+        transactions: &[SanitizedTransaction],
         bank: &Bank,
     ) -> BundleAccountLockerResult<(HashMap<Pubkey, u64>, HashMap<Pubkey, u64>)> {
-        let transaction_locks: Vec<TransactionAccountLocks> = bundle
-            .transactions
+        let transaction_locks: Vec<TransactionAccountLocks> = transactions
             .iter()
             .filter_map(|tx| {
                 tx.get_account_locks(bank.get_transaction_account_lock_limit())
@@ -186,7 +279,7 @@ impl BundleAccountLocker {
             })
             .collect();
 
-        if transaction_locks.len() != bundle.transactions.len() {
+        if transaction_locks.len() != transactions.len() {
             return Err(BundleAccountLockerError::LockingError);
         }
 
@@ -220,7 +313,8 @@ impl BundleAccountLocker {
 mod tests {
     use {
         crate::{
-            bundle_account_locker::BundleAccountLocker, bundle_sanitizer::get_sanitized_bundle,
+            bundle_account_locker::BundleAccountLocker,
+            bundle_sanitizer::{get_sanitized_bundle, BundleLimits},
             packet_bundle::PacketBundle,
         },
         solana_ledger::genesis_utils::create_genesis_config,
@@ -230,13 +324,44 @@ mod tests {
             transaction_error_metrics::TransactionErrorMetrics,
         },
         solana_sdk::{
-            packet::Packet, signature::Signer, signer::keypair::Keypair, system_program,
-            system_transaction::transfer, transaction::VersionedTransaction,
+            bundle::sanitized::SanitizedBundle,
+            clock::Slot,
+            packet::Packet,
+            signature::Signer,
+            signer::keypair::Keypair,
+            system_program,
+            system_transaction::transfer,
+            transaction::{
+                SanitizedTransaction, Transaction, TransactionError, VersionedTransaction,
+            },
         },
-        std::{collections::HashSet, sync::Arc},
+        std::{collections::HashSet, sync::Arc, thread, time::Instant},
         uuid::Uuid,
     };
 
+    fn new_sanitized_bundle(bank: &Bank, transactions: &[Transaction]) -> SanitizedBundle {
+        let packet_bundle = PacketBundle {
+            batch: PacketBatch::new(
+                transactions
+                    .iter()
+                    .map(|tx| Packet::from_data(None, tx).unwrap())
+                    .collect(),
+            ),
+            uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
+        };
+        get_sanitized_bundle(
+            &packet_bundle,
+            bank,
+            &HashSet::default(),
+            &HashSet::default(),
+            &BundleLimits::default(),
+            &mut TransactionErrorMetrics::default(),
+        )
+        .unwrap()
+    }
+
     #[test]
     fn test_simple_lock_bundles() {
         let GenesisConfigInfo {
@@ -267,10 +392,14 @@ mod tests {
         let packet_bundle0 = PacketBundle {
             batch: PacketBatch::new(vec![Packet::from_data(None, &tx0).unwrap()]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
         let packet_bundle1 = PacketBundle {
             batch: PacketBatch::new(vec![Packet::from_data(None, &tx1).unwrap()]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
 
         let mut transaction_errors = TransactionErrorMetrics::default();
@@ -280,6 +409,7 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::default(),
+            &BundleLimits::default(),
             &mut transaction_errors,
         )
         .expect("sanitize bundle 0");
@@ -288,6 +418,7 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::default(),
+            &BundleLimits::default(),
             &mut transaction_errors,
         )
         .expect("sanitize bundle 1");
@@ -331,4 +462,209 @@ mod tests {
         assert!(bundle_account_locker.write_locks().is_empty());
         assert!(bundle_account_locker.read_locks().is_empty());
     }
+
+    #[test]
+    fn test_banking_batch_skips_bundle_locked_accounts() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(10);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+        let bundle_account_locker = BundleAccountLocker::default();
+
+        let bundle_recipient = Keypair::new();
+        let sanitized_bundle = new_sanitized_bundle(
+            &bank,
+            &[transfer(
+                &mint_keypair,
+                &bundle_recipient.pubkey(),
+                1,
+                genesis_config.hash(),
+            )],
+        );
+
+        // one transaction writes an account the bundle writes, the other touches neither
+        let banking_txs = [
+            transfer(
+                &bundle_recipient,
+                &Keypair::new().pubkey(),
+                1,
+                genesis_config.hash(),
+            ),
+            transfer(
+                &Keypair::new(),
+                &Keypair::new().pubkey(),
+                1,
+                genesis_config.hash(),
+            ),
+        ]
+        .map(SanitizedTransaction::from_transaction_for_tests);
+        let prepare_banking_batch = || {
+            let account_locks = bundle_account_locker.account_locks();
+            bank.prepare_sanitized_batch_with_results(
+                &banking_txs,
+                [Ok(()), Ok(())].iter(),
+                &account_locks.read_locks(),
+                &account_locks.write_locks(),
+            )
+            .lock_results()
+            .clone()
+        };
+
+        let locked_bundle = bundle_account_locker
+            .prepare_locked_bundle(&sanitized_bundle, &bank)
+            .unwrap();
+        assert_eq!(
+            prepare_banking_batch(),
+            vec![Err(TransactionError::AccountInUse), Ok(())]
+        );
+
+        // released once the bundle is done with, whether it executed or failed
+        drop(locked_bundle);
+        assert_eq!(prepare_banking_batch(), vec![Ok(()), Ok(())]);
+    }
+
+    #[test]
+    fn test_bundle_account_locker_recovers_from_poison() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(10);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+        let bundle_account_locker = BundleAccountLocker::default();
+        let sanitized_bundle = new_sanitized_bundle(
+            &bank,
+            &[transfer(
+                &mint_keypair,
+                &Keypair::new().pubkey(),
+                1,
+                genesis_config.hash(),
+            )],
+        );
+
+        let locker = bundle_account_locker.clone();
+        assert!(thread::spawn(move || {
+            let _account_locks = locker.account_locks();
+            panic!("poison the bundle account locks");
+        })
+        .join()
+        .is_err());
+
+        let locked_bundle = bundle_account_locker
+            .prepare_locked_bundle(&sanitized_bundle, &bank)
+            .unwrap();
+        assert!(bundle_account_locker
+            .write_locks()
+            .contains(&mint_keypair.pubkey()));
+        drop(locked_bundle);
+        assert!(bundle_account_locker.write_locks().is_empty());
+        assert!(bundle_account_locker.read_locks().is_empty());
+    }
+
+    #[test]
+    fn test_locked_bundles_conflict() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(10);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+        let bundle_account_locker = BundleAccountLocker::default();
+
+        let payer = Keypair::new();
+        let recipient = Keypair::new();
+        let bundles = [
+            new_sanitized_bundle(
+                &bank,
+                &[transfer(
+                    &mint_keypair,
+                    &recipient.pubkey(),
+                    1,
+                    genesis_config.hash(),
+                )],
+            ),
+            // only shares the read locked system program with the first bundle
+            new_sanitized_bundle(
+                &bank,
+                &[transfer(
+                    &payer,
+                    &Keypair::new().pubkey(),
+                    1,
+                    genesis_config.hash(),
+                )],
+            ),
+            // writes the account the first bundle transfers to
+            new_sanitized_bundle(
+                &bank,
+                &[transfer(
+                    &recipient,
+                    &Keypair::new().pubkey(),
+                    1,
+                    genesis_config.hash(),
+                )],
+            ),
+        ];
+        let locked_bundles: Vec<_> = bundles
+            .iter()
+            .map(|bundle| {
+                bundle_account_locker
+                    .prepare_locked_bundle(bundle, &bank)
+                    .unwrap()
+            })
+            .collect();
+
+        assert!(!locked_bundles[0].conflicts_with(&locked_bundles[1]));
+        assert!(!locked_bundles[1].conflicts_with(&locked_bundles[2]));
+        assert!(locked_bundles[0].conflicts_with(&locked_bundles[2]));
+        assert!(locked_bundles[2].conflicts_with(&locked_bundles[0]));
+    }
+
+    #[test]
+    fn test_bundle_account_reservation_handed_to_locked_bundle() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(10);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+        let bundle_account_locker = BundleAccountLocker::default();
+        let recipient = Keypair::new().pubkey();
+        let sanitized_bundle = new_sanitized_bundle(
+            &bank,
+            &[transfer(
+                &mint_keypair,
+                &recipient,
+                1,
+                genesis_config.hash(),
+            )],
+        );
+
+        let reservation = bundle_account_locker
+            .reserve_bundle_accounts(&sanitized_bundle, &bank)
+            .unwrap();
+        assert_eq!(
+            bundle_account_locker.write_locks(),
+            HashSet::from_iter([mint_keypair.pubkey(), recipient])
+        );
+        assert_eq!(
+            bundle_account_locker.read_locks(),
+            HashSet::from_iter([system_program::id()])
+        );
+
+        // the accounts stay locked while the reservation is swapped for the execution locks
+        let locked_bundle = bundle_account_locker
+            .prepare_locked_bundle(&sanitized_bundle, &bank)
+            .unwrap();
+        drop(reservation);
+        assert_eq!(
+            bundle_account_locker.write_locks(),
+            HashSet::from_iter([mint_keypair.pubkey(), recipient])
+        );
+
+        drop(locked_bundle);
+        assert!(bundle_account_locker.write_locks().is_empty());
+        assert!(bundle_account_locker.read_locks().is_empty());
+    }
 }
@@ -0,0 +1,414 @@
+//! Operator policy for refusing bundles.
+//!
+//! Bundles that write to or read any denied account, or invoke any denied program, are dropped
+//! by BundleStage before they're simulated or locked. Accounts are matched against each
+//! transaction's static keys and the keys it loads from address lookup tables, so a denied
+//! account can't be reached by moving it into a lookup table.
+//!
+//! Bundles declaring a smaller tip than `min_tip_lamports` are dropped at the same point. The
+//! declared tip is the one bundles are prioritized by, what's actually paid is checked by the
+//! TipManager once the bundle has executed.
+//!
+//! Separately from the operator's filter, bundles may never write to the validator's identity or
+//! vote account, or optionally the stake accounts delegated to it, since a bundle executed while
+//! we're leader could otherwise deauthorize or redirect them. Reading them is still allowed.
+
+use {
+    solana_runtime::bank::Bank,
+    solana_sdk::{bundle::sanitized::SanitizedBundle, pubkey::Pubkey},
+    std::collections::HashSet,
+};
+
+/// Accounts and programs bundles aren't allowed to touch, and the smallest tip they may declare.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BundleFilter {
+    pub denied_accounts: HashSet<Pubkey>,
+    pub denied_programs: HashSet<Pubkey>,
+    pub min_tip_lamports: u64,
+}
+
+/// Why a bundle was refused by the filter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BundleFilterRejection {
+    DeniedAccount(Pubkey),
+    DeniedProgram(Pubkey),
+    /// The bundle's declared tip, which is below the minimum.
+    TipTooLow(u64),
+    ProtectedAccountWrite(Pubkey),
+}
+
+/// Accounts the operator adds to the ones bundles may never write.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProtectedAccountsConfig {
+    pub extra_accounts: HashSet<Pubkey>,
+    /// Also protect the stake accounts delegated to the validator's vote account when BundleStage
+    /// starts.
+    pub protect_delegated_stake_accounts: bool,
+}
+
+/// Accounts bundles may never write, whatever the operator's bundle filter allows.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProtectedAccounts {
+    accounts: HashSet<Pubkey>,
+}
+
+impl ProtectedAccounts {
+    pub fn new(
+        identity: Pubkey,
+        vote_account: Pubkey,
+        config: &ProtectedAccountsConfig,
+        bank: &Bank,
+    ) -> Self {
+        let mut accounts = HashSet::from([identity, vote_account]);
+        accounts.extend(config.extra_accounts.iter().copied());
+        if config.protect_delegated_stake_accounts {
+            accounts.extend(
+                bank.stakes_cache
+                    .stakes()
+                    .stake_delegations()
+                    .iter()
+                    .filter(|(_, stake_account)| {
+                        stake_account.delegation().voter_pubkey == vote_account
+                    })
+                    .map(|(stake_pubkey, _)| *stake_pubkey),
+            );
+        }
+        Self { accounts }
+    }
+
+    pub fn contains(&self, account: &Pubkey) -> bool {
+        self.accounts.contains(account)
+    }
+
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// Returns the first protected account one of the bundle's transactions writes to. Programs
+    /// can only write the accounts a transaction passes them as writable, so the transactions'
+    /// writable keys cover the accounts reachable through CPI.
+    pub fn check(&self, bundle: &SanitizedBundle) -> Result<(), BundleFilterRejection> {
+        if self.accounts.is_empty() {
+            return Ok(());
+        }
+        for transaction in &bundle.transactions {
+            let message = transaction.message();
+            if let Some(account) = message
+                .account_keys()
+                .iter()
+                .enumerate()
+                .find(|(index, account)| {
+                    self.accounts.contains(account) && message.is_writable(*index)
+                })
+                .map(|(_, account)| account)
+            {
+                return Err(BundleFilterRejection::ProtectedAccountWrite(*account));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BundleFilter {
+    pub fn is_empty(&self) -> bool {
+        self.denied_accounts.is_empty()
+            && self.denied_programs.is_empty()
+            && self.min_tip_lamports == 0
+    }
+
+    /// Checks the tip a bundle declares against the minimum.
+    pub fn check_tip(&self, tip_lamports: u64) -> Result<(), BundleFilterRejection> {
+        if tip_lamports < self.min_tip_lamports {
+            return Err(BundleFilterRejection::TipTooLow(tip_lamports));
+        }
+        Ok(())
+    }
+
+    /// Returns the first denied program or account used by the bundle. Programs are checked
+    /// first since a program id is also one of the transaction's account keys.
+    pub fn check(&self, bundle: &SanitizedBundle) -> Result<(), BundleFilterRejection> {
+        if self.denied_accounts.is_empty() && self.denied_programs.is_empty() {
+            return Ok(());
+        }
+        for transaction in &bundle.transactions {
+            let message = transaction.message();
+            if let Some((program_id, _)) = message
+                .program_instructions_iter()
+                .find(|(program_id, _)| self.denied_programs.contains(program_id))
+            {
+                return Err(BundleFilterRejection::DeniedProgram(*program_id));
+            }
+            if let Some(account) = message
+                .account_keys()
+                .iter()
+                .find(|account| self.denied_accounts.contains(account))
+            {
+                return Err(BundleFilterRejection::DeniedAccount(*account));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_runtime::genesis_utils::{
+            create_genesis_config, create_genesis_config_with_vote_accounts, GenesisConfigInfo,
+            ValidatorVoteKeypairs,
+        },
+        solana_sdk::{
+            address_lookup_table_account::AddressLookupTableAccount,
+            hash::Hash,
+            instruction::{AccountMeta, Instruction},
+            message::{
+                v0::{self, LoadedAddresses},
+                SimpleAddressLoader, VersionedMessage,
+            },
+            signature::{Keypair, Signer},
+            system_transaction::transfer,
+            transaction::{MessageHash, SanitizedTransaction, Transaction, VersionedTransaction},
+        },
+    };
+
+    fn new_bundle(transactions: Vec<SanitizedTransaction>) -> SanitizedBundle {
+        SanitizedBundle {
+            transactions,
+            expiry_slot: 0,
+        }
+    }
+
+    fn new_transfer(to: &Pubkey) -> SanitizedTransaction {
+        SanitizedTransaction::from_transaction_for_tests(transfer(
+            &Keypair::new(),
+            to,
+            1,
+            Hash::default(),
+        ))
+    }
+
+    #[test]
+    fn test_bundle_filter_denied_account() {
+        let denied_account = Pubkey::new_unique();
+        let filter = BundleFilter {
+            denied_accounts: HashSet::from([denied_account]),
+            ..BundleFilter::default()
+        };
+        let bundle = new_bundle(vec![
+            new_transfer(&Pubkey::new_unique()),
+            new_transfer(&denied_account),
+        ]);
+        assert_eq!(
+            filter.check(&bundle),
+            Err(BundleFilterRejection::DeniedAccount(denied_account))
+        );
+    }
+
+    #[test]
+    fn test_bundle_filter_denied_program() {
+        let filter = BundleFilter {
+            denied_programs: HashSet::from([solana_sdk::system_program::id()]),
+            ..BundleFilter::default()
+        };
+        let bundle = new_bundle(vec![new_transfer(&Pubkey::new_unique())]);
+        assert_eq!(
+            filter.check(&bundle),
+            Err(BundleFilterRejection::DeniedProgram(
+                solana_sdk::system_program::id()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_bundle_filter_denied_lookup_table_account() {
+        let payer = Keypair::new();
+        let denied_account = Pubkey::new_unique();
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![denied_account],
+        };
+        let instruction = Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[],
+            vec![AccountMeta::new(denied_account, false)],
+        );
+        let message = v0::Message::try_compile(
+            &payer.pubkey(),
+            &[instruction],
+            &[lookup_table],
+            Hash::default(),
+        )
+        .unwrap();
+        // the denied account is only reachable through the lookup table
+        assert!(!message.account_keys.contains(&denied_account));
+
+        let transaction = SanitizedTransaction::try_create(
+            VersionedTransaction::try_new(VersionedMessage::V0(message), &[&payer]).unwrap(),
+            MessageHash::Compute,
+            Some(false),
+            SimpleAddressLoader::Enabled(LoadedAddresses {
+                writable: vec![denied_account],
+                readonly: vec![],
+            }),
+            true,
+        )
+        .unwrap();
+
+        let filter = BundleFilter {
+            denied_accounts: HashSet::from([denied_account]),
+            ..BundleFilter::default()
+        };
+        assert_eq!(
+            filter.check(&new_bundle(vec![transaction])),
+            Err(BundleFilterRejection::DeniedAccount(denied_account))
+        );
+    }
+
+    #[test]
+    fn test_bundle_filter_min_tip() {
+        let filter = BundleFilter {
+            min_tip_lamports: 1_000,
+            ..BundleFilter::default()
+        };
+        assert!(!filter.is_empty());
+        assert_eq!(filter.check_tip(1_000), Ok(()));
+        assert_eq!(filter.check_tip(1_001), Ok(()));
+        assert_eq!(
+            filter.check_tip(999),
+            Err(BundleFilterRejection::TipTooLow(999))
+        );
+        assert_eq!(BundleFilter::default().check_tip(0), Ok(()));
+    }
+
+    fn new_protected_accounts(
+        identity: Pubkey,
+        vote_account: Pubkey,
+        extra_accounts: &[Pubkey],
+    ) -> ProtectedAccounts {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(1_000_000);
+        ProtectedAccounts::new(
+            identity,
+            vote_account,
+            &ProtectedAccountsConfig {
+                extra_accounts: extra_accounts.iter().copied().collect(),
+                protect_delegated_stake_accounts: false,
+            },
+            &Bank::new_for_tests(&genesis_config),
+        )
+    }
+
+    fn new_instruction_transaction(account_meta: AccountMeta) -> SanitizedTransaction {
+        let payer = Keypair::new();
+        let instruction =
+            Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![account_meta]);
+        SanitizedTransaction::from_transaction_for_tests(Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        ))
+    }
+
+    #[test]
+    fn test_protected_accounts_direct_write() {
+        let identity = Pubkey::new_unique();
+        let vote_account = Pubkey::new_unique();
+        let extra_account = Pubkey::new_unique();
+        let protected_accounts = new_protected_accounts(identity, vote_account, &[extra_account]);
+        assert_eq!(protected_accounts.len(), 3);
+
+        for account in [identity, vote_account, extra_account] {
+            let bundle = new_bundle(vec![
+                new_transfer(&Pubkey::new_unique()),
+                new_transfer(&account),
+            ]);
+            assert_eq!(
+                protected_accounts.check(&bundle),
+                Err(BundleFilterRejection::ProtectedAccountWrite(account))
+            );
+        }
+        assert_eq!(
+            protected_accounts.check(&new_bundle(vec![new_transfer(&Pubkey::new_unique())])),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_protected_accounts_program_write() {
+        let vote_account = Pubkey::new_unique();
+        let protected_accounts = new_protected_accounts(Pubkey::new_unique(), vote_account, &[]);
+
+        // any program passed the vote account as writable could write it or CPI into the vote
+        // program with it
+        let bundle = new_bundle(vec![new_instruction_transaction(AccountMeta::new(
+            vote_account,
+            false,
+        ))]);
+        assert_eq!(
+            protected_accounts.check(&bundle),
+            Err(BundleFilterRejection::ProtectedAccountWrite(vote_account))
+        );
+
+        // reading it is fine
+        let bundle = new_bundle(vec![new_instruction_transaction(
+            AccountMeta::new_readonly(vote_account, false),
+        )]);
+        assert_eq!(protected_accounts.check(&bundle), Ok(()));
+    }
+
+    #[test]
+    fn test_protected_accounts_delegated_stake_accounts() {
+        let validator_keypairs = ValidatorVoteKeypairs::new_rand();
+        let other_validator_keypairs = ValidatorVoteKeypairs::new_rand();
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config_with_vote_accounts(
+            1_000_000_000,
+            &[&validator_keypairs, &other_validator_keypairs],
+            vec![1_000_000, 1_000_000],
+        );
+        let bank = Bank::new_for_tests(&genesis_config);
+        let identity = validator_keypairs.node_keypair.pubkey();
+        let vote_account = validator_keypairs.vote_keypair.pubkey();
+        let stake_account = validator_keypairs.stake_keypair.pubkey();
+
+        let protected_accounts = ProtectedAccounts::new(
+            identity,
+            vote_account,
+            &ProtectedAccountsConfig::default(),
+            &bank,
+        );
+        assert!(!protected_accounts.contains(&stake_account));
+
+        let protected_accounts = ProtectedAccounts::new(
+            identity,
+            vote_account,
+            &ProtectedAccountsConfig {
+                protect_delegated_stake_accounts: true,
+                ..ProtectedAccountsConfig::default()
+            },
+            &bank,
+        );
+        assert!(protected_accounts.contains(&stake_account));
+        assert!(!protected_accounts.contains(&other_validator_keypairs.stake_keypair.pubkey()));
+        assert_eq!(protected_accounts.len(), 3);
+    }
+
+    #[test]
+    fn test_bundle_filter_allows_bundle() {
+        let filter = BundleFilter {
+            denied_accounts: HashSet::from([Pubkey::new_unique()]),
+            denied_programs: HashSet::from([Pubkey::new_unique()]),
+            min_tip_lamports: 0,
+        };
+        let bundle = new_bundle(vec![
+            new_transfer(&Pubkey::new_unique()),
+            new_transfer(&Pubkey::new_unique()),
+        ]);
+        assert_eq!(filter.check(&bundle), Ok(()));
+        assert_eq!(BundleFilter::default().check(&bundle), Ok(()));
+    }
+}
@@ -0,0 +1,665 @@
+//! Bundles BundleStage has received but not executed yet.
+//!
+//! The validator only executes bundles while it's leader, so a block engine can keep sending
+//! bundles through a long non-leader stretch. The queue holds at most `capacity` bundles. Once
+//! it's full, expired bundles are dropped first, then the bundle tipping the least is evicted to
+//! make room, which may be the newly received one.
+//!
+//! While leader, BundleStage executes the queued bundles in order of tip per compute unit rather
+//! than the order they arrived in, see [BundlePriority]. Bundles that don't fit in the rest of the
+//! block are deferred and queued again once poh moves on to a later slot.
+//!
+//! The block engine can send a bundle again after a reconnect. The queue remembers the uuids of
+//! the last [MAX_SEEN_BUNDLES] bundles it received and drops repeats. Bundles without a uuid are
+//! recognized by a hash of their packets instead.
+//!
+//! A bundle whose accounts stayed in use by BankingStage until it gave up on locking them is
+//! queued again a limited number of times. The queue counts the retries of the last
+//! [MAX_SEEN_BUNDLES] bundles it retried.
+//!
+//! Bundles admitted while the validator has a working bank reserve their accounts in the
+//! [BundleAccountLocker] right away, so BankingStage can't write them before the bundle executes.
+//! The reservation is queued with the bundle and released when the bundle leaves the queue, unless
+//! it's handed to the execution pass with [BundleQueue::drain_reserved].
+
+use {
+    crate::{
+        bundle_account_locker::{BundleAccountLocker, BundleAccountReservation},
+        bundle_sanitizer::sanitize_bundle_transactions,
+        packet_bundle::PacketBundle,
+    },
+    lru::LruCache,
+    solana_runtime::bank::Bank,
+    solana_sdk::{
+        clock::Slot,
+        hash::{hashv, Hash},
+        message::VersionedMessage,
+        pubkey::Pubkey,
+        system_instruction::SystemInstruction,
+        system_program,
+        transaction::VersionedTransaction,
+    },
+    std::{
+        cmp::Ordering,
+        collections::{HashSet, VecDeque},
+        mem,
+    },
+    uuid::Uuid,
+};
+
+/// Default max number of bundles BundleStage holds on to.
+pub const DEFAULT_BUNDLE_QUEUE_CAPACITY: usize = 1_024;
+
+/// Number of recently received bundles remembered to drop repeats.
+pub const MAX_SEEN_BUNDLES: usize = 16 * 1_024;
+
+/// Number of bundles dropped by the queue, by reason.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BundleQueueEvictions {
+    /// Newly received bundles dropped because the same bundle was received recently.
+    pub num_duplicates: u64,
+    /// Expired bundles dropped to make room.
+    pub num_expired: u64,
+    /// Queued bundles evicted for a newly received bundle with a higher tip.
+    pub num_lower_tip: u64,
+    /// Newly received bundles dropped because every queued bundle tips at least as much.
+    pub num_rejected: u64,
+}
+
+/// How much a bundle pays for the block space it uses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BundlePriority {
+    /// Lamports the bundle transfers to the tip accounts.
+    pub tip_lamports: u64,
+    /// The cost model's estimate for all of the bundle's transactions.
+    pub compute_units: u64,
+}
+
+impl BundlePriority {
+    /// Orders the bundle paying more tip per compute unit first. Bundles paying the same compare
+    /// equal, so a stable sort keeps them in the order they arrived in.
+    pub fn cmp_descending(&self, other: &Self) -> Ordering {
+        // cross-multiplied to compare the ratios without rounding
+        let other_ratio = u128::from(other.tip_lamports) * u128::from(self.compute_units.max(1));
+        let self_ratio = u128::from(self.tip_lamports) * u128::from(other.compute_units.max(1));
+        other_ratio.cmp(&self_ratio)
+    }
+}
+
+/// Identifies a received bundle to recognize repeats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BundleId {
+    Uuid(Uuid),
+    /// Hash of the packets of a bundle received without a uuid.
+    ContentHash(Hash),
+}
+
+impl BundleId {
+    pub fn new(bundle: &PacketBundle) -> Self {
+        if bundle.uuid.is_nil() {
+            let packets: Vec<&[u8]> = bundle
+                .batch
+                .iter()
+                .filter_map(|packet| packet.data(..))
+                .collect();
+            Self::ContentHash(hashv(&packets))
+        } else {
+            Self::Uuid(bundle.uuid)
+        }
+    }
+}
+
+struct QueuedBundle {
+    bundle: PacketBundle,
+    tip_lamports: u64,
+    // released when the bundle is dropped from the queue
+    reservation: Option<BundleAccountReservation>,
+}
+
+/// Bounded FIFO of bundles waiting to be executed, evicting by tip once full.
+pub struct BundleQueue {
+    capacity: usize,
+    tip_accounts: HashSet<Pubkey>,
+    bundle_account_locker: BundleAccountLocker,
+    bundles: VecDeque<QueuedBundle>,
+    // bundles that didn't fit in the block of deferred_slot
+    deferred: Vec<PacketBundle>,
+    deferred_slot: Slot,
+    // bundles received recently, queued or not
+    seen: LruCache<BundleId, ()>,
+    // number of times a bundle was queued again because its accounts were in use
+    retries: LruCache<BundleId, usize>,
+    evictions: BundleQueueEvictions,
+}
+
+impl BundleQueue {
+    pub fn new(
+        capacity: usize,
+        tip_accounts: HashSet<Pubkey>,
+        bundle_account_locker: BundleAccountLocker,
+    ) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            tip_accounts,
+            bundle_account_locker,
+            bundles: VecDeque::with_capacity(capacity),
+            deferred: Vec::new(),
+            deferred_slot: Slot::default(),
+            seen: LruCache::new(MAX_SEEN_BUNDLES),
+            retries: LruCache::new(MAX_SEEN_BUNDLES),
+            evictions: BundleQueueEvictions::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bundles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bundles.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PacketBundle> {
+        self.bundles.iter().map(|queued| &queued.bundle)
+    }
+
+    /// Queues a newly received `bundle` like [BundleQueue::push], unless the same bundle was
+    /// received recently. If it's admitted while `leader_bank` is the working bank, its accounts
+    /// are reserved against that bank until it leaves the queue. Returns false if `bundle` was
+    /// dropped.
+    pub fn push_received(
+        &mut self,
+        bundle: PacketBundle,
+        current_slot: Slot,
+        leader_bank: Option<&Bank>,
+    ) -> bool {
+        if self.seen.put(BundleId::new(&bundle), ()).is_some() {
+            self.evictions.num_duplicates += 1;
+            return false;
+        }
+        if !self.push(bundle, current_slot) {
+            return false;
+        }
+        if let Some(bank) = leader_bank {
+            // admitted bundles are queued at the back. bundles that don't sanitize aren't
+            // reserved, the execution pass drops them
+            let queued = self.bundles.back_mut().unwrap();
+            queued.reservation = self
+                .bundle_account_locker
+                .reserve_transaction_accounts(
+                    &sanitize_bundle_transactions(&queued.bundle, bank),
+                    bank,
+                )
+                .ok();
+        }
+        true
+    }
+
+    /// Queues `bundle` while poh is ticking in `current_slot`, evicting a bundle if the queue is
+    /// full. Returns false if `bundle` itself was dropped. Bundles aren't checked for repeats, so
+    /// this is also used to queue bundles being retried.
+    pub fn push(&mut self, bundle: PacketBundle, current_slot: Slot) -> bool {
+        let tip_lamports = bundle_tip_lamports(&bundle, &self.tip_accounts);
+        if self.bundles.len() >= self.capacity {
+            let num_bundles_before = self.bundles.len();
+            self.bundles
+                .retain(|queued| queued.bundle.expiry_slot >= current_slot);
+            self.evictions.num_expired += (num_bundles_before - self.bundles.len()) as u64;
+        }
+        if self.bundles.len() >= self.capacity {
+            // on equal tips the bundle received first is kept
+            let (lowest_index, lowest_tip) = self
+                .bundles
+                .iter()
+                .enumerate()
+                .rev()
+                .min_by_key(|(_, queued)| queued.tip_lamports)
+                .map(|(index, queued)| (index, queued.tip_lamports))
+                .unwrap();
+            if tip_lamports <= lowest_tip {
+                self.evictions.num_rejected += 1;
+                return false;
+            }
+            self.bundles.remove(lowest_index);
+            self.evictions.num_lower_tip += 1;
+        }
+        self.bundles.push_back(QueuedBundle {
+            bundle,
+            tip_lamports,
+            reservation: None,
+        });
+        true
+    }
+
+    /// Number of times `bundle` was queued again with [BundleQueue::retry].
+    pub fn num_retries(&self, bundle: &PacketBundle) -> usize {
+        self.retries
+            .peek(&BundleId::new(bundle))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Queues `bundle` again like [BundleQueue::push] after its accounts stayed in use, counting
+    /// the retry. Returns false if `bundle` was dropped.
+    pub fn retry(&mut self, bundle: PacketBundle, current_slot: Slot) -> bool {
+        let id = BundleId::new(&bundle);
+        let num_retries = self.retries.peek(&id).copied().unwrap_or_default();
+        self.retries.put(id, num_retries.saturating_add(1));
+        self.push(bundle, current_slot)
+    }
+
+    /// Keeps only the bundles for which `f` returns true, in order.
+    pub fn retain(&mut self, mut f: impl FnMut(&PacketBundle) -> bool) {
+        self.bundles.retain(|queued| f(&queued.bundle));
+    }
+
+    /// Removes every queued bundle, oldest first, releasing their reservations.
+    pub fn drain(&mut self) -> impl Iterator<Item = PacketBundle> + '_ {
+        self.bundles.drain(..).map(|queued| queued.bundle)
+    }
+
+    /// Removes every queued bundle, oldest first, along with the reservation it was admitted with.
+    pub fn drain_reserved(
+        &mut self,
+    ) -> impl Iterator<Item = (PacketBundle, Option<BundleAccountReservation>)> + '_ {
+        self.bundles
+            .drain(..)
+            .map(|queued| (queued.bundle, queued.reservation))
+    }
+
+    /// Holds `bundle` back until poh is ticking in a slot after `slot`. Deferred bundles aren't
+    /// counted in `len` and aren't drained.
+    pub fn defer(&mut self, bundle: PacketBundle, slot: Slot) {
+        self.deferred_slot = slot;
+        self.deferred.push(bundle);
+    }
+
+    pub fn num_deferred(&self) -> usize {
+        self.deferred.len()
+    }
+
+    /// Queues the bundles deferred in a slot before `current_slot` again, in the order they were
+    /// deferred.
+    pub fn release_deferred(&mut self, current_slot: Slot) {
+        if self.deferred_slot >= current_slot {
+            return;
+        }
+        for bundle in mem::take(&mut self.deferred) {
+            self.push(bundle, current_slot);
+        }
+    }
+
+    /// Returns the evictions since the last call.
+    pub fn take_evictions(&mut self) -> BundleQueueEvictions {
+        std::mem::take(&mut self.evictions)
+    }
+}
+
+/// Sums the lamports the bundle's transactions transfer to `tip_accounts` with system program
+/// transfers. Only static account keys are looked at, tips paid through address lookup tables or
+/// other programs aren't counted.
+pub fn bundle_tip_lamports(bundle: &PacketBundle, tip_accounts: &HashSet<Pubkey>) -> u64 {
+    bundle
+        .batch
+        .iter()
+        .filter(|packet| !packet.meta.discard())
+        .filter_map(|packet| packet.deserialize_slice::<VersionedTransaction, _>(..).ok())
+        .map(|transaction| message_tip_lamports(&transaction.message, tip_accounts))
+        .fold(0, u64::saturating_add)
+}
+
+fn message_tip_lamports(message: &VersionedMessage, tip_accounts: &HashSet<Pubkey>) -> u64 {
+    let account_keys = message.static_account_keys();
+    message
+        .instructions()
+        .iter()
+        .filter_map(|instruction| {
+            let program_id = account_keys.get(instruction.program_id_index as usize)?;
+            if !system_program::check_id(program_id) {
+                return None;
+            }
+            let to = account_keys.get(*instruction.accounts.get(1)? as usize)?;
+            if !tip_accounts.contains(to) {
+                return None;
+            }
+            match bincode::deserialize(&instruction.data) {
+                Ok(SystemInstruction::Transfer { lamports }) => Some(lamports),
+                _ => None,
+            }
+        })
+        .fold(0, u64::saturating_add)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_ledger::genesis_utils::create_genesis_config,
+        solana_perf::packet::{Packet, PacketBatch},
+        solana_sdk::{hash::Hash, signature::Keypair, system_transaction::transfer},
+        std::time::Instant,
+        uuid::Uuid,
+    };
+
+    fn new_bundle(tip_account: &Pubkey, tip_lamports: u64, expiry_slot: Slot) -> PacketBundle {
+        let transaction = transfer(&Keypair::new(), tip_account, tip_lamports, Hash::default());
+        PacketBundle {
+            batch: PacketBatch::new(vec![Packet::from_data(None, &transaction).unwrap()]),
+            uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot,
+        }
+    }
+
+    fn queued_tips(bundle_queue: &BundleQueue) -> Vec<u64> {
+        bundle_queue
+            .iter()
+            .map(|bundle| bundle_tip_lamports(bundle, &bundle_queue.tip_accounts))
+            .collect()
+    }
+
+    #[test]
+    fn test_bundle_tip_lamports() {
+        let tip_account = Pubkey::new_unique();
+        let tip_accounts = HashSet::from([tip_account]);
+        assert_eq!(
+            bundle_tip_lamports(&new_bundle(&tip_account, 1_000, 0), &tip_accounts),
+            1_000
+        );
+        // transfers elsewhere aren't tips
+        assert_eq!(
+            bundle_tip_lamports(&new_bundle(&Pubkey::new_unique(), 1_000, 0), &tip_accounts),
+            0
+        );
+
+        let mut bundle = new_bundle(&tip_account, 1_000, 0);
+        bundle.batch[0].meta.set_discard(true);
+        assert_eq!(bundle_tip_lamports(&bundle, &tip_accounts), 0);
+    }
+
+    #[test]
+    fn test_bundle_queue_evicts_lowest_tip() {
+        let tip_account = Pubkey::new_unique();
+        let mut bundle_queue = BundleQueue::new(
+            4,
+            HashSet::from([tip_account]),
+            BundleAccountLocker::default(),
+        );
+        for tip in [30, 10, 40, 20] {
+            assert!(bundle_queue.push(new_bundle(&tip_account, tip, 100), 0));
+        }
+
+        // the lowest tip makes room for a higher one, the rest keep their order
+        assert!(bundle_queue.push(new_bundle(&tip_account, 25, 100), 0));
+        assert_eq!(queued_tips(&bundle_queue), vec![30, 40, 20, 25]);
+        assert!(bundle_queue.push(new_bundle(&tip_account, 50, 100), 0));
+        assert_eq!(queued_tips(&bundle_queue), vec![30, 40, 25, 50]);
+
+        // a newcomer tipping no more than every queued bundle is dropped
+        assert!(!bundle_queue.push(new_bundle(&tip_account, 25, 100), 0));
+        assert!(!bundle_queue.push(new_bundle(&tip_account, 5, 100), 0));
+        assert_eq!(queued_tips(&bundle_queue), vec![30, 40, 25, 50]);
+
+        assert_eq!(
+            bundle_queue.take_evictions(),
+            BundleQueueEvictions {
+                num_duplicates: 0,
+                num_expired: 0,
+                num_lower_tip: 2,
+                num_rejected: 2,
+            }
+        );
+        assert_eq!(
+            bundle_queue.take_evictions(),
+            BundleQueueEvictions::default()
+        );
+    }
+
+    #[test]
+    fn test_bundle_queue_drops_expired_before_evicting() {
+        let tip_account = Pubkey::new_unique();
+        let mut bundle_queue = BundleQueue::new(
+            3,
+            HashSet::from([tip_account]),
+            BundleAccountLocker::default(),
+        );
+        assert!(bundle_queue.push(new_bundle(&tip_account, 100, 4), 0));
+        assert!(bundle_queue.push(new_bundle(&tip_account, 1, 10), 0));
+        assert!(bundle_queue.push(new_bundle(&tip_account, 100, 5), 0));
+
+        // the expired high tip bundle goes before the unexpired low tip one
+        assert!(bundle_queue.push(new_bundle(&tip_account, 2, 10), 5));
+        assert_eq!(queued_tips(&bundle_queue), vec![1, 100, 2]);
+        assert_eq!(
+            bundle_queue.take_evictions(),
+            BundleQueueEvictions {
+                num_expired: 1,
+                ..BundleQueueEvictions::default()
+            }
+        );
+
+        assert_eq!(bundle_queue.drain().count(), 3);
+        assert!(bundle_queue.is_empty());
+    }
+
+    #[test]
+    fn test_bundle_queue_defers_until_later_slot() {
+        let tip_account = Pubkey::new_unique();
+        let mut bundle_queue = BundleQueue::new(
+            4,
+            HashSet::from([tip_account]),
+            BundleAccountLocker::default(),
+        );
+        assert!(bundle_queue.push(new_bundle(&tip_account, 10, 100), 5));
+        bundle_queue.defer(new_bundle(&tip_account, 20, 100), 5);
+        bundle_queue.defer(new_bundle(&tip_account, 30, 100), 5);
+        assert_eq!(bundle_queue.len(), 1);
+        assert_eq!(bundle_queue.num_deferred(), 2);
+
+        // still building the block they didn't fit in
+        bundle_queue.release_deferred(5);
+        assert_eq!(queued_tips(&bundle_queue), vec![10]);
+
+        bundle_queue.release_deferred(6);
+        assert_eq!(queued_tips(&bundle_queue), vec![10, 20, 30]);
+        assert_eq!(bundle_queue.num_deferred(), 0);
+    }
+
+    #[test]
+    fn test_bundle_queue_drops_replayed_bundles() {
+        let tip_account = Pubkey::new_unique();
+        let mut bundle_queue = BundleQueue::new(
+            10,
+            HashSet::from([tip_account]),
+            BundleAccountLocker::default(),
+        );
+        let bundles: Vec<_> = [10, 20, 30]
+            .into_iter()
+            .map(|tip| new_bundle(&tip_account, tip, 100))
+            .collect();
+        for bundle in &bundles {
+            assert!(bundle_queue.push_received(bundle.clone(), 0, None));
+        }
+        assert_eq!(bundle_queue.drain().count(), 3);
+
+        // the block engine sends the same bundles again after reconnecting
+        for bundle in &bundles {
+            assert!(!bundle_queue.push_received(bundle.clone(), 0, None));
+        }
+        assert!(bundle_queue.is_empty());
+        assert_eq!(
+            bundle_queue.take_evictions(),
+            BundleQueueEvictions {
+                num_duplicates: 3,
+                ..BundleQueueEvictions::default()
+            }
+        );
+
+        // bundles without a uuid are matched by content
+        let mut no_uuid = new_bundle(&tip_account, 40, 100);
+        no_uuid.uuid = Uuid::nil();
+        assert!(bundle_queue.push_received(no_uuid.clone(), 0, None));
+        assert!(!bundle_queue.push_received(no_uuid, 0, None));
+        let mut other_no_uuid = new_bundle(&tip_account, 40, 100);
+        other_no_uuid.uuid = Uuid::nil();
+        assert!(bundle_queue.push_received(other_no_uuid, 0, None));
+
+        // retried bundles aren't repeats
+        assert!(bundle_queue.push(bundles[0].clone(), 0));
+        assert_eq!(queued_tips(&bundle_queue), vec![40, 40, 10]);
+    }
+
+    #[test]
+    fn test_bundle_queue_forgets_oldest_seen_bundles() {
+        let tip_account = Pubkey::new_unique();
+        let mut bundle_queue = BundleQueue::new(
+            10,
+            HashSet::from([tip_account]),
+            BundleAccountLocker::default(),
+        );
+        bundle_queue.seen = LruCache::new(2);
+        let bundles: Vec<_> = [10, 20, 30]
+            .into_iter()
+            .map(|tip| new_bundle(&tip_account, tip, 100))
+            .collect();
+        for bundle in &bundles {
+            assert!(bundle_queue.push_received(bundle.clone(), 0, None));
+        }
+        assert_eq!(bundle_queue.seen.len(), 2);
+
+        // only the oldest one was forgotten
+        assert!(bundle_queue.push_received(bundles[0].clone(), 0, None));
+        assert!(!bundle_queue.push_received(bundles[2].clone(), 0, None));
+    }
+
+    #[test]
+    fn test_bundle_queue_counts_retries() {
+        let tip_account = Pubkey::new_unique();
+        let mut bundle_queue = BundleQueue::new(
+            4,
+            HashSet::from([tip_account]),
+            BundleAccountLocker::default(),
+        );
+        let bundle = new_bundle(&tip_account, 1_000, 100);
+        assert!(bundle_queue.push_received(bundle.clone(), 0, None));
+        assert_eq!(bundle_queue.num_retries(&bundle), 0);
+
+        // retried bundles aren't dropped as repeats and keep their tip
+        for num_retries in 1..=2 {
+            let bundle = bundle_queue.drain().next().unwrap();
+            assert!(bundle_queue.retry(bundle.clone(), 0));
+            assert_eq!(bundle_queue.num_retries(&bundle), num_retries);
+            assert_eq!(queued_tips(&bundle_queue), vec![1_000]);
+        }
+        assert_eq!(
+            bundle_queue.num_retries(&new_bundle(&tip_account, 1_000, 100)),
+            0
+        );
+    }
+
+    #[test]
+    fn test_bundle_queue_reserves_admitted_bundles() {
+        let bank = Bank::new_for_tests(&create_genesis_config(1).genesis_config);
+        let bundle_account_locker = BundleAccountLocker::default();
+        let tip_account = Pubkey::new_unique();
+        let mut bundle_queue = BundleQueue::new(
+            2,
+            HashSet::from([tip_account]),
+            bundle_account_locker.clone(),
+        );
+        let payer = |bundle: &PacketBundle| {
+            bundle.batch[0]
+                .deserialize_slice::<VersionedTransaction, _>(..)
+                .unwrap()
+                .message
+                .static_account_keys()[0]
+        };
+
+        // only bundles admitted while leader are reserved
+        let reserved_bundle = new_bundle(&tip_account, 10, 100);
+        assert!(bundle_queue.push_received(reserved_bundle.clone(), 0, Some(&bank)));
+        let unreserved_bundle = new_bundle(&tip_account, 20, 100);
+        assert!(bundle_queue.push_received(unreserved_bundle.clone(), 0, None));
+        assert_eq!(
+            bundle_account_locker.write_locks(),
+            HashSet::from([payer(&reserved_bundle), tip_account])
+        );
+
+        // a rejected bundle reserves nothing, an evicted one releases its reservation
+        assert!(!bundle_queue.push_received(new_bundle(&tip_account, 5, 100), 0, Some(&bank)));
+        assert_eq!(bundle_account_locker.write_locks().len(), 2);
+        let higher_tip_bundle = new_bundle(&tip_account, 30, 100);
+        assert!(bundle_queue.push_received(higher_tip_bundle.clone(), 0, Some(&bank)));
+        assert_eq!(
+            bundle_account_locker.write_locks(),
+            HashSet::from([payer(&higher_tip_bundle), tip_account])
+        );
+
+        // the reservation is handed over along with the bundle
+        let drained: Vec<_> = bundle_queue.drain_reserved().collect();
+        assert_eq!(
+            drained
+                .iter()
+                .map(|(bundle, reservation)| (bundle.uuid, reservation.is_some()))
+                .collect::<Vec<_>>(),
+            vec![
+                (unreserved_bundle.uuid, false),
+                (higher_tip_bundle.uuid, true)
+            ]
+        );
+        assert_eq!(bundle_account_locker.write_locks().len(), 2);
+        drop(drained);
+        assert!(bundle_account_locker.write_locks().is_empty());
+
+        // expired bundles release their reservation
+        assert!(bundle_queue.push_received(new_bundle(&tip_account, 10, 4), 0, Some(&bank)));
+        assert_eq!(bundle_account_locker.write_locks().len(), 2);
+        bundle_queue.retain(|bundle| bundle.expiry_slot >= 5);
+        assert!(bundle_account_locker.write_locks().is_empty());
+    }
+
+    #[test]
+    fn test_bundle_priority_cmp_descending() {
+        let priority = |tip_lamports, compute_units| BundlePriority {
+            tip_lamports,
+            compute_units,
+        };
+        assert_eq!(
+            priority(2_000, 1_000).cmp_descending(&priority(1_000, 1_000)),
+            Ordering::Less
+        );
+        // a higher tip spread over more compute units can rank lower
+        assert_eq!(
+            priority(3_000, 2_000).cmp_descending(&priority(2_000, 1_000)),
+            Ordering::Greater
+        );
+        assert_eq!(
+            priority(3_000, 2_000).cmp_descending(&priority(1_500, 1_000)),
+            Ordering::Equal
+        );
+        // no estimate counts as a single compute unit
+        assert_eq!(
+            priority(10, 0).cmp_descending(&priority(10, 1)),
+            Ordering::Equal
+        );
+        assert_eq!(
+            priority(u64::MAX, 1).cmp_descending(&priority(u64::MAX, u64::MAX)),
+            Ordering::Less
+        );
+
+        // equal priorities keep their arrival order
+        let mut bundles = vec![
+            (priority(1_000, 1_000), 0),
+            (priority(4_000, 2_000), 1),
+            (priority(2_000, 1_000), 2),
+            (priority(0, 1_000), 3),
+            (priority(6_000, 2_000), 4),
+        ];
+        bundles.sort_by(|(a, _), (b, _)| a.cmp_descending(b));
+        assert_eq!(
+            bundles.into_iter().map(|(_, id)| id).collect::<Vec<_>>(),
+            vec![4, 1, 2, 0, 3]
+        );
+    }
+}
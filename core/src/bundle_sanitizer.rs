@@ -3,25 +3,48 @@ use {
     crate::immutable_deserialized_packet::ImmutableDeserializedPacket,
     crate::packet_bundle::PacketBundle,
     crate::unprocessed_packet_batches::deserialize_packets,
-    solana_perf::sigverify::verify_packet,
+    solana_perf::{packet::PACKET_DATA_SIZE, sigverify::check_for_simple_vote_packet},
     solana_runtime::{bank::Bank, transaction_error_metrics::TransactionErrorMetrics},
     solana_sdk::{
         bundle::sanitized::SanitizedBundle,
         clock::MAX_PROCESSING_AGE,
         feature_set::FeatureSet,
         pubkey::Pubkey,
-        signature::Signature,
-        transaction::{AddressLoader, SanitizedTransaction},
+        saturating_add_assign,
+        transaction::{
+            AddressLoader, SanitizedTransaction, TransactionError, VersionedTransaction,
+        },
     },
     std::{
-        collections::{hash_map::RandomState, HashSet},
+        collections::{HashMap, HashSet},
         iter::repeat,
         sync::Arc,
     },
     thiserror::Error,
 };
 
-pub const MAX_PACKETS_PER_BUNDLE: usize = 5;
+/// Default max number of transactions in a bundle.
+pub const MAX_BUNDLE_TRANSACTIONS: usize = 5;
+
+/// Default max combined size of a bundle's serialized transactions.
+pub const MAX_BUNDLE_SIZE_BYTES: usize = MAX_BUNDLE_TRANSACTIONS * PACKET_DATA_SIZE;
+
+/// Largest bundle that will be sanitized. Bigger bundles hold their account locks for longer and
+/// take up more of the slot in a single poh record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BundleLimits {
+    pub max_transactions: usize,
+    pub max_size_bytes: usize,
+}
+
+impl Default for BundleLimits {
+    fn default() -> Self {
+        Self {
+            max_transactions: MAX_BUNDLE_TRANSACTIONS,
+            max_size_bytes: MAX_BUNDLE_SIZE_BYTES,
+        }
+    }
+}
 
 #[derive(Error, Debug, PartialEq, Eq, Clone)]
 pub enum BundleSanitizerError {
@@ -29,6 +52,12 @@ pub enum BundleSanitizerError {
     VoteOnlyMode,
     #[error("Bundle packet batch failed pre-check")]
     FailedPacketBatchPreCheck,
+    #[error("Bundle contains a vote transaction")]
+    VoteTransaction,
+    #[error("Bundle has {0} transactions, more than the limit of {1}")]
+    TooManyTransactions(usize, usize),
+    #[error("Bundle is {0} bytes, more than the limit of {1}")]
+    TooLarge(usize, usize),
     #[error("Bundle mentions blacklisted account")]
     BlacklistedAccount,
     #[error("Bundle contains a transaction that failed to serialize")]
@@ -37,58 +66,79 @@ pub enum BundleSanitizerError {
     DuplicateTransaction,
     #[error("Bundle failed check_transactions")]
     FailedCheckTransactions,
+    #[error("Bundle contains a transaction with an expired blockhash or an invalid nonce")]
+    BlockhashNotFound,
+    #[error("Bundle contains a transaction whose fee payer can't pay the fee")]
+    InsufficientFundsForFee,
 }
 
 pub type BundleSanitizationResult<T> = Result<T, BundleSanitizerError>;
 
+/// Most transactions a bundle can have on `bank`. A bundle's entries are recorded with a single
+/// PoH record, which has to fit between two ticks, and each transaction may need its own entry.
+/// Bundles over the limit could never be recorded, so they're rejected instead of split.
+fn max_bundle_transactions(bank: &Bank, bundle_limits: &BundleLimits) -> usize {
+    match bank.hashes_per_tick() {
+        Some(hashes_per_tick) => bundle_limits
+            .max_transactions
+            .min(hashes_per_tick.saturating_sub(1) as usize),
+        None => bundle_limits.max_transactions,
+    }
+}
+
 /// An invalid bundle contains one of the following:
 ///  No packets.
-///  Too many packets.
+///  More transactions or bytes than `bundle_limits` allows.
+///  More transactions than PoH can record between two ticks, see [max_bundle_transactions].
 ///  Packets marked for discard (not sure why someone would do this)
-///  One of the packets fails signature verification.
 ///  Mentions an account in consensus or blacklisted accounts.
 ///  Contains a packet that failed to serialize to a transaction.
-///  Contains duplicate transactions within the same bundle.
 ///  Contains a transaction that was already processed or one with an invalid blockhash.
+///  Contains a transaction whose fee payer can't pay its fee, see [check_fee_payers].
+/// The blockhash and fee payer checks are the bundle failures that are cheapest to predict, so
+/// they're done here, before the bundle's accounts are reserved and it's simulated.
 /// NOTE: bundles need to be sanitized for a given bank. For instance, a bundle sanitized
 /// on bank n-1 will be valid for all of bank n-1, and may or may not be valid for bank n
+/// NOTE: bundles are expected to have passed [precheck_bundle] when they were received
+/// NOTE: signatures aren't checked here, every bundle is verified once by BundleSigverifier when
+/// it's received instead of on every execution pass
 pub fn get_sanitized_bundle(
     packet_bundle: &PacketBundle,
     bank: &Arc<Bank>,
     consensus_accounts_cache: &HashSet<Pubkey>,
     blacklisted_accounts: &HashSet<Pubkey>,
+    bundle_limits: &BundleLimits,
     transaction_error_metrics: &mut TransactionErrorMetrics,
 ) -> BundleSanitizationResult<SanitizedBundle> {
     if bank.vote_only_bank() {
         return Err(BundleSanitizerError::VoteOnlyMode);
     }
 
-    if packet_bundle.batch.is_empty()
-        || packet_bundle.batch.len() > MAX_PACKETS_PER_BUNDLE
-        || packet_bundle.batch.iter().any(|p| p.meta.discard())
-        || packet_bundle
-            .batch
-            .iter()
-            .any(|p| !verify_packet(&mut p.clone(), false))
-    {
+    let max_transactions = max_bundle_transactions(bank, bundle_limits);
+    if packet_bundle.batch.len() > max_transactions {
+        return Err(BundleSanitizerError::TooManyTransactions(
+            packet_bundle.batch.len(),
+            max_transactions,
+        ));
+    }
+    let size_bytes = packet_bundle
+        .batch
+        .iter()
+        .map(|p| p.meta.size)
+        .sum::<usize>();
+    if size_bytes > bundle_limits.max_size_bytes {
+        return Err(BundleSanitizerError::TooLarge(
+            size_bytes,
+            bundle_limits.max_size_bytes,
+        ));
+    }
+
+    if packet_bundle.batch.is_empty() || packet_bundle.batch.iter().any(|p| p.meta.discard()) {
         return Err(BundleSanitizerError::FailedPacketBatchPreCheck);
     }
 
-    let packet_indexes = (0..packet_bundle.batch.len()).collect::<Vec<usize>>();
-    let deserialized_packets = deserialize_packets(&packet_bundle.batch, &packet_indexes);
-    let transactions: Vec<SanitizedTransaction> = deserialized_packets
-        .filter_map(|p| {
-            let immutable_packet = p.immutable_section().clone();
-            transaction_from_deserialized_packet(
-                &immutable_packet,
-                &bank.feature_set,
-                bank.as_ref(),
-            )
-        })
-        .collect();
+    let transactions = sanitize_bundle_transactions(packet_bundle, bank);
 
-    let unique_signatures: HashSet<&Signature, RandomState> =
-        HashSet::from_iter(transactions.iter().map(|tx| tx.signature()));
     let contains_blacklisted_account = transactions.iter().any(|tx| {
         let accounts = tx.message().account_keys();
         accounts
@@ -104,10 +154,6 @@ pub fn get_sanitized_bundle(
         return Err(BundleSanitizerError::FailedToSerializeTransaction);
     }
 
-    if unique_signatures.len() != transactions.len() {
-        return Err(BundleSanitizerError::DuplicateTransaction);
-    }
-
     // assume everything locks okay to check for already-processed transaction or expired/invalid blockhash
     let lock_results: Vec<_> = repeat(Ok(())).take(transactions.len()).collect();
     let check_results = bank.check_transactions(
@@ -116,11 +162,99 @@ pub fn get_sanitized_bundle(
         MAX_PROCESSING_AGE,
         transaction_error_metrics,
     );
-    if check_results.iter().any(|r| r.0.is_err()) {
-        return Err(BundleSanitizerError::FailedCheckTransactions);
+    if let Some(e) = check_results
+        .iter()
+        .find_map(|(result, _)| result.as_ref().err())
+    {
+        return Err(match e {
+            TransactionError::BlockhashNotFound => BundleSanitizerError::BlockhashNotFound,
+            _ => BundleSanitizerError::FailedCheckTransactions,
+        });
+    }
+
+    check_fee_payers(bank, &transactions)?;
+
+    Ok(SanitizedBundle {
+        transactions,
+        expiry_slot: packet_bundle.expiry_slot,
+    })
+}
+
+/// Rejects bundles with a simple vote transaction, which could interfere with the validator's own
+/// votes, or with the same transaction twice. These checks don't need a bank and are cheap, so
+/// they're done when a bundle is received, before its signatures are verified.
+pub fn precheck_bundle(packet_bundle: &PacketBundle) -> BundleSanitizationResult<()> {
+    if packet_bundle
+        .batch
+        .iter()
+        .any(|p| check_for_simple_vote_packet(&mut p.clone()))
+    {
+        return Err(BundleSanitizerError::VoteTransaction);
+    }
+
+    // packets that don't deserialize are rejected when the bundle is sanitized
+    let mut signatures = HashSet::with_capacity(packet_bundle.batch.len());
+    for packet in packet_bundle.batch.iter() {
+        if let Some(signature) = packet
+            .deserialize_slice::<VersionedTransaction, _>(..)
+            .ok()
+            .and_then(|transaction| transaction.signatures.first().copied())
+        {
+            if !signatures.insert(signature) {
+                return Err(BundleSanitizerError::DuplicateTransaction);
+            }
+        }
     }
+    Ok(())
+}
+
+/// Returns the bundle's transactions that deserialize and sanitize against `bank`, without any of
+/// the other checks [get_sanitized_bundle] does.
+pub fn sanitize_bundle_transactions(
+    packet_bundle: &PacketBundle,
+    bank: &Bank,
+) -> Vec<SanitizedTransaction> {
+    let packet_indexes = (0..packet_bundle.batch.len()).collect::<Vec<usize>>();
+    deserialize_packets(&packet_bundle.batch, &packet_indexes)
+        .filter_map(|p| {
+            let immutable_packet = p.immutable_section().clone();
+            transaction_from_deserialized_packet(&immutable_packet, &bank.feature_set, bank)
+        })
+        .collect()
+}
 
-    Ok(SanitizedBundle { transactions })
+/// Checks each fee payer has enough lamports for the fees of all the bundle's transactions it
+/// pays for. A fee payer that an earlier transaction in the bundle could have funded isn't
+/// checked, execution finds out if it was.
+fn check_fee_payers(
+    bank: &Bank,
+    transactions: &[SanitizedTransaction],
+) -> BundleSanitizationResult<()> {
+    let mut fees: HashMap<Pubkey, u64> = HashMap::with_capacity(transactions.len());
+    let mut maybe_funded = HashSet::new();
+    for transaction in transactions {
+        let message = transaction.message();
+        let fee_payer = message.fee_payer();
+        if !maybe_funded.contains(fee_payer) {
+            // the blockhash or nonce was checked above, so the fee can be calculated
+            let fee = bank.get_fee_for_message(message).unwrap_or_default();
+            let total_fee = fees.entry(*fee_payer).or_default();
+            saturating_add_assign!(*total_fee, fee);
+            if *total_fee > bank.get_balance(fee_payer) {
+                return Err(BundleSanitizerError::InsufficientFundsForFee);
+            }
+        }
+        maybe_funded.extend(
+            message
+                .account_keys()
+                .iter()
+                .enumerate()
+                .skip(1)
+                .filter(|(index, _)| message.is_writable(*index))
+                .map(|(_, account)| *account),
+        );
+    }
+    Ok(())
 }
 
 // This function deserializes packets into transactions, computes the blake3 hash of transaction
@@ -152,7 +286,10 @@ fn transaction_from_deserialized_packet(
 mod tests {
     use {
         crate::{
-            bundle_sanitizer::{get_sanitized_bundle, MAX_PACKETS_PER_BUNDLE},
+            bundle_sanitizer::{
+                get_sanitized_bundle, precheck_bundle, BundleLimits, BundleSanitizerError,
+                MAX_BUNDLE_TRANSACTIONS,
+            },
             packet_bundle::PacketBundle,
             tip_manager::{TipDistributionAccountConfig, TipManager, TipManagerConfig},
         },
@@ -164,15 +301,22 @@ mod tests {
             transaction_error_metrics::TransactionErrorMetrics,
         },
         solana_sdk::{
+            account::AccountSharedData,
+            clock::Slot,
+            fee_calculator::FeeRateGovernor,
+            genesis_config::GenesisConfig,
             hash::Hash,
             instruction::Instruction,
+            nonce::{self, state::DurableNonce},
             packet::Packet,
             pubkey::Pubkey,
             signature::{Keypair, Signer},
+            system_instruction, system_program,
             system_transaction::transfer,
             transaction::{SanitizedTransaction, Transaction, VersionedTransaction},
         },
-        std::{collections::HashSet, sync::Arc},
+        solana_vote_program::vote_transaction,
+        std::{collections::HashSet, sync::Arc, time::Instant},
         uuid::Uuid,
     };
 
@@ -199,6 +343,8 @@ mod tests {
         let packet_bundle = PacketBundle {
             batch: PacketBatch::new(vec![packet]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
 
         let mut transaction_errors = TransactionErrorMetrics::default();
@@ -207,6 +353,7 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::default(),
+            &BundleLimits::default(),
             &mut transaction_errors,
         )
         .unwrap();
@@ -240,6 +387,8 @@ mod tests {
         let packet_bundle = PacketBundle {
             batch: PacketBatch::new(vec![packet]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
 
         let consensus_accounts_cache = HashSet::from([kp.pubkey()]);
@@ -249,20 +398,20 @@ mod tests {
             &bank,
             &consensus_accounts_cache,
             &HashSet::default(),
+            &BundleLimits::default(),
             &mut transaction_errors
         )
         .is_err());
     }
 
     #[test]
-    fn test_fail_to_sanitize_duplicate_transaction() {
+    fn test_precheck_duplicate_transaction() {
         solana_logger::setup();
         let GenesisConfigInfo {
             genesis_config,
             mint_keypair,
             ..
         } = create_genesis_config(2);
-        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
 
         let kp = Keypair::new();
 
@@ -278,18 +427,73 @@ mod tests {
         let packet_bundle = PacketBundle {
             batch: PacketBatch::new(vec![packet.clone(), packet]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
 
-        // fails to pop because bundle it locks the same transaction twice
-        let mut transaction_errors = TransactionErrorMetrics::default();
-        assert!(get_sanitized_bundle(
-            &packet_bundle,
-            &bank,
-            &HashSet::default(),
-            &HashSet::default(),
-            &mut transaction_errors
-        )
-        .is_err());
+        assert_eq!(
+            precheck_bundle(&packet_bundle),
+            Err(BundleSanitizerError::DuplicateTransaction)
+        );
+
+        // the signatures aren't verified yet
+        let mut forged_tx = tx;
+        forged_tx.signatures[0] = Keypair::new().sign_message(&forged_tx.message.serialize());
+        let forged_packet = Packet::from_data(None, &forged_tx).unwrap();
+        let packet_bundle = PacketBundle {
+            batch: PacketBatch::new(vec![forged_packet.clone(), forged_packet]),
+            uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
+        };
+        assert_eq!(
+            precheck_bundle(&packet_bundle),
+            Err(BundleSanitizerError::DuplicateTransaction)
+        );
+    }
+
+    #[test]
+    fn test_precheck_vote_transaction() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(2);
+
+        let transfer_tx = VersionedTransaction::from(transfer(
+            &mint_keypair,
+            &Keypair::new().pubkey(),
+            1,
+            genesis_config.hash(),
+        ));
+        let vote_keypair = Keypair::new();
+        let vote_tx = VersionedTransaction::from(vote_transaction::new_vote_transaction(
+            vec![0],
+            Hash::default(),
+            genesis_config.hash(),
+            &mint_keypair,
+            &vote_keypair,
+            &vote_keypair,
+            None,
+        ));
+
+        let packet_bundle = PacketBundle {
+            batch: PacketBatch::new(vec![Packet::from_data(None, &transfer_tx).unwrap()]),
+            uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
+        };
+        assert_eq!(precheck_bundle(&packet_bundle), Ok(()));
+
+        let mut packet_bundle = packet_bundle;
+        packet_bundle
+            .batch
+            .push(Packet::from_data(None, &vote_tx).unwrap());
+        assert_eq!(
+            precheck_bundle(&packet_bundle),
+            Err(BundleSanitizerError::VoteTransaction)
+        );
     }
 
     #[test]
@@ -311,6 +515,8 @@ mod tests {
         let packet_bundle = PacketBundle {
             batch: PacketBatch::new(vec![packet.clone(), packet]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
 
         // fails to pop because bundle has bad blockhash
@@ -320,6 +526,7 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::default(),
+            &BundleLimits::default(),
             &mut transaction_errors
         )
         .is_err());
@@ -348,6 +555,8 @@ mod tests {
         let packet_bundle = PacketBundle {
             batch: PacketBatch::new(vec![packet.clone()]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
 
         let mut transaction_errors = TransactionErrorMetrics::default();
@@ -356,6 +565,7 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::default(),
+            &BundleLimits::default(),
             &mut transaction_errors,
         )
         .unwrap();
@@ -374,6 +584,8 @@ mod tests {
         let packet_bundle = PacketBundle {
             batch: PacketBatch::new(vec![packet]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
 
         assert!(get_sanitized_bundle(
@@ -381,6 +593,7 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::default(),
+            &BundleLimits::default(),
             &mut transaction_errors
         )
         .is_err());
@@ -401,6 +614,8 @@ mod tests {
                 vote_account: Pubkey::new_unique(),
                 commission_bps: 0,
             },
+            min_bundle_tip_lamports: 0,
+            ..TipManagerConfig::default()
         });
 
         let kp = Keypair::new();
@@ -422,6 +637,8 @@ mod tests {
         let packet_bundle = PacketBundle {
             batch: PacketBatch::new(vec![packet]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
 
         // fails to pop because bundle mentions tip program
@@ -431,6 +648,7 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::from_iter([tip_manager.tip_payment_program_id()]),
+            &BundleLimits::default(),
             &mut transaction_errors
         )
         .is_err());
@@ -457,6 +675,8 @@ mod tests {
         let packet_bundle = PacketBundle {
             batch: PacketBatch::new(vec![packet]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
 
         let mut transaction_errors = TransactionErrorMetrics::default();
@@ -465,6 +685,7 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::default(),
+            &BundleLimits::default(),
             &mut transaction_errors
         )
         .is_ok());
@@ -479,6 +700,8 @@ mod tests {
         let packet_bundle = PacketBundle {
             batch: PacketBatch::new(vec![]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
         // fails to pop because empty bundle
         let mut transaction_errors = TransactionErrorMetrics::default();
@@ -487,11 +710,35 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::default(),
+            &BundleLimits::default(),
             &mut transaction_errors
         )
         .is_err());
     }
 
+    fn new_transfer_bundle(
+        genesis_config: &GenesisConfig,
+        mint_keypair: &Keypair,
+        num_transactions: usize,
+    ) -> PacketBundle {
+        let kp = Keypair::new();
+        let packets = (0..num_transactions).map(|i| {
+            let tx = VersionedTransaction::from(transfer(
+                mint_keypair,
+                &kp.pubkey(),
+                i as u64,
+                genesis_config.hash(),
+            ));
+            Packet::from_data(None, &tx).unwrap()
+        });
+        PacketBundle {
+            batch: PacketBatch::new(packets.collect()),
+            uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
+        }
+    }
+
     #[test]
     fn test_fails_to_sanitize_too_many_packets() {
         solana_logger::setup();
@@ -501,36 +748,98 @@ mod tests {
             ..
         } = create_genesis_config(2);
         let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+        let mut transaction_errors = TransactionErrorMetrics::default();
 
-        let kp = Keypair::new();
+        let packet_bundle =
+            new_transfer_bundle(&genesis_config, &mint_keypair, MAX_BUNDLE_TRANSACTIONS);
+        let sanitized_bundle = get_sanitized_bundle(
+            &packet_bundle,
+            &bank,
+            &HashSet::default(),
+            &HashSet::default(),
+            &BundleLimits::default(),
+            &mut transaction_errors,
+        )
+        .unwrap();
+        assert_eq!(sanitized_bundle.transactions.len(), MAX_BUNDLE_TRANSACTIONS);
 
-        let packets = (0..MAX_PACKETS_PER_BUNDLE + 1).map(|i| {
-            let tx = VersionedTransaction::from(transfer(
-                &mint_keypair,
-                &kp.pubkey(),
-                i as u64,
-                genesis_config.hash(),
-            ));
-            Packet::from_data(None, &tx).unwrap()
-        });
-        let packet_bundle = PacketBundle {
-            batch: PacketBatch::new(packets.collect()),
-            uuid: Uuid::new_v4(),
-        };
         // fails to pop because too many packets in a bundle
+        let packet_bundle =
+            new_transfer_bundle(&genesis_config, &mint_keypair, MAX_BUNDLE_TRANSACTIONS + 1);
+        assert_eq!(
+            get_sanitized_bundle(
+                &packet_bundle,
+                &bank,
+                &HashSet::default(),
+                &HashSet::default(),
+                &BundleLimits::default(),
+                &mut transaction_errors
+            ),
+            Err(BundleSanitizerError::TooManyTransactions(
+                MAX_BUNDLE_TRANSACTIONS + 1,
+                MAX_BUNDLE_TRANSACTIONS
+            ))
+        );
+
+        // the limit can be lowered
+        let bundle_limits = BundleLimits {
+            max_transactions: 2,
+            ..BundleLimits::default()
+        };
+        let packet_bundle = new_transfer_bundle(&genesis_config, &mint_keypair, 3);
+        assert_eq!(
+            get_sanitized_bundle(
+                &packet_bundle,
+                &bank,
+                &HashSet::default(),
+                &HashSet::default(),
+                &bundle_limits,
+                &mut transaction_errors
+            ),
+            Err(BundleSanitizerError::TooManyTransactions(3, 2))
+        );
+    }
+
+    #[test]
+    fn test_fails_to_sanitize_more_transactions_than_hashes_per_tick() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            mut genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(10_000);
+        // a PoH record fits at most 2 entries between ticks
+        genesis_config.poh_config.hashes_per_tick = Some(3);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
         let mut transaction_errors = TransactionErrorMetrics::default();
+
+        let packet_bundle = new_transfer_bundle(&genesis_config, &mint_keypair, 2);
         assert!(get_sanitized_bundle(
             &packet_bundle,
             &bank,
             &HashSet::default(),
             &HashSet::default(),
+            &BundleLimits::default(),
             &mut transaction_errors
         )
-        .is_err());
+        .is_ok());
+
+        let packet_bundle = new_transfer_bundle(&genesis_config, &mint_keypair, 3);
+        assert_eq!(
+            get_sanitized_bundle(
+                &packet_bundle,
+                &bank,
+                &HashSet::default(),
+                &HashSet::default(),
+                &BundleLimits::default(),
+                &mut transaction_errors
+            ),
+            Err(BundleSanitizerError::TooManyTransactions(3, 2))
+        );
     }
 
     #[test]
-    fn test_fails_to_sanitize_discarded() {
+    fn test_fails_to_sanitize_too_large() {
         solana_logger::setup();
         let GenesisConfigInfo {
             genesis_config,
@@ -538,37 +847,44 @@ mod tests {
             ..
         } = create_genesis_config(2);
         let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+        let mut transaction_errors = TransactionErrorMetrics::default();
 
-        let kp = Keypair::new();
-
-        let tx = VersionedTransaction::from(transfer(
-            &mint_keypair,
-            &kp.pubkey(),
-            1,
-            genesis_config.hash(),
-        ));
-        let mut packet = Packet::from_data(None, &tx).unwrap();
-        packet.meta.set_discard(true);
+        let packet_bundle = new_transfer_bundle(&genesis_config, &mint_keypair, 2);
+        let size_bytes = packet_bundle.batch[0].meta.size + packet_bundle.batch[1].meta.size;
 
-        let packet_bundle = PacketBundle {
-            batch: PacketBatch::new(vec![packet]),
-            uuid: Uuid::new_v4(),
+        let bundle_limits = BundleLimits {
+            max_size_bytes: size_bytes,
+            ..BundleLimits::default()
         };
-
-        // fails to pop because one of the packets is marked as discard
-        let mut transaction_errors = TransactionErrorMetrics::default();
         assert!(get_sanitized_bundle(
             &packet_bundle,
             &bank,
             &HashSet::default(),
             &HashSet::default(),
+            &bundle_limits,
             &mut transaction_errors
         )
-        .is_err());
+        .is_ok());
+
+        let bundle_limits = BundleLimits {
+            max_size_bytes: size_bytes - 1,
+            ..BundleLimits::default()
+        };
+        assert_eq!(
+            get_sanitized_bundle(
+                &packet_bundle,
+                &bank,
+                &HashSet::default(),
+                &HashSet::default(),
+                &bundle_limits,
+                &mut transaction_errors
+            ),
+            Err(BundleSanitizerError::TooLarge(size_bytes, size_bytes - 1))
+        );
     }
 
     #[test]
-    fn test_fails_to_sanitize_bad_sigverify() {
+    fn test_fails_to_sanitize_discarded() {
         solana_logger::setup();
         let GenesisConfigInfo {
             genesis_config,
@@ -579,34 +895,174 @@ mod tests {
 
         let kp = Keypair::new();
 
-        let mut tx = VersionedTransaction::from(transfer(
+        let tx = VersionedTransaction::from(transfer(
             &mint_keypair,
             &kp.pubkey(),
             1,
             genesis_config.hash(),
         ));
-
-        let _ = tx.signatures.pop();
-
-        let bad_kp = Keypair::new();
-        let serialized = tx.message.serialize();
-        let bad_sig = bad_kp.sign_message(&serialized);
-        tx.signatures.push(bad_sig);
-
-        let packet = Packet::from_data(None, &tx).unwrap();
+        let mut packet = Packet::from_data(None, &tx).unwrap();
+        packet.meta.set_discard(true);
 
         let packet_bundle = PacketBundle {
             batch: PacketBatch::new(vec![packet]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
+
+        // fails to pop because one of the packets is marked as discard
         let mut transaction_errors = TransactionErrorMetrics::default();
         assert!(get_sanitized_bundle(
             &packet_bundle,
             &bank,
             &HashSet::default(),
             &HashSet::default(),
+            &BundleLimits::default(),
             &mut transaction_errors
         )
         .is_err());
     }
+
+    fn new_packet_bundle(transactions: &[Transaction]) -> PacketBundle {
+        PacketBundle {
+            batch: PacketBatch::new(
+                transactions
+                    .iter()
+                    .map(|transaction| Packet::from_data(None, transaction).unwrap())
+                    .collect(),
+            ),
+            uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
+        }
+    }
+
+    fn new_bank_with_fees(lamports_per_signature: u64) -> (Arc<Bank>, Keypair) {
+        let GenesisConfigInfo {
+            mut genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        genesis_config.fee_rate_governor = FeeRateGovernor::new(lamports_per_signature, 0);
+        (
+            Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config)),
+            mint_keypair,
+        )
+    }
+
+    fn sanitize(
+        bank: &Arc<Bank>,
+        transactions: &[Transaction],
+    ) -> Result<(), BundleSanitizerError> {
+        get_sanitized_bundle(
+            &new_packet_bundle(transactions),
+            bank,
+            &HashSet::default(),
+            &HashSet::default(),
+            &BundleLimits::default(),
+            &mut TransactionErrorMetrics::default(),
+        )
+        .map(|_| ())
+    }
+
+    #[test]
+    fn test_fails_to_sanitize_expired_blockhash() {
+        let (bank, mint_keypair) = new_bank_with_fees(5_000);
+        let transactions = [
+            transfer(
+                &mint_keypair,
+                &Pubkey::new_unique(),
+                1,
+                bank.last_blockhash(),
+            ),
+            // a blockhash that has aged out of the bank's blockhash queue
+            transfer(&mint_keypair, &Pubkey::new_unique(), 1, Hash::new_unique()),
+        ];
+        assert_eq!(
+            sanitize(&bank, &transactions),
+            Err(BundleSanitizerError::BlockhashNotFound)
+        );
+    }
+
+    #[test]
+    fn test_fails_to_sanitize_insufficient_funds_for_fee() {
+        let (bank, mint_keypair) = new_bank_with_fees(5_000);
+        let fee_payer = Keypair::new();
+        bank.transfer(7_000, &mint_keypair, &fee_payer.pubkey())
+            .unwrap();
+
+        let transaction =
+            |from: &Keypair| transfer(from, &Pubkey::new_unique(), 1, bank.last_blockhash());
+        assert_eq!(sanitize(&bank, &[transaction(&fee_payer)]), Ok(()));
+        // the fee payer can pay for one of the transactions but not both
+        assert_eq!(
+            sanitize(&bank, &[transaction(&fee_payer), transaction(&fee_payer)]),
+            Err(BundleSanitizerError::InsufficientFundsForFee)
+        );
+        assert_eq!(
+            sanitize(&bank, &[transaction(&Keypair::new())]),
+            Err(BundleSanitizerError::InsufficientFundsForFee)
+        );
+
+        // a fee payer funded earlier in the bundle is left for execution to check
+        let funded = Keypair::new();
+        assert_eq!(
+            sanitize(
+                &bank,
+                &[
+                    transfer(
+                        &mint_keypair,
+                        &funded.pubkey(),
+                        10_000,
+                        bank.last_blockhash()
+                    ),
+                    transaction(&funded),
+                ]
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_sanitize_durable_nonce() {
+        let (bank, mint_keypair) = new_bank_with_fees(5_000);
+        let nonce_pubkey = Pubkey::new_unique();
+        let durable_nonce = DurableNonce::from_blockhash(&Hash::new_unique());
+        let nonce_account = AccountSharedData::new_data(
+            1_000_000,
+            &nonce::state::Versions::new(nonce::State::new_initialized(
+                &mint_keypair.pubkey(),
+                durable_nonce,
+                5_000,
+            )),
+            &system_program::id(),
+        )
+        .unwrap();
+        bank.store_account(&nonce_pubkey, &nonce_account);
+
+        // the nonce isn't in the blockhash queue, the nonce account vouches for it
+        let nonce_transaction = |nonce: Hash| {
+            Transaction::new_signed_with_payer(
+                &[
+                    system_instruction::advance_nonce_account(
+                        &nonce_pubkey,
+                        &mint_keypair.pubkey(),
+                    ),
+                    system_instruction::transfer(&mint_keypair.pubkey(), &Pubkey::new_unique(), 1),
+                ],
+                Some(&mint_keypair.pubkey()),
+                &[&mint_keypair],
+                nonce,
+            )
+        };
+        assert_eq!(
+            sanitize(&bank, &[nonce_transaction(*durable_nonce.as_hash())]),
+            Ok(())
+        );
+        assert_eq!(
+            sanitize(&bank, &[nonce_transaction(Hash::new_unique())]),
+            Err(BundleSanitizerError::BlockhashNotFound)
+        );
+    }
 }
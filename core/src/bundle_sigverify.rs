@@ -0,0 +1,175 @@
+//! Verifies the signatures of received bundles before they're queued.
+//!
+//! Bundles don't go through SigVerifyStage, so BundleStage verifies every bundle it's going to
+//! buffer in one batch, offloading to the GPU when perf-libs are available the same way
+//! SigVerifyStage does. A bundle with any transaction that fails verification is dropped whole.
+
+use {
+    crate::packet_bundle::PacketBundle,
+    solana_perf::{
+        cuda_runtime::PinnedVec,
+        packet::PacketBatch,
+        recycler::Recycler,
+        sigverify::{self, TxOffset},
+    },
+};
+
+pub struct BundleSigverifier {
+    recycler: Recycler<TxOffset>,
+    recycler_out: Recycler<PinnedVec<u8>>,
+}
+
+impl Default for BundleSigverifier {
+    fn default() -> Self {
+        sigverify::init();
+        Self {
+            recycler: Recycler::warmed(50, 4096),
+            recycler_out: Recycler::warmed(50, 4096),
+        }
+    }
+}
+
+impl BundleSigverifier {
+    /// Verifies every transaction in `bundles`, returning the bundles that passed and the ones
+    /// that didn't. Packets already marked as discarded fail their bundle.
+    pub fn verify(&self, bundles: Vec<PacketBundle>) -> (Vec<PacketBundle>, Vec<PacketBundle>) {
+        let (mut batches, bundles): (Vec<PacketBatch>, Vec<PacketBundle>) = bundles
+            .into_iter()
+            .map(|mut bundle| (std::mem::take(&mut bundle.batch), bundle))
+            .unzip();
+        let num_valid_packets = batches
+            .iter()
+            .flat_map(|batch| batch.iter())
+            .filter(|packet| !packet.meta.discard())
+            .count();
+        sigverify::ed25519_verify(
+            &mut batches,
+            &self.recycler,
+            &self.recycler_out,
+            false,
+            num_valid_packets,
+        );
+
+        bundles
+            .into_iter()
+            .zip(batches)
+            .map(|(bundle, batch)| PacketBundle { batch, ..bundle })
+            .partition(|bundle| {
+                !bundle.batch.is_empty() && !bundle.batch.iter().any(|p| p.meta.discard())
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_perf::packet::Packet,
+        solana_sdk::{
+            clock::Slot,
+            hash::Hash,
+            pubkey::Pubkey,
+            signature::{Keypair, Signer},
+            system_transaction::transfer,
+            transaction::VersionedTransaction,
+        },
+        std::time::Instant,
+        uuid::Uuid,
+    };
+
+    fn new_packet(lamports: u64) -> Packet {
+        let transaction = transfer(
+            &Keypair::new(),
+            &Pubkey::new_unique(),
+            lamports,
+            Hash::default(),
+        );
+        Packet::from_data(None, &transaction).unwrap()
+    }
+
+    fn new_forged_packet() -> Packet {
+        let mut transaction = VersionedTransaction::from(transfer(
+            &Keypair::new(),
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        transaction.signatures[0] = Keypair::new().sign_message(&transaction.message.serialize());
+        Packet::from_data(None, &transaction).unwrap()
+    }
+
+    fn new_bundle(packets: Vec<Packet>) -> PacketBundle {
+        PacketBundle {
+            batch: PacketBatch::new(packets),
+            uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
+        }
+    }
+
+    #[test]
+    fn test_bundle_sigverify() {
+        let good_bundle = new_bundle((1..=4).map(new_packet).collect());
+        let forged_bundle = new_bundle(vec![
+            new_packet(1),
+            new_packet(2),
+            new_forged_packet(),
+            new_packet(3),
+        ]);
+        let mut discarded_packet = new_packet(5);
+        discarded_packet.meta.set_discard(true);
+        let discarded_bundle = new_bundle(vec![new_packet(4), discarded_packet]);
+        let empty_bundle = new_bundle(vec![]);
+
+        let (verified, rejected) = BundleSigverifier::default().verify(vec![
+            good_bundle.clone(),
+            forged_bundle.clone(),
+            discarded_bundle.clone(),
+            empty_bundle.clone(),
+        ]);
+        assert_eq!(
+            verified
+                .iter()
+                .map(|bundle| bundle.uuid)
+                .collect::<Vec<_>>(),
+            vec![good_bundle.uuid]
+        );
+        assert_eq!(verified[0].batch.len(), 4);
+        assert_eq!(
+            rejected
+                .iter()
+                .map(|bundle| bundle.uuid)
+                .collect::<Vec<_>>(),
+            vec![forged_bundle.uuid, discarded_bundle.uuid, empty_bundle.uuid]
+        );
+        // only the forged transaction failed, the rest of its bundle verified
+        assert_eq!(
+            rejected[0]
+                .batch
+                .iter()
+                .map(|packet| packet.meta.discard())
+                .collect::<Vec<_>>(),
+            vec![false, false, true, false]
+        );
+    }
+
+    #[test]
+    fn test_bundle_sigverify_many_bundles() {
+        // enough packets to be verified in parallel
+        let mut bundles: Vec<_> = (0..64)
+            .map(|i| new_bundle((0..4).map(|j| new_packet(i * 4 + j + 1)).collect()))
+            .collect();
+        bundles[37].batch[2] = new_forged_packet();
+        let forged_uuid = bundles[37].uuid;
+
+        let (verified, rejected) = BundleSigverifier::default().verify(bundles);
+        assert_eq!(verified.len(), 63);
+        assert_eq!(
+            rejected
+                .iter()
+                .map(|bundle| bundle.uuid)
+                .collect::<Vec<_>>(),
+            vec![forged_uuid]
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! Simulates bundles against a bank without committing them.
+//!
+//! BundleStage simulates bundles before locking their accounts so bundles that would fail don't
+//! take account locks or leader time away from ones that pay. Transactions are executed one at a
+//! time, each one seeing the accounts written by the ones before it, the same way the bundle is
+//! executed for real. The bank's account locks aren't taken, so a simulation run while
+//! BankingStage is executing can see state that changes before the bundle is executed.
+
+use {
+    solana_program_runtime::timings::ExecuteTimings,
+    solana_runtime::{account_overrides::AccountOverrides, accounts::AccountLocks, bank::Bank},
+    solana_sdk::{
+        account::ReadableAccount,
+        bundle::sanitized::SanitizedBundle,
+        clock::MAX_PROCESSING_AGE,
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::{self, TransactionError},
+    },
+    std::{collections::HashSet, sync::Mutex},
+};
+
+/// Outcome of simulating one transaction in a bundle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimulatedBundleTransaction {
+    pub signature: Signature,
+    /// `Err(TransactionError::SkippedExecution)` for transactions after the one that failed.
+    pub result: transaction::Result<()>,
+    pub units_consumed: u64,
+}
+
+/// Outcome of simulating a bundle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BundleSimulationResult {
+    /// One result per transaction, in bundle order.
+    pub transaction_results: Vec<SimulatedBundleTransaction>,
+    /// Compute units consumed by all executed transactions.
+    pub units_consumed: u64,
+    /// Net lamports the bundle moved into the tip accounts.
+    pub tip_lamports: u64,
+    /// A tip account the bundle left with fewer lamports than it started with.
+    pub drained_tip_account: Option<Pubkey>,
+}
+
+impl BundleSimulationResult {
+    /// Returns true if every transaction in the bundle succeeded.
+    pub fn is_ok(&self) -> bool {
+        self.transaction_results
+            .iter()
+            .all(|transaction| transaction.result.is_ok())
+    }
+
+    /// Returns the index and error of the transaction that failed the bundle.
+    pub fn error(&self) -> Option<(usize, &TransactionError)> {
+        self.transaction_results
+            .iter()
+            .enumerate()
+            .find_map(|(index, transaction)| match &transaction.result {
+                Err(TransactionError::SkippedExecution) => None,
+                Err(e) => Some((index, e)),
+                Ok(()) => None,
+            })
+    }
+}
+
+/// Executes the bundle's transactions in order on `bank` without committing them, stopping at
+/// the first failure.
+pub fn simulate_bundle(
+    bank: &Bank,
+    bundle: &SanitizedBundle,
+    tip_accounts: &HashSet<Pubkey>,
+) -> BundleSimulationResult {
+    let mut account_overrides = AccountOverrides::default();
+    let mut transaction_results = Vec::with_capacity(bundle.transactions.len());
+    let mut units_consumed: u64 = 0;
+
+    for transaction in &bundle.transactions {
+        let mut batch = bank.prepare_sequential_sanitized_batch_with_results(
+            std::slice::from_ref(transaction),
+            Some(Mutex::new(AccountLocks::default())),
+        );
+        // the accounts were locked in the throwaway AccountLocks above, not the bank's
+        batch.set_needs_unlock(false);
+
+        let (result, transaction_units) = match &batch.lock_results()[0] {
+            Err(e) => (Err(e.clone()), 0),
+            Ok(()) => {
+                let mut output = bank.load_and_execute_transactions(
+                    &batch,
+                    MAX_PROCESSING_AGE,
+                    false,
+                    false,
+                    false,
+                    &mut ExecuteTimings::default(),
+                    Some(&account_overrides),
+                    None,
+                );
+                let result = output.execution_results[0].flattened_result();
+                let transaction_units = output.execution_results[0]
+                    .details()
+                    .map(|details| details.executed_units)
+                    .unwrap_or_default();
+                if result.is_ok() {
+                    for (pubkey, account) in bank.collect_accounts_to_store(
+                        batch.sanitized_transactions(),
+                        &output.execution_results,
+                        &mut output.loaded_transactions,
+                    ) {
+                        account_overrides.set_account(pubkey, Some(account.clone()));
+                    }
+                }
+                (result, transaction_units)
+            }
+        };
+
+        units_consumed = units_consumed.saturating_add(transaction_units);
+        let failed = result.is_err();
+        transaction_results.push(SimulatedBundleTransaction {
+            signature: *transaction.signature(),
+            result,
+            units_consumed: transaction_units,
+        });
+        if failed {
+            break;
+        }
+    }
+
+    let executed = transaction_results.len();
+    transaction_results.extend(bundle.transactions[executed..].iter().map(|transaction| {
+        SimulatedBundleTransaction {
+            signature: *transaction.signature(),
+            result: Err(TransactionError::SkippedExecution),
+            units_consumed: 0,
+        }
+    }));
+
+    let mut drained_tip_account = None;
+    let (pre_tip_lamports, post_tip_lamports) = tip_accounts.iter().fold(
+        (0u64, 0u64),
+        |(pre_tip_lamports, post_tip_lamports), tip_account| {
+            let pre_balance = bank.get_balance(tip_account);
+            let post_balance = account_overrides
+                .get(tip_account)
+                .map(|account| account.lamports())
+                .unwrap_or(pre_balance);
+            if post_balance < pre_balance {
+                drained_tip_account = Some(*tip_account);
+            }
+            (
+                pre_tip_lamports.saturating_add(pre_balance),
+                post_tip_lamports.saturating_add(post_balance),
+            )
+        },
+    );
+
+    BundleSimulationResult {
+        transaction_results,
+        units_consumed,
+        tip_lamports: post_tip_lamports.saturating_sub(pre_tip_lamports),
+        drained_tip_account,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_ledger::genesis_utils::{create_genesis_config, GenesisConfigInfo},
+        solana_sdk::{
+            signature::{Keypair, Signer},
+            system_transaction::transfer,
+            transaction::SanitizedTransaction,
+        },
+    };
+
+    fn new_bundle(transactions: Vec<SanitizedTransaction>) -> SanitizedBundle {
+        SanitizedBundle {
+            transactions,
+            expiry_slot: 0,
+        }
+    }
+
+    #[test]
+    fn test_simulate_bundle_sequential() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let bank = Bank::new_for_tests(&genesis_config);
+        let funded = Keypair::new();
+        let recipient = Pubkey::new_unique();
+
+        // the second transfer is paid for by the account the first one creates
+        let fund = SanitizedTransaction::from_transaction_for_tests(transfer(
+            &mint_keypair,
+            &funded.pubkey(),
+            100_000,
+            genesis_config.hash(),
+        ));
+        let spend = SanitizedTransaction::from_transaction_for_tests(transfer(
+            &funded,
+            &recipient,
+            10_000,
+            genesis_config.hash(),
+        ));
+
+        let result = simulate_bundle(
+            &bank,
+            &new_bundle(vec![fund.clone(), spend.clone()]),
+            &HashSet::default(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.error(), None);
+        assert!(result
+            .transaction_results
+            .iter()
+            .all(|transaction| transaction.units_consumed > 0));
+        assert_eq!(
+            result.units_consumed,
+            result
+                .transaction_results
+                .iter()
+                .map(|transaction| transaction.units_consumed)
+                .sum::<u64>()
+        );
+
+        let result = simulate_bundle(
+            &bank,
+            &new_bundle(vec![spend.clone(), fund.clone()]),
+            &HashSet::default(),
+        );
+        assert!(!result.is_ok());
+        assert_eq!(
+            result.error(),
+            Some((0, &TransactionError::AccountNotFound))
+        );
+        assert_eq!(
+            result.transaction_results,
+            vec![
+                SimulatedBundleTransaction {
+                    signature: *spend.signature(),
+                    result: Err(TransactionError::AccountNotFound),
+                    units_consumed: 0,
+                },
+                SimulatedBundleTransaction {
+                    signature: *fund.signature(),
+                    result: Err(TransactionError::SkippedExecution),
+                    units_consumed: 0,
+                },
+            ]
+        );
+        assert_eq!(result.units_consumed, 0);
+
+        // nothing was committed
+        assert_eq!(bank.get_balance(&funded.pubkey()), 0);
+        assert_eq!(bank.get_balance(&recipient), 0);
+    }
+
+    #[test]
+    fn test_simulate_bundle_tip_lamports() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let bank = Bank::new_for_tests(&genesis_config);
+        let tip_account = Pubkey::new_unique();
+        let tip_accounts = HashSet::from([tip_account, Pubkey::new_unique()]);
+
+        let bundle = new_bundle(vec![
+            SanitizedTransaction::from_transaction_for_tests(transfer(
+                &mint_keypair,
+                &tip_account,
+                5_000,
+                genesis_config.hash(),
+            )),
+            SanitizedTransaction::from_transaction_for_tests(transfer(
+                &mint_keypair,
+                &Pubkey::new_unique(),
+                7_000,
+                genesis_config.hash(),
+            )),
+        ]);
+        let result = simulate_bundle(&bank, &bundle, &tip_accounts);
+        assert!(result.is_ok());
+        assert_eq!(result.tip_lamports, 5_000);
+        assert_eq!(result.drained_tip_account, None);
+        assert_eq!(bank.get_balance(&tip_account), 0);
+    }
+
+    #[test]
+    fn test_simulate_bundle_drained_tip_account() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let bank = Bank::new_for_tests(&genesis_config);
+        // a tip account someone can sign for, standing in for a tip program that can be drained
+        let tip_keypair = Keypair::new();
+        bank.transfer(10_000, &mint_keypair, &tip_keypair.pubkey())
+            .unwrap();
+        let other_tip_account = Pubkey::new_unique();
+        let tip_accounts = HashSet::from([tip_keypair.pubkey(), other_tip_account]);
+
+        // the bundle pays a tip and then takes more than that back out
+        let bundle = new_bundle(vec![
+            SanitizedTransaction::from_transaction_for_tests(transfer(
+                &mint_keypair,
+                &tip_keypair.pubkey(),
+                5_000,
+                genesis_config.hash(),
+            )),
+            SanitizedTransaction::from_transaction_for_tests(transfer(
+                &tip_keypair,
+                &other_tip_account,
+                8_000,
+                genesis_config.hash(),
+            )),
+        ]);
+        let result = simulate_bundle(&bank, &bundle, &tip_accounts);
+        assert!(result.is_ok());
+        assert_eq!(result.drained_tip_account, Some(tip_keypair.pubkey()));
+    }
+}
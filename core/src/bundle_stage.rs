@@ -4,17 +4,37 @@
 use {
     crate::{
         banking_stage::{BatchedTransactionDetails, CommitTransactionDetails},
-        bundle_account_locker::{BundleAccountLocker, BundleAccountLockerResult, LockedBundle},
-        bundle_sanitizer::{get_sanitized_bundle, BundleSanitizerError},
-        bundle_stage_leader_stats::{BundleStageLeaderSlotTrackingMetrics, BundleStageLeaderStats},
+        bundle_account_locker::{
+            BundleAccountLocker, BundleAccountLockerResult, BundleAccountReservation, LockedBundle,
+        },
+        bundle_filter::{
+            BundleFilter, BundleFilterRejection, ProtectedAccounts, ProtectedAccountsConfig,
+        },
+        bundle_queue::{
+            bundle_tip_lamports, BundleId, BundlePriority, BundleQueue,
+            DEFAULT_BUNDLE_QUEUE_CAPACITY,
+        },
+        bundle_sanitizer::{
+            get_sanitized_bundle, precheck_bundle, BundleLimits, BundleSanitizerError,
+        },
+        bundle_sigverify::BundleSigverifier,
+        bundle_simulation::simulate_bundle,
+        bundle_stage_leader_stats::{
+            BundleOutcome, BundleStageLeaderSlotTrackingMetrics, BundleStageLeaderStats,
+            BundleTimings,
+        },
         consensus_cache_updater::ConsensusCacheUpdater,
         leader_slot_banking_stage_timing_metrics::RecordTransactionsTimings,
-        packet_bundle::PacketBundle,
-        proxy::block_engine_stage::BlockBuilderFeeInfo,
+        packet_bundle::{oldest_bundle_age, BundleQueueCounts, BundleQueueStats, PacketBundle},
+        proxy::{
+            block_engine_stage::{BlockBuilderFeeInfo, BlockEngineConfig},
+            DEFAULT_BUNDLE_CHANNEL_CAPACITY,
+        },
         qos_service::QosService,
         tip_manager::TipManager,
     },
-    crossbeam_channel::{Receiver, RecvTimeoutError},
+    crossbeam_channel::{unbounded, Receiver, RecvTimeoutError},
+    rayon::{prelude::*, ThreadPool, ThreadPoolBuilder},
     solana_entry::entry::hash_transactions,
     solana_gossip::cluster_info::ClusterInfo,
     solana_ledger::{
@@ -35,13 +55,15 @@ use {
             TransactionBalancesSet, TransactionExecutionResult,
         },
         bank_utils,
+        block_cost_limits::MAX_BLOCK_UNITS,
         cost_model::{CostModel, TransactionCost},
         transaction_batch::TransactionBatch,
         vote_sender_types::ReplayVoteSender,
     },
     solana_sdk::{
         bundle::{
-            error::BundleExecutionError, sanitized::SanitizedBundle,
+            error::{BundleExecutionError, TipPaymentError},
+            sanitized::SanitizedBundle,
             utils::check_bundle_lock_results,
         },
         clock::{Slot, DEFAULT_TICKS_PER_SLOT, MAX_PROCESSING_AGE},
@@ -65,16 +87,136 @@ use {
 };
 
 const MAX_BUNDLE_RETRY_DURATION: Duration = Duration::from_millis(10);
+/// Default time a bundle's transactions may spend executing before the bundle is abandoned.
+pub const DEFAULT_MAX_BUNDLE_EXECUTION_DURATION: Duration = Duration::from_millis(30);
+/// Default number of times a bundle whose accounts stayed in use by BankingStage is queued again.
+pub const DEFAULT_MAX_BUNDLE_CONTENTION_RETRIES: usize = 3;
+/// Default share of a block's compute units bundles may use.
+pub const DEFAULT_MAX_BUNDLE_BLOCK_CU_RATIO: f64 = 1.0;
+
+/// Default number of slots before its leader slots the validator starts holding on to bundles.
+/// Bundles received earlier than that are dropped.
+pub const DEFAULT_BUNDLE_LEADER_LOOKAHEAD_SLOTS: u64 = 4;
+/// Default number of threads executing bundles. Bundles that don't conflict with each other are
+/// executed in parallel, one per thread.
+pub const DEFAULT_BUNDLE_EXECUTION_THREADS: usize = 2;
+/// How long BundleStage waits for its execution threads to exit once it's shut down.
+const EXECUTION_THREADS_EXIT_TIMEOUT: Duration = Duration::from_secs(1);
 const SLOT_BOUNDARY_CHECK_PERIOD: Duration = Duration::from_millis(10);
 
+/// BundleStage settings taken from the block engine config. The defaults are used when no block
+/// engine is configured.
+#[derive(Clone, Debug)]
+pub struct BundleStageConfig {
+    /// Max number of bundle messages queued between the block engine stage and BundleStage.
+    pub bundle_channel_capacity: usize,
+    /// Max number of bundles held on to before the lowest tipping ones are evicted.
+    pub bundle_queue_capacity: usize,
+    /// Whether bundles are simulated and dropped if they'd fail before their accounts are locked.
+    pub simulate_bundles: bool,
+    /// Whether a datapoint is reported for every bundle executed or dropped.
+    pub report_bundle_outcomes: bool,
+    pub bundle_limits: BundleLimits,
+    pub bundle_leader_lookahead_slots: u64,
+    pub bundle_filter: BundleFilter,
+    /// Resolved against the working bank when the validator starts, see [`ProtectedAccounts`].
+    pub protected_accounts: ProtectedAccountsConfig,
+    pub bundle_execution_threads: usize,
+    pub max_bundle_execution_duration: Duration,
+    pub max_bundle_contention_retries: usize,
+    pub max_bundle_block_cu_ratio: f64,
+}
+
+impl Default for BundleStageConfig {
+    fn default() -> Self {
+        Self {
+            bundle_channel_capacity: DEFAULT_BUNDLE_CHANNEL_CAPACITY,
+            bundle_queue_capacity: DEFAULT_BUNDLE_QUEUE_CAPACITY,
+            simulate_bundles: true,
+            report_bundle_outcomes: false,
+            bundle_limits: BundleLimits::default(),
+            bundle_leader_lookahead_slots: DEFAULT_BUNDLE_LEADER_LOOKAHEAD_SLOTS,
+            bundle_filter: BundleFilter::default(),
+            protected_accounts: ProtectedAccountsConfig::default(),
+            bundle_execution_threads: DEFAULT_BUNDLE_EXECUTION_THREADS,
+            max_bundle_execution_duration: DEFAULT_MAX_BUNDLE_EXECUTION_DURATION,
+            max_bundle_contention_retries: DEFAULT_MAX_BUNDLE_CONTENTION_RETRIES,
+            max_bundle_block_cu_ratio: DEFAULT_MAX_BUNDLE_BLOCK_CU_RATIO,
+        }
+    }
+}
+
+impl From<&BlockEngineConfig> for BundleStageConfig {
+    fn from(block_engine_config: &BlockEngineConfig) -> Self {
+        Self {
+            bundle_channel_capacity: block_engine_config.bundle_channel_capacity,
+            bundle_queue_capacity: block_engine_config.bundle_queue_capacity,
+            simulate_bundles: block_engine_config.simulate_bundles,
+            report_bundle_outcomes: block_engine_config.report_bundle_outcomes,
+            bundle_limits: block_engine_config.bundle_limits,
+            bundle_leader_lookahead_slots: block_engine_config.bundle_leader_lookahead_slots,
+            bundle_filter: block_engine_config.bundle_filter.clone(),
+            protected_accounts: block_engine_config.protected_accounts.clone(),
+            bundle_execution_threads: block_engine_config.bundle_execution_threads,
+            max_bundle_execution_duration: block_engine_config.max_bundle_execution_duration,
+            max_bundle_contention_retries: block_engine_config.max_bundle_contention_retries,
+            max_bundle_block_cu_ratio: block_engine_config.max_bundle_block_cu_ratio,
+        }
+    }
+}
+
 type BundleStageResult<T> = Result<T, BundleExecutionError>;
 
+/// What happened to a transaction in a bundle that was discarded because one of its transactions
+/// failed. Nothing in a discarded bundle is committed or recorded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum BundleTransactionOutcome {
+    /// Executed successfully, but discarded along with the rest of the bundle.
+    Discarded,
+    /// Failed, discarding the bundle.
+    Failed(TransactionError),
+    /// Not executed before the bundle was discarded.
+    NotExecuted,
+}
+
+/// Outcome of every transaction in a discarded bundle of `num_transactions`, given the results of
+/// the chunk starting at `chunk_start` that failed. Every transaction before the chunk executed
+/// successfully.
+fn discarded_bundle_outcomes(
+    num_transactions: usize,
+    chunk_start: usize,
+    chunk_results: &[transaction::Result<()>],
+) -> Vec<BundleTransactionOutcome> {
+    let chunk_outcomes = chunk_results.iter().map(|result| match result {
+        Ok(()) => BundleTransactionOutcome::Discarded,
+        // locked out of the chunk, it would have run in a later one
+        Err(TransactionError::AccountInUse | TransactionError::BundleNotContinuous) => {
+            BundleTransactionOutcome::NotExecuted
+        }
+        Err(e) => BundleTransactionOutcome::Failed(e.clone()),
+    });
+    std::iter::repeat(BundleTransactionOutcome::Discarded)
+        .take(chunk_start)
+        .chain(chunk_outcomes)
+        .chain(std::iter::repeat(BundleTransactionOutcome::NotExecuted))
+        .take(num_transactions)
+        .collect()
+}
+
 // Stats emitted periodically
 struct BundleStageLoopStats {
     last_report: Instant,
 
     num_bundles_received: u64,
     num_bundles_dropped: u64,
+    num_bundles_dropped_not_leader: u64,
+    num_bundles_dropped_precheck: u64,
+    num_bundles_dropped_invalid_signature: u64,
+    num_bundles_dropped_disabled: u64,
+    num_bundles_dropped_duplicate: u64,
+    num_bundles_evicted_expired: u64,
+    num_bundles_evicted_lower_tip: u64,
+    num_bundles_rejected_lower_tip: u64,
     receive_and_buffer_bundles_elapsed_us: u64,
     process_buffered_bundles_elapsed_us: u64,
 }
@@ -85,6 +227,14 @@ impl Default for BundleStageLoopStats {
             last_report: Instant::now(),
             num_bundles_received: 0,
             num_bundles_dropped: 0,
+            num_bundles_dropped_not_leader: 0,
+            num_bundles_dropped_precheck: 0,
+            num_bundles_dropped_invalid_signature: 0,
+            num_bundles_dropped_disabled: 0,
+            num_bundles_dropped_duplicate: 0,
+            num_bundles_evicted_expired: 0,
+            num_bundles_evicted_lower_tip: 0,
+            num_bundles_rejected_lower_tip: 0,
             receive_and_buffer_bundles_elapsed_us: 0,
             process_buffered_bundles_elapsed_us: 0,
         }
@@ -92,13 +242,54 @@ impl Default for BundleStageLoopStats {
 }
 
 impl BundleStageLoopStats {
-    fn maybe_report(&mut self, id: u32, period: Duration) {
+    /// Returns true if the stats were reported.
+    fn maybe_report(&mut self, id: u32, period: Duration) -> bool {
         if self.last_report.elapsed() > period {
             datapoint_info!(
                 "bundle_stage-loop_stats",
                 ("id", id, i64),
                 ("num_bundles_received", self.num_bundles_received, i64),
                 ("num_bundles_dropped", self.num_bundles_dropped, i64),
+                (
+                    "num_bundles_dropped_not_leader",
+                    self.num_bundles_dropped_not_leader,
+                    i64
+                ),
+                (
+                    "num_bundles_dropped_precheck",
+                    self.num_bundles_dropped_precheck,
+                    i64
+                ),
+                (
+                    "num_bundles_dropped_invalid_signature",
+                    self.num_bundles_dropped_invalid_signature,
+                    i64
+                ),
+                (
+                    "num_bundles_dropped_disabled",
+                    self.num_bundles_dropped_disabled,
+                    i64
+                ),
+                (
+                    "num_bundles_dropped_duplicate",
+                    self.num_bundles_dropped_duplicate,
+                    i64
+                ),
+                (
+                    "num_bundles_evicted_expired",
+                    self.num_bundles_evicted_expired,
+                    i64
+                ),
+                (
+                    "num_bundles_evicted_lower_tip",
+                    self.num_bundles_evicted_lower_tip,
+                    i64
+                ),
+                (
+                    "num_bundles_rejected_lower_tip",
+                    self.num_bundles_rejected_lower_tip,
+                    i64
+                ),
                 (
                     "receive_and_buffer_bundles_elapsed_us",
                     self.receive_and_buffer_bundles_elapsed_us,
@@ -111,7 +302,70 @@ impl BundleStageLoopStats {
                 ),
             );
             *self = BundleStageLoopStats::default();
+            return true;
+        }
+        false
+    }
+}
+
+/// Compute units committed by bundles to the block being built, so bundles can be kept to a share
+/// of each block. The count starts over whenever the working bank changes.
+struct BundleBlockUnits {
+    max_units: u64,
+    slot: Slot,
+    units: u64,
+}
+
+impl BundleBlockUnits {
+    fn new(max_units: u64) -> Self {
+        Self {
+            max_units,
+            slot: Slot::default(),
+            units: 0,
+        }
+    }
+
+    fn max_units(&self) -> u64 {
+        self.max_units
+    }
+
+    /// Returns the compute units bundles committed to `slot`.
+    fn units(&mut self, slot: Slot) -> u64 {
+        if slot != self.slot {
+            self.slot = slot;
+            self.units = 0;
         }
+        self.units
+    }
+
+    fn add(&mut self, slot: Slot, units: u64) {
+        self.units = self.units(slot).saturating_add(units);
+    }
+}
+
+/// Whether the tip accounts were brought up to date for the current leader slot. They're checked
+/// at the start of every leader slot. When that fails bundles aren't executed for the rest of the
+/// slot, and the update is tried again in the next one.
+#[derive(Default)]
+struct TipAccountsUpdate {
+    /// Last leader slot the tip accounts were checked in.
+    slot: Slot,
+    failed: bool,
+}
+
+impl TipAccountsUpdate {
+    /// Returns true if the tip accounts should be checked before executing bundles in `slot`.
+    fn should_update(&self, slot: Slot) -> bool {
+        slot != self.slot
+    }
+
+    fn record(&mut self, slot: Slot, result: &BundleStageResult<()>) {
+        self.slot = slot;
+        self.failed = result.is_err();
+    }
+
+    fn failed(&self) -> bool {
+        self.failed
     }
 }
 
@@ -140,7 +394,26 @@ impl BundleStage {
         tip_manager: TipManager,
         bundle_account_locker: BundleAccountLocker,
         block_builder_fee_info: &Arc<Mutex<BlockBuilderFeeInfo>>,
+        bundle_queue_stats: Arc<BundleQueueStats>,
+        bundle_filter_receiver: Receiver<BundleFilter>,
+        // Built from `config.protected_accounts`.
+        protected_accounts: ProtectedAccounts,
+        bundles_enabled: Arc<AtomicBool>,
+        config: BundleStageConfig,
     ) -> Self {
+        let BundleStageConfig {
+            bundle_queue_capacity,
+            simulate_bundles,
+            report_bundle_outcomes,
+            bundle_limits,
+            bundle_leader_lookahead_slots,
+            bundle_filter,
+            bundle_execution_threads,
+            max_bundle_execution_duration,
+            max_bundle_contention_retries,
+            max_bundle_block_cu_ratio,
+            ..
+        } = config;
         Self::start_bundle_thread(
             cluster_info,
             poh_recorder,
@@ -152,7 +425,21 @@ impl BundleStage {
             tip_manager,
             bundle_account_locker,
             MAX_BUNDLE_RETRY_DURATION,
+            max_bundle_execution_duration,
+            max_bundle_contention_retries,
+            max_bundle_block_cu_ratio,
             block_builder_fee_info,
+            bundle_queue_stats,
+            bundle_queue_capacity,
+            simulate_bundles,
+            report_bundle_outcomes,
+            bundle_limits,
+            bundle_leader_lookahead_slots,
+            bundle_filter,
+            bundle_filter_receiver,
+            protected_accounts,
+            bundles_enabled,
+            bundle_execution_threads,
         )
     }
 
@@ -168,7 +455,21 @@ impl BundleStage {
         tip_manager: TipManager,
         bundle_account_locker: BundleAccountLocker,
         max_bundle_retry_duration: Duration,
+        max_bundle_execution_duration: Duration,
+        max_bundle_contention_retries: usize,
+        max_bundle_block_cu_ratio: f64,
         block_builder_fee_info: &Arc<Mutex<BlockBuilderFeeInfo>>,
+        bundle_queue_stats: Arc<BundleQueueStats>,
+        bundle_queue_capacity: usize,
+        simulate_bundles: bool,
+        report_bundle_outcomes: bool,
+        bundle_limits: BundleLimits,
+        leader_lookahead_slots: u64,
+        bundle_filter: BundleFilter,
+        bundle_filter_receiver: Receiver<BundleFilter>,
+        protected_accounts: ProtectedAccounts,
+        bundles_enabled: Arc<AtomicBool>,
+        num_execution_threads: usize,
     ) -> Self {
         const BUNDLE_STAGE_ID: u32 = 10_000;
         let poh_recorder = poh_recorder.clone();
@@ -190,7 +491,21 @@ impl BundleStage {
                     tip_manager,
                     bundle_account_locker,
                     max_bundle_retry_duration,
+                    max_bundle_execution_duration,
+                    max_bundle_contention_retries,
+                    max_bundle_block_cu_ratio,
                     block_builder_fee_info,
+                    bundle_queue_stats,
+                    bundle_queue_capacity,
+                    simulate_bundles,
+                    report_bundle_outcomes,
+                    bundle_limits,
+                    leader_lookahead_slots,
+                    bundle_filter,
+                    bundle_filter_receiver,
+                    protected_accounts,
+                    bundles_enabled,
+                    num_execution_threads,
                 );
             })
             .unwrap();
@@ -281,8 +596,13 @@ impl BundleStage {
 
     /// Calculates QoS and reserves compute space for the bundle. If the bundle succeeds, commits
     /// the results to the cost tracker. If the bundle fails, rolls back any QoS changes made.
+    /// Bundles that don't fit in the rest of the block fail with ExceedsBlockSpace and can be
+    /// retried in a later slot. If `tip_manager` is set, bundles that didn't pay the tip they
+    /// declare or the configured minimum fail without being committed. Bundles containing a
+    /// transaction that already landed fail before taking any block space.
     /// Ensure that SanitizedBundle was returned by BundleAccountLocker to avoid parallelism issues
     /// with banking stage
+    /// Returns the compute units the committed bundle consumed.
     fn update_qos_and_execute_record_commit_bundle(
         sanitized_bundle: &SanitizedBundle,
         recorder: &TransactionRecorder,
@@ -292,9 +612,28 @@ impl BundleStage {
         bank_start: &BankStart,
         bundle_stage_leader_stats: &mut BundleStageLeaderStats,
         max_bundle_retry_duration: &Duration,
-    ) -> BundleStageResult<()> {
+        max_bundle_execution_duration: &Duration,
+        tip_manager: Option<&TipManager>,
+    ) -> BundleStageResult<u64> {
+        // bundles retried after hitting the end of a slot may be executed in a later slot
+        if sanitized_bundle.expiry_slot < bank_start.working_bank.slot() {
+            return Err(BundleExecutionError::Expired(sanitized_bundle.expiry_slot));
+        }
+
+        // a transaction may have landed through BankingStage or an earlier bundle since the bundle
+        // was sanitized
+        if let Some(transaction) = sanitized_bundle.transactions.iter().find(|transaction| {
+            bank_start
+                .working_bank
+                .has_signature(transaction.signature())
+        }) {
+            return Err(BundleExecutionError::DuplicateTransaction(
+                *transaction.signature(),
+            ));
+        }
+
         if sanitized_bundle.transactions.is_empty() {
-            return Ok(());
+            return Ok(0);
         }
 
         let tx_costs = qos_service.compute_transaction_costs(sanitized_bundle.transactions.iter());
@@ -311,7 +650,18 @@ impl BundleStage {
                 transactions_qos_results.iter(),
                 &bank_start.working_bank,
             );
-            return Err(BundleExecutionError::ExceedsCostModel);
+            // the total account data limit spans blocks, every other limit is per block
+            let exceeds_total_limit = transactions_qos_results.iter().any(|result| {
+                matches!(
+                    result,
+                    Err(TransactionError::WouldExceedAccountDataTotalLimit)
+                )
+            });
+            return Err(if exceeds_total_limit {
+                BundleExecutionError::ExceedsCostModel
+            } else {
+                BundleExecutionError::ExceedsBlockSpace
+            });
         }
 
         // accumulates QoS to metrics
@@ -330,6 +680,8 @@ impl BundleStage {
             bank_start,
             bundle_stage_leader_stats,
             max_bundle_retry_duration,
+            max_bundle_execution_duration,
+            tip_manager,
         ) {
             Ok(commit_transaction_details) => {
                 // NOTE: Assumptions made on the QoS transaction costs:
@@ -354,7 +706,13 @@ impl BundleStage {
                 qos_service.accumulate_actual_execute_cu(cu);
                 qos_service.accumulate_actual_execute_time(us);
                 qos_service.report_metrics(bank_start.working_bank.clone());
-                Ok(())
+                Ok(commit_transaction_details
+                    .iter()
+                    .map(|details| match details {
+                        CommitTransactionDetails::Committed { compute_units } => *compute_units,
+                        CommitTransactionDetails::NotCommitted => 0,
+                    })
+                    .sum())
             }
             Err(e) => {
                 QosService::remove_transaction_costs(
@@ -374,21 +732,21 @@ impl BundleStage {
         bank_start: &BankStart,
         bundle_stage_leader_stats: &mut BundleStageLeaderStats,
         max_bundle_retry_duration: &Duration,
-    ) -> BundleStageResult<Vec<AllExecutionResults>> {
+        max_bundle_execution_duration: &Duration,
+        tip_manager: Option<&TipManager>,
+    ) -> BundleStageResult<(Vec<AllExecutionResults>, u64)> {
         let mut account_overrides = AccountOverrides::default();
 
         let mut execution_results = Vec::new();
         let mut mint_decimals: HashMap<Pubkey, u8> = HashMap::new();
 
-        let BankStart {
-            working_bank: bank,
-            bank_creation_time,
-        } = bank_start;
+        let bank = &bank_start.working_bank;
 
         let mut chunk_start = 0;
         let start_time = Instant::now();
+        let mut execution_elapsed = Duration::ZERO;
         while chunk_start != sanitized_bundle.transactions.len() {
-            if !Bank::should_bank_still_be_processing_txs(bank_creation_time, bank.ns_per_slot) {
+            if !Self::bank_can_record(bank_start) {
                 return Err(BundleExecutionError::PohMaxHeightError);
             }
 
@@ -402,8 +760,10 @@ impl BundleStage {
             // Assuming the SanitizedBundle was locked with the BundleAccountLocker, that race
             // condition shall be prevented as it holds an extra set of locks until the entire
             // bundle is processed.
+            // The runtime can't be interrupted mid-transaction, so transactions are executed one
+            // at a time and the execution budget is checked in between them.
             // ************************************************************************
-            let chunk_end = std::cmp::min(sanitized_bundle.transactions.len(), chunk_start + 128);
+            let chunk_end = chunk_start + 1;
             let chunk = &sanitized_bundle.transactions[chunk_start..chunk_end];
             let batch = bank.prepare_sequential_sanitized_batch_with_results(chunk, None);
 
@@ -457,6 +817,7 @@ impl BundleStage {
                     .load_execute_us,
                 load_execute_time.as_us()
             );
+            execution_elapsed = execution_elapsed.saturating_add(load_execute_time.as_duration());
             bundle_stage_leader_stats
                 .transaction_errors()
                 .accumulate(&load_and_execute_transactions_output.error_counters);
@@ -481,6 +842,18 @@ impl BundleStage {
                 bundle_stage_leader_stats
                     .bundle_stage_stats()
                     .increment_num_execution_failures(1);
+                Self::report_discarded_bundle(
+                    sanitized_bundle,
+                    &discarded_bundle_outcomes(
+                        sanitized_bundle.transactions.len(),
+                        chunk_start,
+                        &load_and_execute_transactions_output
+                            .execution_results
+                            .iter()
+                            .map(TransactionExecutionResult::flattened_result)
+                            .collect::<Vec<_>>(),
+                    ),
+                );
 
                 return Err(e);
             }
@@ -512,6 +885,30 @@ impl BundleStage {
                 continue;
             }
 
+            // the transaction ran to completion but the bundle is out of time; it's abandoned
+            // before anything is recorded or committed, even if it was the last transaction
+            if execution_elapsed > *max_bundle_execution_duration {
+                warn!(
+                    "bundle timed out after executing {} of {} transactions in {:?}",
+                    chunk_end,
+                    sanitized_bundle.transactions.len(),
+                    execution_elapsed
+                );
+                Self::report_discarded_bundle(
+                    sanitized_bundle,
+                    &discarded_bundle_outcomes(
+                        sanitized_bundle.transactions.len(),
+                        chunk_start,
+                        &load_and_execute_transactions_output
+                            .execution_results
+                            .iter()
+                            .map(TransactionExecutionResult::flattened_result)
+                            .collect::<Vec<_>>(),
+                    ),
+                );
+                return Err(BundleExecutionError::ExecutionTimeout(execution_elapsed));
+            }
+
             // *********************************************************************************
             // Cache results so next iterations of bundle execution can load cached state
             // instead of using AccountsDB which contains stale execution data.
@@ -559,12 +956,23 @@ impl BundleStage {
 
             drop(batch);
         }
-        Ok(execution_results)
+
+        // the bundle fails like any other if it didn't pay the tip it promised
+        let tip_lamports = match tip_manager {
+            Some(tip_manager) => tip_manager.check_tip_paid(
+                &sanitized_bundle.transactions,
+                bank,
+                &account_overrides,
+            )?,
+            None => 0,
+        };
+        Ok((execution_results, tip_lamports))
     }
 
     /// Executes a bundle, where all transactions in the bundle are executed all-or-nothing.
     /// Executes all transactions until the end or the first failure. The account state between
-    /// iterations is cached to a temporary HashMap to be used on successive runs
+    /// iterations is cached to a temporary HashMap to be used on successive runs.
+    /// Nothing is recorded to PoH or committed to the bank unless every transaction succeeded.
     #[allow(clippy::too_many_arguments)]
     fn execute_record_commit_bundle(
         sanitized_bundle: &SanitizedBundle,
@@ -574,25 +982,34 @@ impl BundleStage {
         bank_start: &BankStart,
         bundle_stage_leader_stats: &mut BundleStageLeaderStats,
         max_bundle_retry_duration: &Duration,
+        max_bundle_execution_duration: &Duration,
+        tip_manager: Option<&TipManager>,
     ) -> BundleStageResult<Vec<CommitTransactionDetails>> {
-        let execution_results = Self::execute_bundle(
+        let (execution_results, tip_lamports) = Self::execute_bundle(
             sanitized_bundle,
             transaction_status_sender,
             bank_start,
             bundle_stage_leader_stats,
             max_bundle_retry_duration,
+            max_bundle_execution_duration,
+            tip_manager,
         )?;
         // in order for bundle to succeed, it most have something to record + commit
         assert!(!execution_results.is_empty());
 
-        Self::record_commit_bundle(
+        let commit_transaction_details = Self::record_commit_bundle(
             execution_results,
             &bank_start.working_bank,
             recorder,
             bundle_stage_leader_stats,
             transaction_status_sender,
             gossip_vote_sender,
-        )
+        )?;
+        // only the tips of committed bundles count, failed ones never touched the bank
+        if let Some(tip_manager) = tip_manager {
+            tip_manager.record_committed_tip(&bank_start.working_bank, tip_lamports);
+        }
+        Ok(commit_transaction_details)
     }
 
     /// Records the entire bundle to PoH and if successful, commits all transactions to the Bank
@@ -612,13 +1029,19 @@ impl BundleStage {
         // not all together
         // *********************************************************************************
         debug!("grabbing freeze lock");
-        let (_freeze_lock, freeze_lock_time) = measure!(bank.freeze_lock(), "freeze_lock");
+        let (freeze_lock, freeze_lock_time) = measure!(bank.freeze_lock(), "freeze_lock");
         saturating_add_assign!(
             bundle_stage_leader_stats
                 .execute_and_commit_timings()
                 .freeze_lock_us,
             freeze_lock_time.as_us()
         );
+        // the bank may have been frozen while the bundle was executing. The freeze lock keeps it
+        // from being frozen until the bundle is committed, but the bundle can't go in a bank that
+        // already is; it's retried in the next one.
+        if *freeze_lock != Hash::default() {
+            return Err(BundleExecutionError::PohMaxHeightError);
+        }
 
         let (slot, mixins) = Self::prepare_poh_record_bundle(
             &bank.slot(),
@@ -744,24 +1167,14 @@ impl BundleStage {
         Ok(commit_transaction_details)
     }
 
-    /// Returns true if any of the transactions in a bundle mention one of the tip PDAs
-    fn bundle_touches_tip_pdas(
-        transactions: &[SanitizedTransaction],
-        tip_pdas: &HashSet<Pubkey>,
-    ) -> bool {
-        let mut bundle_touches_tip_pdas = false;
-        for tx in transactions {
-            if tx
-                .message()
-                .account_keys()
-                .iter()
-                .any(|a| tip_pdas.contains(a))
-            {
-                bundle_touches_tip_pdas = true;
-                break;
-            }
-        }
-        bundle_touches_tip_pdas
+    /// Returns false once the working bank's slot is over: its time is up, it reached its max tick
+    /// height or it was frozen. A bundle can't be recorded in it after that, so one isn't started.
+    fn bank_can_record(bank_start: &BankStart) -> bool {
+        Bank::should_bank_still_be_processing_txs(
+            &bank_start.bank_creation_time,
+            bank_start.working_bank.ns_per_slot,
+        ) && !bank_start.working_bank.is_complete()
+            && !bank_start.working_bank.is_frozen()
     }
 
     fn accumulate_execute_units_and_time(execute_timings: &ExecuteTimings) -> (u64, u64) {
@@ -809,64 +1222,13 @@ impl BundleStage {
         }
     }
 
-    /// When executed the first time, there's some accounts that need to be initialized.
-    /// This is only helpful for local testing, on testnet and mainnet these will never be executed.
-    /// TODO (LB): consider removing this for mainnet/testnet and move to program deployment?
-    fn get_initialize_tip_accounts_transactions(
-        bank: &Bank,
-        tip_manager: &TipManager,
-        cluster_info: &Arc<ClusterInfo>,
-    ) -> BundleStageResult<Vec<SanitizedTransaction>> {
-        let maybe_init_tip_payment_config_tx =
-            if tip_manager.should_initialize_tip_payment_program(bank) {
-                info!("building initialize_tip_payment_program_tx");
-                Some(tip_manager.initialize_tip_payment_program_tx(
-                    bank.last_blockhash(),
-                    &cluster_info.keypair(),
-                ))
-            } else {
-                None
-            };
-
-        let maybe_init_tip_distro_config_tx =
-            if tip_manager.should_initialize_tip_distribution_config(bank) {
-                info!("building initialize_tip_distribution_config_tx");
-                Some(tip_manager.initialize_tip_distribution_config_tx(
-                    bank.last_blockhash(),
-                    &cluster_info.keypair(),
-                ))
-            } else {
-                None
-            };
-
-        let maybe_init_tip_distro_account_tx = if tip_manager
-            .should_init_tip_distribution_account(bank)
-        {
-            info!("building init_tip_distribution_account_tx");
-            Some(tip_manager.init_tip_distribution_account_tx(bank.last_blockhash(), bank.epoch()))
-        } else {
-            None
-        };
-
-        let transactions = [
-            maybe_init_tip_payment_config_tx,
-            maybe_init_tip_distro_config_tx,
-            maybe_init_tip_distro_account_tx,
-        ]
-        .into_iter()
-        .flatten()
-        .collect::<Vec<SanitizedTransaction>>();
-
-        Ok(transactions)
-    }
-
     /// Execute all unprocessed bundles until no more left or POH max tick height is reached.
     /// For any bundles that didn't execute due to POH max tick height reached, add them
     /// back onto the front of unprocessed_bundles in reverse order to preserve original ordering
     #[allow(clippy::too_many_arguments)]
     fn execute_bundles_until_empty_or_end_of_slot(
         bundle_account_locker: &BundleAccountLocker,
-        unprocessed_bundles: &mut VecDeque<PacketBundle>,
+        unprocessed_bundles: &mut BundleQueue,
         blacklisted_accounts: &HashSet<Pubkey>,
         bank_start: &BankStart,
         consensus_accounts_cache: &HashSet<Pubkey>,
@@ -877,26 +1239,105 @@ impl BundleStage {
         qos_service: &QosService,
         tip_manager: &TipManager,
         max_bundle_retry_duration: &Duration,
-        last_tip_update_slot: &mut Slot,
+        max_bundle_execution_duration: &Duration,
+        max_bundle_contention_retries: usize,
+        bundle_block_units: &mut BundleBlockUnits,
+        tip_accounts_update: &mut TipAccountsUpdate,
         bundle_stage_leader_stats: &mut BundleStageLeaderStats,
         block_builder_fee_info: &Arc<Mutex<BlockBuilderFeeInfo>>,
+        simulate_bundles: bool,
+        bundle_limits: &BundleLimits,
+        bundle_filter: &BundleFilter,
+        protected_accounts: &ProtectedAccounts,
+        thread_pool: &ThreadPool,
     ) {
+        Self::drop_expired_bundles(
+            unprocessed_bundles,
+            bank_start.working_bank.slot(),
+            bundle_stage_leader_stats,
+        );
+
+        // between two of our leader slots the working bank can be frozen or out of ticks before
+        // the next one is set. the bundles stay queued as they are instead of being dequeued and
+        // failing, the slot check above still drops the ones that expire in the meantime.
+        if !Self::bank_can_record(bank_start) {
+            return;
+        }
+
+        // the tip accounts are brought up to date before any bundle in the slot can tip
+        let slot = bank_start.working_bank.slot();
+        if tip_accounts_update.should_update(slot) {
+            let result = Self::maybe_initialize_tip_accounts(
+                bundle_account_locker,
+                bank_start,
+                cluster_info,
+                recorder,
+                transaction_status_sender,
+                gossip_vote_sender,
+                qos_service,
+                tip_manager,
+                max_bundle_retry_duration,
+                max_bundle_execution_duration,
+                bundle_stage_leader_stats,
+            )
+            .and_then(|_| {
+                Self::maybe_change_tip_receiver(
+                    bundle_account_locker,
+                    bank_start,
+                    cluster_info,
+                    recorder,
+                    transaction_status_sender,
+                    gossip_vote_sender,
+                    qos_service,
+                    tip_manager,
+                    max_bundle_retry_duration,
+                    max_bundle_execution_duration,
+                    bundle_stage_leader_stats,
+                    block_builder_fee_info,
+                )
+            });
+            if let Err(e) = &result {
+                error!(
+                    "failed to update the tip accounts in slot {}, not executing bundles until the next slot: {}",
+                    slot, e
+                );
+            }
+            tip_accounts_update.record(slot, &result);
+        }
+        if tip_accounts_update.failed() {
+            let queued_bundles: Vec<PacketBundle> = unprocessed_bundles.drain().collect();
+            for packet_bundle in queued_bundles {
+                unprocessed_bundles.defer(packet_bundle, slot);
+            }
+            return;
+        }
+
+        let dequeued_at = Instant::now();
+        let tip_accounts = tip_manager.get_tip_accounts();
+        let queued_bundles: Vec<(PacketBundle, Option<BundleAccountReservation>)> =
+            unprocessed_bundles.drain_reserved().collect();
+        // reservations taken when bundles were admitted, released here if they don't sanitize
+        let mut admitted_reservations = HashMap::new();
         let (sanitized_bundles, sanitized_bundle_elapsed) = measure!(
-            unprocessed_bundles
-                .drain(..)
+            queued_bundles
                 .into_iter()
-                .filter_map(|packet_bundle| {
+                .filter_map(|(packet_bundle, reservation)| {
                     match get_sanitized_bundle(
                         &packet_bundle,
                         &bank_start.working_bank,
                         consensus_accounts_cache,
                         blacklisted_accounts,
+                        bundle_limits,
                         bundle_stage_leader_stats.transaction_errors(),
                     ) {
                         Ok(sanitized_bundle) => {
                             bundle_stage_leader_stats
                                 .bundle_stage_stats()
                                 .increment_sanitize_transaction_ok(1);
+                            if let Some(reservation) = reservation {
+                                admitted_reservations
+                                    .insert(BundleId::new(&packet_bundle), reservation);
+                            }
                             Some((packet_bundle, sanitized_bundle))
                         }
                         Err(BundleSanitizerError::VoteOnlyMode) => {
@@ -911,30 +1352,69 @@ impl BundleStage {
                                 .increment_sanitize_transaction_failed_precheck(1);
                             None
                         }
-                        Err(BundleSanitizerError::BlacklistedAccount) => {
+                        Err(BundleSanitizerError::TooManyTransactions(..)) => {
                             bundle_stage_leader_stats
                                 .bundle_stage_stats()
-                                .increment_sanitize_transaction_blacklisted_account(1);
+                                .increment_sanitize_transaction_too_many_transactions(1);
                             None
                         }
-                        Err(BundleSanitizerError::FailedToSerializeTransaction) => {
+                        Err(BundleSanitizerError::TooLarge(..)) => {
                             bundle_stage_leader_stats
                                 .bundle_stage_stats()
-                                .increment_sanitize_transaction_failed_to_serialize(1);
+                                .increment_sanitize_transaction_too_large(1);
+                            None
+                        }
+                        Err(BundleSanitizerError::BlacklistedAccount) => {
+                            bundle_stage_leader_stats
+                                .bundle_stage_stats()
+                                .increment_sanitize_transaction_blacklisted_account(1);
                             None
                         }
-                        Err(BundleSanitizerError::DuplicateTransaction) => {
+                        Err(BundleSanitizerError::FailedToSerializeTransaction) => {
                             bundle_stage_leader_stats
                                 .bundle_stage_stats()
-                                .increment_sanitize_transaction_duplicate_transaction(1);
+                                .increment_sanitize_transaction_failed_to_serialize(1);
                             None
                         }
+                        // rejected by precheck_bundle when the bundle was received
+                        Err(BundleSanitizerError::VoteTransaction)
+                        | Err(BundleSanitizerError::DuplicateTransaction) => None,
                         Err(BundleSanitizerError::FailedCheckTransactions) => {
                             bundle_stage_leader_stats
                                 .bundle_stage_stats()
                                 .increment_sanitize_transaction_failed_check(1);
                             None
                         }
+                        Err(BundleSanitizerError::BlockhashNotFound) => {
+                            let bundle_stage_stats = bundle_stage_leader_stats.bundle_stage_stats();
+                            bundle_stage_stats
+                                .increment_sanitize_transaction_blockhash_not_found(1);
+                            bundle_stage_stats.record_bundle_outcome(
+                                &packet_bundle.uuid,
+                                packet_bundle.batch.len(),
+                                0,
+                                bundle_tip_lamports(&packet_bundle, &tip_accounts),
+                                unprocessed_bundles.num_retries(&packet_bundle),
+                                BundleOutcome::BlockhashNotFound,
+                                None,
+                            );
+                            None
+                        }
+                        Err(BundleSanitizerError::InsufficientFundsForFee) => {
+                            let bundle_stage_stats = bundle_stage_leader_stats.bundle_stage_stats();
+                            bundle_stage_stats
+                                .increment_sanitize_transaction_insufficient_funds_for_fee(1);
+                            bundle_stage_stats.record_bundle_outcome(
+                                &packet_bundle.uuid,
+                                packet_bundle.batch.len(),
+                                0,
+                                bundle_tip_lamports(&packet_bundle, &tip_accounts),
+                                unprocessed_bundles.num_retries(&packet_bundle),
+                                BundleOutcome::InsufficientFundsForFee,
+                                None,
+                            );
+                            None
+                        }
                     }
                 })
                 .collect::<VecDeque<(PacketBundle, SanitizedBundle)>>(),
@@ -944,42 +1424,121 @@ impl BundleStage {
             .bundle_stage_stats()
             .increment_sanitize_bundle_elapsed_us(sanitized_bundle_elapsed.as_us());
 
+        // Bundles admitted while leader were reserved when they were queued. The others are
+        // reserved now, before they're filtered, simulated and prioritized, so BankingStage can't
+        // change the state a bundle is simulated against before it's locked for execution.
+        // Bundles are told apart by BundleId since bundles without a uuid all share the nil one.
+        let mut bundle_reservations: HashMap<BundleId, BundleAccountReservation> =
+            sanitized_bundles
+                .iter()
+                .filter_map(|(packet_bundle, sanitized_bundle)| {
+                    let bundle_id = BundleId::new(packet_bundle);
+                    admitted_reservations
+                        .remove(&bundle_id)
+                        .or_else(|| {
+                            bundle_account_locker
+                                .reserve_bundle_accounts(sanitized_bundle, &bank_start.working_bank)
+                                .ok()
+                        })
+                        .map(|reservation| (bundle_id, reservation))
+                })
+                .collect();
+
+        let sanitized_bundles = Self::drop_denied_bundles(
+            sanitized_bundles,
+            unprocessed_bundles,
+            bundle_filter,
+            protected_accounts,
+            &tip_accounts,
+            bundle_stage_leader_stats,
+        );
+        let sanitized_bundles = if simulate_bundles {
+            let (sanitized_bundles, simulate_bundles_elapsed) = measure!(
+                Self::drop_failing_bundles(
+                    sanitized_bundles,
+                    unprocessed_bundles,
+                    &bank_start.working_bank,
+                    &tip_accounts,
+                    bundle_stage_leader_stats,
+                ),
+                "simulate_bundles_elapsed"
+            );
+            bundle_stage_leader_stats
+                .bundle_stage_stats()
+                .increment_simulate_bundles_elapsed_us(simulate_bundles_elapsed.as_us());
+            sanitized_bundles
+        } else {
+            sanitized_bundles
+        };
+        // everything queued is re-sorted on every pass, including bundles received since the last
+        let sanitized_bundles =
+            Self::prioritize_bundles(sanitized_bundles, &tip_accounts, qos_service);
+        let sanitized_bundles = Self::defer_bundles_over_block_units(
+            sanitized_bundles,
+            unprocessed_bundles,
+            bundle_block_units,
+            bank_start.working_bank.slot(),
+            qos_service,
+            bundle_stage_leader_stats,
+        );
+        Self::release_dropped_bundle_reservations(
+            &mut bundle_reservations,
+            &sanitized_bundles,
+            bundle_stage_leader_stats,
+        );
+
         // Prepare locked bundles, which will RW lock accounts in sanitized_bundles so
         // BankingStage can't lock them. This adds a layer of protection since a transaction in a bundle
         // will not hold the AccountLocks through TransactionBatch across load-execute-commit cycle.
         // We collect here to ensure that all of the bundles are locked ahead of time for priority over
         // BankingStage
         #[allow(clippy::needless_collect)]
-        let (locked_bundles, locked_bundles_elapsed) = measure!(
+        let ((locked_bundles, lock_us), locked_bundles_elapsed): (
+            (Vec<BundleAccountLockerResult<LockedBundle>>, Vec<u64>),
+            _,
+        ) = measure!(
             sanitized_bundles
                 .iter()
                 .map(|(_, sanitized_bundle)| {
-                    bundle_account_locker
-                        .prepare_locked_bundle(sanitized_bundle, &bank_start.working_bank)
+                    let (locked_bundle, lock_elapsed) = measure!(
+                        bundle_account_locker
+                            .prepare_locked_bundle(sanitized_bundle, &bank_start.working_bank),
+                        "prepare_locked_bundle"
+                    );
+                    (locked_bundle, lock_elapsed.as_us())
                 })
-                .collect::<Vec<BundleAccountLockerResult<LockedBundle>>>(),
+                .unzip(),
             "locked_bundles_elapsed"
         );
         bundle_stage_leader_stats
             .bundle_stage_stats()
             .increment_locked_bundle_elapsed_us(locked_bundles_elapsed.as_us());
+        // the locked bundles hold the accounts from here on
+        for (_, reservation) in bundle_reservations.drain() {
+            bundle_stage_leader_stats
+                .bundle_stage_stats()
+                .record_bundle_reservation(reservation.held_for().as_micros() as u64);
+        }
 
-        let (execution_results, execute_locked_bundles_elapsed) = measure!(
+        let ((execution_results, execution_timings), execute_locked_bundles_elapsed): (
+            (Vec<BundleStageResult<u64>>, Vec<BundleTimings>),
+            _,
+        ) = measure!(
             Self::execute_locked_bundles(
-                bundle_account_locker,
                 locked_bundles,
                 bank_start,
-                cluster_info,
                 recorder,
                 transaction_status_sender,
                 gossip_vote_sender,
                 qos_service,
                 tip_manager,
                 max_bundle_retry_duration,
-                last_tip_update_slot,
+                max_bundle_execution_duration,
                 bundle_stage_leader_stats,
-                block_builder_fee_info
-            ),
+                thread_pool
+            )
+            .into_iter()
+            .unzip(),
             "execute_locked_bundles_elapsed"
         );
 
@@ -987,56 +1546,421 @@ impl BundleStage {
             .bundle_stage_stats()
             .increment_execute_locked_bundles_elapsed_us(execute_locked_bundles_elapsed.as_us());
 
+        // bundles that only failed because BankingStage held their accounts are tried again
+        let retry_contended: Vec<bool> = execution_results
+            .iter()
+            .zip(sanitized_bundles.iter())
+            .map(|(bundle_execution_result, (packet_bundle, _))| {
+                matches!(
+                    bundle_execution_result,
+                    Err(BundleExecutionError::MaxRetriesExceeded(_))
+                ) && packet_bundle.expiry_slot >= bank_start.working_bank.slot()
+                    && unprocessed_bundles.num_retries(packet_bundle)
+                        < max_bundle_contention_retries
+                    && Self::bank_can_record(bank_start)
+            })
+            .collect();
+
+        for (
+            (
+                ((bundle_execution_result, (packet_bundle, sanitized_bundle)), retry_contended),
+                timings,
+            ),
+            lock_us,
+        ) in execution_results
+            .iter()
+            .zip(sanitized_bundles.iter())
+            .zip(retry_contended.iter())
+            .zip(execution_timings)
+            .zip(lock_us)
+        {
+            if let Some(outcome) = BundleOutcome::from_execution_result(bundle_execution_result) {
+                let timings = BundleTimings {
+                    queued_us: dequeued_at
+                        .saturating_duration_since(packet_bundle.received_at)
+                        .as_micros() as u64,
+                    lock_us,
+                    total_us: packet_bundle.received_at.elapsed().as_micros() as u64,
+                    ..timings
+                };
+                bundle_stage_leader_stats
+                    .bundle_stage_stats()
+                    .record_bundle_outcome(
+                        &packet_bundle.uuid,
+                        sanitized_bundle.transactions.len(),
+                        *bundle_execution_result.as_ref().unwrap_or(&0),
+                        bundle_tip_lamports(packet_bundle, &tip_accounts),
+                        unprocessed_bundles.num_retries(packet_bundle),
+                        if *retry_contended {
+                            BundleOutcome::RetriedAccountLocks
+                        } else {
+                            outcome
+                        },
+                        Some(&timings),
+                    );
+            }
+        }
+
         execution_results
             .into_iter()
             .zip(sanitized_bundles.into_iter())
+            .zip(retry_contended.into_iter())
             .for_each(
-                |(bundle_execution_result, (packet_bundle, _))| match bundle_execution_result {
-                    Ok(_) => {
-                        bundle_stage_leader_stats
-                            .bundle_stage_stats()
-                            .increment_execution_results_ok(1);
-                    }
-                    Err(BundleExecutionError::PohMaxHeightError) => {
-                        bundle_stage_leader_stats
-                            .bundle_stage_stats()
-                            .increment_execution_results_poh_max_height(1);
-                        // retry the bundle
-                        unprocessed_bundles.push_back(packet_bundle);
-                    }
-                    Err(BundleExecutionError::TransactionFailure(_)) => {
-                        bundle_stage_leader_stats
-                            .bundle_stage_stats()
-                            .increment_execution_results_transaction_failures(1);
-                    }
-                    Err(BundleExecutionError::ExceedsCostModel) => {
-                        bundle_stage_leader_stats
-                            .bundle_stage_stats()
-                            .increment_execution_results_exceeds_cost_model(1);
-                    }
-                    Err(BundleExecutionError::TipError(_)) => {
-                        bundle_stage_leader_stats
-                            .bundle_stage_stats()
-                            .increment_execution_results_tip_errors(1);
-                    }
-                    Err(BundleExecutionError::Shutdown) => {}
-                    Err(BundleExecutionError::MaxRetriesExceeded(_)) => {
-                        bundle_stage_leader_stats
-                            .bundle_stage_stats()
-                            .increment_execution_results_max_retries(1);
-                    }
-                    Err(BundleExecutionError::LockError) => {
-                        bundle_stage_leader_stats
-                            .bundle_stage_stats()
-                            .increment_execution_results_lock_errors(1);
+                |((bundle_execution_result, (packet_bundle, _)), retry_contended)| {
+                    match bundle_execution_result {
+                        Ok(compute_units) => {
+                            bundle_stage_leader_stats
+                                .bundle_stage_stats()
+                                .increment_execution_results_ok(1);
+                            bundle_block_units.add(bank_start.working_bank.slot(), compute_units);
+                        }
+                        Err(BundleExecutionError::PohMaxHeightError) => {
+                            bundle_stage_leader_stats
+                                .bundle_stage_stats()
+                                .increment_execution_results_poh_max_height(1);
+                            // retry the bundle
+                            unprocessed_bundles.push(packet_bundle, bank_start.working_bank.slot());
+                        }
+                        Err(BundleExecutionError::TransactionFailure(_)) => {
+                            bundle_stage_leader_stats
+                                .bundle_stage_stats()
+                                .increment_execution_results_transaction_failures(1);
+                        }
+                        Err(BundleExecutionError::ExceedsCostModel) => {
+                            bundle_stage_leader_stats
+                                .bundle_stage_stats()
+                                .increment_execution_results_exceeds_cost_model(1);
+                        }
+                        Err(BundleExecutionError::ExceedsBlockSpace) => {
+                            bundle_stage_leader_stats
+                                .bundle_stage_stats()
+                                .increment_execution_results_exceeds_block_space(1);
+                            // retry the bundle once the next block is being built
+                            unprocessed_bundles
+                                .defer(packet_bundle, bank_start.working_bank.slot());
+                        }
+                        Err(BundleExecutionError::TipError(
+                            TipPaymentError::TipAccountViolation(tip_account),
+                        )) => {
+                            debug!(
+                                "bundle {} violated tip account {}",
+                                packet_bundle.uuid, tip_account
+                            );
+                            bundle_stage_leader_stats
+                                .bundle_stage_stats()
+                                .increment_num_tip_account_violations(1);
+                        }
+                        Err(BundleExecutionError::TipError(_)) => {
+                            bundle_stage_leader_stats
+                                .bundle_stage_stats()
+                                .increment_execution_results_tip_errors(1);
+                        }
+                        Err(BundleExecutionError::Shutdown) => {}
+                        Err(BundleExecutionError::MaxRetriesExceeded(_)) => {
+                            if retry_contended {
+                                bundle_stage_leader_stats
+                                    .bundle_stage_stats()
+                                    .increment_execution_results_contention_retries(1);
+                                unprocessed_bundles
+                                    .retry(packet_bundle, bank_start.working_bank.slot());
+                            } else {
+                                bundle_stage_leader_stats
+                                    .bundle_stage_stats()
+                                    .increment_execution_results_max_retries(1);
+                            }
+                        }
+                        Err(BundleExecutionError::LockError) => {
+                            bundle_stage_leader_stats
+                                .bundle_stage_stats()
+                                .increment_execution_results_lock_errors(1);
+                        }
+                        Err(BundleExecutionError::Expired(_)) => {
+                            bundle_stage_leader_stats
+                                .bundle_stage_stats()
+                                .increment_execution_results_expired(1);
+                            Self::report_expired_bundle(
+                                &packet_bundle,
+                                bank_start.working_bank.slot(),
+                            );
+                        }
+                        Err(BundleExecutionError::DuplicateTransaction(_)) => {
+                            bundle_stage_leader_stats
+                                .bundle_stage_stats()
+                                .increment_execution_results_duplicate_transactions(1);
+                        }
+                        Err(BundleExecutionError::ExecutionTimeout(_)) => {
+                            bundle_stage_leader_stats
+                                .bundle_stage_stats()
+                                .increment_execution_results_timed_out(1);
+                        }
                     }
                 },
             );
     }
 
-    /// This only needs to be done once on program initialization
-    /// TODO (LB): may make sense to remove this and move to program deployment instead, but helpful
-    ///  during development
+    /// Orders bundles by tip per estimated compute unit, highest first. Bundles paying the same
+    /// keep the order they arrived in.
+    fn prioritize_bundles(
+        sanitized_bundles: VecDeque<(PacketBundle, SanitizedBundle)>,
+        tip_accounts: &HashSet<Pubkey>,
+        qos_service: &QosService,
+    ) -> VecDeque<(PacketBundle, SanitizedBundle)> {
+        let mut prioritized_bundles = sanitized_bundles
+            .into_iter()
+            .map(|(packet_bundle, sanitized_bundle)| {
+                let priority = BundlePriority {
+                    tip_lamports: bundle_tip_lamports(&packet_bundle, tip_accounts),
+                    compute_units: qos_service
+                        .estimate_transactions_cost(sanitized_bundle.transactions.iter()),
+                };
+                (priority, (packet_bundle, sanitized_bundle))
+            })
+            .collect::<Vec<_>>();
+        prioritized_bundles.sort_by(|(a, _), (b, _)| a.cmp_descending(b));
+        prioritized_bundles
+            .into_iter()
+            .map(|(_, bundle)| bundle)
+            .collect()
+    }
+
+    /// Defers bundles to the next slot once the bundles committed to this block, together with the
+    /// cost model's estimate for the bundles admitted ahead of them, reach the share of the block
+    /// bundles may use. Bundles are admitted in priority order, so the ones deferred are the ones
+    /// paying the least per compute unit.
+    fn defer_bundles_over_block_units(
+        sanitized_bundles: VecDeque<(PacketBundle, SanitizedBundle)>,
+        unprocessed_bundles: &mut BundleQueue,
+        bundle_block_units: &mut BundleBlockUnits,
+        slot: Slot,
+        qos_service: &QosService,
+        bundle_stage_leader_stats: &mut BundleStageLeaderStats,
+    ) -> VecDeque<(PacketBundle, SanitizedBundle)> {
+        let mut admitted_units = bundle_block_units.units(slot);
+        let mut admitted_bundles = VecDeque::with_capacity(sanitized_bundles.len());
+        for (packet_bundle, sanitized_bundle) in sanitized_bundles {
+            if admitted_units >= bundle_block_units.max_units() {
+                bundle_stage_leader_stats
+                    .bundle_stage_stats()
+                    .increment_num_deferred_block_units(1);
+                unprocessed_bundles.defer(packet_bundle, slot);
+                continue;
+            }
+            admitted_units = admitted_units.saturating_add(
+                qos_service.estimate_transactions_cost(sanitized_bundle.transactions.iter()),
+            );
+            admitted_bundles.push_back((packet_bundle, sanitized_bundle));
+        }
+        admitted_bundles
+    }
+
+    /// Releases the account reservations of bundles dropped earlier in this execution pass.
+    fn release_dropped_bundle_reservations(
+        bundle_reservations: &mut HashMap<BundleId, BundleAccountReservation>,
+        sanitized_bundles: &VecDeque<(PacketBundle, SanitizedBundle)>,
+        bundle_stage_leader_stats: &mut BundleStageLeaderStats,
+    ) {
+        let remaining: HashSet<BundleId> = sanitized_bundles
+            .iter()
+            .map(|(packet_bundle, _)| BundleId::new(packet_bundle))
+            .collect();
+        let dropped: Vec<BundleId> = bundle_reservations
+            .keys()
+            .filter(|bundle_id| !remaining.contains(bundle_id))
+            .copied()
+            .collect();
+        for bundle_id in dropped {
+            if let Some(reservation) = bundle_reservations.remove(&bundle_id) {
+                bundle_stage_leader_stats
+                    .bundle_stage_stats()
+                    .record_bundle_reservation(reservation.held_for().as_micros() as u64);
+            }
+        }
+    }
+
+    /// Simulates each bundle on the working bank and drops the ones that would fail before they
+    /// take any account locks. Bundles are simulated independently of each other, so a bundle
+    /// that only fails because of a bundle executed ahead of it still fails at execution.
+    fn drop_failing_bundles(
+        sanitized_bundles: VecDeque<(PacketBundle, SanitizedBundle)>,
+        unprocessed_bundles: &BundleQueue,
+        bank: &Bank,
+        tip_accounts: &HashSet<Pubkey>,
+        bundle_stage_leader_stats: &mut BundleStageLeaderStats,
+    ) -> VecDeque<(PacketBundle, SanitizedBundle)> {
+        sanitized_bundles
+            .into_iter()
+            .filter(|(packet_bundle, sanitized_bundle)| {
+                let simulation_result = simulate_bundle(bank, sanitized_bundle, tip_accounts);
+                if let Some((index, error)) = simulation_result.error() {
+                    debug!(
+                        "bundle {} failed simulation at transaction {}: {:?}",
+                        packet_bundle.uuid, index, error
+                    );
+                    let bundle_stage_stats = bundle_stage_leader_stats.bundle_stage_stats();
+                    bundle_stage_stats.increment_num_simulation_failures(1);
+                    bundle_stage_stats.record_bundle_outcome(
+                        &packet_bundle.uuid,
+                        sanitized_bundle.transactions.len(),
+                        simulation_result.units_consumed,
+                        bundle_tip_lamports(packet_bundle, tip_accounts),
+                        unprocessed_bundles.num_retries(packet_bundle),
+                        BundleOutcome::SimulationFailed,
+                        None,
+                    );
+                    return false;
+                }
+                if let Some(tip_account) = simulation_result.drained_tip_account {
+                    debug!(
+                        "bundle {} took lamports from tip account {} in simulation",
+                        packet_bundle.uuid, tip_account
+                    );
+                    let bundle_stage_stats = bundle_stage_leader_stats.bundle_stage_stats();
+                    bundle_stage_stats.increment_num_tip_account_violations(1);
+                    bundle_stage_stats.record_bundle_outcome(
+                        &packet_bundle.uuid,
+                        sanitized_bundle.transactions.len(),
+                        simulation_result.units_consumed,
+                        bundle_tip_lamports(packet_bundle, tip_accounts),
+                        unprocessed_bundles.num_retries(packet_bundle),
+                        BundleOutcome::TipAccountViolation,
+                        None,
+                    );
+                    return false;
+                }
+                true
+            })
+            .collect()
+    }
+
+    /// Drops bundles that write to a protected account, use an account or program denied by the
+    /// operator's bundle filter, or declare a smaller tip than its minimum.
+    fn drop_denied_bundles(
+        sanitized_bundles: VecDeque<(PacketBundle, SanitizedBundle)>,
+        unprocessed_bundles: &BundleQueue,
+        bundle_filter: &BundleFilter,
+        protected_accounts: &ProtectedAccounts,
+        tip_accounts: &HashSet<Pubkey>,
+        bundle_stage_leader_stats: &mut BundleStageLeaderStats,
+    ) -> VecDeque<(PacketBundle, SanitizedBundle)> {
+        if bundle_filter.is_empty() && protected_accounts.is_empty() {
+            return sanitized_bundles;
+        }
+        sanitized_bundles
+            .into_iter()
+            .filter(|(packet_bundle, sanitized_bundle)| {
+                let tip_lamports = bundle_tip_lamports(packet_bundle, tip_accounts);
+                let result = protected_accounts
+                    .check(sanitized_bundle)
+                    .and_then(|_| bundle_filter.check_tip(tip_lamports))
+                    .and_then(|_| bundle_filter.check(sanitized_bundle));
+                if let Err(rejection) = result {
+                    debug!("bundle {} denied: {:?}", packet_bundle.uuid, rejection);
+                    let bundle_stage_stats = bundle_stage_leader_stats.bundle_stage_stats();
+                    let outcome = match rejection {
+                        BundleFilterRejection::TipTooLow(_) => {
+                            bundle_stage_stats.increment_num_tip_too_low_bundles(1);
+                            BundleOutcome::TipTooLow
+                        }
+                        BundleFilterRejection::ProtectedAccountWrite(_) => {
+                            bundle_stage_stats.increment_num_protected_account_write_bundles(1);
+                            BundleOutcome::ProtectedAccountWrite
+                        }
+                        BundleFilterRejection::DeniedAccount(_)
+                        | BundleFilterRejection::DeniedProgram(_) => {
+                            bundle_stage_stats.increment_num_denied_bundles(1);
+                            BundleOutcome::Denied
+                        }
+                    };
+                    bundle_stage_stats.record_bundle_outcome(
+                        &packet_bundle.uuid,
+                        sanitized_bundle.transactions.len(),
+                        0,
+                        tip_lamports,
+                        unprocessed_bundles.num_retries(packet_bundle),
+                        outcome,
+                        None,
+                    );
+                    return false;
+                }
+                true
+            })
+            .collect()
+    }
+
+    /// Drops bundles whose expiry slot is before `slot`; they can no longer land in the slot
+    /// the searcher targeted.
+    fn drop_expired_bundles(
+        unprocessed_bundles: &mut BundleQueue,
+        slot: Slot,
+        bundle_stage_leader_stats: &mut BundleStageLeaderStats,
+    ) {
+        let num_bundles_before = unprocessed_bundles.len();
+        unprocessed_bundles.retain(|packet_bundle| {
+            let is_expired = packet_bundle.expiry_slot < slot;
+            if is_expired {
+                Self::report_expired_bundle(packet_bundle, slot);
+            }
+            !is_expired
+        });
+        bundle_stage_leader_stats
+            .bundle_stage_stats()
+            .increment_num_expired_bundles((num_bundles_before - unprocessed_bundles.len()) as u64);
+    }
+
+    /// Reports which transaction discarded the bundle and what happened to the others.
+    fn report_discarded_bundle(
+        sanitized_bundle: &SanitizedBundle,
+        outcomes: &[BundleTransactionOutcome],
+    ) {
+        for (transaction, outcome) in sanitized_bundle.transactions.iter().zip(outcomes) {
+            debug!(
+                "discarded bundle transaction {}: {:?}",
+                transaction.signature(),
+                outcome
+            );
+        }
+        if let Some((index, error)) =
+            outcomes
+                .iter()
+                .enumerate()
+                .find_map(|(index, outcome)| match outcome {
+                    BundleTransactionOutcome::Failed(e) => Some((index, e)),
+                    _ => None,
+                })
+        {
+            datapoint_info!(
+                "bundle_stage-discarded_bundle",
+                (
+                    "signature",
+                    sanitized_bundle.transactions[index].signature().to_string(),
+                    String
+                ),
+                ("num_transactions", outcomes.len(), i64),
+                ("failed_index", index, i64),
+                ("error", error.to_string(), String),
+                (
+                    "num_discarded",
+                    outcomes
+                        .iter()
+                        .filter(|outcome| **outcome == BundleTransactionOutcome::Discarded)
+                        .count(),
+                    i64
+                ),
+            );
+        }
+    }
+
+    fn report_expired_bundle(packet_bundle: &PacketBundle, slot: Slot) {
+        datapoint_info!(
+            "bundle_stage-expired_bundle",
+            ("uuid", packet_bundle.uuid.to_string(), String),
+            ("expiry_slot", packet_bundle.expiry_slot, i64),
+            ("slot", slot, i64),
+        );
+    }
+
+    /// Initializes the tip accounts the bank doesn't have yet. Past the program's first leader
+    /// slot this only creates this validator's tip distribution account at the start of an epoch.
     #[allow(clippy::too_many_arguments)]
     fn maybe_initialize_tip_accounts(
         bundle_account_locker: &BundleAccountLocker,
@@ -1048,17 +1972,19 @@ impl BundleStage {
         qos_service: &QosService,
         tip_manager: &TipManager,
         max_bundle_retry_duration: &Duration,
+        max_bundle_execution_duration: &Duration,
         bundle_stage_leader_stats: &mut BundleStageLeaderStats,
     ) -> BundleStageResult<()> {
         let initialize_tip_accounts_bundle = SanitizedBundle {
-            transactions: Self::get_initialize_tip_accounts_transactions(
-                &bank_start.working_bank,
-                tip_manager,
-                cluster_info,
-            )?,
+            transactions: tip_manager
+                .initialize_tip_accounts_txs(&bank_start.working_bank, &cluster_info.keypair()),
+            expiry_slot: bank_start.working_bank.slot(),
         };
         if !initialize_tip_accounts_bundle.transactions.is_empty() {
-            debug!("initialize tip account");
+            info!(
+                "initializing tip accounts with {} transactions",
+                initialize_tip_accounts_bundle.transactions.len()
+            );
 
             let locked_init_tip_bundle = bundle_account_locker
                 .prepare_locked_bundle(&initialize_tip_accounts_bundle, &bank_start.working_bank)
@@ -1072,6 +1998,8 @@ impl BundleStage {
                 bank_start,
                 bundle_stage_leader_stats,
                 max_bundle_retry_duration,
+                max_bundle_execution_duration,
+                None,
             );
 
             match &result {
@@ -1088,7 +2016,7 @@ impl BundleStage {
                         .increment_num_init_tip_account_errors(1);
                 }
             }
-            result
+            result.map(|_| ())
         } else {
             Ok(())
         }
@@ -1096,8 +2024,8 @@ impl BundleStage {
 
     /// change tip receiver, draining tips to the previous tip_receiver in the process
     /// note that this needs to happen after the above tip-related bundle initializes
-    /// config accounts because get_configured_tip_receiver relies on an account
-    /// existing in the bank
+    /// config accounts because the tip payment config account has to exist in the bank.
+    /// Nothing is executed if the tip receiver and block builder are already up to date.
     #[allow(clippy::too_many_arguments)]
     fn maybe_change_tip_receiver(
         bundle_account_locker: &BundleAccountLocker,
@@ -1109,32 +2037,33 @@ impl BundleStage {
         qos_service: &QosService,
         tip_manager: &TipManager,
         max_bundle_retry_duration: &Duration,
+        max_bundle_execution_duration: &Duration,
         bundle_stage_leader_stats: &mut BundleStageLeaderStats,
         block_builder_fee_info: &Arc<Mutex<BlockBuilderFeeInfo>>,
     ) -> BundleStageResult<()> {
         let start_handle_tips = Instant::now();
 
-        let configured_tip_receiver =
-            tip_manager.get_configured_tip_receiver(&bank_start.working_bank)?;
-        let my_tip_distribution_pda =
-            tip_manager.get_my_tip_distribution_pda(bank_start.working_bank.epoch());
-        if configured_tip_receiver != my_tip_distribution_pda {
-            info!(
-                "changing tip receiver from {} to {}",
-                configured_tip_receiver, my_tip_distribution_pda
-            );
-
+        let maybe_change_tip_receiver_tx = {
             let bb_info = block_builder_fee_info.lock().unwrap();
-            let change_tip_receiver_tx = tip_manager.change_tip_receiver_and_block_builder_tx(
-                &my_tip_distribution_pda,
+            let maybe_change_tip_receiver_tx = tip_manager.maybe_change_tip_receiver_tx(
                 &bank_start.working_bank,
                 &cluster_info.keypair(),
-                &bb_info.block_builder,
-                bb_info.block_builder_commission,
+                &bb_info,
             )?;
+            bundle_stage_leader_stats
+                .bundle_stage_stats()
+                .set_block_builder_commission(bb_info.block_builder_commission);
+            maybe_change_tip_receiver_tx
+        };
+        if let Some(change_tip_receiver_tx) = maybe_change_tip_receiver_tx {
+            info!(
+                "changing tip receiver to {}",
+                tip_manager.get_my_tip_distribution_pda(bank_start.working_bank.epoch())
+            );
 
             let change_tip_receiver_bundle = SanitizedBundle {
                 transactions: vec![change_tip_receiver_tx],
+                expiry_slot: bank_start.working_bank.slot(),
             };
             let locked_change_tip_receiver_bundle = bundle_account_locker
                 .prepare_locked_bundle(&change_tip_receiver_bundle, &bank_start.working_bank)
@@ -1148,6 +2077,8 @@ impl BundleStage {
                 bank_start,
                 bundle_stage_leader_stats,
                 max_bundle_retry_duration,
+                max_bundle_execution_duration,
+                None,
             );
 
             bundle_stage_leader_stats
@@ -1170,115 +2101,369 @@ impl BundleStage {
                         .increment_num_change_tip_receiver_errors(1);
                 }
             }
-            result
+            result.map(|_| ())
         } else {
             Ok(())
         }
     }
 
+    /// Splits the locked bundles into batches of up to `max_batch_size` bundles that don't
+    /// conflict with each other, returning the indexes of each batch's bundles. A bundle goes in
+    /// the first batch with room after the last batch holding a bundle it conflicts with, so
+    /// conflicting bundles still execute in priority order. Bundles that failed to lock are left
+    /// out.
+    fn schedule_bundle_batches(
+        locked_bundles: &[BundleAccountLockerResult<LockedBundle>],
+        max_batch_size: usize,
+    ) -> Vec<Vec<usize>> {
+        let mut batches: Vec<Vec<usize>> = Vec::new();
+        for (index, locked_bundle) in locked_bundles.iter().enumerate() {
+            let locked_bundle = match locked_bundle {
+                Ok(locked_bundle) => locked_bundle,
+                Err(_) => continue,
+            };
+            let first_batch = if max_batch_size <= 1 {
+                batches.len()
+            } else {
+                batches
+                    .iter()
+                    .rposition(|batch| {
+                        batch.iter().any(|other| {
+                            locked_bundles[*other]
+                                .as_ref()
+                                .map(|other| other.conflicts_with(locked_bundle))
+                                .unwrap_or_default()
+                        })
+                    })
+                    .map(|batch| batch + 1)
+                    .unwrap_or_default()
+            };
+            match batches[first_batch..]
+                .iter_mut()
+                .find(|batch| batch.len() < max_batch_size)
+            {
+                Some(batch) => batch.push(index),
+                None => batches.push(vec![index]),
+            }
+        }
+        batches
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn execute_locked_bundle(
+        locked_bundle: &LockedBundle,
+        bank_start: &BankStart,
+        recorder: &TransactionRecorder,
+        transaction_status_sender: &Option<TransactionStatusSender>,
+        gossip_vote_sender: &ReplayVoteSender,
+        qos_service: &QosService,
+        tip_manager: &TipManager,
+        max_bundle_retry_duration: &Duration,
+        max_bundle_execution_duration: &Duration,
+        bundle_stage_leader_stats: &mut BundleStageLeaderStats,
+    ) -> (BundleStageResult<u64>, BundleTimings) {
+        if !Self::bank_can_record(bank_start) {
+            return (
+                Err(BundleExecutionError::PohMaxHeightError),
+                BundleTimings::default(),
+            );
+        }
+        let start = BundleTimings::from_execution_timings(
+            bundle_stage_leader_stats.execute_and_commit_timings(),
+        );
+        let result = Self::update_qos_and_execute_record_commit_bundle(
+            locked_bundle.sanitized_bundle(),
+            recorder,
+            transaction_status_sender,
+            gossip_vote_sender,
+            qos_service,
+            bank_start,
+            bundle_stage_leader_stats,
+            max_bundle_retry_duration,
+            max_bundle_execution_duration,
+            Some(tip_manager),
+        );
+        let timings = BundleTimings::from_execution_timings(
+            bundle_stage_leader_stats.execute_and_commit_timings(),
+        )
+        .execution_since(&start);
+        (result, timings)
+    }
+
+    /// Executes the locked bundles on `thread_pool`, running batches of bundles that don't
+    /// conflict with each other in parallel, so the BundleStage thread is left to manage the
+    /// queue. Each bundle is still recorded in a single PoH record. Returns one
+    /// result per bundle, in the same order as `locked_bundles`, along with the time spent
+    /// executing, recording and committing it.
     #[allow(clippy::too_many_arguments)]
     fn execute_locked_bundles(
-        bundle_account_locker: &BundleAccountLocker,
         locked_bundles: Vec<BundleAccountLockerResult<LockedBundle>>,
         bank_start: &BankStart,
-        cluster_info: &Arc<ClusterInfo>,
         recorder: &TransactionRecorder,
         transaction_status_sender: &Option<TransactionStatusSender>,
         gossip_vote_sender: &ReplayVoteSender,
         qos_service: &QosService,
         tip_manager: &TipManager,
         max_bundle_retry_duration: &Duration,
-        last_tip_update_slot: &mut Slot,
+        max_bundle_execution_duration: &Duration,
         bundle_stage_leader_stats: &mut BundleStageLeaderStats,
-        block_builder_fee_info: &Arc<Mutex<BlockBuilderFeeInfo>>,
-    ) -> Vec<BundleStageResult<()>> {
-        let tip_pdas = tip_manager.get_tip_accounts();
+        thread_pool: &ThreadPool,
+    ) -> Vec<(BundleStageResult<u64>, BundleTimings)> {
+        let batches =
+            Self::schedule_bundle_batches(&locked_bundles, thread_pool.current_num_threads());
 
-        // make sure each locked_bundle is dropped after processing to unlock BankingStage
-        locked_bundles
-            .into_iter()
+        let mut results: Vec<Option<(BundleStageResult<u64>, BundleTimings)>> = locked_bundles
+            .iter()
             .map(|maybe_locked_bundle| {
-                let locked_bundle = maybe_locked_bundle.as_ref().map_err(|_| {
+                maybe_locked_bundle.as_ref().err().map(|_| {
                     bundle_stage_leader_stats
                         .bundle_stage_stats()
                         .increment_num_lock_errors(1);
+                    (
+                        Err(BundleExecutionError::LockError),
+                        BundleTimings::default(),
+                    )
+                })
+            })
+            .collect();
+        // make sure each locked_bundle is dropped after processing to unlock BankingStage
+        let mut locked_bundles: Vec<Option<LockedBundle>> =
+            locked_bundles.into_iter().map(Result::ok).collect();
 
-                    BundleExecutionError::LockError
-                })?;
+        for batch in batches {
+            if batch.len() > 1 {
+                bundle_stage_leader_stats
+                    .bundle_stage_stats()
+                    .record_parallel_bundle_batch(batch.len() as u64);
+            }
+            let report_bundle_outcomes = bundle_stage_leader_stats
+                .bundle_stage_stats()
+                .report_bundle_outcomes();
+            let execute = |index: &usize, stats: &mut BundleStageLeaderStats| {
+                Self::execute_locked_bundle(
+                    locked_bundles[*index].as_ref().unwrap(),
+                    bank_start,
+                    recorder,
+                    transaction_status_sender,
+                    gossip_vote_sender,
+                    qos_service,
+                    tip_manager,
+                    max_bundle_retry_duration,
+                    max_bundle_execution_duration,
+                    stats,
+                )
+            };
+            let batch_results: Vec<(usize, (BundleStageResult<u64>, BundleTimings))> = thread_pool
+                .install(|| {
+                    batch
+                        .par_iter()
+                        .map(|index| {
+                            let mut stats = BundleStageLeaderStats::new(report_bundle_outcomes);
+                            let result = execute(index, &mut stats);
+                            (*index, result, stats)
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .into_iter()
+                .map(|(index, result, stats)| {
+                    bundle_stage_leader_stats.accumulate(&stats);
+                    (index, result)
+                })
+                .collect();
+            for (index, result) in batch_results {
+                results[index] = Some(result);
+                locked_bundles[index] = None;
+            }
+        }
 
-                if !Bank::should_bank_still_be_processing_txs(
-                    &bank_start.bank_creation_time,
-                    bank_start.working_bank.ns_per_slot,
-                ) {
-                    Err(BundleExecutionError::PohMaxHeightError)
-                } else {
-                    let sanitized_bundle = locked_bundle.sanitized_bundle();
-
-                    if Self::bundle_touches_tip_pdas(&sanitized_bundle.transactions, &tip_pdas)
-                        && bank_start.working_bank.slot() != *last_tip_update_slot
-                    {
-                        Self::maybe_initialize_tip_accounts(
-                            bundle_account_locker,
-                            bank_start,
-                            cluster_info,
-                            recorder,
-                            transaction_status_sender,
-                            gossip_vote_sender,
-                            qos_service,
-                            tip_manager,
-                            max_bundle_retry_duration,
-                            bundle_stage_leader_stats,
-                        )?;
-
-                        Self::maybe_change_tip_receiver(
-                            bundle_account_locker,
-                            bank_start,
-                            cluster_info,
-                            recorder,
-                            transaction_status_sender,
-                            gossip_vote_sender,
-                            qos_service,
-                            tip_manager,
-                            max_bundle_retry_duration,
-                            bundle_stage_leader_stats,
-                            block_builder_fee_info,
-                        )?;
-
-                        *last_tip_update_slot = bank_start.working_bank.slot();
-                    }
+        results
+            .into_iter()
+            .map(|result| result.expect("every bundle is locked and executed or failed to lock"))
+            .collect()
+    }
 
-                    Self::update_qos_and_execute_record_commit_bundle(
-                        sanitized_bundle,
-                        recorder,
-                        transaction_status_sender,
-                        gossip_vote_sender,
-                        qos_service,
-                        bank_start,
-                        bundle_stage_leader_stats,
-                        max_bundle_retry_duration,
-                    )
-                }
+    /// Builds the pool bundles are executed on, along with a receiver that gets a message as each
+    /// of its threads exits.
+    fn new_execution_thread_pool(num_threads: usize) -> (ThreadPool, Receiver<()>) {
+        let (thread_exit_sender, thread_exit_receiver) = unbounded();
+        let thread_pool = ThreadPoolBuilder::new()
+            .num_threads(num_threads.max(1))
+            .thread_name(|i| format!("solBundleExec-{}", i))
+            .exit_handler(move |_| {
+                let _ = thread_exit_sender.send(());
             })
-            .collect()
+            .build()
+            .unwrap();
+        (thread_pool, thread_exit_receiver)
+    }
+
+    /// Shuts down the execution thread pool and waits up to `timeout` for its threads to exit.
+    /// Returns false if some of them didn't.
+    fn join_execution_thread_pool(
+        thread_pool: ThreadPool,
+        thread_exit_receiver: &Receiver<()>,
+        timeout: Duration,
+    ) -> bool {
+        let num_threads = thread_pool.current_num_threads();
+        drop(thread_pool);
+        let deadline = Instant::now() + timeout;
+        (0..num_threads).all(|_| thread_exit_receiver.recv_deadline(deadline).is_ok())
     }
 
+    /// Moves received bundles into `unprocessed_bundles`, evicting bundles once it's full. Unless
+    /// the validator is or will soon be leader, received bundles are dropped instead since they'd
+    /// be stale by its next leader slot. Bundles that are kept are dropped if they contain a vote
+    /// or the same transaction twice, then have their signatures verified and are dropped if any
+    /// of their transactions fail. While bundles are disabled,
+    /// received and queued bundles are all dropped.
+    /// Bundles queued while `leader_bank` is the working bank reserve their accounts against it.
+    /// Returns the number of bundles received, including the ones that were dropped.
+    #[allow(clippy::too_many_arguments)]
     fn receive_and_buffer_bundles(
         bundle_receiver: &Receiver<Vec<PacketBundle>>,
-        unprocessed_bundles: &mut VecDeque<PacketBundle>,
+        unprocessed_bundles: &mut BundleQueue,
+        bundle_sigverifier: &BundleSigverifier,
+        bundle_queue_stats: &BundleQueueStats,
+        bundle_stage_stats: &mut BundleStageLoopStats,
+        bundle_stage_leader_stats: &mut BundleStageLeaderSlotTrackingMetrics,
+        current_slot: Slot,
+        leader_bank: Option<&Bank>,
+        would_be_leader: bool,
+        bundles_enabled: bool,
         timeout: Duration,
     ) -> Result<usize, RecvTimeoutError> {
-        let bundles = bundle_receiver.recv_timeout(timeout)?;
-        let num_bundles_before = unprocessed_bundles.len();
-        unprocessed_bundles.extend(bundles);
-        unprocessed_bundles.extend(bundle_receiver.try_iter().flatten());
-        let num_bundles_after = unprocessed_bundles.len();
-        Ok(num_bundles_after - num_bundles_before)
+        // bundles queued before execution was turned off don't get to run either
+        if !bundles_enabled && !unprocessed_bundles.is_empty() {
+            let queued_bundles: Vec<PacketBundle> = unprocessed_bundles.drain().collect();
+            Self::drop_disabled_bundles(
+                queued_bundles,
+                bundle_stage_stats,
+                bundle_stage_leader_stats,
+            );
+        }
+
+        let bundles: Vec<PacketBundle> = bundle_receiver
+            .recv_timeout(timeout)?
+            .into_iter()
+            .chain(bundle_receiver.try_iter().flatten())
+            .collect();
+        let num_bundles_received = bundles.len();
+        bundle_queue_stats.increment_dequeued(num_bundles_received as u64);
+
+        if !bundles_enabled {
+            Self::drop_disabled_bundles(bundles, bundle_stage_stats, bundle_stage_leader_stats);
+            return Ok(num_bundles_received);
+        }
+
+        if !would_be_leader {
+            Self::drop_not_leader_bundles(bundles, bundle_stage_stats, bundle_stage_leader_stats);
+            return Ok(num_bundles_received);
+        }
+
+        // the checks that don't need the signatures are cheap, so they go first
+        let bundles: Vec<PacketBundle> = bundles
+            .into_iter()
+            .filter(|bundle| match precheck_bundle(bundle) {
+                Ok(()) => true,
+                Err(e) => {
+                    debug!("bundle {} failed precheck: {}", bundle.uuid, e);
+                    saturating_add_assign!(bundle_stage_stats.num_bundles_dropped_precheck, 1);
+                    let bundle_stage_stats = bundle_stage_leader_stats
+                        .bundle_stage_leader_stats()
+                        .bundle_stage_stats();
+                    match e {
+                        BundleSanitizerError::VoteTransaction => {
+                            bundle_stage_stats.increment_sanitize_transaction_vote_transaction(1)
+                        }
+                        BundleSanitizerError::DuplicateTransaction => bundle_stage_stats
+                            .increment_sanitize_transaction_duplicate_transaction(1),
+                        _ => {}
+                    }
+                    false
+                }
+            })
+            .collect();
+        let (verified_bundles, rejected_bundles) = bundle_sigverifier.verify(bundles);
+        saturating_add_assign!(
+            bundle_stage_stats.num_bundles_dropped_invalid_signature,
+            rejected_bundles.len() as u64
+        );
+        for bundle in rejected_bundles {
+            debug!("bundle {} failed signature verification", bundle.uuid);
+            bundle_stage_leader_stats
+                .bundle_stage_leader_stats()
+                .bundle_stage_stats()
+                .record_bundle_outcome(
+                    &bundle.uuid,
+                    bundle.batch.len(),
+                    0,
+                    0,
+                    0,
+                    BundleOutcome::InvalidSignature,
+                    None,
+                );
+        }
+        for bundle in verified_bundles {
+            unprocessed_bundles.push_received(bundle, current_slot, leader_bank);
+        }
+        Ok(num_bundles_received)
+    }
+
+    fn drop_disabled_bundles(
+        bundles: Vec<PacketBundle>,
+        bundle_stage_stats: &mut BundleStageLoopStats,
+        bundle_stage_leader_stats: &mut BundleStageLeaderSlotTrackingMetrics,
+    ) {
+        saturating_add_assign!(
+            bundle_stage_stats.num_bundles_dropped_disabled,
+            bundles.len() as u64
+        );
+        let bundle_stage_stats = bundle_stage_leader_stats
+            .bundle_stage_leader_stats()
+            .bundle_stage_stats();
+        bundle_stage_stats.increment_num_disabled_bundles(bundles.len() as u64);
+        for bundle in bundles {
+            bundle_stage_stats.record_bundle_outcome(
+                &bundle.uuid,
+                bundle.batch.len(),
+                0,
+                0,
+                0,
+                BundleOutcome::Disabled,
+                None,
+            );
+        }
+    }
+
+    fn drop_not_leader_bundles(
+        bundles: Vec<PacketBundle>,
+        bundle_stage_stats: &mut BundleStageLoopStats,
+        bundle_stage_leader_stats: &mut BundleStageLeaderSlotTrackingMetrics,
+    ) {
+        saturating_add_assign!(
+            bundle_stage_stats.num_bundles_dropped_not_leader,
+            bundles.len() as u64
+        );
+        let bundle_stage_stats = bundle_stage_leader_stats
+            .bundle_stage_leader_stats()
+            .bundle_stage_stats();
+        for bundle in bundles {
+            bundle_stage_stats.record_bundle_outcome(
+                &bundle.uuid,
+                bundle.batch.len(),
+                0,
+                0,
+                0,
+                BundleOutcome::NotLeader,
+                None,
+            );
+        }
     }
 
     #[allow(clippy::too_many_arguments)]
     fn process_buffered_bundles(
         bundle_account_locker: &BundleAccountLocker,
-        unprocessed_bundles: &mut VecDeque<PacketBundle>,
+        unprocessed_bundles: &mut BundleQueue,
         blacklisted_accounts: &HashSet<Pubkey>,
         consensus_cache_updater: &mut ConsensusCacheUpdater,
         cluster_info: &Arc<ClusterInfo>,
@@ -1289,54 +2474,79 @@ impl BundleStage {
         qos_service: &QosService,
         tip_manager: &TipManager,
         max_bundle_retry_duration: &Duration,
-        last_tip_update_slot: &mut u64,
+        max_bundle_execution_duration: &Duration,
+        max_bundle_contention_retries: usize,
+        bundle_block_units: &mut BundleBlockUnits,
+        tip_accounts_update: &mut TipAccountsUpdate,
         bundle_stage_leader_stats: &mut BundleStageLeaderSlotTrackingMetrics,
         bundle_stage_stats: &mut BundleStageLoopStats,
         id: u32,
         block_builder_fee_info: &Arc<Mutex<BlockBuilderFeeInfo>>,
+        simulate_bundles: bool,
+        bundle_limits: &BundleLimits,
+        leader_lookahead_slots: u64,
+        bundle_filter: &BundleFilter,
+        protected_accounts: &ProtectedAccounts,
+        bundles_enabled: bool,
+        thread_pool: &ThreadPool,
     ) {
-        const DROP_BUNDLE_SLOT_OFFSET: u64 = 4;
-
         let r_poh_recorder = poh_recorder.read().unwrap();
         let poh_recorder_bank = r_poh_recorder.get_poh_recorder_bank();
         let working_bank_start = poh_recorder_bank.working_bank_start();
-        let would_be_leader_soon =
-            r_poh_recorder.would_be_leader(DROP_BUNDLE_SLOT_OFFSET * DEFAULT_TICKS_PER_SLOT);
+        let would_be_leader_soon = r_poh_recorder
+            .would_be_leader(leader_lookahead_slots.saturating_mul(DEFAULT_TICKS_PER_SLOT));
         drop(r_poh_recorder);
 
         bundle_stage_leader_stats.maybe_report(id, &working_bank_start);
+        tip_manager.maybe_report_slot_tips(
+            working_bank_start.map(|bank_start| bank_start.working_bank.slot()),
+        );
 
         match (working_bank_start, would_be_leader_soon) {
-            // leader now, insert new read bundles + as many as can read then return bank
-            (Some(bank_start), _) => {
+            // leader now, insert new read bundles + as many as can read then return bank. while
+            // bundles are turned off nothing is executed, including the tip account updates
+            (Some(bank_start), _) if bundles_enabled => {
                 consensus_cache_updater.maybe_update(&bank_start.working_bank);
 
-                Self::execute_bundles_until_empty_or_end_of_slot(
-                    bundle_account_locker,
-                    unprocessed_bundles,
-                    blacklisted_accounts,
-                    bank_start,
-                    consensus_cache_updater.consensus_accounts_cache(),
-                    cluster_info,
-                    recorder,
-                    transaction_status_sender,
-                    gossip_vote_sender,
-                    qos_service,
-                    tip_manager,
-                    max_bundle_retry_duration,
-                    last_tip_update_slot,
-                    bundle_stage_leader_stats.bundle_stage_leader_stats(),
-                    block_builder_fee_info,
+                let (_, execute_bundles_elapsed) = measure!(
+                    Self::execute_bundles_until_empty_or_end_of_slot(
+                        bundle_account_locker,
+                        unprocessed_bundles,
+                        blacklisted_accounts,
+                        bank_start,
+                        consensus_cache_updater.consensus_accounts_cache(),
+                        cluster_info,
+                        recorder,
+                        transaction_status_sender,
+                        gossip_vote_sender,
+                        qos_service,
+                        tip_manager,
+                        max_bundle_retry_duration,
+                        max_bundle_execution_duration,
+                        max_bundle_contention_retries,
+                        bundle_block_units,
+                        tip_accounts_update,
+                        bundle_stage_leader_stats.bundle_stage_leader_stats(),
+                        block_builder_fee_info,
+                        simulate_bundles,
+                        bundle_limits,
+                        bundle_filter,
+                        protected_accounts,
+                        thread_pool,
+                    ),
+                    "execute_bundles_elapsed"
                 );
+                bundle_stage_leader_stats
+                    .bundle_stage_leader_stats()
+                    .bundle_stage_stats()
+                    .increment_execute_bundles_elapsed_us(execute_bundles_elapsed.as_us());
             }
             // not leader now and not soon, clear bundles
             (None, false) => {
                 saturating_add_assign!(
                     bundle_stage_stats.num_bundles_dropped,
-                    unprocessed_bundles.len() as u64
+                    unprocessed_bundles.drain().count() as u64
                 );
-
-                unprocessed_bundles.clear();
             }
             _ => {}
         }
@@ -1355,27 +2565,74 @@ impl BundleStage {
         tip_manager: TipManager,
         bundle_account_locker: BundleAccountLocker,
         max_bundle_retry_duration: Duration,
+        max_bundle_execution_duration: Duration,
+        max_bundle_contention_retries: usize,
+        max_bundle_block_cu_ratio: f64,
         block_builder_fee_info: Arc<Mutex<BlockBuilderFeeInfo>>,
+        bundle_queue_stats: Arc<BundleQueueStats>,
+        bundle_queue_capacity: usize,
+        simulate_bundles: bool,
+        report_bundle_outcomes: bool,
+        bundle_limits: BundleLimits,
+        leader_lookahead_slots: u64,
+        mut bundle_filter: BundleFilter,
+        bundle_filter_receiver: Receiver<BundleFilter>,
+        protected_accounts: ProtectedAccounts,
+        bundles_enabled: Arc<AtomicBool>,
+        num_execution_threads: usize,
     ) {
         const LOOP_STATS_METRICS_PERIOD: Duration = Duration::from_secs(1);
 
         let recorder = poh_recorder.read().unwrap().recorder();
         let qos_service = QosService::new(cost_model, id);
+        let (thread_pool, thread_exit_receiver) =
+            Self::new_execution_thread_pool(num_execution_threads);
 
         // Bundles can't mention any accounts related to consensus
         let mut consensus_cache_updater = ConsensusCacheUpdater::default();
-        let mut last_tip_update_slot = Slot::default();
+        let mut tip_accounts_update = TipAccountsUpdate::default();
+        let mut bundle_block_units = BundleBlockUnits::new(
+            (MAX_BLOCK_UNITS as f64 * max_bundle_block_cu_ratio.clamp(0.0, 1.0)) as u64,
+        );
 
         let mut last_leader_slots_update_time = Instant::now();
-        let mut bundle_stage_leader_stats = BundleStageLeaderSlotTrackingMetrics::default();
+        let mut bundle_stage_leader_stats =
+            BundleStageLeaderSlotTrackingMetrics::new(report_bundle_outcomes);
         let mut bundle_stage_stats = BundleStageLoopStats::default();
+        let mut last_bundle_queue_counts = BundleQueueCounts::default();
 
         // Bundles can't mention the tip payment program to ensure that a malicious entity doesn't
         // steal tips mid-slot
         let blacklisted_accounts = HashSet::from_iter([tip_manager.tip_payment_program_id()]);
 
-        let mut unprocessed_bundles: VecDeque<PacketBundle> = VecDeque::with_capacity(1000);
+        let mut unprocessed_bundles = BundleQueue::new(
+            bundle_queue_capacity,
+            tip_manager.get_tip_accounts(),
+            bundle_account_locker.clone(),
+        );
+        let bundle_sigverifier = BundleSigverifier::default();
+        let mut last_bundles_enabled = true;
         while !exit.load(Ordering::Relaxed) {
+            while let Ok(updated_bundle_filter) = bundle_filter_receiver.try_recv() {
+                info!(
+                    "bundle filter updated, {} denied accounts, {} denied programs and a minimum tip of {} lamports",
+                    updated_bundle_filter.denied_accounts.len(),
+                    updated_bundle_filter.denied_programs.len(),
+                    updated_bundle_filter.min_tip_lamports
+                );
+                bundle_filter = updated_bundle_filter;
+            }
+
+            let is_bundles_enabled = bundles_enabled.load(Ordering::Relaxed);
+            if is_bundles_enabled != last_bundles_enabled {
+                if is_bundles_enabled {
+                    info!("bundle execution enabled");
+                } else {
+                    warn!("bundle execution disabled, dropping bundles until it's enabled");
+                }
+                last_bundles_enabled = is_bundles_enabled;
+            }
+
             if !unprocessed_bundles.is_empty()
                 || last_leader_slots_update_time.elapsed() >= SLOT_BOUNDARY_CHECK_PERIOD
             {
@@ -1393,11 +2650,21 @@ impl BundleStage {
                         &qos_service,
                         &tip_manager,
                         &max_bundle_retry_duration,
-                        &mut last_tip_update_slot,
+                        &max_bundle_execution_duration,
+                        max_bundle_contention_retries,
+                        &mut bundle_block_units,
+                        &mut tip_accounts_update,
                         &mut bundle_stage_leader_stats,
                         &mut bundle_stage_stats,
                         id,
-                        &block_builder_fee_info
+                        &block_builder_fee_info,
+                        simulate_bundles,
+                        &bundle_limits,
+                        leader_lookahead_slots,
+                        &bundle_filter,
+                        &protected_accounts,
+                        is_bundles_enabled,
+                        &thread_pool,
                     ),
                     "process_buffered_bundles_elapsed"
                 );
@@ -1409,24 +2676,71 @@ impl BundleStage {
                 last_leader_slots_update_time = Instant::now();
             }
 
-            bundle_stage_stats.maybe_report(id, LOOP_STATS_METRICS_PERIOD);
+            if bundle_stage_stats.maybe_report(id, LOOP_STATS_METRICS_PERIOD) {
+                bundle_queue_stats.report(
+                    &mut last_bundle_queue_counts,
+                    unprocessed_bundles.len(),
+                    oldest_bundle_age(unprocessed_bundles.iter(), Instant::now()),
+                );
+            }
+
+            let (current_slot, would_be_leader, leader_bank) = {
+                let poh_recorder = poh_recorder.read().unwrap();
+                (
+                    poh_recorder.tick_height() / poh_recorder.ticks_per_slot(),
+                    poh_recorder.would_be_leader(
+                        leader_lookahead_slots.saturating_mul(DEFAULT_TICKS_PER_SLOT),
+                    ),
+                    poh_recorder
+                        .bank_start()
+                        .filter(Self::bank_can_record)
+                        .map(|bank_start| bank_start.working_bank),
+                )
+            };
 
             // ensure bundle stage can run immediately if bundles to process, otherwise okay
-            // chilling for a few
-            let sleep_time = if !unprocessed_bundles.is_empty() {
+            // chilling for a few. queued bundles can't be executed until there's a working bank
+            // to record them in, so between leader slots this waits on the channel instead of
+            // spinning over them.
+            let sleep_time = if !unprocessed_bundles.is_empty() && leader_bank.is_some() {
                 Duration::from_millis(0)
             } else {
                 Duration::from_millis(10)
             };
-
+            unprocessed_bundles.release_deferred(current_slot);
             let (res, receive_and_buffer_elapsed) = measure!(
                 Self::receive_and_buffer_bundles(
                     &bundle_receiver,
                     &mut unprocessed_bundles,
+                    &bundle_sigverifier,
+                    &bundle_queue_stats,
+                    &mut bundle_stage_stats,
+                    &mut bundle_stage_leader_stats,
+                    current_slot,
+                    leader_bank.as_deref(),
+                    would_be_leader,
+                    is_bundles_enabled,
                     sleep_time,
                 ),
                 "receive_and_buffer_elapsed"
             );
+            let evictions = unprocessed_bundles.take_evictions();
+            saturating_add_assign!(
+                bundle_stage_stats.num_bundles_dropped_duplicate,
+                evictions.num_duplicates
+            );
+            saturating_add_assign!(
+                bundle_stage_stats.num_bundles_evicted_expired,
+                evictions.num_expired
+            );
+            saturating_add_assign!(
+                bundle_stage_stats.num_bundles_evicted_lower_tip,
+                evictions.num_lower_tip
+            );
+            saturating_add_assign!(
+                bundle_stage_stats.num_bundles_rejected_lower_tip,
+                evictions.num_rejected
+            );
             saturating_add_assign!(
                 bundle_stage_stats.receive_and_buffer_bundles_elapsed_us,
                 receive_and_buffer_elapsed.as_us()
@@ -1438,6 +2752,10 @@ impl BundleStage {
                         bundle_stage_stats.num_bundles_received,
                         num_bundles_received as u64
                     );
+                    bundle_stage_leader_stats
+                        .bundle_stage_leader_stats()
+                        .bundle_stage_stats()
+                        .increment_num_bundles_received(num_bundles_received as u64);
                 }
                 Err(RecvTimeoutError::Timeout) => {}
                 Err(RecvTimeoutError::Disconnected) => {
@@ -1445,59 +2763,96 @@ impl BundleStage {
                 }
             }
         }
+
+        let num_bundles_drained = unprocessed_bundles.drain().count();
+        if num_bundles_drained > 0 {
+            datapoint_info!(
+                "bundle_stage-exit",
+                ("id", id, i64),
+                ("num_bundles_drained", num_bundles_drained, i64),
+            );
+        }
+
+        if !Self::join_execution_thread_pool(
+            thread_pool,
+            &thread_exit_receiver,
+            EXECUTION_THREADS_EXIT_TIMEOUT,
+        ) {
+            warn!("bundle execution threads didn't exit in time");
+        }
     }
 
+    /// Returns the entries the bundle's executed transactions are recorded in. They're recorded
+    /// with a single PoH record so no other entry lands between them, see [Self::bundle_entries].
     fn prepare_poh_record_bundle(
         bank_slot: &Slot,
         execution_results_txs: &[AllExecutionResults],
         record_transactions_timings: &mut RecordTransactionsTimings,
     ) -> (Slot, Vec<(Hash, Vec<VersionedTransaction>)>) {
-        let mut new_record_transaction_timings = RecordTransactionsTimings::default();
-
-        let mixins_txs = execution_results_txs
-            .iter()
-            .map(|r| {
-                let (processed_transactions, results_to_transactions_elapsed) = measure!(
-                    {
-                        r.load_and_execute_tx_output
-                            .execution_results
-                            .iter()
-                            .zip(r.sanitized_txs.iter())
-                            .filter_map(|(execution_result, tx)| {
-                                if execution_result.was_executed() {
-                                    Some(tx.to_versioned_transaction())
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect::<Vec<VersionedTransaction>>()
-                    },
-                    "results_to_transactions_elapsed"
-                );
-
-                let (hash, hash_elapsed) = measure!(
-                    hash_transactions(&processed_transactions[..]),
-                    "hash_elapsed"
-                );
-
-                saturating_add_assign!(
-                    new_record_transaction_timings.execution_results_to_transactions_us,
-                    results_to_transactions_elapsed.as_us()
-                );
-                saturating_add_assign!(
-                    new_record_transaction_timings.hash_us,
-                    hash_elapsed.as_us()
-                );
+        let (entries, results_to_transactions_elapsed) = measure!(
+            Self::bundle_entries(execution_results_txs.iter().flat_map(|r| {
+                r.load_and_execute_tx_output
+                    .execution_results
+                    .iter()
+                    .zip(r.sanitized_txs.iter())
+                    .filter(|(execution_result, _)| execution_result.was_executed())
+                    .map(|(_, tx)| tx)
+            })),
+            "results_to_transactions_elapsed"
+        );
 
-                (hash, processed_transactions)
-            })
-            .collect();
+        let (mixins_txs, hash_elapsed) = measure!(
+            entries
+                .into_iter()
+                .map(|transactions| (hash_transactions(&transactions), transactions))
+                .collect(),
+            "hash_elapsed"
+        );
 
-        record_transactions_timings.accumulate(&new_record_transaction_timings);
+        record_transactions_timings.accumulate(&RecordTransactionsTimings {
+            execution_results_to_transactions_us: results_to_transactions_elapsed.as_us(),
+            hash_us: hash_elapsed.as_us(),
+            poh_record_us: 0,
+        });
 
         (*bank_slot, mixins_txs)
     }
 
+    /// Groups a bundle's transactions into as few entries as replay allows, keeping their order.
+    /// Replay locks all of an entry's transactions at once and fails the block if they conflict
+    /// with each other, so a transaction that conflicts with one already in the entry starts the
+    /// next entry. A bundle whose transactions don't share writable accounts is a single entry.
+    fn bundle_entries<'a>(
+        transactions: impl Iterator<Item = &'a SanitizedTransaction>,
+    ) -> Vec<Vec<VersionedTransaction>> {
+        let mut entries: Vec<Vec<VersionedTransaction>> = vec![];
+        let mut write_locks = HashSet::new();
+        let mut read_locks = HashSet::new();
+        for transaction in transactions {
+            let account_locks = transaction.get_account_locks_unchecked();
+            let conflicts = account_locks
+                .writable
+                .iter()
+                .any(|account| write_locks.contains(*account) || read_locks.contains(*account))
+                || account_locks
+                    .readonly
+                    .iter()
+                    .any(|account| write_locks.contains(*account));
+            if conflicts || entries.is_empty() {
+                entries.push(vec![]);
+                write_locks.clear();
+                read_locks.clear();
+            }
+            write_locks.extend(account_locks.writable.into_iter().copied());
+            read_locks.extend(account_locks.readonly.into_iter().copied());
+            entries
+                .last_mut()
+                .unwrap()
+                .push(transaction.to_versioned_transaction());
+        }
+        entries
+    }
+
     pub fn join(self) -> thread::Result<()> {
         self.bundle_thread.join()
     }
@@ -1519,29 +2874,39 @@ impl BundleStage {
 mod tests {
     use {
         super::*,
-        crate::bundle_stage::tests::TestOption::{
-            AssertDuplicateInBundleDropped, AssertNonZeroCostModel, AssertZeroedCostModel,
-            LowComputeBudget,
+        crate::{
+            banking_stage::BankingStage,
+            bundle_account_locker::BundleAccountLockerError,
+            bundle_filter::ProtectedAccountsConfig,
+            bundle_stage::tests::TestOption::{
+                AssertCostTrackerMatchesReplay, AssertDuplicateInBundleDropped,
+                AssertNonZeroCostModel, AssertZeroedCostModel, LowComputeBudget,
+            },
+            tip_manager::{TipManagerConfig, TipStats},
         },
         crossbeam_channel::unbounded,
+        solana_entry::entry::Entry,
+        solana_gossip::contact_info::ContactInfo,
         solana_ledger::{
             blockstore::Blockstore,
+            blockstore_processor::{process_entries_for_tests, TransactionStatusMessage},
             genesis_utils::{create_genesis_config, GenesisConfigInfo},
             get_tmp_ledger_path_auto_delete,
         },
         solana_perf::packet::PacketBatch,
         solana_poh::poh_recorder::create_test_recorder,
         solana_sdk::{
-            bundle::error::BundleExecutionError::{
-                ExceedsCostModel, PohMaxHeightError, TransactionFailure,
+            bundle::error::{
+                BundleExecutionError::{ExceedsBlockSpace, PohMaxHeightError, TransactionFailure},
+                TipPaymentError,
             },
             compute_budget::ComputeBudgetInstruction,
             genesis_config::GenesisConfig,
-            instruction::InstructionError,
+            instruction::{AccountMeta, Instruction, InstructionError},
             message::Message,
             packet::Packet,
             poh_config::PohConfig,
-            signature::{Keypair, Signer},
+            signature::{Keypair, Signature, Signer},
             system_instruction,
             system_transaction::{self, transfer},
             transaction::{
@@ -1549,17 +2914,21 @@ mod tests {
                 TransactionError::{self, AccountNotFound},
             },
         },
-        std::{collections::HashSet, sync::atomic::Ordering},
+        solana_streamer::socket::SocketAddrSpace,
+        std::{collections::HashSet, sync::atomic::Ordering, thread::sleep},
         uuid::Uuid,
     };
 
     const TEST_MAX_RETRY_DURATION: Duration = Duration::from_millis(500);
+    const TEST_MAX_EXECUTION_DURATION: Duration = Duration::from_secs(5);
 
     enum TestOption {
         LowComputeBudget,
         AssertZeroedCostModel,
         AssertNonZeroCostModel,
         AssertDuplicateInBundleDropped,
+        AssertCostTrackerMatchesReplay,
+        AssertProcessedTransactionRejected,
     }
 
     #[cfg(test)]
@@ -1596,7 +2965,7 @@ mod tests {
             create_test_recorder(&bank, &blockstore, Some(poh_config), None);
         let recorder = poh_recorder.read().unwrap().recorder();
         let cost_model = Arc::new(RwLock::new(CostModel::default()));
-        let qos_service = QosService::new(cost_model, 0);
+        let qos_service = QosService::new(cost_model.clone(), 0);
         let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
         let bank_start = poh_recorder.read().unwrap().bank_start().unwrap();
         let sanitized_bundle = get_sanitized_bundle(
@@ -1604,6 +2973,7 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::default(),
+            &BundleLimits::default(),
             bundle_stage_leader_stats.transaction_errors(),
         )
         .unwrap();
@@ -1617,8 +2987,63 @@ mod tests {
             &bank_start,
             &mut bundle_stage_leader_stats,
             &TEST_MAX_RETRY_DURATION,
+            &TEST_MAX_EXECUTION_DURATION,
+            None,
         );
 
+        // replay charges each transaction its cost without the estimated bpf execution cost plus
+        // the compute units it actually consumed
+        if options.is_some()
+            && options
+                .as_ref()
+                .unwrap()
+                .iter()
+                .any(|option| matches!(option, AssertCostTrackerMatchesReplay))
+        {
+            let cost_model = cost_model.read().unwrap();
+            let replay_cost = sanitized_bundle
+                .transactions
+                .iter()
+                .map(|tx| cost_model.calculate_cost(tx).sum_without_bpf())
+                .sum::<u64>()
+                + results.as_ref().unwrap();
+            assert_eq!(bank.read_cost_tracker().unwrap().block_cost(), replay_cost);
+        }
+        let results = results.map(|_compute_units| ());
+
+        // executing the bundle again fails before touching the cost tracker
+        if options.is_some()
+            && options
+                .as_ref()
+                .unwrap()
+                .iter()
+                .any(|option| matches!(option, AssertProcessedTransactionRejected))
+        {
+            assert_eq!(results, Ok(()));
+            let transaction_count = bank.read_cost_tracker().unwrap().transaction_count();
+            assert_eq!(
+                BundleStage::update_qos_and_execute_record_commit_bundle(
+                    &sanitized_bundle,
+                    &recorder,
+                    &None,
+                    &gossip_vote_sender,
+                    &qos_service,
+                    &bank_start,
+                    &mut bundle_stage_leader_stats,
+                    &TEST_MAX_RETRY_DURATION,
+                    &TEST_MAX_EXECUTION_DURATION,
+                    None,
+                ),
+                Err(BundleExecutionError::DuplicateTransaction(
+                    *sanitized_bundle.transactions[0].signature()
+                ))
+            );
+            assert_eq!(
+                bank.read_cost_tracker().unwrap().transaction_count(),
+                transaction_count
+            );
+        }
+
         // This is ugly, not really an option for testing but a test itself.
         // Still preferable to duplicating the entirety of this method
         // just to test duplicate txs are dropped.
@@ -1635,6 +3060,7 @@ mod tests {
                 &bank,
                 &HashSet::default(),
                 &HashSet::default(),
+                &BundleLimits::default(),
                 bundle_stage_leader_stats.transaction_errors(),
             )
             .is_err());
@@ -1679,6 +3105,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_bundle_cost_matches_replay() {
+        let (genesis_config, bundle) = setup_successful_tx();
+        assert_eq!(
+            test_single_bundle(
+                genesis_config,
+                bundle,
+                Some(vec![AssertCostTrackerMatchesReplay])
+            ),
+            Ok(())
+        );
+    }
+
     #[test]
     fn test_bundle_contains_processed_transaction() {
         let (genesis_config, bundle) = setup_successful_tx();
@@ -1692,6 +3131,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_bundle_rejects_processed_transaction() {
+        let (genesis_config, bundle) = setup_successful_tx();
+        assert_eq!(
+            test_single_bundle(
+                genesis_config,
+                bundle,
+                Some(vec![AssertProcessedTransactionRejected]),
+            ),
+            Ok(())
+        );
+    }
+
     #[cfg(test)]
     fn setup_successful_tx() -> (GenesisConfig, PacketBundle) {
         let GenesisConfigInfo {
@@ -1713,6 +3165,8 @@ mod tests {
             PacketBundle {
                 batch: PacketBatch::new(vec![packet]),
                 uuid: Uuid::new_v4(),
+                received_at: Instant::now(),
+                expiry_slot: Slot::MAX,
             },
         )
     }
@@ -1740,10 +3194,16 @@ mod tests {
         let bundle = PacketBundle {
             batch: PacketBatch::new(vec![packet]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
         assert_eq!(
-            test_single_bundle(genesis_config, bundle, Some(vec![LowComputeBudget])),
-            Err(ExceedsCostModel)
+            test_single_bundle(
+                genesis_config,
+                bundle,
+                Some(vec![LowComputeBudget, AssertZeroedCostModel])
+            ),
+            Err(ExceedsBlockSpace)
         );
     }
 
@@ -1773,6 +3233,8 @@ mod tests {
         let bundle = PacketBundle {
             batch: PacketBatch::new(vec![packet]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
 
         assert_eq!(
@@ -1807,6 +3269,8 @@ mod tests {
         let bundle = PacketBundle {
             batch: PacketBatch::new(vec![successful_packet, failed_packet]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
 
         assert_eq!(
@@ -1833,6 +3297,8 @@ mod tests {
         let bundle = PacketBundle {
             batch: PacketBatch::new(vec![packet]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
 
         assert_eq!(
@@ -1860,6 +3326,8 @@ mod tests {
         let bundle = PacketBundle {
             batch: PacketBatch::new(vec![packet]),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
         assert_eq!(
             test_single_bundle(genesis_config, bundle, None),
@@ -1868,18 +3336,15 @@ mod tests {
     }
 
     #[test]
-    fn test_bundle_max_retries() {
-        solana_logger::setup_with_default("INFO");
-
+    fn test_bundle_last_transaction_fails() {
+        solana_logger::setup();
         let GenesisConfigInfo {
             genesis_config,
             mint_keypair,
             ..
-        } = create_genesis_config(100_000_000);
+        } = create_genesis_config(100_000);
         let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
-        bank.write_cost_tracker()
-            .unwrap()
-            .set_limits(u64::MAX, u64::MAX, u64::MAX);
+        let mint_balance = bank.get_balance(&mint_keypair.pubkey());
 
         let ledger_path = get_tmp_ledger_path_auto_delete!();
         let blockstore = Arc::new(
@@ -1887,75 +3352,2317 @@ mod tests {
                 .expect("Expected to be able to open database ledger"),
         );
         let poh_config = PohConfig {
-            // limit tick count to avoid clearing working_bank at
-            // PohRecord then PohRecorderError(MaxHeightReached) at BankingStage
-            target_tick_count: Some(bank.max_tick_height() - 1), // == 1, only enough for ticks, not txs
+            target_tick_count: Some(bank.max_tick_height() - 1),
             ..PohConfig::default()
         };
-        let (exit, poh_recorder, poh_service, _entry_receiver) =
+        let (exit, poh_recorder, poh_service, entry_receiver) =
             create_test_recorder(&bank, &blockstore, Some(poh_config), None);
         let recorder = poh_recorder.read().unwrap().recorder();
         let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
-        let cost_model = Arc::new(RwLock::new(CostModel::default()));
-        let qos_service = QosService::new(cost_model, 0);
+        let qos_service = QosService::new(Arc::new(RwLock::new(CostModel::default())), 0);
         let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
         let bank_start = poh_recorder.read().unwrap().bank_start().unwrap();
 
-        // Create two transfers
-        // 0. mint_keypair -> keypair0
-        // 1. keypair0 -> keypair 1
-        // Lock the accounts through the bank for tx1 and try to process tx0.
-        // It should timeout because BundleStage will continue to fail to get locks on keypair0.
-
-        let keypair0 = Keypair::new();
-        let keypair1 = Keypair::new();
-
-        let tx0 = VersionedTransaction::from(transfer(
-            &mint_keypair,
-            &keypair0.pubkey(),
-            100_000,
-            genesis_config.hash(),
-        ));
-
-        let tx1 = transfer(&keypair0, &keypair1.pubkey(), 50_000, genesis_config.hash());
-        let sanitized_txs_1 = vec![SanitizedTransaction::from_transaction_for_tests(tx1)];
-
-        // grab lock on tx1
-        let _batch = bank.prepare_sanitized_batch(&sanitized_txs_1);
-
-        // push and pop tx0
+        // the first two transfers succeed, the last one overdraws the mint once they've landed
+        let kp_a = Keypair::new();
+        let kp_b = Keypair::new();
+        let transactions = [
+            transfer(&mint_keypair, &kp_a.pubkey(), 40_000, genesis_config.hash()),
+            transfer(&mint_keypair, &kp_b.pubkey(), 40_000, genesis_config.hash()),
+            transfer(&mint_keypair, &kp_b.pubkey(), 40_001, genesis_config.hash()),
+        ];
         let bundle = PacketBundle {
-            batch: PacketBatch::new(vec![Packet::from_data(None, tx0).unwrap()]),
+            batch: PacketBatch::new(
+                transactions
+                    .iter()
+                    .map(|tx| Packet::from_data(None, tx).unwrap())
+                    .collect(),
+            ),
             uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
         };
-        info!("test_bundle_max_retries uuid: {:?}", bundle.uuid);
-
         let sanitized_bundle = get_sanitized_bundle(
             &bundle,
             &bank,
             &HashSet::default(),
             &HashSet::default(),
+            &BundleLimits::default(),
             bundle_stage_leader_stats.transaction_errors(),
         )
         .unwrap();
 
-        let result = BundleStage::update_qos_and_execute_record_commit_bundle(
-            &sanitized_bundle,
+        assert_eq!(
+            BundleStage::update_qos_and_execute_record_commit_bundle(
+                &sanitized_bundle,
+                &recorder,
+                &None,
+                &gossip_vote_sender,
+                &qos_service,
+                &bank_start,
+                &mut bundle_stage_leader_stats,
+                &TEST_MAX_RETRY_DURATION,
+                &TEST_MAX_EXECUTION_DURATION,
+                None,
+            ),
+            Err(TransactionFailure(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(1),
+            )))
+        );
+
+        // nothing from the bundle landed, including the fees
+        assert_eq!(bank.get_balance(&mint_keypair.pubkey()), mint_balance);
+        assert_eq!(bank.get_balance(&kp_a.pubkey()), 0);
+        assert_eq!(bank.get_balance(&kp_b.pubkey()), 0);
+        assert_eq!(bank.transaction_count(), 0);
+        assert_eq!(bank.read_cost_tracker().unwrap().block_cost(), 0);
+
+        exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+        // and nothing was recorded
+        assert!(entry_receiver
+            .try_iter()
+            .all(|(_, (entry, _))| entry.transactions.is_empty()));
+    }
+
+    #[test]
+    fn test_bundles_execute_in_priority_order() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(
+            Blockstore::open(ledger_path.path())
+                .expect("Expected to be able to open database ledger"),
+        );
+        let poh_config = PohConfig {
+            target_tick_count: Some(bank.max_tick_height() - 1),
+            ..PohConfig::default()
+        };
+        let (exit, poh_recorder, poh_service, entry_receiver) =
+            create_test_recorder(&bank, &blockstore, Some(poh_config), None);
+        let recorder = poh_recorder.read().unwrap().recorder();
+        let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+        let qos_service = QosService::new(Arc::new(RwLock::new(CostModel::default())), 0);
+        let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
+        let bank_start = poh_recorder.read().unwrap().bank_start().unwrap();
+
+        let tip_account = Pubkey::new_unique();
+        let tip_accounts = HashSet::from([tip_account]);
+        let new_bundle = |tips: &[u64]| {
+            let transactions = tips
+                .iter()
+                .map(|tip| transfer(&mint_keypair, &tip_account, *tip, genesis_config.hash()))
+                .collect::<Vec<_>>();
+            let bundle = PacketBundle {
+                batch: PacketBatch::new(
+                    transactions
+                        .iter()
+                        .map(|tx| Packet::from_data(None, tx).unwrap())
+                        .collect(),
+                ),
+                uuid: Uuid::new_v4(),
+                received_at: Instant::now(),
+                expiry_slot: Slot::MAX,
+            };
+            (bundle, transactions)
+        };
+
+        // queued out of value order; the two transaction bundle tips the most in total but uses
+        // twice the compute units of the others
+        let bundles = [
+            new_bundle(&[1_000]),
+            new_bundle(&[2_400, 2_600]),
+            new_bundle(&[2_000]),
+            new_bundle(&[3_000]),
+        ];
+        let mut bundle_queue = BundleQueue::new(
+            bundles.len(),
+            tip_accounts.clone(),
+            BundleAccountLocker::default(),
+        );
+        for (bundle, _) in &bundles {
+            assert!(bundle_queue.push(bundle.clone(), bank.slot()));
+        }
+
+        let sanitized_bundles = bundle_queue
+            .drain()
+            .map(|packet_bundle| {
+                let sanitized_bundle = get_sanitized_bundle(
+                    &packet_bundle,
+                    &bank,
+                    &HashSet::default(),
+                    &HashSet::default(),
+                    &BundleLimits::default(),
+                    bundle_stage_leader_stats.transaction_errors(),
+                )
+                .unwrap();
+                (packet_bundle, sanitized_bundle)
+            })
+            .collect();
+        let prioritized_bundles =
+            BundleStage::prioritize_bundles(sanitized_bundles, &tip_accounts, &qos_service);
+        for (_, sanitized_bundle) in &prioritized_bundles {
+            assert_matches!(
+                BundleStage::update_qos_and_execute_record_commit_bundle(
+                    sanitized_bundle,
+                    &recorder,
+                    &None,
+                    &gossip_vote_sender,
+                    &qos_service,
+                    &bank_start,
+                    &mut bundle_stage_leader_stats,
+                    &TEST_MAX_RETRY_DURATION,
+                    &TEST_MAX_EXECUTION_DURATION,
+                    None,
+                ),
+                Ok(compute_units) if compute_units > 0
+            );
+        }
+        assert_eq!(bank.get_balance(&tip_account), 11_000);
+
+        exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+        let recorded_signatures = entry_receiver
+            .try_iter()
+            .flat_map(|(_, (entry, _))| entry.transactions)
+            .map(|transaction| transaction.signatures[0])
+            .collect::<Vec<_>>();
+        let expected_signatures = [3, 1, 2, 0]
+            .into_iter()
+            .flat_map(|index| bundles[index].1.iter())
+            .map(|transaction| transaction.signatures[0])
+            .collect::<Vec<_>>();
+        assert_eq!(recorded_signatures, expected_signatures);
+    }
+
+    /// Locks and executes bundles made of the given transactions on a pool of `num_threads`
+    /// threads, returning the results, stats and the entries with transactions in the order they
+    /// were recorded.
+    fn execute_test_bundles(
+        bank: &Arc<Bank>,
+        bundles: Vec<Vec<Transaction>>,
+        num_threads: usize,
+    ) -> (
+        Vec<BundleStageResult<u64>>,
+        BundleStageLeaderStats,
+        Vec<Entry>,
+    ) {
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(
+            Blockstore::open(ledger_path.path())
+                .expect("Expected to be able to open database ledger"),
+        );
+        let poh_config = PohConfig {
+            target_tick_count: Some(bank.max_tick_height() - 1),
+            ..PohConfig::default()
+        };
+        let (exit, poh_recorder, poh_service, entry_receiver) =
+            create_test_recorder(bank, &blockstore, Some(poh_config), None);
+        let recorder = poh_recorder.read().unwrap().recorder();
+        let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+        let qos_service = QosService::new(Arc::new(RwLock::new(CostModel::default())), 0);
+        let bank_start = poh_recorder.read().unwrap().bank_start().unwrap();
+        let tip_manager = TipManager::new(TipManagerConfig::default());
+        let (thread_pool, thread_exit_receiver) =
+            BundleStage::new_execution_thread_pool(num_threads);
+
+        let sanitized_bundles = bundles
+            .into_iter()
+            .map(|transactions| SanitizedBundle {
+                transactions: transactions
+                    .into_iter()
+                    .map(SanitizedTransaction::from_transaction_for_tests)
+                    .collect(),
+                expiry_slot: Slot::MAX,
+            })
+            .collect::<Vec<_>>();
+        let bundle_account_locker = BundleAccountLocker::default();
+        let locked_bundles = sanitized_bundles
+            .iter()
+            .map(|sanitized_bundle| {
+                bundle_account_locker.prepare_locked_bundle(sanitized_bundle, bank)
+            })
+            .collect();
+
+        let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
+        let results = BundleStage::execute_locked_bundles(
+            locked_bundles,
+            &bank_start,
             &recorder,
             &None,
             &gossip_vote_sender,
             &qos_service,
-            &bank_start,
-            &mut bundle_stage_leader_stats,
+            &tip_manager,
             &TEST_MAX_RETRY_DURATION,
-        );
-        info!("test_bundle_max_retries result: {:?}", result);
-        assert!(matches!(
-            result,
-            Err(BundleExecutionError::MaxRetriesExceeded(_))
+            &TEST_MAX_EXECUTION_DURATION,
+            &mut bundle_stage_leader_stats,
+            &thread_pool,
+        )
+        .into_iter()
+        .map(|(result, _)| result)
+        .collect();
+        // every bundle's locks were released
+        assert!(bundle_account_locker.write_locks().is_empty());
+        // and every execution thread exits once the pool is shut down
+        assert!(BundleStage::join_execution_thread_pool(
+            thread_pool,
+            &thread_exit_receiver,
+            Duration::from_secs(10),
         ));
 
         exit.store(true, Ordering::Relaxed);
         poh_service.join().unwrap();
+        let recorded_entries = entry_receiver
+            .try_iter()
+            .map(|(_, (entry, _))| entry)
+            .filter(|entry| !entry.is_tick())
+            .collect();
+        (results, bundle_stage_leader_stats, recorded_entries)
+    }
+
+    fn entry_signatures(entries: &[Entry]) -> Vec<Vec<Signature>> {
+        entries
+            .iter()
+            .map(|entry| {
+                entry
+                    .transactions
+                    .iter()
+                    .map(|transaction| transaction.signatures[0])
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_execution_thread_pool_joined() {
+        let (thread_pool, thread_exit_receiver) = BundleStage::new_execution_thread_pool(2);
+        let thread_name = thread_pool.install(|| thread::current().name().map(str::to_string));
+        assert!(matches!(
+            thread_name.as_deref(),
+            Some("solBundleExec-0") | Some("solBundleExec-1")
+        ));
+
+        // returns once both threads have exited rather than after a fixed wait
+        assert!(BundleStage::join_execution_thread_pool(
+            thread_pool,
+            &thread_exit_receiver,
+            Duration::from_secs(10),
+        ));
+        assert!(thread_exit_receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_schedule_bundle_batches() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+        let bundle_account_locker = BundleAccountLocker::default();
+        let payer = Keypair::new();
+        let funded = Keypair::new();
+        let new_bundle = |from: &Keypair, to: &Pubkey| SanitizedBundle {
+            transactions: vec![SanitizedTransaction::from_transaction_for_tests(transfer(
+                from,
+                to,
+                1,
+                genesis_config.hash(),
+            ))],
+            expiry_slot: Slot::MAX,
+        };
+        let sanitized_bundles = [
+            new_bundle(&mint_keypair, &funded.pubkey()),
+            new_bundle(&funded, &Pubkey::new_unique()),
+            new_bundle(&payer, &Pubkey::new_unique()),
+            new_bundle(&funded, &Pubkey::new_unique()),
+            new_bundle(&Keypair::new(), &Pubkey::new_unique()),
+        ];
+        let mut locked_bundles = sanitized_bundles
+            .iter()
+            .map(|sanitized_bundle| {
+                bundle_account_locker.prepare_locked_bundle(sanitized_bundle, &bank)
+            })
+            .collect::<Vec<_>>();
+
+        // bundles spending from `funded` wait for the ones before them, the others fill the
+        // earliest batch with room
+        assert_eq!(
+            BundleStage::schedule_bundle_batches(&locked_bundles, 2),
+            vec![vec![0, 2], vec![1, 4], vec![3]]
+        );
+        assert_eq!(
+            BundleStage::schedule_bundle_batches(&locked_bundles, 8),
+            vec![vec![0, 2, 4], vec![1], vec![3]]
+        );
+        assert_eq!(
+            BundleStage::schedule_bundle_batches(&locked_bundles, 1),
+            vec![vec![0], vec![1], vec![2], vec![3], vec![4]]
+        );
+
+        locked_bundles[0] = Err(BundleAccountLockerError::LockingError);
+        assert_eq!(
+            BundleStage::schedule_bundle_batches(&locked_bundles, 8),
+            vec![vec![1, 2, 4], vec![3]]
+        );
+    }
+
+    #[test]
+    fn test_execute_non_conflicting_bundles_in_parallel() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+        let payers = [Keypair::new(), Keypair::new()];
+        for payer in &payers {
+            bank.transfer(100_000, &mint_keypair, &payer.pubkey())
+                .unwrap();
+        }
+
+        let bundles = payers
+            .iter()
+            .map(|payer| {
+                vec![transfer(
+                    payer,
+                    &Pubkey::new_unique(),
+                    10_000,
+                    genesis_config.hash(),
+                )]
+            })
+            .collect::<Vec<_>>();
+        let (results, mut stats, recorded_entries) =
+            execute_test_bundles(&bank, bundles.clone(), 2);
+        assert_matches!(results[..], [Ok(_), Ok(_)]);
+        // both bundles were handed to the thread pool at the same time
+        assert_eq!(stats.bundle_stage_stats().max_parallel_bundles(), 2);
+        assert_eq!(
+            entry_signatures(&recorded_entries)
+                .into_iter()
+                .flatten()
+                .collect::<HashSet<_>>(),
+            bundles
+                .iter()
+                .flatten()
+                .map(|transaction| transaction.signatures[0])
+                .collect::<HashSet<_>>()
+        );
+        for payer in &payers {
+            assert!(bank.get_balance(&payer.pubkey()) < 90_000);
+        }
+    }
+
+    #[test]
+    fn test_execute_conflicting_bundles_in_priority_order() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+        let funded = Keypair::new();
+        let recipient = Pubkey::new_unique();
+
+        // the second bundle can only pay for itself after the first one executed
+        let bundles = vec![
+            vec![transfer(
+                &mint_keypair,
+                &funded.pubkey(),
+                100_000,
+                genesis_config.hash(),
+            )],
+            vec![transfer(&funded, &recipient, 10_000, genesis_config.hash())],
+        ];
+        let (results, mut stats, recorded_entries) =
+            execute_test_bundles(&bank, bundles.clone(), 2);
+        assert_matches!(results[..], [Ok(_), Ok(_)]);
+        assert_eq!(stats.bundle_stage_stats().max_parallel_bundles(), 0);
+        assert_eq!(bank.get_balance(&recipient), 10_000);
+        assert_eq!(
+            entry_signatures(&recorded_entries)
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>(),
+            bundles
+                .iter()
+                .flatten()
+                .map(|transaction| transaction.signatures[0])
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_bundle_recorded_in_fewest_entries() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let payers = [Keypair::new(), Keypair::new()];
+        let new_bank = || {
+            let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+            for payer in &payers {
+                bank.transfer(100_000, &mint_keypair, &payer.pubkey())
+                    .unwrap();
+            }
+            bank
+        };
+        let bank = new_bank();
+
+        let funded = Keypair::new();
+        let recipients: Vec<_> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let bundles = vec![
+            // nothing in common, so one entry
+            vec![
+                transfer(&payers[0], &recipients[0], 10_000, genesis_config.hash()),
+                transfer(&payers[1], &recipients[1], 10_000, genesis_config.hash()),
+            ],
+            // the second transaction spends what the first one sent, so two entries
+            vec![
+                transfer(
+                    &mint_keypair,
+                    &funded.pubkey(),
+                    100_000,
+                    genesis_config.hash(),
+                ),
+                transfer(&funded, &recipients[2], 10_000, genesis_config.hash()),
+            ],
+        ];
+        let (results, _, recorded_entries) = execute_test_bundles(&bank, bundles.clone(), 1);
+        assert_matches!(results[..], [Ok(_), Ok(_)]);
+        let signatures = |bundle: &[Transaction]| {
+            bundle
+                .iter()
+                .map(|transaction| transaction.signatures[0])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            entry_signatures(&recorded_entries),
+            vec![
+                signatures(&bundles[0]),
+                signatures(&bundles[1][..1]),
+                signatures(&bundles[1][1..]),
+            ]
+        );
+
+        // the entries replay to the same state
+        let replay_bank = new_bank();
+        process_entries_for_tests(&replay_bank, recorded_entries, false, None, None).unwrap();
+        for account in recipients.iter().chain([funded.pubkey()].iter()) {
+            assert!(bank.get_balance(account) > 0);
+            assert_eq!(replay_bank.get_balance(account), bank.get_balance(account));
+        }
+    }
+
+    #[test]
+    fn test_bundle_entries() {
+        let payer = Keypair::new();
+        let shared = Pubkey::new_unique();
+        let new_transaction = |payer: &Keypair, to: &Pubkey| {
+            SanitizedTransaction::from_transaction_for_tests(transfer(
+                payer,
+                to,
+                1,
+                Hash::default(),
+            ))
+        };
+        let num_transactions = |transactions: &[SanitizedTransaction]| {
+            BundleStage::bundle_entries(transactions.iter())
+                .iter()
+                .map(|entry| entry.len())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(BundleStage::bundle_entries([].iter()).is_empty());
+        assert_eq!(
+            num_transactions(&[
+                new_transaction(&Keypair::new(), &Pubkey::new_unique()),
+                new_transaction(&Keypair::new(), &Pubkey::new_unique()),
+                new_transaction(&Keypair::new(), &Pubkey::new_unique()),
+            ]),
+            vec![3]
+        );
+        // a write conflict starts a new entry, which later transactions are grouped into
+        assert_eq!(
+            num_transactions(&[
+                new_transaction(&payer, &Pubkey::new_unique()),
+                new_transaction(&Keypair::new(), &shared),
+                new_transaction(&payer, &Pubkey::new_unique()),
+                new_transaction(&Keypair::new(), &Pubkey::new_unique()),
+                new_transaction(&Keypair::new(), &shared),
+            ]),
+            vec![2, 3]
+        );
+    }
+
+    #[test]
+    fn test_bundle_execution_timeout() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(
+            Blockstore::open(ledger_path.path())
+                .expect("Expected to be able to open database ledger"),
+        );
+        let poh_config = PohConfig {
+            target_tick_count: Some(bank.max_tick_height() - 1),
+            ..PohConfig::default()
+        };
+        let (exit, poh_recorder, poh_service, entry_receiver) =
+            create_test_recorder(&bank, &blockstore, Some(poh_config), None);
+        let recorder = poh_recorder.read().unwrap().recorder();
+        let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+        let qos_service = QosService::new(Arc::new(RwLock::new(CostModel::default())), 0);
+        let bank_start = poh_recorder.read().unwrap().bank_start().unwrap();
+
+        let recipient = Pubkey::new_unique();
+        let new_bundle = |num_transactions: u64| SanitizedBundle {
+            transactions: (1..=num_transactions)
+                .map(|lamports| {
+                    SanitizedTransaction::from_transaction_for_tests(transfer(
+                        &mint_keypair,
+                        &recipient,
+                        lamports,
+                        genesis_config.hash(),
+                    ))
+                })
+                .collect(),
+            expiry_slot: Slot::MAX,
+        };
+        let execute = |sanitized_bundle: &SanitizedBundle, max_execution_duration: &Duration| {
+            let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
+            let result = BundleStage::update_qos_and_execute_record_commit_bundle(
+                sanitized_bundle,
+                &recorder,
+                &None,
+                &gossip_vote_sender,
+                &qos_service,
+                &bank_start,
+                &mut bundle_stage_leader_stats,
+                &TEST_MAX_RETRY_DURATION,
+                max_execution_duration,
+                None,
+            );
+            let num_executed = bundle_stage_leader_stats
+                .execute_and_commit_timings()
+                .execute_timings
+                .details
+                .per_program_timings
+                .values()
+                .map(|program_timing| program_timing.count)
+                .sum::<u32>();
+            (result, num_executed)
+        };
+
+        // the budget is checked once the transaction finished, so a bundle whose only
+        // transaction runs over it is abandoned after executing it
+        let (result, num_executed) = execute(&new_bundle(1), &Duration::ZERO);
+        assert_matches!(result, Err(BundleExecutionError::ExecutionTimeout(_)));
+        assert_eq!(num_executed, 1);
+
+        // the rest of the bundle isn't executed once the first transaction ran over
+        let (result, num_executed) = execute(&new_bundle(3), &Duration::ZERO);
+        assert_matches!(result, Err(BundleExecutionError::ExecutionTimeout(_)));
+        assert_eq!(num_executed, 1);
+
+        // nothing from the abandoned bundles was committed, recorded or charged to the block
+        assert_eq!(bank.get_balance(&recipient), 0);
+        assert_eq!(bank.read_cost_tracker().unwrap().transaction_count(), 0);
+
+        let (result, num_executed) = execute(&new_bundle(3), &TEST_MAX_EXECUTION_DURATION);
+        assert_matches!(result, Ok(compute_units) if compute_units > 0);
+        assert_eq!(num_executed, 3);
+        assert_eq!(bank.get_balance(&recipient), 6);
+
+        exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+        assert_eq!(
+            entry_receiver
+                .try_iter()
+                .map(|(_, (entry, _))| entry.transactions.len())
+                .sum::<usize>(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_bundle_pays_different_tip_accounts() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(
+            Blockstore::open(ledger_path.path())
+                .expect("Expected to be able to open database ledger"),
+        );
+        let poh_config = PohConfig {
+            target_tick_count: Some(bank.max_tick_height() - 1),
+            ..PohConfig::default()
+        };
+        let (exit, poh_recorder, poh_service, _entry_receiver) =
+            create_test_recorder(&bank, &blockstore, Some(poh_config), None);
+        let recorder = poh_recorder.read().unwrap().recorder();
+        let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+        let qos_service = QosService::new(Arc::new(RwLock::new(CostModel::default())), 0);
+        let bank_start = poh_recorder.read().unwrap().bank_start().unwrap();
+
+        let tip_manager = TipManager::new(TipManagerConfig {
+            min_bundle_tip_lamports: 1_000,
+            ..TipManagerConfig::default()
+        });
+        let tip_accounts: Vec<_> = tip_manager.get_tip_accounts().into_iter().collect();
+        let new_bundle = |tips: &[(&Pubkey, u64)]| SanitizedBundle {
+            transactions: tips
+                .iter()
+                .map(|(tip_account, lamports)| {
+                    SanitizedTransaction::from_transaction_for_tests(transfer(
+                        &mint_keypair,
+                        tip_account,
+                        *lamports,
+                        genesis_config.hash(),
+                    ))
+                })
+                .collect(),
+            expiry_slot: Slot::MAX,
+        };
+        let execute = |sanitized_bundle: &SanitizedBundle| {
+            BundleStage::update_qos_and_execute_record_commit_bundle(
+                sanitized_bundle,
+                &recorder,
+                &None,
+                &gossip_vote_sender,
+                &qos_service,
+                &bank_start,
+                &mut BundleStageLeaderStats::default(),
+                &TEST_MAX_RETRY_DURATION,
+                &TEST_MAX_EXECUTION_DURATION,
+                Some(&tip_manager),
+            )
+        };
+
+        // the tip is summed across every tip account the bundle pays
+        assert_matches!(
+            execute(&new_bundle(&[
+                (&tip_accounts[0], 600),
+                (&tip_accounts[1], 400)
+            ])),
+            Ok(_)
+        );
+        assert_eq!(bank.get_balance(&tip_accounts[0]), 600);
+        assert_eq!(bank.get_balance(&tip_accounts[1]), 400);
+
+        // splitting a tip that's too small between tip accounts doesn't get it past the minimum
+        assert_eq!(
+            execute(&new_bundle(&[
+                (&tip_accounts[2], 500),
+                (&tip_accounts[3], 400)
+            ])),
+            Err(BundleExecutionError::TipError(
+                TipPaymentError::InsufficientTip {
+                    paid: 900,
+                    required: 1_000
+                }
+            ))
+        );
+        assert_eq!(bank.get_balance(&tip_accounts[2]), 0);
+        assert_eq!(bank.get_balance(&tip_accounts[3]), 0);
+
+        // paying a single tip account works as well
+        assert_matches!(execute(&new_bundle(&[(&tip_accounts[7], 1_000)])), Ok(_));
+        assert_eq!(bank.get_balance(&tip_accounts[7]), 1_000);
+
+        // a tip paid by a bundle that fails afterwards is rolled back and not counted
+        let mut rolled_back_bundle = new_bundle(&[(&tip_accounts[4], 5_000)]);
+        rolled_back_bundle
+            .transactions
+            .push(SanitizedTransaction::from_transaction_for_tests(transfer(
+                &Keypair::new(),
+                &Pubkey::new_unique(),
+                1,
+                genesis_config.hash(),
+            )));
+        assert_matches!(
+            execute(&rolled_back_bundle),
+            Err(BundleExecutionError::TransactionFailure(_))
+        );
+        assert_eq!(bank.get_balance(&tip_accounts[4]), 0);
+
+        // only the committed bundles' tips were collected
+        assert_eq!(
+            tip_manager.tip_stats(),
+            TipStats {
+                slot: bank.slot(),
+                slot_tip_lamports: 2_000,
+                slot_num_tipping_bundles: 2,
+                slot_max_tip_lamports: 1_000,
+                epoch: bank.epoch(),
+                epoch_tip_lamports: 2_000,
+                epoch_num_tipping_bundles: 2,
+            }
+        );
+
+        exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+    }
+
+    #[test]
+    fn test_bundle_sends_transaction_statuses() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(
+            Blockstore::open(ledger_path.path())
+                .expect("Expected to be able to open database ledger"),
+        );
+        let poh_config = PohConfig {
+            target_tick_count: Some(bank.max_tick_height() - 1),
+            ..PohConfig::default()
+        };
+        let (exit, poh_recorder, poh_service, _entry_receiver) =
+            create_test_recorder(&bank, &blockstore, Some(poh_config), None);
+        let recorder = poh_recorder.read().unwrap().recorder();
+        let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+        let (transaction_status_sender, transaction_status_receiver) = unbounded();
+        let qos_service = QosService::new(Arc::new(RwLock::new(CostModel::default())), 0);
+        let bank_start = poh_recorder.read().unwrap().bank_start().unwrap();
+
+        // the second transaction spends lamports the first one transferred
+        let funded = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        let transactions = vec![
+            transfer(
+                &mint_keypair,
+                &funded.pubkey(),
+                100_000,
+                genesis_config.hash(),
+            ),
+            transfer(&funded, &recipient, 10_000, genesis_config.hash()),
+        ];
+        let sanitized_bundle = SanitizedBundle {
+            transactions: transactions
+                .iter()
+                .cloned()
+                .map(SanitizedTransaction::from_transaction_for_tests)
+                .collect(),
+            expiry_slot: Slot::MAX,
+        };
+
+        assert_matches!(
+            BundleStage::update_qos_and_execute_record_commit_bundle(
+                &sanitized_bundle,
+                &recorder,
+                &Some(TransactionStatusSender {
+                    sender: transaction_status_sender,
+                }),
+                &gossip_vote_sender,
+                &qos_service,
+                &bank_start,
+                &mut BundleStageLeaderStats::default(),
+                &TEST_MAX_RETRY_DURATION,
+                &TEST_MAX_EXECUTION_DURATION,
+                None,
+            ),
+            Ok(_)
+        );
+        exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+
+        let mut signatures = Vec::new();
+        let mut transaction_indexes = Vec::new();
+        let mut balances = Vec::new();
+        for message in transaction_status_receiver.try_iter() {
+            let batch = match message {
+                TransactionStatusMessage::Batch(batch) => batch,
+                TransactionStatusMessage::Freeze(_) => continue,
+            };
+            assert_eq!(batch.bank.slot(), bank.slot());
+            for details in &batch.execution_results {
+                let details = details.as_ref().unwrap();
+                assert_eq!(details.status, Ok(()));
+                assert!(!details.log_messages.as_ref().unwrap().is_empty());
+            }
+            signatures.extend(batch.transactions.iter().map(|tx| *tx.signature()));
+            transaction_indexes.extend(batch.transaction_indexes);
+            balances.extend(
+                batch
+                    .balances
+                    .pre_balances
+                    .into_iter()
+                    .zip(batch.balances.post_balances),
+            );
+        }
+        assert_eq!(
+            signatures,
+            transactions
+                .iter()
+                .map(|tx| tx.signatures[0])
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(transaction_indexes, vec![0, 1]);
+
+        // account keys are [payer, recipient, system program]; the second transaction's balances
+        // include the lamports the first one transferred
+        let (pre_balances, post_balances) = &balances[0];
+        assert_eq!((pre_balances[1], post_balances[1]), (0, 100_000));
+        let (pre_balances, post_balances) = &balances[1];
+        assert_eq!(pre_balances[0], 100_000);
+        assert!(post_balances[0] < 90_000);
+        assert_eq!((pre_balances[1], post_balances[1]), (0, 10_000));
+    }
+
+    #[test]
+    fn test_bundle_not_recorded_after_slot_ends() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(
+            Blockstore::open(ledger_path.path())
+                .expect("Expected to be able to open database ledger"),
+        );
+        let poh_config = PohConfig {
+            target_tick_count: Some(bank.max_tick_height() - 1),
+            ..PohConfig::default()
+        };
+        let (exit, poh_recorder, poh_service, entry_receiver) =
+            create_test_recorder(&bank, &blockstore, Some(poh_config), None);
+        let recorder = poh_recorder.read().unwrap().recorder();
+        let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+        let qos_service = QosService::new(Arc::new(RwLock::new(CostModel::default())), 0);
+        let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
+        let bank_start = poh_recorder.read().unwrap().bank_start().unwrap();
+
+        let recipient = Pubkey::new_unique();
+        let sanitized_bundle = SanitizedBundle {
+            transactions: [1_000, 2_000]
+                .into_iter()
+                .map(|lamports| {
+                    SanitizedTransaction::from_transaction_for_tests(transfer(
+                        &mint_keypair,
+                        &recipient,
+                        lamports,
+                        genesis_config.hash(),
+                    ))
+                })
+                .collect(),
+            expiry_slot: Slot::MAX,
+        };
+        let mut execute = || {
+            BundleStage::execute_bundle(
+                &sanitized_bundle,
+                &None,
+                &bank_start,
+                &mut bundle_stage_leader_stats,
+                &TEST_MAX_RETRY_DURATION,
+                &TEST_MAX_EXECUTION_DURATION,
+                None,
+            )
+            .unwrap()
+            .0
+        };
+
+        // PoH moved off the bank after the bundle executed
+        let execution_results = execute();
+        poh_recorder.write().unwrap().reset(bank.clone(), None);
+        assert_eq!(
+            BundleStage::record_commit_bundle(
+                execution_results,
+                &bank,
+                &recorder,
+                &mut BundleStageLeaderStats::default(),
+                &None,
+                &gossip_vote_sender,
+            ),
+            Err(PohMaxHeightError)
+        );
+
+        // the bank was frozen after the bundle executed
+        let execution_results = execute();
+        bank.freeze();
+        assert_eq!(
+            BundleStage::record_commit_bundle(
+                execution_results,
+                &bank,
+                &recorder,
+                &mut BundleStageLeaderStats::default(),
+                &None,
+                &gossip_vote_sender,
+            ),
+            Err(PohMaxHeightError)
+        );
+
+        // a bundle isn't started in a frozen bank and the cost model is rolled back
+        assert_eq!(
+            BundleStage::update_qos_and_execute_record_commit_bundle(
+                &sanitized_bundle,
+                &recorder,
+                &None,
+                &gossip_vote_sender,
+                &qos_service,
+                &bank_start,
+                &mut BundleStageLeaderStats::default(),
+                &TEST_MAX_RETRY_DURATION,
+                &TEST_MAX_EXECUTION_DURATION,
+                None,
+            ),
+            Err(PohMaxHeightError)
+        );
+        assert_eq!(bank.read_cost_tracker().unwrap().transaction_count(), 0);
+
+        // no part of the bundle was committed or recorded
+        assert_eq!(bank.get_balance(&recipient), 0);
+        assert_eq!(bank.transaction_count(), 0);
+        exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+        assert!(entry_receiver
+            .try_iter()
+            .all(|(_, (entry, _))| entry.transactions.is_empty()));
+    }
+
+    #[test]
+    fn test_reserved_bundle_executes_before_conflicting_transaction() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(
+            Blockstore::open(ledger_path.path())
+                .expect("Expected to be able to open database ledger"),
+        );
+        let poh_config = PohConfig {
+            target_tick_count: Some(bank.max_tick_height() - 1),
+            ..PohConfig::default()
+        };
+        let (exit, poh_recorder, poh_service, _entry_receiver) =
+            create_test_recorder(&bank, &blockstore, Some(poh_config), None);
+        let recorder = poh_recorder.read().unwrap().recorder();
+        let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+        let qos_service = QosService::new(Arc::new(RwLock::new(CostModel::default())), 0);
+        let bank_start = poh_recorder.read().unwrap().bank_start().unwrap();
+        let bundle_account_locker = BundleAccountLocker::default();
+
+        // the bundle and the BankingStage transaction both spend the payer's whole balance
+        let payer = Keypair::new();
+        bank.transfer(10_000, &mint_keypair, &payer.pubkey())
+            .unwrap();
+        let bundle_recipient = Pubkey::new_unique();
+        let banking_recipient = Pubkey::new_unique();
+        let bundle_transaction = transfer(&payer, &bundle_recipient, 10_000, genesis_config.hash());
+        let packet_bundle = PacketBundle {
+            batch: PacketBatch::new(vec![Packet::from_data(None, &bundle_transaction).unwrap()]),
+            uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
+        };
+        let sanitized_bundle = SanitizedBundle {
+            transactions: vec![SanitizedTransaction::from_transaction_for_tests(
+                bundle_transaction,
+            )],
+            expiry_slot: Slot::MAX,
+        };
+        let banking_transactions = vec![SanitizedTransaction::from_transaction_for_tests(
+            transfer(&payer, &banking_recipient, 10_000, genesis_config.hash()),
+        )];
+        let process_banking_transactions = || {
+            BankingStage::process_and_record_transactions(
+                &bank,
+                &banking_transactions,
+                &recorder,
+                0,
+                None,
+                &gossip_vote_sender,
+                &qos_service,
+                None,
+                &bundle_account_locker,
+            )
+        };
+
+        // the bundle is admitted while leader, then BankingStage gets to the conflicting
+        // transaction before an execution pass picks the bundle up
+        let mut unprocessed_bundles =
+            BundleQueue::new(10, HashSet::default(), bundle_account_locker.clone());
+        assert!(unprocessed_bundles.push_received(packet_bundle, bank.slot(), Some(&bank)));
+        process_banking_transactions();
+        assert_eq!(bank.get_balance(&payer.pubkey()), 10_000);
+        assert_eq!(bank.get_balance(&banking_recipient), 0);
+        let (_, reservation) = unprocessed_bundles.drain_reserved().next().unwrap();
+        let reservation = reservation.unwrap();
+        process_banking_transactions();
+        assert_eq!(bank.get_balance(&banking_recipient), 0);
+
+        // the reservation is handed to the execution locks without a gap
+        let locked_bundle = bundle_account_locker
+            .prepare_locked_bundle(&sanitized_bundle, &bank)
+            .unwrap();
+        drop(reservation);
+        process_banking_transactions();
+        assert_eq!(bank.get_balance(&banking_recipient), 0);
+
+        assert!(BundleStage::update_qos_and_execute_record_commit_bundle(
+            locked_bundle.sanitized_bundle(),
+            &recorder,
+            &None,
+            &gossip_vote_sender,
+            &qos_service,
+            &bank_start,
+            &mut BundleStageLeaderStats::default(),
+            &TEST_MAX_RETRY_DURATION,
+            &TEST_MAX_EXECUTION_DURATION,
+            None,
+        )
+        .is_ok());
+        drop(locked_bundle);
+
+        // the deferred transaction is retried against the state the bundle left behind
+        process_banking_transactions();
+        assert_eq!(bank.get_balance(&bundle_recipient), 10_000);
+        assert_eq!(bank.get_balance(&banking_recipient), 0);
+        assert_eq!(bank.get_balance(&payer.pubkey()), 0);
+
+        // reservations of bundles dropped before they're locked are released and measured.
+        // bundles without a uuid are told apart by their transactions
+        let new_nil_uuid_bundle = |lamports| {
+            let transaction = transfer(&payer, &bundle_recipient, lamports, genesis_config.hash());
+            let packet_bundle = PacketBundle {
+                batch: PacketBatch::new(vec![Packet::from_data(None, &transaction).unwrap()]),
+                uuid: Uuid::nil(),
+                received_at: Instant::now(),
+                expiry_slot: Slot::MAX,
+            };
+            let sanitized_bundle = SanitizedBundle {
+                transactions: vec![SanitizedTransaction::from_transaction_for_tests(
+                    transaction,
+                )],
+                expiry_slot: Slot::MAX,
+            };
+            (packet_bundle, sanitized_bundle)
+        };
+        let kept_bundle = new_nil_uuid_bundle(1);
+        let dropped_bundle = new_nil_uuid_bundle(2);
+        let mut bundle_reservations = HashMap::from([
+            (
+                BundleId::new(&kept_bundle.0),
+                bundle_account_locker
+                    .reserve_bundle_accounts(&kept_bundle.1, &bank)
+                    .unwrap(),
+            ),
+            (
+                BundleId::new(&dropped_bundle.0),
+                bundle_account_locker
+                    .reserve_bundle_accounts(&dropped_bundle.1, &bank)
+                    .unwrap(),
+            ),
+        ]);
+        let kept_bundle_id = BundleId::new(&kept_bundle.0);
+        let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
+        BundleStage::release_dropped_bundle_reservations(
+            &mut bundle_reservations,
+            &VecDeque::from([kept_bundle]),
+            &mut bundle_stage_leader_stats,
+        );
+        assert_eq!(
+            bundle_reservations.keys().collect::<Vec<_>>(),
+            vec![&kept_bundle_id]
+        );
+        assert_eq!(
+            bundle_stage_leader_stats
+                .bundle_stage_stats()
+                .num_bundle_reservations(),
+            1
+        );
+        drop(bundle_reservations);
+        assert!(bundle_account_locker.write_locks().is_empty());
+
+        exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+    }
+
+    #[test]
+    fn test_discarded_bundle_outcomes() {
+        use BundleTransactionOutcome::{Discarded, Failed, NotExecuted};
+
+        // the second chunk starts at the third transaction, its first transaction fails
+        assert_eq!(
+            discarded_bundle_outcomes(
+                6,
+                2,
+                &[
+                    Err(AccountNotFound),
+                    Ok(()),
+                    Err(TransactionError::BundleNotContinuous),
+                ],
+            ),
+            vec![
+                Discarded,
+                Discarded,
+                Failed(AccountNotFound),
+                Discarded,
+                NotExecuted,
+                NotExecuted,
+            ]
+        );
+        assert_eq!(
+            discarded_bundle_outcomes(
+                2,
+                0,
+                &[
+                    Ok(()),
+                    Err(TransactionError::InstructionError(
+                        0,
+                        InstructionError::Custom(1)
+                    )),
+                ],
+            ),
+            vec![
+                Discarded,
+                Failed(TransactionError::InstructionError(
+                    0,
+                    InstructionError::Custom(1)
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_drop_bundles_below_min_tip() {
+        let tip_account = Pubkey::new_unique();
+        let tip_accounts = HashSet::from([tip_account]);
+        let new_bundle = |tip_lamports: u64| {
+            let transaction =
+                transfer(&Keypair::new(), &tip_account, tip_lamports, Hash::default());
+            (
+                PacketBundle {
+                    batch: PacketBatch::new(vec![Packet::from_data(None, &transaction).unwrap()]),
+                    uuid: Uuid::new_v4(),
+                    received_at: Instant::now(),
+                    expiry_slot: Slot::MAX,
+                },
+                SanitizedBundle {
+                    transactions: vec![SanitizedTransaction::from_transaction_for_tests(
+                        transaction,
+                    )],
+                    expiry_slot: Slot::MAX,
+                },
+            )
+        };
+        let at_min_tip = new_bundle(1_000);
+        let below_min_tip = new_bundle(999);
+        let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
+
+        let sanitized_bundles = BundleStage::drop_denied_bundles(
+            VecDeque::from([at_min_tip.clone(), below_min_tip]),
+            &BundleQueue::new(10, tip_accounts.clone(), BundleAccountLocker::default()),
+            &BundleFilter {
+                min_tip_lamports: 1_000,
+                ..BundleFilter::default()
+            },
+            &ProtectedAccounts::default(),
+            &tip_accounts,
+            &mut bundle_stage_leader_stats,
+        );
+        assert_eq!(
+            sanitized_bundles
+                .iter()
+                .map(|(packet_bundle, _)| packet_bundle.uuid)
+                .collect::<Vec<_>>(),
+            vec![at_min_tip.0.uuid]
+        );
+        let slot_stats = bundle_stage_leader_stats.bundle_stage_stats().slot_stats();
+        assert_eq!(slot_stats.num_tip_too_low, 1);
+        assert_eq!(slot_stats.num_denied, 0);
+    }
+
+    #[test]
+    fn test_drop_bundles_writing_protected_accounts() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(1_000_000);
+        let vote_account = Pubkey::new_unique();
+        let protected_accounts = ProtectedAccounts::new(
+            Pubkey::new_unique(),
+            vote_account,
+            &ProtectedAccountsConfig::default(),
+            &Bank::new_for_tests(&genesis_config),
+        );
+        let new_bundle = |transaction: Transaction| {
+            (
+                PacketBundle {
+                    batch: PacketBatch::new(vec![Packet::from_data(None, &transaction).unwrap()]),
+                    uuid: Uuid::new_v4(),
+                    received_at: Instant::now(),
+                    expiry_slot: Slot::MAX,
+                },
+                SanitizedBundle {
+                    transactions: vec![SanitizedTransaction::from_transaction_for_tests(
+                        transaction,
+                    )],
+                    expiry_slot: Slot::MAX,
+                },
+            )
+        };
+        let payer = Keypair::new();
+        let writes_vote_account = new_bundle(transfer(&payer, &vote_account, 1, Hash::default()));
+        let reads_vote_account = new_bundle(Transaction::new_signed_with_payer(
+            &[Instruction::new_with_bytes(
+                Pubkey::new_unique(),
+                &[],
+                vec![AccountMeta::new_readonly(vote_account, false)],
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        ));
+        let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
+
+        // the operator's filter being empty doesn't turn off the protection
+        let sanitized_bundles = BundleStage::drop_denied_bundles(
+            VecDeque::from([writes_vote_account, reads_vote_account.clone()]),
+            &BundleQueue::new(10, HashSet::default(), BundleAccountLocker::default()),
+            &BundleFilter::default(),
+            &protected_accounts,
+            &HashSet::default(),
+            &mut bundle_stage_leader_stats,
+        );
+        assert_eq!(
+            sanitized_bundles
+                .iter()
+                .map(|(packet_bundle, _)| packet_bundle.uuid)
+                .collect::<Vec<_>>(),
+            vec![reads_vote_account.0.uuid]
+        );
+        let slot_stats = bundle_stage_leader_stats.bundle_stage_stats().slot_stats();
+        assert_eq!(slot_stats.num_protected_account_writes, 1);
+        assert_eq!(slot_stats.num_denied, 0);
+    }
+
+    #[test]
+    fn test_bundle_max_retries() {
+        solana_logger::setup_with_default("INFO");
+
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(100_000_000);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+        bank.write_cost_tracker()
+            .unwrap()
+            .set_limits(u64::MAX, u64::MAX, u64::MAX);
+
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(
+            Blockstore::open(ledger_path.path())
+                .expect("Expected to be able to open database ledger"),
+        );
+        let poh_config = PohConfig {
+            // limit tick count to avoid clearing working_bank at
+            // PohRecord then PohRecorderError(MaxHeightReached) at BankingStage
+            target_tick_count: Some(bank.max_tick_height() - 1), // == 1, only enough for ticks, not txs
+            ..PohConfig::default()
+        };
+        let (exit, poh_recorder, poh_service, _entry_receiver) =
+            create_test_recorder(&bank, &blockstore, Some(poh_config), None);
+        let recorder = poh_recorder.read().unwrap().recorder();
+        let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+        let cost_model = Arc::new(RwLock::new(CostModel::default()));
+        let qos_service = QosService::new(cost_model, 0);
+        let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
+        let bank_start = poh_recorder.read().unwrap().bank_start().unwrap();
+
+        // Create two transfers
+        // 0. mint_keypair -> keypair0
+        // 1. keypair0 -> keypair 1
+        // Lock the accounts through the bank for tx1 and try to process tx0.
+        // It should timeout because BundleStage will continue to fail to get locks on keypair0.
+
+        let keypair0 = Keypair::new();
+        let keypair1 = Keypair::new();
+
+        let tx0 = VersionedTransaction::from(transfer(
+            &mint_keypair,
+            &keypair0.pubkey(),
+            100_000,
+            genesis_config.hash(),
+        ));
+
+        let tx1 = transfer(&keypair0, &keypair1.pubkey(), 50_000, genesis_config.hash());
+        let sanitized_txs_1 = vec![SanitizedTransaction::from_transaction_for_tests(tx1)];
+
+        // grab lock on tx1
+        let _batch = bank.prepare_sanitized_batch(&sanitized_txs_1);
+
+        // push and pop tx0
+        let bundle = PacketBundle {
+            batch: PacketBatch::new(vec![Packet::from_data(None, tx0).unwrap()]),
+            uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
+        };
+        info!("test_bundle_max_retries uuid: {:?}", bundle.uuid);
+
+        let sanitized_bundle = get_sanitized_bundle(
+            &bundle,
+            &bank,
+            &HashSet::default(),
+            &HashSet::default(),
+            &BundleLimits::default(),
+            bundle_stage_leader_stats.transaction_errors(),
+        )
+        .unwrap();
+
+        let result = BundleStage::update_qos_and_execute_record_commit_bundle(
+            &sanitized_bundle,
+            &recorder,
+            &None,
+            &gossip_vote_sender,
+            &qos_service,
+            &bank_start,
+            &mut bundle_stage_leader_stats,
+            &TEST_MAX_RETRY_DURATION,
+            &TEST_MAX_EXECUTION_DURATION,
+            None,
+        );
+        info!("test_bundle_max_retries result: {:?}", result);
+        assert!(matches!(
+            result,
+            Err(BundleExecutionError::MaxRetriesExceeded(_))
+        ));
+
+        exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+    }
+
+    #[test]
+    fn test_bundle_contention_retries() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(100_000_000);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(
+            Blockstore::open(ledger_path.path())
+                .expect("Expected to be able to open database ledger"),
+        );
+        let poh_config = PohConfig {
+            target_tick_count: Some(bank.max_tick_height() - 1),
+            ..PohConfig::default()
+        };
+        let (exit, poh_recorder, poh_service, _entry_receiver) =
+            create_test_recorder(&bank, &blockstore, Some(poh_config), None);
+        let recorder = poh_recorder.read().unwrap().recorder();
+        let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+        let qos_service = QosService::new(Arc::new(RwLock::new(CostModel::default())), 0);
+        let bank_start = poh_recorder.read().unwrap().bank_start().unwrap();
+        let keypair = Arc::new(Keypair::new());
+        let cluster_info = Arc::new(ClusterInfo::new(
+            ContactInfo::new_localhost(&keypair.pubkey(), 0),
+            keypair.clone(),
+            SocketAddrSpace::Unspecified,
+        ));
+        let block_builder_fee_info = Arc::new(Mutex::new(BlockBuilderFeeInfo {
+            block_builder: keypair.pubkey(),
+            block_builder_commission: 0,
+        }));
+        let tip_manager = TipManager::new(TipManagerConfig::default());
+        let thread_pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let bundle_account_locker = BundleAccountLocker::default();
+        let mut unprocessed_bundles =
+            BundleQueue::new(10, HashSet::default(), BundleAccountLocker::default());
+        let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
+        let mut execute_bundles =
+            |unprocessed_bundles: &mut BundleQueue,
+             bundle_stage_leader_stats: &mut BundleStageLeaderStats| {
+                BundleStage::execute_bundles_until_empty_or_end_of_slot(
+                    &bundle_account_locker,
+                    unprocessed_bundles,
+                    &HashSet::default(),
+                    &bank_start,
+                    &HashSet::default(),
+                    &cluster_info,
+                    &recorder,
+                    &None,
+                    &gossip_vote_sender,
+                    &qos_service,
+                    &tip_manager,
+                    &TEST_MAX_RETRY_DURATION,
+                    &TEST_MAX_EXECUTION_DURATION,
+                    1,
+                    &mut BundleBlockUnits::new(u64::MAX),
+                    &mut TipAccountsUpdate::default(),
+                    bundle_stage_leader_stats,
+                    &block_builder_fee_info,
+                    false,
+                    &BundleLimits::default(),
+                    &BundleFilter::default(),
+                    &ProtectedAccounts::default(),
+                    &thread_pool,
+                )
+            };
+
+        // BankingStage holds the account the bundles transfer to
+        let recipient = Keypair::new();
+        let banking_txs = vec![SanitizedTransaction::from_transaction_for_tests(transfer(
+            &recipient,
+            &Pubkey::new_unique(),
+            1,
+            genesis_config.hash(),
+        ))];
+        let new_bundle = |lamports: u64| PacketBundle {
+            batch: PacketBatch::new(vec![Packet::from_data(
+                None,
+                transfer(
+                    &mint_keypair,
+                    &recipient.pubkey(),
+                    lamports,
+                    genesis_config.hash(),
+                ),
+            )
+            .unwrap()]),
+            uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
+        };
+        let banking_batch = bank.prepare_sanitized_batch(&banking_txs);
+
+        // the bundle is queued again once, then dropped while the account is still in use
+        let bundle = new_bundle(1_000);
+        assert!(unprocessed_bundles.push_received(bundle.clone(), bank.slot(), None));
+        execute_bundles(&mut unprocessed_bundles, &mut bundle_stage_leader_stats);
+        assert_eq!(unprocessed_bundles.len(), 1);
+        assert_eq!(unprocessed_bundles.num_retries(&bundle), 1);
+        execute_bundles(&mut unprocessed_bundles, &mut bundle_stage_leader_stats);
+        assert!(unprocessed_bundles.is_empty());
+        let slot_stats = bundle_stage_leader_stats.bundle_stage_stats().slot_stats();
+        assert_eq!(slot_stats.num_retried_account_locks, 1);
+        assert_eq!(slot_stats.num_failed_account_locks, 1);
+        assert_eq!(slot_stats.num_executed, 0);
+
+        // the retry succeeds once BankingStage is done with the account
+        let bundle = new_bundle(2_000);
+        assert!(unprocessed_bundles.push_received(bundle.clone(), bank.slot(), None));
+        execute_bundles(&mut unprocessed_bundles, &mut bundle_stage_leader_stats);
+        assert_eq!(unprocessed_bundles.num_retries(&bundle), 1);
+        drop(banking_batch);
+        execute_bundles(&mut unprocessed_bundles, &mut bundle_stage_leader_stats);
+        assert!(unprocessed_bundles.is_empty());
+        let slot_stats = bundle_stage_leader_stats.bundle_stage_stats().slot_stats();
+        assert_eq!(slot_stats.num_retried_account_locks, 2);
+        assert_eq!(slot_stats.num_failed_account_locks, 1);
+        assert_eq!(slot_stats.num_executed, 1);
+        assert_eq!(bank.get_balance(&recipient.pubkey()), 2_000);
+
+        exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+    }
+
+    #[test]
+    fn test_tip_accounts_update_retried_next_slot() {
+        let mut tip_accounts_update = TipAccountsUpdate::default();
+        assert!(tip_accounts_update.should_update(10));
+        tip_accounts_update.record(10, &Ok(()));
+        assert!(!tip_accounts_update.should_update(10));
+        assert!(tip_accounts_update.should_update(11));
+
+        // a failure blocks the rest of the slot
+        tip_accounts_update.record(11, &Err(BundleExecutionError::LockError));
+        assert!(tip_accounts_update.failed());
+        assert!(!tip_accounts_update.should_update(11));
+
+        // and is tried again in the next leader slot, even within the same leader window
+        assert!(tip_accounts_update.should_update(12));
+        tip_accounts_update.record(12, &Err(BundleExecutionError::LockError));
+        assert!(tip_accounts_update.failed());
+        assert!(tip_accounts_update.should_update(13));
+        tip_accounts_update.record(13, &Ok(()));
+        assert!(!tip_accounts_update.failed());
+        assert!(tip_accounts_update.should_update(20));
+    }
+
+    #[test]
+    fn test_bundle_block_units() {
+        let mut bundle_block_units = BundleBlockUnits::new(1_000);
+        assert_eq!(bundle_block_units.units(5), 0);
+        bundle_block_units.add(5, 600);
+        bundle_block_units.add(5, 600);
+        assert_eq!(bundle_block_units.units(5), 1_200);
+
+        // the count starts over with the next bank
+        assert_eq!(bundle_block_units.units(6), 0);
+        bundle_block_units.add(6, 100);
+        assert_eq!(bundle_block_units.units(6), 100);
+        bundle_block_units.add(7, 50);
+        assert_eq!(bundle_block_units.units(7), 50);
+        assert_eq!(bundle_block_units.max_units(), 1_000);
+    }
+
+    #[test]
+    fn test_bundle_block_units_cap() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(100_000_000);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(
+            Blockstore::open(ledger_path.path())
+                .expect("Expected to be able to open database ledger"),
+        );
+        let poh_config = PohConfig {
+            target_tick_count: Some(bank.max_tick_height() - 1),
+            ..PohConfig::default()
+        };
+        let (exit, poh_recorder, poh_service, _entry_receiver) =
+            create_test_recorder(&bank, &blockstore, Some(poh_config), None);
+        let recorder = poh_recorder.read().unwrap().recorder();
+        let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+        let qos_service = QosService::new(Arc::new(RwLock::new(CostModel::default())), 0);
+        let bank_start = poh_recorder.read().unwrap().bank_start().unwrap();
+        let keypair = Arc::new(Keypair::new());
+        let cluster_info = Arc::new(ClusterInfo::new(
+            ContactInfo::new_localhost(&keypair.pubkey(), 0),
+            keypair.clone(),
+            SocketAddrSpace::Unspecified,
+        ));
+        let block_builder_fee_info = Arc::new(Mutex::new(BlockBuilderFeeInfo {
+            block_builder: keypair.pubkey(),
+            block_builder_commission: 0,
+        }));
+        let tip_manager = TipManager::new(TipManagerConfig::default());
+        let thread_pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let bundle_account_locker = BundleAccountLocker::default();
+        let mut unprocessed_bundles =
+            BundleQueue::new(10, HashSet::default(), BundleAccountLocker::default());
+        let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
+        // the first bundle admitted uses up the bundles' share of the block
+        let mut bundle_block_units = BundleBlockUnits::new(1);
+
+        let recipients: Vec<_> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        for recipient in &recipients {
+            let bundle = PacketBundle {
+                batch: PacketBatch::new(vec![Packet::from_data(
+                    None,
+                    transfer(&mint_keypair, recipient, 1_000, genesis_config.hash()),
+                )
+                .unwrap()]),
+                uuid: Uuid::new_v4(),
+                received_at: Instant::now(),
+                expiry_slot: Slot::MAX,
+            };
+            assert!(unprocessed_bundles.push_received(bundle, bank.slot(), None));
+        }
+        BundleStage::execute_bundles_until_empty_or_end_of_slot(
+            &bundle_account_locker,
+            &mut unprocessed_bundles,
+            &HashSet::default(),
+            &bank_start,
+            &HashSet::default(),
+            &cluster_info,
+            &recorder,
+            &None,
+            &gossip_vote_sender,
+            &qos_service,
+            &tip_manager,
+            &TEST_MAX_RETRY_DURATION,
+            &TEST_MAX_EXECUTION_DURATION,
+            DEFAULT_MAX_BUNDLE_CONTENTION_RETRIES,
+            &mut bundle_block_units,
+            &mut TipAccountsUpdate::default(),
+            &mut bundle_stage_leader_stats,
+            &block_builder_fee_info,
+            false,
+            &BundleLimits::default(),
+            &BundleFilter::default(),
+            &ProtectedAccounts::default(),
+            &thread_pool,
+        );
+        assert_eq!(
+            recipients
+                .iter()
+                .filter(|recipient| bank.get_balance(recipient) > 0)
+                .count(),
+            1
+        );
+        assert!(bundle_block_units.units(bank.slot()) > 0);
+        assert!(unprocessed_bundles.is_empty());
+        assert_eq!(unprocessed_bundles.num_deferred(), 2);
+        let slot_stats = bundle_stage_leader_stats.bundle_stage_stats().slot_stats();
+        assert_eq!(slot_stats.num_executed, 1);
+        assert_eq!(slot_stats.num_deferred_block_units, 2);
+
+        // bundles are admitted again once the next bank starts
+        let next_slot = bank.slot() + 1;
+        unprocessed_bundles.release_deferred(next_slot);
+        assert_eq!(unprocessed_bundles.len(), 2);
+        assert_eq!(bundle_block_units.units(next_slot), 0);
+        let deferred_bundles = unprocessed_bundles
+            .drain()
+            .map(|packet_bundle| {
+                let sanitized_bundle = SanitizedBundle {
+                    transactions: packet_bundle
+                        .batch
+                        .iter()
+                        .map(|packet| {
+                            SanitizedTransaction::from_transaction_for_tests(
+                                packet.deserialize_slice::<Transaction, _>(..).unwrap(),
+                            )
+                        })
+                        .collect(),
+                    expiry_slot: Slot::MAX,
+                };
+                (packet_bundle, sanitized_bundle)
+            })
+            .collect();
+        let admitted_bundles = BundleStage::defer_bundles_over_block_units(
+            deferred_bundles,
+            &mut unprocessed_bundles,
+            &mut bundle_block_units,
+            next_slot,
+            &qos_service,
+            &mut bundle_stage_leader_stats,
+        );
+        assert_eq!(admitted_bundles.len(), 1);
+        assert_eq!(unprocessed_bundles.num_deferred(), 1);
+
+        exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+    }
+
+    #[test]
+    fn test_bundle_timings() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(100_000_000);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(
+            Blockstore::open(ledger_path.path())
+                .expect("Expected to be able to open database ledger"),
+        );
+        let poh_config = PohConfig {
+            target_tick_count: Some(bank.max_tick_height() - 1),
+            ..PohConfig::default()
+        };
+        let (exit, poh_recorder, poh_service, _entry_receiver) =
+            create_test_recorder(&bank, &blockstore, Some(poh_config), None);
+        let recorder = poh_recorder.read().unwrap().recorder();
+        let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+        let qos_service = QosService::new(Arc::new(RwLock::new(CostModel::default())), 0);
+        let bank_start = poh_recorder.read().unwrap().bank_start().unwrap();
+        let keypair = Arc::new(Keypair::new());
+        let cluster_info = Arc::new(ClusterInfo::new(
+            ContactInfo::new_localhost(&keypair.pubkey(), 0),
+            keypair.clone(),
+            SocketAddrSpace::Unspecified,
+        ));
+        let block_builder_fee_info = Arc::new(Mutex::new(BlockBuilderFeeInfo {
+            block_builder: keypair.pubkey(),
+            block_builder_commission: 0,
+        }));
+        let tip_manager = TipManager::new(TipManagerConfig::default());
+        let thread_pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let mut unprocessed_bundles =
+            BundleQueue::new(10, HashSet::default(), BundleAccountLocker::default());
+        let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
+
+        let bundle = PacketBundle {
+            batch: PacketBatch::new(vec![Packet::from_data(
+                None,
+                transfer(
+                    &mint_keypair,
+                    &Pubkey::new_unique(),
+                    1_000,
+                    genesis_config.hash(),
+                ),
+            )
+            .unwrap()]),
+            uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
+        };
+        let received_at = bundle.received_at;
+        assert!(unprocessed_bundles.push_received(bundle, bank.slot(), None));
+        sleep(Duration::from_millis(10));
+
+        BundleStage::execute_bundles_until_empty_or_end_of_slot(
+            &BundleAccountLocker::default(),
+            &mut unprocessed_bundles,
+            &HashSet::default(),
+            &bank_start,
+            &HashSet::default(),
+            &cluster_info,
+            &recorder,
+            &None,
+            &gossip_vote_sender,
+            &qos_service,
+            &tip_manager,
+            &TEST_MAX_RETRY_DURATION,
+            &TEST_MAX_EXECUTION_DURATION,
+            DEFAULT_MAX_BUNDLE_CONTENTION_RETRIES,
+            &mut BundleBlockUnits::new(u64::MAX),
+            &mut TipAccountsUpdate::default(),
+            &mut bundle_stage_leader_stats,
+            &block_builder_fee_info,
+            false,
+            &BundleLimits::default(),
+            &BundleFilter::default(),
+            &ProtectedAccounts::default(),
+            &thread_pool,
+        );
+        let elapsed_us = received_at.elapsed().as_micros() as u64;
+
+        let bundle_timings = bundle_stage_leader_stats
+            .bundle_stage_stats()
+            .bundle_timings()
+            .to_vec();
+        assert_eq!(bundle_timings.len(), 1);
+        let timings = bundle_timings[0];
+        assert!(timings.queued_us >= 10_000);
+        assert!(timings.execute_us > 0);
+        assert!(timings.total_us <= elapsed_us);
+        // the phases account for the bundle's time in BundleStage, less sanitizing and simulating
+        assert!(timings.phases_us() <= timings.total_us);
+        assert!(timings.total_us - timings.phases_us() < 100_000);
+
+        exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+    }
+
+    #[test]
+    fn test_bundle_expires_while_queued() {
+        let new_bundle = |expiry_slot: Slot| PacketBundle {
+            batch: PacketBatch::new(vec![Packet::default()]),
+            uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot,
+        };
+        let mut unprocessed_bundles =
+            BundleQueue::new(10, HashSet::default(), BundleAccountLocker::default());
+        for expiry_slot in [3, 5, 4, 10] {
+            assert!(unprocessed_bundles.push(new_bundle(expiry_slot), 0));
+        }
+        let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
+
+        // bundles may still be executed in their expiry slot
+        BundleStage::drop_expired_bundles(
+            &mut unprocessed_bundles,
+            5,
+            &mut bundle_stage_leader_stats,
+        );
+        assert_eq!(
+            unprocessed_bundles
+                .iter()
+                .map(|bundle| bundle.expiry_slot)
+                .collect::<Vec<_>>(),
+            vec![5, 10]
+        );
+
+        // the leader's next slot expires the next bundle
+        BundleStage::drop_expired_bundles(
+            &mut unprocessed_bundles,
+            6,
+            &mut bundle_stage_leader_stats,
+        );
+        assert_eq!(unprocessed_bundles.len(), 1);
+        assert_eq!(unprocessed_bundles.iter().next().unwrap().expiry_slot, 10);
+    }
+
+    #[test]
+    fn test_bundle_expires_before_execution() {
+        let (genesis_config, mut bundle) = setup_successful_tx();
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(
+            Blockstore::open(ledger_path.path())
+                .expect("Expected to be able to open database ledger"),
+        );
+        let (exit, poh_recorder, poh_service, _entry_receiver) =
+            create_test_recorder(&bank, &blockstore, None, None);
+        let recorder = poh_recorder.read().unwrap().recorder();
+        let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+        let qos_service = QosService::new(Arc::new(RwLock::new(CostModel::default())), 0);
+        let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
+
+        // scheduled while still valid
+        bundle.expiry_slot = bank.slot();
+        let sanitized_bundle = get_sanitized_bundle(
+            &bundle,
+            &bank,
+            &HashSet::default(),
+            &HashSet::default(),
+            &BundleLimits::default(),
+            bundle_stage_leader_stats.transaction_errors(),
+        )
+        .unwrap();
+
+        // but only executed once the leader moved on to a later slot
+        let next_bank = Arc::new(Bank::new_from_parent(
+            &bank,
+            &Pubkey::default(),
+            bank.slot() + 1,
+        ));
+        let bank_start = BankStart {
+            working_bank: next_bank.clone(),
+            bank_creation_time: Arc::new(Instant::now()),
+        };
+        assert_eq!(
+            BundleStage::update_qos_and_execute_record_commit_bundle(
+                &sanitized_bundle,
+                &recorder,
+                &None,
+                &gossip_vote_sender,
+                &qos_service,
+                &bank_start,
+                &mut bundle_stage_leader_stats,
+                &TEST_MAX_RETRY_DURATION,
+                &TEST_MAX_EXECUTION_DURATION,
+                None,
+            ),
+            Err(BundleExecutionError::Expired(bank.slot()))
+        );
+        assert_eq!(
+            next_bank.read_cost_tracker().unwrap().transaction_count(),
+            0
+        );
+
+        exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+    }
+
+    #[test]
+    fn test_bundle_queued_between_leader_slots() {
+        let (genesis_config, bundle) = setup_successful_tx();
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(
+            Blockstore::open(ledger_path.path())
+                .expect("Expected to be able to open database ledger"),
+        );
+        let (exit, poh_recorder, poh_service, _entry_receiver) =
+            create_test_recorder(&bank, &blockstore, None, None);
+        let recorder = poh_recorder.read().unwrap().recorder();
+        let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+        let qos_service = QosService::new(Arc::new(RwLock::new(CostModel::default())), 0);
+        let keypair = Arc::new(Keypair::new());
+        let cluster_info = Arc::new(ClusterInfo::new(
+            ContactInfo::new_localhost(&keypair.pubkey(), 0),
+            keypair.clone(),
+            SocketAddrSpace::Unspecified,
+        ));
+        let block_builder_fee_info = Arc::new(Mutex::new(BlockBuilderFeeInfo {
+            block_builder: keypair.pubkey(),
+            block_builder_commission: 0,
+        }));
+        let tip_manager = TipManager::new(TipManagerConfig::default());
+        let thread_pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let bundle_account_locker = BundleAccountLocker::default();
+        let mut unprocessed_bundles =
+            BundleQueue::new(10, HashSet::default(), BundleAccountLocker::default());
+        let mut bundle_stage_leader_stats = BundleStageLeaderStats::default();
+        let mut execute_bundles =
+            |unprocessed_bundles: &mut BundleQueue,
+             bank_start: &BankStart,
+             bundle_stage_leader_stats: &mut BundleStageLeaderStats| {
+                BundleStage::execute_bundles_until_empty_or_end_of_slot(
+                    &bundle_account_locker,
+                    unprocessed_bundles,
+                    &HashSet::default(),
+                    bank_start,
+                    &HashSet::default(),
+                    &cluster_info,
+                    &recorder,
+                    &None,
+                    &gossip_vote_sender,
+                    &qos_service,
+                    &tip_manager,
+                    &TEST_MAX_RETRY_DURATION,
+                    &TEST_MAX_EXECUTION_DURATION,
+                    1,
+                    &mut BundleBlockUnits::new(u64::MAX),
+                    &mut TipAccountsUpdate::default(),
+                    bundle_stage_leader_stats,
+                    &block_builder_fee_info,
+                    false,
+                    &BundleLimits::default(),
+                    &BundleFilter::default(),
+                    &ProtectedAccounts::default(),
+                    &thread_pool,
+                )
+            };
+
+        // the bundle arrives after our first leader slot's bank was frozen
+        let bank_start = poh_recorder.read().unwrap().bank_start().unwrap();
+        bank.freeze();
+        assert!(unprocessed_bundles.push_received(bundle.clone(), bank.slot(), None));
+        execute_bundles(
+            &mut unprocessed_bundles,
+            &bank_start,
+            &mut bundle_stage_leader_stats,
+        );
+        assert_eq!(unprocessed_bundles.len(), 1);
+        assert_eq!(unprocessed_bundles.num_retries(&bundle), 0);
+        assert_eq!(
+            unprocessed_bundles.iter().next().unwrap().expiry_slot,
+            Slot::MAX
+        );
+        let slot_stats = bundle_stage_leader_stats.bundle_stage_stats().slot_stats();
+        assert_eq!(slot_stats.num_failed_poh_record, 0);
+        assert_eq!(slot_stats.num_executed, 0);
+
+        // and is executed in the next one
+        let next_bank = Arc::new(Bank::new_from_parent(
+            &bank,
+            &Pubkey::default(),
+            bank.slot() + 1,
+        ));
+        poh_recorder.write().unwrap().set_bank(&next_bank, false);
+        let bank_start = poh_recorder.read().unwrap().bank_start().unwrap();
+        execute_bundles(
+            &mut unprocessed_bundles,
+            &bank_start,
+            &mut bundle_stage_leader_stats,
+        );
+        assert!(unprocessed_bundles.is_empty());
+        let slot_stats = bundle_stage_leader_stats.bundle_stage_stats().slot_stats();
+        assert_eq!(slot_stats.num_executed, 1);
+        assert_eq!(
+            next_bank.read_cost_tracker().unwrap().transaction_count(),
+            1
+        );
+
+        exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+    }
+
+    fn new_signed_packet_bundle() -> PacketBundle {
+        let transaction = transfer(&Keypair::new(), &Pubkey::new_unique(), 1, Hash::default());
+        PacketBundle {
+            batch: PacketBatch::new(vec![Packet::from_data(None, &transaction).unwrap()]),
+            uuid: Uuid::new_v4(),
+            received_at: Instant::now(),
+            expiry_slot: Slot::MAX,
+        }
+    }
+
+    #[test]
+    fn test_bundles_dropped_unless_leader_soon() {
+        let (bundle_sender, bundle_receiver) = unbounded();
+        let bundle_queue_stats = BundleQueueStats::default();
+        let mut bundle_stage_stats = BundleStageLoopStats::default();
+        let mut unprocessed_bundles =
+            BundleQueue::new(10, HashSet::default(), BundleAccountLocker::default());
+        let bundle_sigverifier = BundleSigverifier::default();
+        let mut bundle_stage_leader_stats = BundleStageLeaderSlotTrackingMetrics::new(false);
+        let new_bundle = new_signed_packet_bundle;
+
+        // held on to within the lookahead
+        bundle_sender.send(vec![new_bundle()]).unwrap();
+        assert_eq!(
+            BundleStage::receive_and_buffer_bundles(
+                &bundle_receiver,
+                &mut unprocessed_bundles,
+                &bundle_sigverifier,
+                &bundle_queue_stats,
+                &mut bundle_stage_stats,
+                &mut bundle_stage_leader_stats,
+                0,
+                None,
+                true,
+                true,
+                Duration::from_millis(10),
+            ),
+            Ok(1)
+        );
+        assert_eq!(unprocessed_bundles.len(), 1);
+        assert_eq!(bundle_stage_stats.num_bundles_dropped_not_leader, 0);
+
+        // dropped on arrival once the leader window has passed, the queued bundle is untouched
+        bundle_sender
+            .send(vec![new_bundle(), new_bundle()])
+            .unwrap();
+        assert_eq!(
+            BundleStage::receive_and_buffer_bundles(
+                &bundle_receiver,
+                &mut unprocessed_bundles,
+                &bundle_sigverifier,
+                &bundle_queue_stats,
+                &mut bundle_stage_stats,
+                &mut bundle_stage_leader_stats,
+                0,
+                None,
+                false,
+                true,
+                Duration::from_millis(10),
+            ),
+            Ok(2)
+        );
+        assert_eq!(unprocessed_bundles.len(), 1);
+        assert_eq!(bundle_stage_stats.num_bundles_dropped_not_leader, 2);
+    }
+
+    #[test]
+    fn test_bundles_with_invalid_signature_dropped() {
+        let (bundle_sender, bundle_receiver) = unbounded();
+        let mut bundle_stage_stats = BundleStageLoopStats::default();
+        let mut unprocessed_bundles =
+            BundleQueue::new(10, HashSet::default(), BundleAccountLocker::default());
+
+        // one forged signature fails the whole bundle, the others are queued
+        let mut forged_bundle = new_signed_packet_bundle();
+        let mut transaction = VersionedTransaction::from(transfer(
+            &Keypair::new(),
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        transaction.signatures[0] = Keypair::new().sign_message(&transaction.message.serialize());
+        forged_bundle
+            .batch
+            .push(Packet::from_data(None, &transaction).unwrap());
+        bundle_sender
+            .send(vec![
+                new_signed_packet_bundle(),
+                forged_bundle,
+                new_signed_packet_bundle(),
+            ])
+            .unwrap();
+
+        assert_eq!(
+            BundleStage::receive_and_buffer_bundles(
+                &bundle_receiver,
+                &mut unprocessed_bundles,
+                &BundleSigverifier::default(),
+                &BundleQueueStats::default(),
+                &mut bundle_stage_stats,
+                &mut BundleStageLeaderSlotTrackingMetrics::new(false),
+                0,
+                None,
+                true,
+                true,
+                Duration::from_millis(10),
+            ),
+            Ok(3)
+        );
+        assert_eq!(unprocessed_bundles.len(), 2);
+        assert_eq!(bundle_stage_stats.num_bundles_dropped_invalid_signature, 1);
+    }
+
+    #[test]
+    fn test_bundles_failing_precheck_dropped_before_sigverify() {
+        let (bundle_sender, bundle_receiver) = unbounded();
+        let mut bundle_stage_stats = BundleStageLoopStats::default();
+        let mut unprocessed_bundles =
+            BundleQueue::new(10, HashSet::default(), BundleAccountLocker::default());
+
+        // the duplicate is caught before the forged signature is ever looked at
+        let mut transaction = VersionedTransaction::from(transfer(
+            &Keypair::new(),
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        transaction.signatures[0] = Keypair::new().sign_message(&transaction.message.serialize());
+        let packet = Packet::from_data(None, &transaction).unwrap();
+        let duplicate_bundle = PacketBundle {
+            batch: PacketBatch::new(vec![packet.clone(), packet]),
+            ..new_signed_packet_bundle()
+        };
+        bundle_sender
+            .send(vec![duplicate_bundle, new_signed_packet_bundle()])
+            .unwrap();
+
+        assert_eq!(
+            BundleStage::receive_and_buffer_bundles(
+                &bundle_receiver,
+                &mut unprocessed_bundles,
+                &BundleSigverifier::default(),
+                &BundleQueueStats::default(),
+                &mut bundle_stage_stats,
+                &mut BundleStageLeaderSlotTrackingMetrics::new(false),
+                0,
+                None,
+                true,
+                true,
+                Duration::from_millis(10),
+            ),
+            Ok(2)
+        );
+        assert_eq!(unprocessed_bundles.len(), 1);
+        assert_eq!(bundle_stage_stats.num_bundles_dropped_precheck, 1);
+        assert_eq!(bundle_stage_stats.num_bundles_dropped_invalid_signature, 0);
+    }
+
+    #[test]
+    fn test_bundles_dropped_while_disabled() {
+        let (bundle_sender, bundle_receiver) = unbounded();
+        let bundle_queue_stats = BundleQueueStats::default();
+        let mut bundle_stage_stats = BundleStageLoopStats::default();
+        let mut unprocessed_bundles =
+            BundleQueue::new(10, HashSet::default(), BundleAccountLocker::default());
+        let bundle_sigverifier = BundleSigverifier::default();
+        let mut bundle_stage_leader_stats = BundleStageLeaderSlotTrackingMetrics::new(false);
+        let bundles_enabled = AtomicBool::new(true);
+        let mut receive_bundles =
+            |bundles: Vec<PacketBundle>,
+             unprocessed_bundles: &mut BundleQueue,
+             bundle_stage_stats: &mut BundleStageLoopStats| {
+                bundle_sender.send(bundles).unwrap();
+                BundleStage::receive_and_buffer_bundles(
+                    &bundle_receiver,
+                    unprocessed_bundles,
+                    &bundle_sigverifier,
+                    &bundle_queue_stats,
+                    bundle_stage_stats,
+                    &mut bundle_stage_leader_stats,
+                    0,
+                    None,
+                    true,
+                    bundles_enabled.load(Ordering::Relaxed),
+                    Duration::from_millis(10),
+                )
+            };
+
+        assert_eq!(
+            receive_bundles(
+                vec![new_signed_packet_bundle()],
+                &mut unprocessed_bundles,
+                &mut bundle_stage_stats
+            ),
+            Ok(1)
+        );
+        assert_eq!(unprocessed_bundles.len(), 1);
+
+        // the queued bundle is dropped along with the new ones, leaving nothing to execute
+        bundles_enabled.store(false, Ordering::Relaxed);
+        assert_eq!(
+            receive_bundles(
+                vec![new_signed_packet_bundle(), new_signed_packet_bundle()],
+                &mut unprocessed_bundles,
+                &mut bundle_stage_stats
+            ),
+            Ok(2)
+        );
+        assert!(unprocessed_bundles.is_empty());
+        assert_eq!(bundle_stage_stats.num_bundles_dropped_disabled, 3);
+
+        bundles_enabled.store(true, Ordering::Relaxed);
+        assert_eq!(
+            receive_bundles(
+                vec![new_signed_packet_bundle()],
+                &mut unprocessed_bundles,
+                &mut bundle_stage_stats
+            ),
+            Ok(1)
+        );
+        assert_eq!(unprocessed_bundles.len(), 1);
+        assert_eq!(bundle_stage_stats.num_bundles_dropped_disabled, 3);
+        assert_eq!(
+            bundle_stage_leader_stats
+                .bundle_stage_leader_stats()
+                .bundle_stage_stats()
+                .slot_stats()
+                .num_disabled,
+            3
+        );
+    }
+
+    #[test]
+    fn test_bundle_queue_stats_stalled_consumer() {
+        let (bundle_sender, bundle_receiver) = unbounded();
+        let bundle_queue_stats = BundleQueueStats::default();
+
+        // the block engine keeps sending while BundleStage isn't reading
+        for _ in 0..3 {
+            bundle_sender
+                .send(vec![new_signed_packet_bundle()])
+                .unwrap();
+            bundle_queue_stats.increment_enqueued(1);
+        }
+        assert_eq!(bundle_queue_stats.channel_depth(), 3);
+
+        let mut unprocessed_bundles =
+            BundleQueue::new(10, HashSet::default(), BundleAccountLocker::default());
+        assert_eq!(
+            BundleStage::receive_and_buffer_bundles(
+                &bundle_receiver,
+                &mut unprocessed_bundles,
+                &BundleSigverifier::default(),
+                &bundle_queue_stats,
+                &mut BundleStageLoopStats::default(),
+                &mut BundleStageLeaderSlotTrackingMetrics::new(false),
+                0,
+                None,
+                true,
+                true,
+                Duration::from_millis(10),
+            ),
+            Ok(3)
+        );
+        assert_eq!(bundle_queue_stats.channel_depth(), 0);
+
+        // buffered bundles that aren't executed keep getting older
+        let now = Instant::now();
+        let age = oldest_bundle_age(unprocessed_bundles.iter(), now).unwrap();
+        let later_age =
+            oldest_bundle_age(unprocessed_bundles.iter(), now + Duration::from_millis(100))
+                .unwrap();
+        assert_eq!(later_age, age + Duration::from_millis(100));
+        assert_eq!(oldest_bundle_age(&[], now), None);
+
+        let mut last_counts = BundleQueueCounts::default();
+        bundle_queue_stats.report(&mut last_counts, unprocessed_bundles.len(), Some(later_age));
+        assert_eq!(
+            last_counts,
+            BundleQueueCounts {
+                num_enqueued: 3,
+                num_dequeued: 3,
+            }
+        );
     }
 }
@@ -2,7 +2,12 @@ use {
     crate::leader_slot_banking_stage_timing_metrics::LeaderExecuteAndCommitTimings,
     solana_poh::poh_recorder::BankStart,
     solana_runtime::transaction_error_metrics::TransactionErrorMetrics,
-    solana_sdk::{clock::Slot, saturating_add_assign},
+    solana_sdk::{
+        bundle::error::{BundleExecutionError, TipPaymentError},
+        clock::Slot,
+        saturating_add_assign,
+    },
+    uuid::Uuid,
 };
 
 // Stats emitted only during leader slots
@@ -10,9 +15,19 @@ use {
 pub struct BundleStageLeaderSlotTrackingMetrics {
     current_slot: Option<Slot>,
     bundle_stage_leader_stats: BundleStageLeaderStats,
+    // report a datapoint for every bundle executed or dropped while leader
+    report_bundle_outcomes: bool,
 }
 
 impl BundleStageLeaderSlotTrackingMetrics {
+    pub fn new(report_bundle_outcomes: bool) -> Self {
+        Self {
+            current_slot: None,
+            bundle_stage_leader_stats: BundleStageLeaderStats::new(report_bundle_outcomes),
+            report_bundle_outcomes,
+        }
+    }
+
     pub fn maybe_report(&mut self, id: u32, bank_start: &Option<&BankStart>) {
         match (self.current_slot, bank_start) {
             // not was leader, not is leader
@@ -20,18 +35,21 @@ impl BundleStageLeaderSlotTrackingMetrics {
             // was leader, not leader anymore
             (Some(current_slot), None) => {
                 self.bundle_stage_leader_stats.report(id, current_slot);
-                self.bundle_stage_leader_stats = BundleStageLeaderStats::default();
+                self.bundle_stage_leader_stats =
+                    BundleStageLeaderStats::new(self.report_bundle_outcomes);
             }
             // was leader, is leader
             (Some(current_slot), Some(bank_start)) => {
                 if current_slot != bank_start.working_bank.slot() {
                     self.bundle_stage_leader_stats.report(id, current_slot);
-                    self.bundle_stage_leader_stats = BundleStageLeaderStats::default();
+                    self.bundle_stage_leader_stats =
+                        BundleStageLeaderStats::new(self.report_bundle_outcomes);
                 }
             }
             // not was leader, is leader
             (None, Some(_)) => {
-                self.bundle_stage_leader_stats = BundleStageLeaderStats::default();
+                self.bundle_stage_leader_stats =
+                    BundleStageLeaderStats::new(self.report_bundle_outcomes);
             }
         }
 
@@ -53,6 +71,16 @@ pub struct BundleStageLeaderStats {
 }
 
 impl BundleStageLeaderStats {
+    pub fn new(report_bundle_outcomes: bool) -> Self {
+        Self {
+            bundle_stage_stats: BundleStageStats {
+                report_bundle_outcomes,
+                ..BundleStageStats::default()
+            },
+            ..Self::default()
+        }
+    }
+
     pub fn transaction_errors(&mut self) -> &mut TransactionErrorMetrics {
         &mut self.transaction_errors
     }
@@ -65,6 +93,16 @@ impl BundleStageLeaderStats {
         &mut self.bundle_stage_stats
     }
 
+    /// Adds the stats of `other`, e.g. from a worker thread, to these stats.
+    pub fn accumulate(&mut self, other: &BundleStageLeaderStats) {
+        self.transaction_errors
+            .accumulate(&other.transaction_errors);
+        self.execute_and_commit_timings
+            .accumulate(&other.execute_and_commit_timings);
+        self.bundle_stage_stats
+            .accumulate(&other.bundle_stage_stats);
+    }
+
     pub fn report(&self, id: u32, slot: Slot) {
         self.transaction_errors.report(id, slot);
         self.execute_and_commit_timings.report(id, slot);
@@ -72,17 +110,237 @@ impl BundleStageLeaderStats {
     }
 }
 
+/// What happened to a bundle BundleStage executed or dropped while leader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BundleOutcome {
+    Executed,
+    /// One of the bundle's transactions failed signature verification when it was received.
+    InvalidSignature,
+    /// One of the bundle's transactions has a blockhash too old for the bank or an invalid nonce.
+    BlockhashNotFound,
+    /// One of the bundle's fee payers can't pay for its transactions.
+    InsufficientFundsForFee,
+    SimulationFailed,
+    /// The bundle used an account or program denied by the operator's bundle filter.
+    Denied,
+    /// The bundle declared a smaller tip than the bundle filter's minimum.
+    TipTooLow,
+    /// The bundle wrote to the validator's identity, vote account or another protected account.
+    ProtectedAccountWrite,
+    /// Bundle execution was turned off by the operator when the bundle arrived.
+    Disabled,
+    /// The bundle arrived while the validator wasn't leader and wouldn't be soon.
+    NotLeader,
+    /// The bundle's accounts couldn't be locked, or stayed in use until it timed out.
+    AccountLocks,
+    ExceedsCostModel,
+    /// The bundle didn't fit in the rest of the block; it's retried in a later slot.
+    ExceedsBlockSpace,
+    Expired,
+    /// One of the bundle's transactions already landed, through BankingStage or another bundle.
+    DuplicateTransaction,
+    /// The slot ended before the bundle could be recorded; it's retried.
+    PohRecordFailed,
+    /// Executing the bundle's transactions took longer than the per bundle budget.
+    TimedOut,
+    /// The bundle's accounts stayed in use by other transactions; it's queued again.
+    RetriedAccountLocks,
+    TransactionFailed,
+    TipError,
+    /// The bundle took lamports from a tip account or wrote to one other than to pay a tip.
+    TipAccountViolation,
+}
+
+impl BundleOutcome {
+    /// Returns None for bundles abandoned because the validator is shutting down.
+    pub fn from_execution_result<T>(result: &Result<T, BundleExecutionError>) -> Option<Self> {
+        match result {
+            Ok(_) => Some(Self::Executed),
+            Err(BundleExecutionError::PohMaxHeightError) => Some(Self::PohRecordFailed),
+            Err(BundleExecutionError::TransactionFailure(_)) => Some(Self::TransactionFailed),
+            Err(BundleExecutionError::ExceedsCostModel) => Some(Self::ExceedsCostModel),
+            Err(BundleExecutionError::ExceedsBlockSpace) => Some(Self::ExceedsBlockSpace),
+            Err(BundleExecutionError::TipError(TipPaymentError::TipAccountViolation(_))) => {
+                Some(Self::TipAccountViolation)
+            }
+            Err(BundleExecutionError::TipError(_)) => Some(Self::TipError),
+            Err(BundleExecutionError::Shutdown) => None,
+            Err(BundleExecutionError::MaxRetriesExceeded(_))
+            | Err(BundleExecutionError::LockError) => Some(Self::AccountLocks),
+            Err(BundleExecutionError::Expired(_)) => Some(Self::Expired),
+            Err(BundleExecutionError::DuplicateTransaction(_)) => Some(Self::DuplicateTransaction),
+            Err(BundleExecutionError::ExecutionTimeout(_)) => Some(Self::TimedOut),
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Executed => "executed",
+            Self::InvalidSignature => "invalid_signature",
+            Self::BlockhashNotFound => "blockhash_not_found",
+            Self::InsufficientFundsForFee => "insufficient_funds_for_fee",
+            Self::SimulationFailed => "simulation_failed",
+            Self::Denied => "denied",
+            Self::TipTooLow => "tip_too_low",
+            Self::ProtectedAccountWrite => "protected_account_write",
+            Self::Disabled => "disabled",
+            Self::NotLeader => "not_leader",
+            Self::AccountLocks => "account_locks",
+            Self::ExceedsCostModel => "exceeds_cost_model",
+            Self::ExceedsBlockSpace => "exceeds_block_space",
+            Self::Expired => "expired",
+            Self::DuplicateTransaction => "duplicate_transaction",
+            Self::PohRecordFailed => "poh_record_failed",
+            Self::TimedOut => "timed_out",
+            Self::RetriedAccountLocks => "retried_account_locks",
+            Self::TransactionFailed => "transaction_failed",
+            Self::TipError => "tip_error",
+            Self::TipAccountViolation => "tip_account_violation",
+        }
+    }
+}
+
+/// Where the time went for a bundle that was locked and executed. The phases don't overlap;
+/// whatever isn't covered by them, like sanitizing, simulating and waiting for the bundles
+/// executed ahead of it, is the difference between their sum and `total_us`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BundleTimings {
+    /// From being received to being taken off the queue for the attempt that produced the outcome.
+    pub queued_us: u64,
+    /// Taking the bundle's account locks.
+    pub lock_us: u64,
+    /// Loading and executing the bundle's transactions, including collecting their balances.
+    pub execute_us: u64,
+    /// Waiting for the bank's freeze lock and recording the bundle to PoH.
+    pub record_us: u64,
+    /// Committing the bundle to the bank and sending out its votes.
+    pub commit_us: u64,
+    /// From being received to the outcome.
+    pub total_us: u64,
+}
+
+impl BundleTimings {
+    /// Returns the time spent in the measured phases.
+    pub fn phases_us(&self) -> u64 {
+        [
+            self.queued_us,
+            self.lock_us,
+            self.execute_us,
+            self.record_us,
+            self.commit_us,
+        ]
+        .into_iter()
+        .fold(0, u64::saturating_add)
+    }
+
+    /// Sets the execute, record and commit phases to the time added to `timings` since `start`.
+    pub fn set_execution_phases(
+        &mut self,
+        start: &LeaderExecuteAndCommitTimings,
+        timings: &LeaderExecuteAndCommitTimings,
+    ) {
+        let elapsed = |start: u64, end: u64| end.saturating_sub(start);
+        self.execute_us = elapsed(
+            start
+                .load_execute_us
+                .saturating_add(start.collect_balances_us),
+            timings
+                .load_execute_us
+                .saturating_add(timings.collect_balances_us),
+        );
+        self.record_us = elapsed(
+            start.freeze_lock_us.saturating_add(start.record_us),
+            timings.freeze_lock_us.saturating_add(timings.record_us),
+        );
+        self.commit_us = elapsed(
+            start.commit_us.saturating_add(start.find_and_send_votes_us),
+            timings
+                .commit_us
+                .saturating_add(timings.find_and_send_votes_us),
+        );
+    }
+}
+
+/// Returns the value at `percentile` of `values`, which are sorted in place.
+fn percentile(values: &mut [u64], percentile: usize) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    values.sort_unstable();
+    values[(values.len() - 1) * percentile / 100]
+}
+
+/// Summary of the bundles BundleStage handled in a leader slot.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BundleStageSlotStats {
+    pub num_received: u64,
+    pub num_executed: u64,
+    /// Bundles dropped before execution for an expired blockhash or an invalid nonce.
+    pub num_blockhash_not_found: u64,
+    /// Bundles dropped before execution because a fee payer couldn't pay its fees.
+    pub num_insufficient_funds_for_fee: u64,
+    pub num_failed_simulation: u64,
+    pub num_denied: u64,
+    pub num_tip_too_low: u64,
+    /// Bundles that wrote to one of the validator's protected accounts.
+    pub num_protected_account_writes: u64,
+    /// Bundles dropped because bundle execution was turned off.
+    pub num_disabled: u64,
+    /// Bundles deferred to a later slot because bundles had used their share of the block.
+    pub num_deferred_block_units: u64,
+    pub num_failed_account_locks: u64,
+    /// Bundles queued again because their accounts stayed in use, counted once per retry.
+    pub num_retried_account_locks: u64,
+    pub num_failed_cost_model: u64,
+    pub num_failed_expired: u64,
+    pub num_failed_poh_record: u64,
+    pub num_failed_timed_out: u64,
+    pub num_failed_transaction: u64,
+    pub num_failed_tip: u64,
+    /// Bundles that took lamports from or wrote to a tip account, in simulation or execution.
+    pub num_tip_account_violations: u64,
+    /// Compute units consumed by the executed bundles.
+    pub compute_units: u64,
+    /// Lamports the executed bundles transferred to the tip accounts.
+    pub tip_lamports: u64,
+    /// Percentage of the tips that goes to the block builder.
+    pub block_builder_commission: u64,
+    /// The block builder's share of `tip_lamports`, the rest goes to the validator.
+    pub block_builder_commission_lamports: u64,
+    /// Time spent sanitizing, simulating, locking and executing bundles.
+    pub execution_elapsed_us: u64,
+}
+
 #[derive(Default)]
 pub struct BundleStageStats {
+    num_bundles_received: u64,
+
     sanitize_transaction_ok: u64,
     sanitize_transaction_vote_only_mode: u64,
     sanitize_transaction_failed_precheck: u64,
+    sanitize_transaction_too_many_transactions: u64,
+    sanitize_transaction_too_large: u64,
     sanitize_transaction_blacklisted_account: u64,
     sanitize_transaction_failed_to_serialize: u64,
     sanitize_transaction_duplicate_transaction: u64,
+    sanitize_transaction_vote_transaction: u64,
     sanitize_transaction_failed_check: u64,
+    sanitize_transaction_blockhash_not_found: u64,
+    sanitize_transaction_insufficient_funds_for_fee: u64,
     sanitize_bundle_elapsed_us: u64,
 
+    num_simulation_failures: u64,
+    simulate_bundles_elapsed_us: u64,
+
+    num_denied_bundles: u64,
+    num_tip_too_low_bundles: u64,
+    num_protected_account_write_bundles: u64,
+    num_disabled_bundles: u64,
+
+    num_deferred_block_units: u64,
+
+    num_expired_bundles: u64,
+
     locked_bundle_elapsed_us: u64,
 
     num_lock_errors: u64,
@@ -104,13 +362,377 @@ pub struct BundleStageStats {
     execution_results_poh_max_height: u64,
     execution_results_transaction_failures: u64,
     execution_results_exceeds_cost_model: u64,
+    execution_results_exceeds_block_space: u64,
     execution_results_tip_errors: u64,
+    num_tip_account_violations: u64,
     execution_results_max_retries: u64,
     execution_results_lock_errors: u64,
+    execution_results_expired: u64,
+    execution_results_duplicate_transactions: u64,
+    execution_results_timed_out: u64,
+    execution_results_contention_retries: u64,
+
+    bundle_compute_units: u64,
+    bundle_tip_lamports: u64,
+    block_builder_commission: u64,
+    execute_bundles_elapsed_us: u64,
+
+    num_parallel_bundle_batches: u64,
+    max_parallel_bundles: u64,
+
+    // accounts reserved for bundles reaching an execution pass, from admission until they're
+    // locked or dropped
+    num_bundle_reservations: u64,
+    bundle_reservations_held_us: u64,
+    max_bundle_reservation_held_us: u64,
+
+    // timings of the bundles that got as far as being locked, reported as percentiles
+    bundle_timings: Vec<BundleTimings>,
+
+    // report a datapoint for every bundle executed or dropped
+    report_bundle_outcomes: bool,
 }
 
 impl BundleStageStats {
+    pub fn slot_stats(&self) -> BundleStageSlotStats {
+        BundleStageSlotStats {
+            num_received: self.num_bundles_received,
+            num_executed: self.execution_results_ok,
+            num_blockhash_not_found: self.sanitize_transaction_blockhash_not_found,
+            num_insufficient_funds_for_fee: self.sanitize_transaction_insufficient_funds_for_fee,
+            num_failed_simulation: self.num_simulation_failures,
+            num_denied: self.num_denied_bundles,
+            num_tip_too_low: self.num_tip_too_low_bundles,
+            num_protected_account_writes: self.num_protected_account_write_bundles,
+            num_disabled: self.num_disabled_bundles,
+            num_deferred_block_units: self.num_deferred_block_units,
+            num_failed_account_locks: self
+                .execution_results_lock_errors
+                .saturating_add(self.execution_results_max_retries),
+            num_retried_account_locks: self.execution_results_contention_retries,
+            num_failed_cost_model: self.execution_results_exceeds_cost_model,
+            num_failed_expired: self
+                .num_expired_bundles
+                .saturating_add(self.execution_results_expired),
+            num_failed_poh_record: self.execution_results_poh_max_height,
+            num_failed_timed_out: self.execution_results_timed_out,
+            num_failed_transaction: self.execution_results_transaction_failures,
+            num_failed_tip: self.execution_results_tip_errors,
+            num_tip_account_violations: self.num_tip_account_violations,
+            compute_units: self.bundle_compute_units,
+            tip_lamports: self.bundle_tip_lamports,
+            block_builder_commission: self.block_builder_commission,
+            block_builder_commission_lamports: (self.bundle_tip_lamports as u128
+                * self.block_builder_commission.min(100) as u128
+                / 100) as u64,
+            execution_elapsed_us: self.execute_bundles_elapsed_us,
+        }
+    }
+
+    /// Adds the counts of `other`, e.g. from a worker thread, to these stats.
+    pub fn accumulate(&mut self, other: &BundleStageStats) {
+        saturating_add_assign!(self.num_bundles_received, other.num_bundles_received);
+        saturating_add_assign!(self.sanitize_transaction_ok, other.sanitize_transaction_ok);
+        saturating_add_assign!(
+            self.sanitize_transaction_vote_only_mode,
+            other.sanitize_transaction_vote_only_mode
+        );
+        saturating_add_assign!(
+            self.sanitize_transaction_failed_precheck,
+            other.sanitize_transaction_failed_precheck
+        );
+        saturating_add_assign!(
+            self.sanitize_transaction_too_many_transactions,
+            other.sanitize_transaction_too_many_transactions
+        );
+        saturating_add_assign!(
+            self.sanitize_transaction_too_large,
+            other.sanitize_transaction_too_large
+        );
+        saturating_add_assign!(
+            self.sanitize_transaction_blacklisted_account,
+            other.sanitize_transaction_blacklisted_account
+        );
+        saturating_add_assign!(
+            self.sanitize_transaction_failed_to_serialize,
+            other.sanitize_transaction_failed_to_serialize
+        );
+        saturating_add_assign!(
+            self.sanitize_transaction_duplicate_transaction,
+            other.sanitize_transaction_duplicate_transaction
+        );
+        saturating_add_assign!(
+            self.sanitize_transaction_vote_transaction,
+            other.sanitize_transaction_vote_transaction
+        );
+        saturating_add_assign!(
+            self.sanitize_transaction_failed_check,
+            other.sanitize_transaction_failed_check
+        );
+        saturating_add_assign!(
+            self.sanitize_transaction_blockhash_not_found,
+            other.sanitize_transaction_blockhash_not_found
+        );
+        saturating_add_assign!(
+            self.sanitize_transaction_insufficient_funds_for_fee,
+            other.sanitize_transaction_insufficient_funds_for_fee
+        );
+        saturating_add_assign!(
+            self.sanitize_bundle_elapsed_us,
+            other.sanitize_bundle_elapsed_us
+        );
+        saturating_add_assign!(self.num_simulation_failures, other.num_simulation_failures);
+        saturating_add_assign!(
+            self.simulate_bundles_elapsed_us,
+            other.simulate_bundles_elapsed_us
+        );
+        saturating_add_assign!(self.num_denied_bundles, other.num_denied_bundles);
+        saturating_add_assign!(self.num_tip_too_low_bundles, other.num_tip_too_low_bundles);
+        saturating_add_assign!(
+            self.num_protected_account_write_bundles,
+            other.num_protected_account_write_bundles
+        );
+        saturating_add_assign!(self.num_disabled_bundles, other.num_disabled_bundles);
+        saturating_add_assign!(
+            self.num_deferred_block_units,
+            other.num_deferred_block_units
+        );
+        saturating_add_assign!(self.num_expired_bundles, other.num_expired_bundles);
+        saturating_add_assign!(
+            self.locked_bundle_elapsed_us,
+            other.locked_bundle_elapsed_us
+        );
+        saturating_add_assign!(self.num_lock_errors, other.num_lock_errors);
+        saturating_add_assign!(
+            self.num_init_tip_account_errors,
+            other.num_init_tip_account_errors
+        );
+        saturating_add_assign!(self.num_init_tip_account_ok, other.num_init_tip_account_ok);
+        saturating_add_assign!(
+            self.num_change_tip_receiver_errors,
+            other.num_change_tip_receiver_errors
+        );
+        saturating_add_assign!(
+            self.num_change_tip_receiver_ok,
+            other.num_change_tip_receiver_ok
+        );
+        saturating_add_assign!(
+            self.change_tip_receiver_elapsed_us,
+            other.change_tip_receiver_elapsed_us
+        );
+        saturating_add_assign!(self.num_execution_failures, other.num_execution_failures);
+        saturating_add_assign!(self.num_execution_timeouts, other.num_execution_timeouts);
+        saturating_add_assign!(self.num_execution_retries, other.num_execution_retries);
+        saturating_add_assign!(
+            self.execute_locked_bundles_elapsed_us,
+            other.execute_locked_bundles_elapsed_us
+        );
+        saturating_add_assign!(self.execution_results_ok, other.execution_results_ok);
+        saturating_add_assign!(
+            self.execution_results_poh_max_height,
+            other.execution_results_poh_max_height
+        );
+        saturating_add_assign!(
+            self.execution_results_transaction_failures,
+            other.execution_results_transaction_failures
+        );
+        saturating_add_assign!(
+            self.execution_results_exceeds_cost_model,
+            other.execution_results_exceeds_cost_model
+        );
+        saturating_add_assign!(
+            self.execution_results_exceeds_block_space,
+            other.execution_results_exceeds_block_space
+        );
+        saturating_add_assign!(
+            self.execution_results_tip_errors,
+            other.execution_results_tip_errors
+        );
+        saturating_add_assign!(
+            self.num_tip_account_violations,
+            other.num_tip_account_violations
+        );
+        saturating_add_assign!(
+            self.execution_results_max_retries,
+            other.execution_results_max_retries
+        );
+        saturating_add_assign!(
+            self.execution_results_lock_errors,
+            other.execution_results_lock_errors
+        );
+        saturating_add_assign!(
+            self.execution_results_expired,
+            other.execution_results_expired
+        );
+        saturating_add_assign!(
+            self.execution_results_duplicate_transactions,
+            other.execution_results_duplicate_transactions
+        );
+        saturating_add_assign!(
+            self.execution_results_timed_out,
+            other.execution_results_timed_out
+        );
+        saturating_add_assign!(
+            self.execution_results_contention_retries,
+            other.execution_results_contention_retries
+        );
+        saturating_add_assign!(self.bundle_compute_units, other.bundle_compute_units);
+        saturating_add_assign!(self.bundle_tip_lamports, other.bundle_tip_lamports);
+        self.block_builder_commission = self
+            .block_builder_commission
+            .max(other.block_builder_commission);
+        saturating_add_assign!(
+            self.execute_bundles_elapsed_us,
+            other.execute_bundles_elapsed_us
+        );
+        saturating_add_assign!(
+            self.num_parallel_bundle_batches,
+            other.num_parallel_bundle_batches
+        );
+        self.max_parallel_bundles = self.max_parallel_bundles.max(other.max_parallel_bundles);
+        saturating_add_assign!(self.num_bundle_reservations, other.num_bundle_reservations);
+        saturating_add_assign!(
+            self.bundle_reservations_held_us,
+            other.bundle_reservations_held_us
+        );
+        self.max_bundle_reservation_held_us = self
+            .max_bundle_reservation_held_us
+            .max(other.max_bundle_reservation_held_us);
+        self.bundle_timings.extend_from_slice(&other.bundle_timings);
+    }
+
     pub fn report(&self, id: u32, slot: Slot) {
+        let slot_stats = self.slot_stats();
+        datapoint_info!(
+            "bundle_stage-slot_stats",
+            ("id", id, i64),
+            ("slot", slot, i64),
+            ("num_received", slot_stats.num_received, i64),
+            ("num_executed", slot_stats.num_executed, i64),
+            (
+                "num_blockhash_not_found",
+                slot_stats.num_blockhash_not_found,
+                i64
+            ),
+            (
+                "num_insufficient_funds_for_fee",
+                slot_stats.num_insufficient_funds_for_fee,
+                i64
+            ),
+            (
+                "num_failed_simulation",
+                slot_stats.num_failed_simulation,
+                i64
+            ),
+            ("num_denied", slot_stats.num_denied, i64),
+            ("num_tip_too_low", slot_stats.num_tip_too_low, i64),
+            (
+                "num_protected_account_writes",
+                slot_stats.num_protected_account_writes,
+                i64
+            ),
+            ("num_disabled", slot_stats.num_disabled, i64),
+            (
+                "num_deferred_block_units",
+                slot_stats.num_deferred_block_units,
+                i64
+            ),
+            (
+                "num_failed_account_locks",
+                slot_stats.num_failed_account_locks,
+                i64
+            ),
+            (
+                "num_retried_account_locks",
+                slot_stats.num_retried_account_locks,
+                i64
+            ),
+            (
+                "num_failed_cost_model",
+                slot_stats.num_failed_cost_model,
+                i64
+            ),
+            ("num_failed_expired", slot_stats.num_failed_expired, i64),
+            (
+                "num_failed_poh_record",
+                slot_stats.num_failed_poh_record,
+                i64
+            ),
+            ("num_failed_timed_out", slot_stats.num_failed_timed_out, i64),
+            (
+                "num_failed_transaction",
+                slot_stats.num_failed_transaction,
+                i64
+            ),
+            ("num_failed_tip", slot_stats.num_failed_tip, i64),
+            (
+                "num_tip_account_violations",
+                slot_stats.num_tip_account_violations,
+                i64
+            ),
+            ("compute_units", slot_stats.compute_units, i64),
+            ("tip_lamports", slot_stats.tip_lamports, i64),
+            (
+                "block_builder_commission",
+                slot_stats.block_builder_commission,
+                i64
+            ),
+            (
+                "block_builder_commission_lamports",
+                slot_stats.block_builder_commission_lamports,
+                i64
+            ),
+            (
+                "validator_tip_lamports",
+                slot_stats
+                    .tip_lamports
+                    .saturating_sub(slot_stats.block_builder_commission_lamports),
+                i64
+            ),
+            ("execution_elapsed_us", slot_stats.execution_elapsed_us, i64),
+        );
+
+        if !self.bundle_timings.is_empty() {
+            let phase = |us: fn(&BundleTimings) -> u64| {
+                let mut values: Vec<u64> = self.bundle_timings.iter().map(us).collect();
+                (
+                    percentile(&mut values, 50) as i64,
+                    percentile(&mut values, 90) as i64,
+                    percentile(&mut values, 100) as i64,
+                )
+            };
+            let queued = phase(|timings| timings.queued_us);
+            let lock = phase(|timings| timings.lock_us);
+            let execute = phase(|timings| timings.execute_us);
+            let record = phase(|timings| timings.record_us);
+            let commit = phase(|timings| timings.commit_us);
+            let total = phase(|timings| timings.total_us);
+            datapoint_info!(
+                "bundle_stage-bundle_timings",
+                ("id", id, i64),
+                ("slot", slot, i64),
+                ("num_bundles", self.bundle_timings.len(), i64),
+                ("queued_us_p50", queued.0, i64),
+                ("queued_us_p90", queued.1, i64),
+                ("queued_us_max", queued.2, i64),
+                ("lock_us_p50", lock.0, i64),
+                ("lock_us_p90", lock.1, i64),
+                ("lock_us_max", lock.2, i64),
+                ("execute_us_p50", execute.0, i64),
+                ("execute_us_p90", execute.1, i64),
+                ("execute_us_max", execute.2, i64),
+                ("record_us_p50", record.0, i64),
+                ("record_us_p90", record.1, i64),
+                ("record_us_max", record.2, i64),
+                ("commit_us_p50", commit.0, i64),
+                ("commit_us_p90", commit.1, i64),
+                ("commit_us_max", commit.2, i64),
+                ("total_us_p50", total.0, i64),
+                ("total_us_p90", total.1, i64),
+                ("total_us_max", total.2, i64),
+            );
+        }
+
         datapoint_info!(
             "bundle_stage-stats",
             ("id", id, i64),
@@ -126,6 +748,16 @@ impl BundleStageStats {
                 self.sanitize_transaction_failed_precheck,
                 i64
             ),
+            (
+                "sanitize_transaction_too_many_transactions",
+                self.sanitize_transaction_too_many_transactions,
+                i64
+            ),
+            (
+                "sanitize_transaction_too_large",
+                self.sanitize_transaction_too_large,
+                i64
+            ),
             (
                 "sanitize_transaction_blacklisted_account",
                 self.sanitize_transaction_blacklisted_account,
@@ -141,16 +773,51 @@ impl BundleStageStats {
                 self.sanitize_transaction_duplicate_transaction,
                 i64
             ),
+            (
+                "sanitize_transaction_vote_transaction",
+                self.sanitize_transaction_vote_transaction,
+                i64
+            ),
             (
                 "sanitize_transaction_failed_check",
                 self.sanitize_transaction_failed_check,
                 i64
             ),
+            (
+                "sanitize_transaction_blockhash_not_found",
+                self.sanitize_transaction_blockhash_not_found,
+                i64
+            ),
+            (
+                "sanitize_transaction_insufficient_funds_for_fee",
+                self.sanitize_transaction_insufficient_funds_for_fee,
+                i64
+            ),
             (
                 "sanitize_bundle_elapsed_us",
                 self.sanitize_bundle_elapsed_us,
                 i64
             ),
+            ("num_simulation_failures", self.num_simulation_failures, i64),
+            (
+                "simulate_bundles_elapsed_us",
+                self.simulate_bundles_elapsed_us,
+                i64
+            ),
+            ("num_denied_bundles", self.num_denied_bundles, i64),
+            ("num_tip_too_low_bundles", self.num_tip_too_low_bundles, i64),
+            (
+                "num_protected_account_write_bundles",
+                self.num_protected_account_write_bundles,
+                i64
+            ),
+            ("num_disabled_bundles", self.num_disabled_bundles, i64),
+            (
+                "num_deferred_block_units",
+                self.num_deferred_block_units,
+                i64
+            ),
+            ("num_expired_bundles", self.num_expired_bundles, i64),
             (
                 "locked_bundle_elapsed_us",
                 self.locked_bundle_elapsed_us,
@@ -202,11 +869,21 @@ impl BundleStageStats {
                 self.execution_results_exceeds_cost_model,
                 i64
             ),
+            (
+                "execution_results_exceeds_block_space",
+                self.execution_results_exceeds_block_space,
+                i64
+            ),
             (
                 "execution_results_tip_errors",
                 self.execution_results_tip_errors,
                 i64
             ),
+            (
+                "num_tip_account_violations",
+                self.num_tip_account_violations,
+                i64
+            ),
             (
                 "execution_results_max_retries",
                 self.execution_results_max_retries,
@@ -217,9 +894,105 @@ impl BundleStageStats {
                 self.execution_results_lock_errors,
                 i64
             ),
+            (
+                "execution_results_expired",
+                self.execution_results_expired,
+                i64
+            ),
+            (
+                "execution_results_duplicate_transactions",
+                self.execution_results_duplicate_transactions,
+                i64
+            ),
+            (
+                "execution_results_timed_out",
+                self.execution_results_timed_out,
+                i64
+            ),
+            (
+                "execution_results_contention_retries",
+                self.execution_results_contention_retries,
+                i64
+            ),
+            (
+                "num_parallel_bundle_batches",
+                self.num_parallel_bundle_batches,
+                i64
+            ),
+            ("max_parallel_bundles", self.max_parallel_bundles, i64),
+            ("num_bundle_reservations", self.num_bundle_reservations, i64),
+            (
+                "bundle_reservations_held_us",
+                self.bundle_reservations_held_us,
+                i64
+            ),
+            (
+                "max_bundle_reservation_held_us",
+                self.max_bundle_reservation_held_us,
+                i64
+            ),
         );
     }
 
+    pub fn increment_num_bundles_received(&mut self, num: u64) {
+        saturating_add_assign!(self.num_bundles_received, num);
+    }
+
+    pub fn increment_execute_bundles_elapsed_us(&mut self, num: u64) {
+        saturating_add_assign!(self.execute_bundles_elapsed_us, num);
+    }
+
+    /// Adds an executed bundle's compute units and tips to the slot totals, and reports the
+    /// bundle on its own if enabled. The per reason counters are incremented separately.
+    /// `timings` are only known for bundles that got as far as being locked.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_bundle_outcome(
+        &mut self,
+        uuid: &Uuid,
+        num_transactions: usize,
+        compute_units: u64,
+        tip_lamports: u64,
+        num_retries: usize,
+        outcome: BundleOutcome,
+        timings: Option<&BundleTimings>,
+    ) {
+        if outcome == BundleOutcome::Executed {
+            saturating_add_assign!(self.bundle_compute_units, compute_units);
+            saturating_add_assign!(self.bundle_tip_lamports, tip_lamports);
+        }
+        if let Some(timings) = timings {
+            self.bundle_timings.push(*timings);
+        }
+        if self.report_bundle_outcomes {
+            let timings = timings.copied().unwrap_or_default();
+            datapoint_info!(
+                "bundle_stage-bundle",
+                ("uuid", uuid.to_string(), String),
+                ("num_transactions", num_transactions, i64),
+                ("compute_units", compute_units, i64),
+                ("tip_lamports", tip_lamports, i64),
+                ("num_retries", num_retries, i64),
+                ("outcome", outcome.as_str(), String),
+                ("queued_us", timings.queued_us, i64),
+                ("lock_us", timings.lock_us, i64),
+                ("execute_us", timings.execute_us, i64),
+                (
+                    "execute_us_per_transaction",
+                    timings.execute_us / num_transactions.max(1) as u64,
+                    i64
+                ),
+                ("record_us", timings.record_us, i64),
+                ("commit_us", timings.commit_us, i64),
+                ("total_us", timings.total_us, i64),
+            );
+        }
+    }
+
+    /// Timings of the bundles executed so far in the slot.
+    pub fn bundle_timings(&self) -> &[BundleTimings] {
+        &self.bundle_timings
+    }
+
     pub fn increment_sanitize_transaction_ok(&mut self, num: u64) {
         saturating_add_assign!(self.sanitize_transaction_ok, num);
     }
@@ -232,6 +1005,14 @@ impl BundleStageStats {
         saturating_add_assign!(self.sanitize_transaction_failed_precheck, num);
     }
 
+    pub fn increment_sanitize_transaction_too_many_transactions(&mut self, num: u64) {
+        saturating_add_assign!(self.sanitize_transaction_too_many_transactions, num);
+    }
+
+    pub fn increment_sanitize_transaction_too_large(&mut self, num: u64) {
+        saturating_add_assign!(self.sanitize_transaction_too_large, num);
+    }
+
     pub fn increment_sanitize_transaction_blacklisted_account(&mut self, num: u64) {
         saturating_add_assign!(self.sanitize_transaction_blacklisted_account, num);
     }
@@ -244,14 +1025,58 @@ impl BundleStageStats {
         saturating_add_assign!(self.sanitize_transaction_duplicate_transaction, num);
     }
 
+    pub fn increment_sanitize_transaction_vote_transaction(&mut self, num: u64) {
+        saturating_add_assign!(self.sanitize_transaction_vote_transaction, num);
+    }
+
     pub fn increment_sanitize_transaction_failed_check(&mut self, num: u64) {
         saturating_add_assign!(self.sanitize_transaction_failed_check, num);
     }
 
+    pub fn increment_sanitize_transaction_blockhash_not_found(&mut self, num: u64) {
+        saturating_add_assign!(self.sanitize_transaction_blockhash_not_found, num);
+    }
+
+    pub fn increment_sanitize_transaction_insufficient_funds_for_fee(&mut self, num: u64) {
+        saturating_add_assign!(self.sanitize_transaction_insufficient_funds_for_fee, num);
+    }
+
     pub fn increment_sanitize_bundle_elapsed_us(&mut self, num: u64) {
         saturating_add_assign!(self.sanitize_bundle_elapsed_us, num);
     }
 
+    pub fn increment_num_simulation_failures(&mut self, num: u64) {
+        saturating_add_assign!(self.num_simulation_failures, num);
+    }
+
+    pub fn increment_num_denied_bundles(&mut self, num: u64) {
+        saturating_add_assign!(self.num_denied_bundles, num);
+    }
+
+    pub fn increment_num_tip_too_low_bundles(&mut self, num: u64) {
+        saturating_add_assign!(self.num_tip_too_low_bundles, num);
+    }
+
+    pub fn increment_num_protected_account_write_bundles(&mut self, num: u64) {
+        saturating_add_assign!(self.num_protected_account_write_bundles, num);
+    }
+
+    pub fn increment_num_disabled_bundles(&mut self, num: u64) {
+        saturating_add_assign!(self.num_disabled_bundles, num);
+    }
+
+    pub fn increment_num_deferred_block_units(&mut self, num: u64) {
+        saturating_add_assign!(self.num_deferred_block_units, num);
+    }
+
+    pub fn increment_simulate_bundles_elapsed_us(&mut self, num: u64) {
+        saturating_add_assign!(self.simulate_bundles_elapsed_us, num);
+    }
+
+    pub fn increment_num_expired_bundles(&mut self, num: u64) {
+        saturating_add_assign!(self.num_expired_bundles, num);
+    }
+
     pub fn increment_locked_bundle_elapsed_us(&mut self, num: u64) {
         saturating_add_assign!(self.locked_bundle_elapsed_us, num);
     }
@@ -280,6 +1105,11 @@ impl BundleStageStats {
         saturating_add_assign!(self.change_tip_receiver_elapsed_us, num);
     }
 
+    /// Sets the block builder's commission the tip accounts were updated with for the slot.
+    pub fn set_block_builder_commission(&mut self, block_builder_commission: u64) {
+        self.block_builder_commission = block_builder_commission;
+    }
+
     pub fn increment_num_execution_failures(&mut self, num: u64) {
         saturating_add_assign!(self.num_execution_failures, num);
     }
@@ -312,10 +1142,18 @@ impl BundleStageStats {
         saturating_add_assign!(self.execution_results_exceeds_cost_model, num);
     }
 
+    pub fn increment_execution_results_exceeds_block_space(&mut self, num: u64) {
+        saturating_add_assign!(self.execution_results_exceeds_block_space, num);
+    }
+
     pub fn increment_execution_results_tip_errors(&mut self, num: u64) {
         saturating_add_assign!(self.execution_results_tip_errors, num);
     }
 
+    pub fn increment_num_tip_account_violations(&mut self, num: u64) {
+        saturating_add_assign!(self.num_tip_account_violations, num);
+    }
+
     pub fn increment_execution_results_max_retries(&mut self, num: u64) {
         saturating_add_assign!(self.execution_results_max_retries, num);
     }
@@ -323,4 +1161,249 @@ impl BundleStageStats {
     pub fn increment_execution_results_lock_errors(&mut self, num: u64) {
         saturating_add_assign!(self.execution_results_lock_errors, num);
     }
+
+    pub fn increment_execution_results_expired(&mut self, num: u64) {
+        saturating_add_assign!(self.execution_results_expired, num);
+    }
+
+    pub fn increment_execution_results_duplicate_transactions(&mut self, num: u64) {
+        saturating_add_assign!(self.execution_results_duplicate_transactions, num);
+    }
+
+    pub fn increment_execution_results_timed_out(&mut self, num: u64) {
+        saturating_add_assign!(self.execution_results_timed_out, num);
+    }
+
+    pub fn increment_execution_results_contention_retries(&mut self, num: u64) {
+        saturating_add_assign!(self.execution_results_contention_retries, num);
+    }
+
+    /// Records a batch of `num_bundles` bundles executed at the same time.
+    pub fn record_parallel_bundle_batch(&mut self, num_bundles: u64) {
+        saturating_add_assign!(self.num_parallel_bundle_batches, 1);
+        self.max_parallel_bundles = self.max_parallel_bundles.max(num_bundles);
+    }
+
+    pub fn max_parallel_bundles(&self) -> u64 {
+        self.max_parallel_bundles
+    }
+
+    /// Records how long a bundle's account reservation was held before it was released.
+    pub fn record_bundle_reservation(&mut self, held_us: u64) {
+        saturating_add_assign!(self.num_bundle_reservations, 1);
+        saturating_add_assign!(self.bundle_reservations_held_us, held_us);
+        self.max_bundle_reservation_held_us = self.max_bundle_reservation_held_us.max(held_us);
+    }
+
+    pub fn num_bundle_reservations(&self) -> u64 {
+        self.num_bundle_reservations
+    }
+
+    pub fn report_bundle_outcomes(&self) -> bool {
+        self.report_bundle_outcomes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::{pubkey::Pubkey, signature::Signature, transaction::TransactionError},
+        std::time::Duration,
+    };
+
+    #[test]
+    fn test_bundle_outcome_from_execution_result() {
+        let outcome = |result: Result<u64, BundleExecutionError>| {
+            BundleOutcome::from_execution_result(&result)
+        };
+        assert_eq!(outcome(Ok(100)), Some(BundleOutcome::Executed));
+        assert_eq!(
+            outcome(Err(BundleExecutionError::PohMaxHeightError)),
+            Some(BundleOutcome::PohRecordFailed)
+        );
+        assert_eq!(
+            outcome(Err(BundleExecutionError::TransactionFailure(
+                TransactionError::AccountNotFound
+            ))),
+            Some(BundleOutcome::TransactionFailed)
+        );
+        assert_eq!(
+            outcome(Err(BundleExecutionError::LockError)),
+            Some(BundleOutcome::AccountLocks)
+        );
+        assert_eq!(
+            outcome(Err(BundleExecutionError::MaxRetriesExceeded(
+                Duration::from_millis(10)
+            ))),
+            Some(BundleOutcome::AccountLocks)
+        );
+        assert_eq!(
+            outcome(Err(BundleExecutionError::Expired(5))),
+            Some(BundleOutcome::Expired)
+        );
+        assert_eq!(
+            outcome(Err(BundleExecutionError::DuplicateTransaction(
+                Signature::default()
+            ))),
+            Some(BundleOutcome::DuplicateTransaction)
+        );
+        assert_eq!(
+            outcome(Err(BundleExecutionError::ExecutionTimeout(
+                Duration::from_millis(30)
+            ))),
+            Some(BundleOutcome::TimedOut)
+        );
+        assert_eq!(
+            outcome(Err(BundleExecutionError::TipError(
+                TipPaymentError::TipAccountViolation(Pubkey::default())
+            ))),
+            Some(BundleOutcome::TipAccountViolation)
+        );
+        assert_eq!(outcome(Err(BundleExecutionError::Shutdown)), None);
+    }
+
+    #[test]
+    fn test_bundle_stage_slot_stats() {
+        let mut stats = BundleStageStats::default();
+        stats.increment_num_bundles_received(10);
+        stats.increment_sanitize_transaction_blockhash_not_found(2);
+        stats.increment_sanitize_transaction_insufficient_funds_for_fee(1);
+        stats.increment_num_simulation_failures(1);
+        stats.increment_num_denied_bundles(1);
+        stats.increment_num_tip_too_low_bundles(3);
+        stats.increment_num_protected_account_write_bundles(1);
+        stats.increment_num_disabled_bundles(2);
+        stats.increment_num_deferred_block_units(4);
+        stats.increment_num_expired_bundles(1);
+        stats.increment_execution_results_expired(1);
+        stats.increment_execution_results_ok(2);
+        stats.increment_execution_results_lock_errors(1);
+        stats.increment_execution_results_max_retries(1);
+        stats.increment_execution_results_exceeds_cost_model(1);
+        stats.increment_execution_results_poh_max_height(1);
+        stats.increment_execution_results_transaction_failures(1);
+        stats.increment_execution_results_timed_out(1);
+        stats.increment_execution_results_contention_retries(2);
+        stats.increment_num_tip_account_violations(1);
+        stats.increment_execute_bundles_elapsed_us(300);
+        stats.increment_execute_bundles_elapsed_us(200);
+        stats.set_block_builder_commission(5);
+
+        // only executed bundles count towards the compute units and tips
+        for (compute_units, tip_lamports, outcome) in [
+            (1_000, 5_000, BundleOutcome::Executed),
+            (2_000, 7_000, BundleOutcome::Executed),
+            (500, 9_000, BundleOutcome::SimulationFailed),
+            (0, 9_000, BundleOutcome::AccountLocks),
+        ] {
+            stats.record_bundle_outcome(
+                &Uuid::new_v4(),
+                1,
+                compute_units,
+                tip_lamports,
+                0,
+                outcome,
+                None,
+            );
+        }
+
+        assert_eq!(
+            stats.slot_stats(),
+            BundleStageSlotStats {
+                num_received: 10,
+                num_executed: 2,
+                num_blockhash_not_found: 2,
+                num_insufficient_funds_for_fee: 1,
+                num_failed_simulation: 1,
+                num_denied: 1,
+                num_tip_too_low: 3,
+                num_protected_account_writes: 1,
+                num_disabled: 2,
+                num_deferred_block_units: 4,
+                num_failed_account_locks: 2,
+                num_retried_account_locks: 2,
+                num_failed_cost_model: 1,
+                num_failed_expired: 2,
+                num_failed_poh_record: 1,
+                num_failed_timed_out: 1,
+                num_failed_transaction: 1,
+                num_failed_tip: 0,
+                num_tip_account_violations: 1,
+                compute_units: 3_000,
+                tip_lamports: 12_000,
+                block_builder_commission: 5,
+                block_builder_commission_lamports: 600,
+                execution_elapsed_us: 500,
+            }
+        );
+
+        // the block builder's share at either end of the range
+        stats.set_block_builder_commission(0);
+        assert_eq!(stats.slot_stats().block_builder_commission_lamports, 0);
+        stats.set_block_builder_commission(100);
+        assert_eq!(stats.slot_stats().block_builder_commission_lamports, 12_000);
+    }
+
+    #[test]
+    fn test_bundle_stage_leader_stats_report_bundle_outcomes() {
+        assert!(
+            !BundleStageLeaderStats::default()
+                .bundle_stage_stats()
+                .report_bundle_outcomes
+        );
+        assert!(
+            BundleStageLeaderSlotTrackingMetrics::new(true)
+                .bundle_stage_leader_stats()
+                .bundle_stage_stats()
+                .report_bundle_outcomes
+        );
+    }
+
+    #[test]
+    fn test_bundle_outcome_as_str() {
+        assert_eq!(BundleOutcome::Disabled.as_str(), "disabled");
+        assert_eq!(BundleOutcome::NotLeader.as_str(), "not_leader");
+        assert_eq!(
+            BundleOutcome::InvalidSignature.as_str(),
+            "invalid_signature"
+        );
+    }
+
+    #[test]
+    fn test_bundle_timings() {
+        let start = BundleTimings::from_execution_timings(&LeaderExecuteAndCommitTimings {
+            load_execute_us: 100,
+            collect_balances_us: 10,
+            freeze_lock_us: 5,
+            record_us: 20,
+            commit_us: 30,
+            ..LeaderExecuteAndCommitTimings::default()
+        });
+        let end = BundleTimings::from_execution_timings(&LeaderExecuteAndCommitTimings {
+            load_execute_us: 150,
+            collect_balances_us: 20,
+            freeze_lock_us: 5,
+            record_us: 25,
+            commit_us: 40,
+            find_and_send_votes_us: 1,
+            ..LeaderExecuteAndCommitTimings::default()
+        });
+        let timings = BundleTimings {
+            queued_us: 1_000,
+            lock_us: 2,
+            total_us: 1_200,
+            ..end.execution_since(&start)
+        };
+        assert_eq!(timings.execute_us, 60);
+        assert_eq!(timings.record_us, 5);
+        assert_eq!(timings.commit_us, 11);
+        assert_eq!(timings.phases_us(), 1_078);
+
+        let mut values = vec![5, 1, 4, 2, 3, 10, 9, 8, 7, 6];
+        assert_eq!(percentile(&mut values, 50), 5);
+        assert_eq!(percentile(&mut values, 90), 9);
+        assert_eq!(percentile(&mut values, 100), 10);
+        assert_eq!(percentile(&mut [], 50), 0);
+    }
 }
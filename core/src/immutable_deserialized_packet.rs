@@ -94,6 +94,14 @@ impl ImmutableDeserializedPacket {
     pub fn compute_unit_limit(&self) -> u64 {
         self.priority_details.compute_unit_limit
     }
+
+    /// Scales the priority the packet is ordered by to `percent` of the transaction's priority.
+    /// Saturates at `u64::MAX` when `percent` is over 100.
+    pub fn scale_priority(&mut self, percent: u64) {
+        self.priority_details.priority =
+            u64::try_from(u128::from(self.priority_details.priority) * u128::from(percent) / 100)
+                .unwrap_or(u64::MAX);
+    }
 }
 
 impl PartialOrd for ImmutableDeserializedPacket {
@@ -141,4 +149,41 @@ mod tests {
 
         assert!(matches!(deserialized_packet, Ok(_)));
     }
+
+    #[test]
+    fn test_scale_priority() {
+        let tx = system_transaction::transfer(
+            &Keypair::new(),
+            &solana_sdk::pubkey::new_rand(),
+            1,
+            Hash::new_unique(),
+        );
+        let new_packet = |priority| {
+            ImmutableDeserializedPacket::new(
+                Packet::from_data(None, &tx).unwrap(),
+                Some(TransactionPriorityDetails {
+                    priority,
+                    compute_unit_limit: 0,
+                }),
+            )
+            .unwrap()
+        };
+
+        let mut packet = new_packet(1_000);
+        packet.scale_priority(50);
+        assert_eq!(packet.priority(), 500);
+
+        let mut packet = new_packet(1_000);
+        packet.scale_priority(150);
+        assert_eq!(packet.priority(), 1_500);
+
+        // doesn't wrap around when scaled past u64::MAX
+        let mut packet = new_packet(u64::MAX);
+        packet.scale_priority(200);
+        assert_eq!(packet.priority(), u64::MAX);
+
+        let mut packet = new_packet(u64::MAX);
+        packet.scale_priority(100);
+        assert_eq!(packet.priority(), u64::MAX);
+    }
 }
@@ -44,6 +44,9 @@ pub(crate) struct ProcessTransactionsSummary {
     // Total amount of time spent running the cost model
     pub cost_model_us: u64,
 
+    // The number of transactions deferred because they conflict with accounts held by bundles
+    pub bundle_locked_transactions_count: usize,
+
     // Breakdown of time spent executing and comitting transactions
     pub execute_and_commit_timings: LeaderExecuteAndCommitTimings,
 
@@ -119,6 +122,11 @@ struct LeaderSlotPacketCountMetrics {
     // already counted in `self.retrayble_errored_transaction_count`.
     cost_model_throttled_transactions_count: u64,
 
+    // total number of transactions that were excluded from the block because a bundle held one
+    // of their accounts. These transactions are added back to the buffered queue and are already
+    // counted in `self.account_lock_throttled_transactions_count`.
+    bundle_locked_transactions_count: u64,
+
     // total number of forwardsable packets that failed forwarding
     failed_forwarded_packets_count: u64,
 
@@ -222,6 +230,11 @@ impl LeaderSlotPacketCountMetrics {
                 self.cost_model_throttled_transactions_count as i64,
                 i64
             ),
+            (
+                "bundle_locked_transactions_count",
+                self.bundle_locked_transactions_count as i64,
+                i64
+            ),
             (
                 "failed_forwarded_packets_count",
                 self.failed_forwarded_packets_count as i64,
@@ -413,6 +426,7 @@ impl LeaderSlotMetricsTracker {
                 ref retryable_transaction_indexes,
                 cost_model_throttled_transactions_count,
                 cost_model_us,
+                bundle_locked_transactions_count,
                 ref execute_and_commit_timings,
                 error_counters,
                 ..
@@ -483,6 +497,13 @@ impl LeaderSlotMetricsTracker {
                 *cost_model_throttled_transactions_count as u64
             );
 
+            saturating_add_assign!(
+                leader_slot_metrics
+                    .packet_count_metrics
+                    .bundle_locked_transactions_count,
+                *bundle_locked_transactions_count as u64
+            );
+
             saturating_add_assign!(
                 leader_slot_metrics
                     .timing_metrics
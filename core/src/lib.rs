@@ -14,7 +14,11 @@ mod backoff;
 pub mod banking_stage;
 pub mod broadcast_stage;
 pub mod bundle_account_locker;
+pub mod bundle_filter;
+pub mod bundle_queue;
 pub mod bundle_sanitizer;
+pub mod bundle_sigverify;
+pub mod bundle_simulation;
 pub mod bundle_stage;
 mod bundle_stage_leader_stats;
 pub mod cache_block_meta_service;
@@ -150,3 +154,11 @@ pub fn proto_packet_to_packet(p: jito_protos::proto::packet::Packet) -> Packet {
     }
     packet
 }
+
+/// Converts a packet received on the relayer packet stream, tagging it so banking stage can
+/// prioritize relayer packets separately from the validator's own TPU packets.
+pub fn relayer_proto_packet_to_packet(p: jito_protos::proto::packet::Packet) -> Packet {
+    let mut packet = proto_packet_to_packet(p);
+    packet.meta.set_from_relayer(true);
+    packet
+}
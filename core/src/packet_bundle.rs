@@ -1,7 +1,105 @@
-use {solana_perf::packet::PacketBatch, uuid::Uuid};
+use {
+    solana_perf::packet::PacketBatch,
+    solana_sdk::clock::Slot,
+    std::{
+        sync::atomic::{AtomicU64, Ordering},
+        time::{Duration, Instant},
+    },
+    uuid::Uuid,
+};
 
 #[derive(Clone, Debug)]
 pub struct PacketBundle {
     pub batch: PacketBatch,
     pub uuid: Uuid,
+    /// When the validator received the bundle, used to measure how long it waits to be executed.
+    pub received_at: Instant,
+    /// Last slot the bundle may be executed in. Bundles still queued after this slot are dropped.
+    pub expiry_slot: Slot,
+}
+
+/// Cumulative counts of bundles handed from the block engine stage to BundleStage.
+#[derive(Default)]
+pub struct BundleQueueStats {
+    num_enqueued: AtomicU64,
+    num_dequeued: AtomicU64,
+}
+
+/// Counts as of the last report, used to compute per-interval deltas.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BundleQueueCounts {
+    pub num_enqueued: u64,
+    pub num_dequeued: u64,
+}
+
+impl BundleQueueStats {
+    pub fn increment_enqueued(&self, num_bundles: u64) {
+        self.num_enqueued.fetch_add(num_bundles, Ordering::Relaxed);
+    }
+
+    pub fn increment_dequeued(&self, num_bundles: u64) {
+        self.num_dequeued.fetch_add(num_bundles, Ordering::Relaxed);
+    }
+
+    pub fn counts(&self) -> BundleQueueCounts {
+        // load dequeued first so a concurrent enqueue can't make the channel depth negative
+        let num_dequeued = self.num_dequeued.load(Ordering::Relaxed);
+        let num_enqueued = self.num_enqueued.load(Ordering::Relaxed);
+        BundleQueueCounts {
+            num_enqueued,
+            num_dequeued,
+        }
+    }
+
+    /// Number of bundles sent to BundleStage that it hasn't received yet.
+    pub fn channel_depth(&self) -> u64 {
+        let counts = self.counts();
+        counts.num_enqueued.saturating_sub(counts.num_dequeued)
+    }
+
+    /// Reports the queue depth and the bundles enqueued and dequeued since `last_counts`.
+    pub fn report(
+        &self,
+        last_counts: &mut BundleQueueCounts,
+        num_buffered: usize,
+        oldest_buffered_age: Option<Duration>,
+    ) {
+        let counts = self.counts();
+        datapoint_info!(
+            "bundle_queue-stats",
+            (
+                "channel_depth",
+                counts.num_enqueued.saturating_sub(counts.num_dequeued),
+                i64
+            ),
+            ("num_buffered", num_buffered, i64),
+            (
+                "oldest_buffered_age_us",
+                oldest_buffered_age.unwrap_or_default().as_micros() as u64,
+                i64
+            ),
+            (
+                "num_enqueued",
+                counts.num_enqueued.saturating_sub(last_counts.num_enqueued),
+                i64
+            ),
+            (
+                "num_dequeued",
+                counts.num_dequeued.saturating_sub(last_counts.num_dequeued),
+                i64
+            ),
+        );
+        *last_counts = counts;
+    }
+}
+
+/// Returns how long the oldest of `bundles` has been waiting.
+pub fn oldest_bundle_age<'a>(
+    bundles: impl IntoIterator<Item = &'a PacketBundle>,
+    now: Instant,
+) -> Option<Duration> {
+    bundles
+        .into_iter()
+        .map(|bundle| now.saturating_duration_since(bundle.received_at))
+        .max()
 }
@@ -28,12 +28,18 @@ pub struct ReceivePacketResults {
 pub struct PacketDeserializer {
     /// Receiver for packet batches from sigverify stage
     packet_batch_receiver: BankingPacketReceiver,
+    /// Percent of their priority packets from the relayer are ordered by, if set
+    relayer_priority_percent: Option<u64>,
 }
 
 impl PacketDeserializer {
-    pub fn new(packet_batch_receiver: BankingPacketReceiver) -> Self {
+    pub fn new(
+        packet_batch_receiver: BankingPacketReceiver,
+        relayer_priority_percent: Option<u64>,
+    ) -> Self {
         Self {
             packet_batch_receiver,
+            relayer_priority_percent,
         }
     }
 
@@ -48,6 +54,7 @@ impl PacketDeserializer {
         Ok(Self::deserialize_and_collect_packets(
             &packet_batches,
             sigverify_tracer_stats_option,
+            self.relayer_priority_percent,
         ))
     }
 
@@ -55,6 +62,7 @@ impl PacketDeserializer {
     fn deserialize_and_collect_packets(
         packet_batches: &[PacketBatch],
         sigverify_tracer_stats_option: Option<SigverifyTracerPacketStats>,
+        relayer_priority_percent: Option<u64>,
     ) -> ReceivePacketResults {
         let packet_count: usize = packet_batches.iter().map(|x| x.len()).sum();
         let mut passed_sigverify_count: usize = 0;
@@ -66,7 +74,16 @@ impl PacketDeserializer {
             passed_sigverify_count += packet_indexes.len();
             failed_sigverify_count += packet_batch.len().saturating_sub(packet_indexes.len());
 
-            deserialized_packets.extend(Self::deserialize_packets(packet_batch, &packet_indexes));
+            deserialized_packets.extend(
+                Self::deserialize_packets(packet_batch, &packet_indexes).map(|mut packet| {
+                    if let Some(percent) = relayer_priority_percent {
+                        if packet.original_packet().meta.is_from_relayer() {
+                            packet.scale_priority(percent);
+                        }
+                    }
+                    packet
+                }),
+            );
         }
 
         ReceivePacketResults {
@@ -141,9 +158,14 @@ impl PacketDeserializer {
 mod tests {
     use {
         super::*,
+        crate::unprocessed_packet_batches::{DeserializedPacket, UnprocessedPacketBatches},
         solana_perf::packet::to_packet_batches,
         solana_sdk::{
-            hash::Hash, pubkey::Pubkey, signature::Keypair, system_transaction,
+            compute_budget::ComputeBudgetInstruction,
+            hash::Hash,
+            pubkey::Pubkey,
+            signature::{Keypair, Signer},
+            system_instruction, system_transaction,
             transaction::Transaction,
         },
     };
@@ -154,7 +176,7 @@ mod tests {
 
     #[test]
     fn test_deserialize_and_collect_packets_empty() {
-        let results = PacketDeserializer::deserialize_and_collect_packets(&[], None);
+        let results = PacketDeserializer::deserialize_and_collect_packets(&[], None, None);
         assert_eq!(results.deserialized_packets.len(), 0);
         assert!(results.new_tracer_stats_option.is_none());
         assert_eq!(results.passed_sigverify_count, 0);
@@ -167,7 +189,8 @@ mod tests {
         let packet_batches = to_packet_batches(&transactions, 1);
         assert_eq!(packet_batches.len(), 2);
 
-        let results = PacketDeserializer::deserialize_and_collect_packets(&packet_batches, None);
+        let results =
+            PacketDeserializer::deserialize_and_collect_packets(&packet_batches, None, None);
         assert_eq!(results.deserialized_packets.len(), 2);
         assert!(results.new_tracer_stats_option.is_none());
         assert_eq!(results.passed_sigverify_count, 2);
@@ -181,10 +204,54 @@ mod tests {
         assert_eq!(packet_batches.len(), 2);
         packet_batches[0][0].meta.set_discard(true);
 
-        let results = PacketDeserializer::deserialize_and_collect_packets(&packet_batches, None);
+        let results =
+            PacketDeserializer::deserialize_and_collect_packets(&packet_batches, None, None);
         assert_eq!(results.deserialized_packets.len(), 1);
         assert!(results.new_tracer_stats_option.is_none());
         assert_eq!(results.passed_sigverify_count, 1);
         assert_eq!(results.failed_sigverify_count, 1);
     }
+
+    #[test]
+    fn test_deserialize_and_collect_packets_relayer_priority() {
+        let prioritized_transfer = |compute_unit_price: u64| {
+            let payer = Keypair::new();
+            Transaction::new_signed_with_payer(
+                &[
+                    ComputeBudgetInstruction::set_compute_unit_price(compute_unit_price),
+                    system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1),
+                ],
+                Some(&payer.pubkey()),
+                &[&payer],
+                Hash::default(),
+            )
+        };
+        let mut packet_batches =
+            to_packet_batches(&[prioritized_transfer(100), prioritized_transfer(200)], 1);
+        packet_batches[0][0].meta.set_from_relayer(true);
+
+        let pop_order = |relayer_priority_percent| {
+            let results = PacketDeserializer::deserialize_and_collect_packets(
+                &packet_batches,
+                None,
+                relayer_priority_percent,
+            );
+            let mut unprocessed_packets = UnprocessedPacketBatches::from_iter(
+                results
+                    .deserialized_packets
+                    .into_iter()
+                    .map(DeserializedPacket::from_immutable_section),
+                10,
+            );
+            std::iter::from_fn(|| unprocessed_packets.packet_priority_queue.pop_max())
+                .map(|packet| packet.original_packet().meta.is_from_relayer())
+                .collect::<Vec<_>>()
+        };
+
+        // relayer packets are prioritized like any other packet by default
+        assert_eq!(pop_order(None), vec![false, true]);
+        assert_eq!(pop_order(Some(100)), vec![false, true]);
+        // tripling the relayer packet's priority puts it ahead
+        assert_eq!(pop_order(Some(300)), vec![true, false]);
+    }
 }
@@ -5,13 +5,16 @@ use {
         GenerateAuthTokensRequest, RefreshAccessTokenRequest, Role, Token,
     },
     solana_gossip::cluster_info::ClusterInfo,
-    solana_sdk::signature::{Keypair, Signer},
+    solana_sdk::{
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+    },
     std::{
         sync::{
             atomic::{AtomicBool, Ordering},
             Arc, Mutex,
         },
-        time::Duration,
+        time::{Duration, Instant},
     },
     tokio::time::sleep,
     tonic::{service::Interceptor, transport::Channel, Request, Status},
@@ -31,12 +34,14 @@ impl AuthInterceptor {
 
 impl Interceptor for AuthInterceptor {
     fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
-        request.metadata_mut().insert(
-            "authorization",
-            format!("Bearer {}", self.access_token.lock().unwrap().value)
-                .parse()
-                .unwrap(),
-        );
+        let access_token = self.access_token.lock().unwrap();
+        // relayers reached over a unix socket may be connected to before any token exists
+        if !access_token.value.is_empty() {
+            request.metadata_mut().insert(
+                "authorization",
+                format!("Bearer {}", access_token.value).parse().unwrap(),
+            );
+        }
 
         Ok(request)
     }
@@ -44,11 +49,19 @@ impl Interceptor for AuthInterceptor {
 
 /// Contains collection of utility functions responsible for generating and refreshing new tokens.
 pub(crate) mod token_manager {
-    use {super::*, crate::proxy::ProxyError, tonic::transport::Endpoint};
+    use {
+        super::*,
+        crate::proxy::{
+            http_proxy::HttpProxyConnector, with_rpc_timeout, ProxyError, DEFAULT_RPC_TIMEOUT,
+        },
+        tonic::transport::Endpoint,
+    };
 
-    /// Control loop responsible for making sure access and refresh tokens are updated.
+    /// Control loop responsible for making sure access and refresh tokens are updated. The auth
+    /// service is reached through `http_proxy_connector` if it's set.
     pub(crate) async fn auth_tokens_update_loop(
         auth_service_endpoint: Endpoint,
+        http_proxy_connector: Option<HttpProxyConnector>,
         access_token: Arc<Mutex<Token>>,
         cluster_info: Arc<ClusterInfo>,
         exit: Arc<AtomicBool>,
@@ -61,7 +74,15 @@ pub(crate) mod token_manager {
         while !exit.load(Ordering::Relaxed) {
             sleep(RETRY_INTERVAL).await;
 
-            match auth_service_endpoint.connect().await {
+            let connect_result = match &http_proxy_connector {
+                Some(connector) => {
+                    auth_service_endpoint
+                        .connect_with_connector(connector.clone())
+                        .await
+                }
+                None => auth_service_endpoint.connect().await,
+            };
+            match connect_result {
                 Ok(channel) => {
                     if let Err(e) = auth_tokens_update_loop_helper(
                         AuthServiceClient::new(channel),
@@ -107,6 +128,8 @@ pub(crate) mod token_manager {
         const REFRESH_WITHIN_SECS: i64 = 300;
         let mut num_full_refreshes = 0;
         let mut num_refresh_access_token = 0;
+        // identity the current tokens were generated for
+        let mut token_identity: Option<Pubkey> = None;
 
         while !exit.load(Ordering::Relaxed) {
             let access_token_expiry: i64 = access_token
@@ -132,8 +155,28 @@ pub(crate) mod token_manager {
                     ProxyError::InvalidData("Received invalid refresh_token expiration".to_string())
                 })? <= REFRESH_WITHIN_SECS;
 
-            match (should_refresh_access, should_generate_new_tokens) {
-                // Generate new tokens if the refresh_token is close to being expired.
+            // The identity can be changed at runtime with set-identity. Tokens belong to the
+            // identity that signed the challenge, so they're regenerated with the new keypair.
+            let identity = cluster_info.id();
+            let identity_changed = token_identity.map_or(false, |pubkey| pubkey != identity);
+            if identity_changed {
+                info!(
+                    "validator identity changed to {}, generating new auth tokens",
+                    identity
+                );
+                datapoint_info!(
+                    "auth_tokens_update_loop-identity_changed",
+                    ("url", url, String),
+                    ("identity", identity.to_string(), String),
+                );
+            }
+
+            match (
+                should_refresh_access,
+                should_generate_new_tokens || identity_changed,
+            ) {
+                // Generate new tokens if the refresh_token is close to being expired or the
+                // identity changed.
                 (_, true) => {
                     let kp = cluster_info.keypair().clone();
 
@@ -142,6 +185,7 @@ pub(crate) mod token_manager {
 
                     *access_token.lock().unwrap() = new_access_token.clone();
                     refresh_token = new_refresh_token;
+                    token_identity = Some(kp.pubkey());
 
                     num_full_refreshes += 1;
                     datapoint_info!(
@@ -165,32 +209,52 @@ pub(crate) mod token_manager {
                     );
                 }
                 // Sleep and do nothing if neither token is close to expired,
-                (false, false) => sleep(sleep_interval).await,
+                (false, false) => {
+                    sleep_unless_identity_changes(&cluster_info, identity, sleep_interval).await
+                }
             }
         }
 
         Ok(())
     }
 
+    /// Sleeps for `duration`, waking up early if the validator identity is no longer `identity`.
+    async fn sleep_unless_identity_changes(
+        cluster_info: &ClusterInfo,
+        identity: Pubkey,
+        duration: Duration,
+    ) {
+        const IDENTITY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+        let deadline = Instant::now() + duration;
+        while cluster_info.id() == identity {
+            let now = Instant::now();
+            if now >= deadline {
+                return;
+            }
+            sleep(IDENTITY_CHECK_INTERVAL.min(deadline - now)).await;
+        }
+    }
+
     /// Invokes the refresh_access_token gRPC method.
     /// Returns a new access_token.
     async fn refresh_access_token(
         auth_service_client: &mut AuthServiceClient<Channel>,
         refresh_token: Token,
     ) -> crate::proxy::Result<Token> {
-        match auth_service_client
-            .refresh_access_token(RefreshAccessTokenRequest {
+        let resp = with_rpc_timeout(
+            "refresh_access_token",
+            DEFAULT_RPC_TIMEOUT,
+            auth_service_client.refresh_access_token(RefreshAccessTokenRequest {
                 refresh_token: refresh_token.value,
-            })
-            .await
-        {
-            Ok(resp) => get_validated_token(resp.into_inner().access_token),
-            Err(e) => Err(ProxyError::GrpcError(e)),
-        }
+            }),
+        )
+        .await?;
+        get_validated_token(resp.into_inner().access_token)
     }
 
     /// Generates an auth challenge then generates and returns validated auth tokens.
-    async fn generate_auth_tokens(
+    pub(crate) async fn generate_auth_tokens(
         auth_service_client: &mut AuthServiceClient<Channel>,
         // used to sign challenges
         keypair: &Keypair,
@@ -198,12 +262,15 @@ pub(crate) mod token_manager {
         Token, /* access_token */
         Token, /* refresh_token */
     )> {
-        let challenge_response = auth_service_client
-            .generate_auth_challenge(GenerateAuthChallengeRequest {
+        let challenge_response = with_rpc_timeout(
+            "generate_auth_challenge",
+            DEFAULT_RPC_TIMEOUT,
+            auth_service_client.generate_auth_challenge(GenerateAuthChallengeRequest {
                 role: Role::Validator as i32,
                 pubkey: keypair.pubkey().as_ref().to_vec(),
-            })
-            .await?;
+            }),
+        )
+        .await?;
 
         let formatted_challenge = format!(
             "{}-{}",
@@ -215,13 +282,16 @@ pub(crate) mod token_manager {
             .as_ref()
             .to_vec();
 
-        let auth_tokens = auth_service_client
-            .generate_auth_tokens(GenerateAuthTokensRequest {
+        let auth_tokens = with_rpc_timeout(
+            "generate_auth_tokens",
+            DEFAULT_RPC_TIMEOUT,
+            auth_service_client.generate_auth_tokens(GenerateAuthTokensRequest {
                 challenge: formatted_challenge,
                 client_pubkey: keypair.pubkey().as_ref().to_vec(),
                 signed_challenge,
-            })
-            .await?;
+            }),
+        )
+        .await?;
 
         let inner = auth_tokens.into_inner();
         let access_token = get_validated_token(inner.access_token)?;
@@ -7,15 +7,19 @@
 use {
     crate::{
         backoff::BackoffStrategy,
-        packet_bundle::PacketBundle,
+        bundle_filter::{BundleFilter, ProtectedAccountsConfig},
+        bundle_sanitizer::BundleLimits,
+        packet_bundle::{BundleQueueStats, PacketBundle},
         proto_packet_to_packet,
         proxy::{
             auth::{token_manager::auth_tokens_update_loop, AuthInterceptor},
-            ProxyError,
+            sleep_unless_exit, wait_for_exit, with_rpc_timeout, BlockEngineStreamStatus,
+            DropOldestSender, LastMinuteCounter, MevStatus, ProxyError, DEFAULT_RPC_TIMEOUT,
+            EXIT_CHECK_INTERVAL,
         },
         sigverify::SigverifyTracerPacketStats,
     },
-    crossbeam_channel::Sender,
+    crossbeam_channel::{Sender, TrySendError},
     jito_protos::proto::{
         auth::Token,
         block_engine::{
@@ -25,17 +29,18 @@ use {
     },
     solana_gossip::cluster_info::ClusterInfo,
     solana_perf::packet::PacketBatch,
-    solana_sdk::{pubkey::Pubkey, saturating_add_assign},
+    solana_poh::poh_recorder::PohRecorder,
+    solana_sdk::{clock::Slot, pubkey::Pubkey, saturating_add_assign},
     std::{
         str::FromStr,
         sync::{
             atomic::{AtomicBool, Ordering},
-            Arc, Mutex,
+            Arc, Mutex, RwLock,
         },
         thread::{self, Builder, JoinHandle},
-        time::Duration,
+        time::{Duration, Instant},
     },
-    tokio::time::{interval, sleep},
+    tokio::time::interval,
     tonic::{
         codegen::InterceptedService,
         transport::{Channel, Endpoint},
@@ -44,26 +49,140 @@ use {
     uuid::Uuid,
 };
 
+const METRICS_TICK: Duration = Duration::from_secs(1);
+
+type BlockEngineClient = BlockEngineValidatorClient<InterceptedService<Channel, AuthInterceptor>>;
+
+/// The block engine subscriptions. Each one runs on its own connection so an error on one
+/// doesn't interrupt the other, even though both go to the same address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BlockEngineStream {
+    Packets,
+    Bundles,
+}
+
+impl BlockEngineStream {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Packets => "packets",
+            Self::Bundles => "bundles",
+        }
+    }
+
+    fn status<'a>(&self, mev_status: &'a mut MevStatus) -> &'a mut BlockEngineStreamStatus {
+        match self {
+            Self::Packets => &mut mev_status.block_engine.packet_stream,
+            Self::Bundles => &mut mev_status.block_engine.bundle_stream,
+        }
+    }
+}
+
+/// Connection lifecycle of one block engine stream: connecting, backing off between attempts and
+/// reporting the stream's health.
+struct BlockEngineConnection {
+    stream: BlockEngineStream,
+    endpoint: Endpoint,
+    access_token: Arc<Mutex<Token>>,
+    mev_status: Arc<RwLock<MevStatus>>,
+    backoff: BackoffStrategy,
+    num_stream_errors: usize,
+    num_connect_errors: usize,
+}
+
+impl BlockEngineConnection {
+    /// Connects to the block engine, backing off between failed attempts. Returns `None` once the
+    /// validator is exiting.
+    async fn connect(&mut self, exit: &Arc<AtomicBool>) -> Option<BlockEngineClient> {
+        while !exit.load(Ordering::Relaxed) {
+            let connect_result = tokio::select! {
+                result = self.endpoint.connect() => result,
+                _ = wait_for_exit(exit) => return None,
+            };
+            match connect_result {
+                Ok(channel) => {
+                    return Some(BlockEngineValidatorClient::with_interceptor(
+                        channel,
+                        AuthInterceptor::new(self.access_token.clone()),
+                    ));
+                }
+                Err(e) => {
+                    self.num_connect_errors += 1;
+                    self.record_failure();
+                    datapoint_error!(
+                        "block_engine_stage-connect_error",
+                        ("stream", self.stream.name(), String),
+                        ("count", self.num_connect_errors, i64),
+                        ("error", e.to_string(), String),
+                    );
+                    sleep_unless_exit(Duration::from_millis(self.backoff.next_wait()), exit).await;
+                }
+            }
+        }
+        None
+    }
+
+    /// Called once the stream is subscribed.
+    fn on_subscribed(&mut self) {
+        self.backoff.reset();
+        let mut mev_status = self.mev_status.write().unwrap();
+        let status = self.stream.status(&mut mev_status);
+        status.connected = true;
+        status.consecutive_failures = 0;
+    }
+
+    /// Records how the stream ended and waits out the backoff before reconnecting.
+    async fn on_disconnected(&mut self, result: crate::proxy::Result<()>, exit: &AtomicBool) {
+        self.stream
+            .status(&mut self.mev_status.write().unwrap())
+            .connected = false;
+        if let Err(e) = result {
+            self.num_stream_errors += 1;
+            self.record_failure();
+            datapoint_error!(
+                "block_engine_stage-stream_error",
+                ("stream", self.stream.name(), String),
+                ("count", self.num_stream_errors, i64),
+                ("error", e.to_string(), String),
+            );
+        }
+        sleep_unless_exit(Duration::from_millis(self.backoff.next_wait()), exit).await;
+    }
+
+    fn record_failure(&self) {
+        let mut mev_status = self.mev_status.write().unwrap();
+        let status = self.stream.status(&mut mev_status);
+        status.consecutive_failures = status.consecutive_failures.saturating_add(1);
+    }
+}
+
 #[derive(Default)]
 struct BlockEngineStageStats {
     num_bundles: u64,
     num_bundle_packets: u64,
+    num_bundles_dropped: u64,
     num_packets: u64,
+    num_packets_dropped: u64,
     num_empty_packets: u64,
 }
 
 impl BlockEngineStageStats {
-    pub(crate) fn report(&self) {
+    fn report(&self, stream: BlockEngineStream) {
         datapoint_info!(
             "block_engine_stage-stats",
+            ("stream", stream.name(), String),
             ("num_bundles", self.num_bundles, i64),
             ("num_bundle_packets", self.num_bundle_packets, i64),
+            ("num_bundles_dropped", self.num_bundles_dropped, i64),
             ("num_packets", self.num_packets, i64),
+            ("num_packets_dropped", self.num_packets_dropped, i64),
             ("num_empty_packets", self.num_empty_packets, i64)
         );
     }
 }
 
+/// Fee account and commission of the block builder, refreshed from the block engine. Until the
+/// first update arrives [`BlockEngineConfig::default_block_builder_fee_info`] is used.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BlockBuilderFeeInfo {
     pub block_builder: Pubkey,
     pub block_builder_commission: u64,
@@ -79,6 +198,75 @@ pub struct BlockEngineConfig {
 
     /// If set then it will be assumed the backend verified packets so signature verification will be bypassed in the validator.
     pub trust_packets: bool,
+
+    /// Max number of packet batches queued downstream before the oldest ones are dropped.
+    pub packet_channel_capacity: usize,
+
+    /// Max number of bundle messages queued for BundleStage before newly received ones are dropped.
+    pub bundle_channel_capacity: usize,
+
+    /// Max number of bundles BundleStage holds on to before evicting the lowest tipping ones.
+    pub bundle_queue_capacity: usize,
+
+    /// If set then BundleStage simulates bundles and drops the ones that would fail before
+    /// locking their accounts.
+    pub simulate_bundles: bool,
+
+    /// If set then BundleStage reports a datapoint for every bundle it executes or drops.
+    pub report_bundle_outcomes: bool,
+
+    /// Largest bundle BundleStage will execute, bigger ones are dropped.
+    pub bundle_limits: BundleLimits,
+
+    /// Number of slots before its leader slots BundleStage starts holding on to bundles.
+    pub bundle_leader_lookahead_slots: u64,
+
+    /// Number of slots after the current slot a received bundle may still be executed in.
+    pub bundle_ttl_slots: u64,
+
+    /// Accounts and programs bundles may not use. Can be replaced while the validator is running
+    /// through `Tpu::bundle_filter_sender`.
+    pub bundle_filter: BundleFilter,
+
+    /// Number of threads BundleStage executes bundles that don't conflict with each other on.
+    pub bundle_execution_threads: usize,
+
+    /// Time a bundle's transactions may spend executing before BundleStage abandons the bundle.
+    /// It's checked between transactions, so a single slow transaction runs to completion first.
+    pub max_bundle_execution_duration: Duration,
+
+    /// Number of times a bundle whose accounts stayed in use by BankingStage is queued again
+    /// while the validator is leader.
+    pub max_bundle_contention_retries: usize,
+
+    /// Share of each block's compute units, between 0 and 1, bundles may use. Bundles beyond it
+    /// are deferred to the next leader slot.
+    pub max_bundle_block_cu_ratio: f64,
+
+    /// Accounts bundles may not write in addition to the validator's identity and vote account.
+    pub protected_accounts: ProtectedAccountsConfig,
+
+    /// Block builder fee account and commission used until the block engine sends its own. The
+    /// validator identity with zero commission is used if unset.
+    pub default_block_builder_fee_info: Option<BlockBuilderFeeInfo>,
+}
+
+/// Default number of slots after the current slot a bundle may still be executed in.
+pub const DEFAULT_BUNDLE_TTL_SLOTS: u64 = 2;
+
+/// Stamps received bundles with the last slot they may be executed in.
+struct BundleExpiry {
+    poh_recorder: Arc<RwLock<PohRecorder>>,
+    ttl_slots: u64,
+}
+
+impl BundleExpiry {
+    fn expiry_slot(&self) -> Slot {
+        let poh_recorder = self.poh_recorder.read().unwrap();
+        // the slot poh is currently ticking in
+        let current_slot = poh_recorder.tick_height() / poh_recorder.ticks_per_slot();
+        current_slot.saturating_add(self.ttl_slots)
+    }
 }
 
 pub struct BlockEngineStage {
@@ -86,6 +274,7 @@ pub struct BlockEngineStage {
 }
 
 impl BlockEngineStage {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         block_engine_config: BlockEngineConfig,
         // Channel that bundles get piped through.
@@ -93,16 +282,27 @@ impl BlockEngineStage {
         // The keypair stored here is used to sign auth challenges.
         cluster_info: Arc<ClusterInfo>,
         // Channel that non-trusted packets get piped through.
-        packet_tx: Sender<PacketBatch>,
+        packet_tx: DropOldestSender<PacketBatch>,
         // Channel that trusted packets get piped through.
-        verified_packet_tx: Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
+        verified_packet_tx: DropOldestSender<(
+            Vec<PacketBatch>,
+            Option<SigverifyTracerPacketStats>,
+        )>,
         exit: Arc<AtomicBool>,
         block_builder_fee_info: &Arc<Mutex<BlockBuilderFeeInfo>>,
+        // Connection status reported to health checks.
+        mev_status: Arc<RwLock<MevStatus>>,
+        // Counts bundles sent to BundleStage so it can report the queue depth.
+        bundle_queue_stats: Arc<BundleQueueStats>,
+        // Used to stamp bundles with the slot they expire in.
+        poh_recorder: Arc<RwLock<PohRecorder>>,
     ) -> Self {
         let BlockEngineConfig {
             auth_service_endpoint,
             backend_endpoint,
             trust_packets,
+            bundle_ttl_slots,
+            ..
         } = block_engine_config;
 
         let access_token = Arc::new(Mutex::new(Token::default()));
@@ -117,6 +317,7 @@ impl BlockEngineStage {
                     .unwrap();
                 rt.spawn(auth_tokens_update_loop(
                     auth_service_endpoint,
+                    None,
                     access_token.clone(),
                     cluster_info.clone(),
                     exit.clone(),
@@ -130,6 +331,12 @@ impl BlockEngineStage {
                     verified_packet_tx,
                     exit,
                     block_builder_fee_info,
+                    mev_status,
+                    bundle_queue_stats,
+                    BundleExpiry {
+                        poh_recorder,
+                        ttl_slots: bundle_ttl_slots,
+                    },
                 ));
             })
             .unwrap();
@@ -151,17 +358,21 @@ impl BlockEngineStage {
         access_token: Arc<Mutex<Token>>,
         block_engine_endpoint: Endpoint,
         bundle_tx: Sender<Vec<PacketBundle>>,
-        packet_tx: Sender<PacketBatch>,
+        packet_tx: DropOldestSender<PacketBatch>,
         trust_packets: bool,
-        verified_packet_tx: Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
+        verified_packet_tx: DropOldestSender<(
+            Vec<PacketBatch>,
+            Option<SigverifyTracerPacketStats>,
+        )>,
         exit: Arc<AtomicBool>,
         block_builder_fee_info: Arc<Mutex<BlockBuilderFeeInfo>>,
+        mev_status: Arc<RwLock<MevStatus>>,
+        bundle_queue_stats: Arc<BundleQueueStats>,
+        bundle_expiry: BundleExpiry,
     ) {
         const WAIT_FOR_FIRST_AUTH: Duration = Duration::from_secs(5);
 
         let mut num_wait_for_auth: usize = 0;
-        let mut num_stream_errors: usize = 0;
-        let mut num_connect_errors: usize = 0;
 
         while access_token.lock().unwrap().value.is_empty() {
             if exit.load(Ordering::Relaxed) {
@@ -172,151 +383,263 @@ impl BlockEngineStage {
                 "block_engine_stage-wait_for_auth",
                 ("wait_count", num_wait_for_auth, i64)
             );
-            sleep(WAIT_FOR_FIRST_AUTH).await;
+            sleep_unless_exit(WAIT_FOR_FIRST_AUTH, &exit).await;
         }
 
-        let mut backoff = BackoffStrategy::new();
-        while !exit.load(Ordering::Relaxed) {
-            match block_engine_endpoint.connect().await {
-                Ok(channel) => {
-                    match Self::start_consuming_block_engine_bundles_and_packets(
-                        &mut backoff,
-                        &bundle_tx,
-                        BlockEngineValidatorClient::with_interceptor(
-                            channel,
-                            AuthInterceptor::new(access_token.clone()),
-                        ),
-                        &packet_tx,
-                        trust_packets,
-                        &verified_packet_tx,
-                        &exit,
-                        &block_builder_fee_info,
-                    )
-                    .await
-                    {
-                        Ok(_) => {}
-                        Err(e) => {
-                            num_stream_errors += 1;
-                            datapoint_error!(
-                                "block_engine_stage-stream_error",
-                                ("count", num_stream_errors, i64),
-                                ("error", e.to_string(), String),
-                            );
-                        }
-                    }
-                }
-                Err(e) => {
-                    num_connect_errors += 1;
-                    datapoint_error!(
-                        "block_engine_stage-connect_error",
-                        ("count", num_connect_errors, i64),
-                        ("error", e.to_string(), String),
-                    );
-                }
-            }
+        mev_status.write().unwrap().block_engine.active_endpoint =
+            Some(block_engine_endpoint.uri().to_string());
+        let new_connection = |stream| BlockEngineConnection {
+            stream,
+            endpoint: block_engine_endpoint.clone(),
+            access_token: access_token.clone(),
+            mev_status: mev_status.clone(),
+            backoff: BackoffStrategy::new(),
+            num_stream_errors: 0,
+            num_connect_errors: 0,
+        };
+        tokio::join!(
+            Self::run_packet_stream(
+                new_connection(BlockEngineStream::Packets),
+                &packet_tx,
+                trust_packets,
+                &verified_packet_tx,
+                &exit,
+            ),
+            Self::run_bundle_stream(
+                new_connection(BlockEngineStream::Bundles),
+                &bundle_tx,
+                &exit,
+                &block_builder_fee_info,
+                &mev_status,
+                &bundle_queue_stats,
+                &bundle_expiry,
+            ),
+        );
+    }
+
+    /// Keeps the packet subscription up until exit, reconnecting on its own connection.
+    async fn run_packet_stream(
+        mut connection: BlockEngineConnection,
+        packet_tx: &DropOldestSender<PacketBatch>,
+        trust_packets: bool,
+        verified_packet_tx: &DropOldestSender<(
+            Vec<PacketBatch>,
+            Option<SigverifyTracerPacketStats>,
+        )>,
+        exit: &Arc<AtomicBool>,
+    ) {
+        while let Some(mut client) = connection.connect(exit).await {
+            let result = async {
+                let packet_stream = with_rpc_timeout(
+                    "subscribe_packets",
+                    DEFAULT_RPC_TIMEOUT,
+                    client.subscribe_packets(block_engine::SubscribePacketsRequest {}),
+                )
+                .await?
+                .into_inner();
+                connection.on_subscribed();
 
-            sleep(Duration::from_millis(backoff.next_wait())).await;
+                Self::consume_packet_stream(
+                    packet_stream,
+                    packet_tx,
+                    trust_packets,
+                    verified_packet_tx,
+                    exit,
+                )
+                .await
+            }
+            .await;
+            connection.on_disconnected(result, exit).await;
         }
     }
 
-    async fn start_consuming_block_engine_bundles_and_packets(
-        backoff: &mut BackoffStrategy,
+    /// Keeps the bundle subscription up until exit, reconnecting on its own connection. The block
+    /// builder fee info is fetched on this connection since it's only needed for bundles.
+    #[allow(clippy::too_many_arguments)]
+    async fn run_bundle_stream(
+        mut connection: BlockEngineConnection,
         bundle_tx: &Sender<Vec<PacketBundle>>,
-        mut client: BlockEngineValidatorClient<InterceptedService<Channel, AuthInterceptor>>,
-        packet_tx: &Sender<PacketBatch>,
-        trust_packets: bool,
-        verified_packet_tx: &Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
         exit: &Arc<AtomicBool>,
         block_builder_fee_info: &Arc<Mutex<BlockBuilderFeeInfo>>,
-    ) -> crate::proxy::Result<()> {
-        let subscribe_packets_stream = client
-            .subscribe_packets(block_engine::SubscribePacketsRequest {})
-            .await?
-            .into_inner();
-        let subscribe_bundles_stream = client
-            .subscribe_bundles(block_engine::SubscribeBundlesRequest {})
-            .await?
-            .into_inner();
-
-        let block_builder_info = client
-            .get_block_builder_fee_info(BlockBuilderFeeInfoRequest {})
-            .await?
-            .into_inner();
-        {
-            let mut bb_fee = block_builder_fee_info.lock().unwrap();
-            bb_fee.block_builder_commission = block_builder_info.commission;
-            bb_fee.block_builder =
-                Pubkey::from_str(&block_builder_info.pubkey).unwrap_or(bb_fee.block_builder);
+        mev_status: &RwLock<MevStatus>,
+        bundle_queue_stats: &BundleQueueStats,
+        bundle_expiry: &BundleExpiry,
+    ) {
+        let mut bundles_received = LastMinuteCounter::default();
+        while let Some(mut client) = connection.connect(exit).await {
+            let result = async {
+                let bundle_stream = with_rpc_timeout(
+                    "subscribe_bundles",
+                    DEFAULT_RPC_TIMEOUT,
+                    client.subscribe_bundles(block_engine::SubscribeBundlesRequest {}),
+                )
+                .await?
+                .into_inner();
+
+                let block_builder_info = with_rpc_timeout(
+                    "get_block_builder_fee_info",
+                    DEFAULT_RPC_TIMEOUT,
+                    client.get_block_builder_fee_info(BlockBuilderFeeInfoRequest {}),
+                )
+                .await?
+                .into_inner();
+                Self::update_block_builder_fee_info(
+                    block_builder_fee_info,
+                    &block_builder_info.pubkey,
+                    block_builder_info.commission,
+                );
+                connection.on_subscribed();
+
+                Self::consume_bundle_stream(
+                    &mut client,
+                    bundle_stream,
+                    bundle_tx,
+                    exit,
+                    block_builder_fee_info,
+                    mev_status,
+                    bundle_queue_stats,
+                    bundle_expiry,
+                    &mut bundles_received,
+                )
+                .await
+            }
+            .await;
+            connection.on_disconnected(result, exit).await;
         }
+    }
+
+    async fn consume_packet_stream(
+        mut packet_stream: Streaming<block_engine::SubscribePacketsResponse>,
+        packet_tx: &DropOldestSender<PacketBatch>,
+        trust_packets: bool,
+        verified_packet_tx: &DropOldestSender<(
+            Vec<PacketBatch>,
+            Option<SigverifyTracerPacketStats>,
+        )>,
+        exit: &Arc<AtomicBool>,
+    ) -> crate::proxy::Result<()> {
+        let mut block_engine_stats = BlockEngineStageStats::default();
+        let mut metrics_tick = interval(METRICS_TICK);
+        let mut exit_check_interval = interval(EXIT_CHECK_INTERVAL);
 
-        backoff.reset();
+        info!("connected to block engine packet stream");
 
-        Self::consume_bundle_and_packet_stream(
-            client,
-            (subscribe_bundles_stream, subscribe_packets_stream),
-            bundle_tx,
-            packet_tx,
-            trust_packets,
-            verified_packet_tx,
-            exit,
-            block_builder_fee_info,
-        )
-        .await
+        while !exit.load(Ordering::Relaxed) {
+            tokio::select! {
+                maybe_msg = packet_stream.message() => {
+                    let resp = maybe_msg?.ok_or(ProxyError::GrpcStreamDisconnected)?;
+                    Self::handle_block_engine_packets(resp, packet_tx, verified_packet_tx, trust_packets, &mut block_engine_stats)?;
+                }
+                _ = metrics_tick.tick() => {
+                    block_engine_stats.report(BlockEngineStream::Packets);
+                    block_engine_stats = BlockEngineStageStats::default();
+                }
+                _ = exit_check_interval.tick() => {}
+            }
+        }
+
+        // cancel the subscription instead of leaving it open until the runtime shuts down
+        info!("exiting, closing block engine packet stream");
+        drop(packet_stream);
+        Ok(())
     }
 
-    async fn consume_bundle_and_packet_stream(
-        mut client: BlockEngineValidatorClient<InterceptedService<Channel, AuthInterceptor>>,
-        (mut bundle_stream, mut packet_stream): (
-            Streaming<block_engine::SubscribeBundlesResponse>,
-            Streaming<block_engine::SubscribePacketsResponse>,
-        ),
+    #[allow(clippy::too_many_arguments)]
+    async fn consume_bundle_stream(
+        client: &mut BlockEngineClient,
+        mut bundle_stream: Streaming<block_engine::SubscribeBundlesResponse>,
         bundle_tx: &Sender<Vec<PacketBundle>>,
-        packet_tx: &Sender<PacketBatch>,
-        trust_packets: bool,
-        verified_packet_tx: &Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
         exit: &Arc<AtomicBool>,
         block_builder_fee_info: &Arc<Mutex<BlockBuilderFeeInfo>>,
+        mev_status: &RwLock<MevStatus>,
+        bundle_queue_stats: &BundleQueueStats,
+        bundle_expiry: &BundleExpiry,
+        bundles_received: &mut LastMinuteCounter,
     ) -> crate::proxy::Result<()> {
-        const METRICS_TICK: Duration = Duration::from_secs(1);
         const MAINTENANCE_TICK: Duration = Duration::from_secs(10 * 60);
 
         let mut block_engine_stats = BlockEngineStageStats::default();
         let mut metrics_tick = interval(METRICS_TICK);
         let mut maintenance_tick = interval(MAINTENANCE_TICK);
+        let mut exit_check_interval = interval(EXIT_CHECK_INTERVAL);
 
-        info!("connected to packet and bundle stream");
+        info!("connected to block engine bundle stream");
 
         while !exit.load(Ordering::Relaxed) {
             tokio::select! {
-                maybe_msg = packet_stream.message() => {
-                    let resp = maybe_msg?.ok_or(ProxyError::GrpcStreamDisconnected)?;
-                    Self::handle_block_engine_packets(resp, packet_tx, verified_packet_tx, trust_packets, &mut block_engine_stats)?;
-                }
                 maybe_bundles = bundle_stream.message() => {
-                    Self::handle_block_engine_maybe_bundles(maybe_bundles, bundle_tx, &mut block_engine_stats)?;
+                    Self::handle_block_engine_maybe_bundles(maybe_bundles, bundle_expiry.expiry_slot(), bundle_tx, bundle_queue_stats, &mut block_engine_stats)?;
                 }
                 _ = metrics_tick.tick() => {
-                    block_engine_stats.report();
+                    block_engine_stats.report(BlockEngineStream::Bundles);
+                    mev_status.write().unwrap().block_engine.bundles_received_last_minute =
+                        bundles_received.record(block_engine_stats.num_bundles);
                     block_engine_stats = BlockEngineStageStats::default();
                 }
                 _ = maintenance_tick.tick() => {
-                    let block_builder_info = client.get_block_builder_fee_info(BlockBuilderFeeInfoRequest{}).await?.into_inner();
-                    let mut bb_fee = block_builder_fee_info.lock().unwrap();
-                    bb_fee.block_builder_commission = block_builder_info.commission;
-                    bb_fee.block_builder = Pubkey::from_str(&block_builder_info.pubkey).unwrap_or(bb_fee.block_builder);
+                    let block_builder_info = with_rpc_timeout("get_block_builder_fee_info", DEFAULT_RPC_TIMEOUT, client.get_block_builder_fee_info(BlockBuilderFeeInfoRequest{})).await?.into_inner();
+                    Self::update_block_builder_fee_info(block_builder_fee_info, &block_builder_info.pubkey, block_builder_info.commission);
                 }
+                _ = exit_check_interval.tick() => {}
             }
         }
 
+        // cancel the subscription instead of leaving it open until the runtime shuts down
+        info!("exiting, closing block engine bundle stream");
+        drop(bundle_stream);
         Ok(())
     }
 
+    /// Replaces the block builder and commission together so BundleStage never observes a mix of
+    /// old and new values. An unparsable pubkey or a commission over 100% leaves the current info
+    /// in place.
+    fn update_block_builder_fee_info(
+        block_builder_fee_info: &Mutex<BlockBuilderFeeInfo>,
+        block_builder: &str,
+        block_builder_commission: u64,
+    ) {
+        if block_builder_commission > 100 {
+            warn!(
+                "ignoring block builder fee info with commission over 100%: {}",
+                block_builder_commission
+            );
+            return;
+        }
+        let block_builder = match Pubkey::from_str(block_builder) {
+            Ok(block_builder) => block_builder,
+            Err(e) => {
+                warn!(
+                    "ignoring block builder fee info with invalid pubkey {}: {:?}",
+                    block_builder, e
+                );
+                return;
+            }
+        };
+        let new_fee_info = BlockBuilderFeeInfo {
+            block_builder,
+            block_builder_commission,
+        };
+
+        let mut fee_info = block_builder_fee_info.lock().unwrap();
+        if *fee_info != new_fee_info {
+            info!(
+                "block builder fee info updated from {:?} to {:?}",
+                *fee_info, new_fee_info
+            );
+            *fee_info = new_fee_info;
+        }
+    }
+
     fn handle_block_engine_maybe_bundles(
         maybe_bundles_response: Result<Option<block_engine::SubscribeBundlesResponse>, Status>,
+        // bundles don't carry an expiry from the block engine, so every bundle gets the default
+        expiry_slot: Slot,
         bundle_sender: &Sender<Vec<PacketBundle>>,
+        bundle_queue_stats: &BundleQueueStats,
         block_engine_stats: &mut BlockEngineStageStats,
     ) -> crate::proxy::Result<()> {
         let bundles_response = maybe_bundles_response?.ok_or(ProxyError::GrpcStreamDisconnected)?;
+        let received_at = Instant::now();
         let bundles: Vec<PacketBundle> = bundles_response
             .bundles
             .into_iter()
@@ -330,11 +653,29 @@ impl BlockEngineStage {
                             .map(proto_packet_to_packet)
                             .collect(),
                     ),
-                    uuid: Uuid::from_str(&bundle.uuid).ok()?,
+                    // bundles without a valid uuid are deduplicated by content in BundleStage
+                    uuid: Uuid::from_str(&bundle.uuid).unwrap_or_else(|_| Uuid::nil()),
+                    received_at,
+                    expiry_slot,
                 })
             })
             .collect();
 
+        Self::forward_bundles(
+            bundles,
+            bundle_sender,
+            bundle_queue_stats,
+            block_engine_stats,
+        )
+    }
+
+    /// Bundles are dropped instead of blocking the stream if BundleStage falls behind.
+    fn forward_bundles(
+        bundles: Vec<PacketBundle>,
+        bundle_sender: &Sender<Vec<PacketBundle>>,
+        bundle_queue_stats: &BundleQueueStats,
+        block_engine_stats: &mut BlockEngineStageStats,
+    ) -> crate::proxy::Result<()> {
         saturating_add_assign!(block_engine_stats.num_bundles, bundles.len() as u64);
         saturating_add_assign!(
             block_engine_stats.num_bundle_packets,
@@ -342,15 +683,30 @@ impl BlockEngineStage {
         );
 
         // NOTE: bundles are sanitized in bundle_sanitizer module
-        bundle_sender
-            .send(bundles)
-            .map_err(|_| ProxyError::PacketForwardError)
+        let num_bundles = bundles.len() as u64;
+        match bundle_sender.try_send(bundles) {
+            Ok(()) => {
+                bundle_queue_stats.increment_enqueued(num_bundles);
+                Ok(())
+            }
+            Err(TrySendError::Full(bundles)) => {
+                saturating_add_assign!(
+                    block_engine_stats.num_bundles_dropped,
+                    bundles.len() as u64
+                );
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(_)) => Err(ProxyError::PacketForwardError),
+        }
     }
 
     fn handle_block_engine_packets(
         resp: block_engine::SubscribePacketsResponse,
-        packet_tx: &Sender<PacketBatch>,
-        verified_packet_tx: &Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
+        packet_tx: &DropOldestSender<PacketBatch>,
+        verified_packet_tx: &DropOldestSender<(
+            Vec<PacketBatch>,
+            Option<SigverifyTracerPacketStats>,
+        )>,
         trust_packets: bool,
         block_engine_stats: &mut BlockEngineStageStats,
     ) -> crate::proxy::Result<()> {
@@ -365,15 +721,15 @@ impl BlockEngineStage {
 
             saturating_add_assign!(block_engine_stats.num_packets, packet_batch.len() as u64);
 
-            if trust_packets {
-                verified_packet_tx
-                    .send((vec![packet_batch], None))
-                    .map_err(|_| ProxyError::PacketForwardError)?;
+            let num_batches_dropped = if trust_packets {
+                verified_packet_tx.send((vec![packet_batch], None))?
             } else {
-                packet_tx
-                    .send(packet_batch)
-                    .map_err(|_| ProxyError::PacketForwardError)?;
-            }
+                packet_tx.send(packet_batch)?
+            };
+            saturating_add_assign!(
+                block_engine_stats.num_packets_dropped,
+                num_batches_dropped as u64
+            );
         } else {
             saturating_add_assign!(block_engine_stats.num_empty_packets, 1);
         }
@@ -381,3 +737,373 @@ impl BlockEngineStage {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            bundle_queue::DEFAULT_BUNDLE_QUEUE_CAPACITY,
+            bundle_stage::{
+                DEFAULT_BUNDLE_EXECUTION_THREADS, DEFAULT_BUNDLE_LEADER_LOOKAHEAD_SLOTS,
+                DEFAULT_MAX_BUNDLE_BLOCK_CU_RATIO, DEFAULT_MAX_BUNDLE_CONTENTION_RETRIES,
+                DEFAULT_MAX_BUNDLE_EXECUTION_DURATION,
+            },
+            proxy::test_utils::MockBlockEngine,
+        },
+        crossbeam_channel::{bounded, unbounded},
+        solana_gossip::contact_info::ContactInfo,
+        solana_ledger::{
+            blockstore::Blockstore, genesis_utils::create_genesis_config,
+            get_tmp_ledger_path_auto_delete,
+        },
+        solana_poh::poh_recorder::create_test_recorder,
+        solana_runtime::bank::Bank,
+        solana_sdk::signature::{Keypair, Signer},
+        solana_streamer::socket::SocketAddrSpace,
+    };
+
+    // The auth loop waits a few seconds before fetching the first token, so connecting to the
+    // block engine takes a while.
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+    fn wait_for(condition: impl Fn() -> bool, timeout: Duration) -> bool {
+        let start = Instant::now();
+        while !condition() {
+            if start.elapsed() > timeout {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        true
+    }
+
+    fn new_proto_packet_batch(num_packets: usize) -> jito_protos::proto::packet::PacketBatch {
+        jito_protos::proto::packet::PacketBatch {
+            packets: vec![jito_protos::proto::packet::Packet::default(); num_packets],
+        }
+    }
+
+    #[test]
+    fn test_bundle_stream_failure_keeps_packets_flowing() {
+        let mock_block_engine = MockBlockEngine::start();
+
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(Blockstore::open(ledger_path.path()).unwrap());
+        let bank = Arc::new(Bank::new_for_tests(
+            &create_genesis_config(10_000).genesis_config,
+        ));
+        let (poh_exit, poh_recorder, poh_service, _entry_receiver) =
+            create_test_recorder(&bank, &blockstore, None, None);
+
+        let keypair = Arc::new(Keypair::new());
+        let cluster_info = Arc::new(ClusterInfo::new(
+            ContactInfo::new_localhost(&keypair.pubkey(), 0),
+            keypair,
+            SocketAddrSpace::Unspecified,
+        ));
+        let (bundle_tx, _bundle_rx) = unbounded();
+        let (packet_tx, packet_rx) = unbounded();
+        let (verified_packet_tx, verified_packet_rx) = unbounded();
+        let mev_status = Arc::new(RwLock::new(MevStatus::default()));
+        let exit = Arc::new(AtomicBool::new(false));
+        let block_engine_stage = BlockEngineStage::new(
+            BlockEngineConfig {
+                auth_service_endpoint: mock_block_engine.endpoint(),
+                backend_endpoint: mock_block_engine.endpoint(),
+                trust_packets: false,
+                packet_channel_capacity: 10,
+                bundle_channel_capacity: 10,
+                bundle_queue_capacity: DEFAULT_BUNDLE_QUEUE_CAPACITY,
+                simulate_bundles: true,
+                report_bundle_outcomes: false,
+                bundle_limits: BundleLimits::default(),
+                bundle_leader_lookahead_slots: DEFAULT_BUNDLE_LEADER_LOOKAHEAD_SLOTS,
+                bundle_ttl_slots: DEFAULT_BUNDLE_TTL_SLOTS,
+                bundle_filter: BundleFilter::default(),
+                bundle_execution_threads: DEFAULT_BUNDLE_EXECUTION_THREADS,
+                max_bundle_execution_duration: DEFAULT_MAX_BUNDLE_EXECUTION_DURATION,
+                max_bundle_contention_retries: DEFAULT_MAX_BUNDLE_CONTENTION_RETRIES,
+                max_bundle_block_cu_ratio: DEFAULT_MAX_BUNDLE_BLOCK_CU_RATIO,
+                protected_accounts: ProtectedAccountsConfig::default(),
+                default_block_builder_fee_info: None,
+            },
+            bundle_tx,
+            cluster_info,
+            DropOldestSender::new(packet_tx, packet_rx.clone(), 10),
+            DropOldestSender::new(verified_packet_tx, verified_packet_rx, 10),
+            exit.clone(),
+            &Arc::new(Mutex::new(BlockBuilderFeeInfo {
+                block_builder: Pubkey::new_unique(),
+                block_builder_commission: 0,
+            })),
+            mev_status.clone(),
+            Arc::default(),
+            poh_recorder,
+        );
+
+        assert!(wait_for(
+            || mev_status.read().unwrap().block_engine.connected(),
+            CONNECT_TIMEOUT
+        ));
+        mock_block_engine.send_packets(new_proto_packet_batch(2));
+        let packet_batch = packet_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(packet_batch.len(), 2);
+        // only packets from the relayer are tagged
+        assert!(packet_batch
+            .iter()
+            .all(|packet| !packet.meta.is_from_relayer()));
+
+        // only the bundle stream reconnects
+        mock_block_engine.fail_bundle_streams();
+        assert!(wait_for(
+            || mock_block_engine.num_bundle_subscriptions() == 2,
+            Duration::from_secs(5)
+        ));
+        assert_eq!(mock_block_engine.num_packet_subscriptions(), 1);
+        assert!(
+            mev_status
+                .read()
+                .unwrap()
+                .block_engine
+                .packet_stream
+                .connected
+        );
+
+        mock_block_engine.send_packets(new_proto_packet_batch(3));
+        assert_eq!(
+            packet_rx
+                .recv_timeout(Duration::from_secs(1))
+                .unwrap()
+                .len(),
+            3
+        );
+        assert!(wait_for(
+            || mev_status
+                .read()
+                .unwrap()
+                .block_engine
+                .bundle_stream
+                .connected,
+            Duration::from_secs(5)
+        ));
+
+        exit.store(true, Ordering::Relaxed);
+        block_engine_stage.join().unwrap();
+        poh_exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+    }
+
+    #[test]
+    fn test_block_builder_fee_info_updates_reach_bundle_stage() {
+        let mock_block_engine = MockBlockEngine::start();
+        let block_builder = Pubkey::new_unique();
+        mock_block_engine.set_block_builder_fee_info(block_builder.to_string(), 5);
+
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(Blockstore::open(ledger_path.path()).unwrap());
+        let bank = Arc::new(Bank::new_for_tests(
+            &create_genesis_config(10_000).genesis_config,
+        ));
+        let (poh_exit, poh_recorder, poh_service, _entry_receiver) =
+            create_test_recorder(&bank, &blockstore, None, None);
+
+        let keypair = Arc::new(Keypair::new());
+        let cluster_info = Arc::new(ClusterInfo::new(
+            ContactInfo::new_localhost(&keypair.pubkey(), 0),
+            keypair,
+            SocketAddrSpace::Unspecified,
+        ));
+        let default_block_builder_fee_info = BlockBuilderFeeInfo {
+            block_builder: Pubkey::new_unique(),
+            block_builder_commission: 1,
+        };
+        // Tpu::new hands BundleStage a clone of the same handle
+        let block_builder_fee_info = Arc::new(Mutex::new(default_block_builder_fee_info.clone()));
+        let bundle_stage_fee_info = block_builder_fee_info.clone();
+        let (bundle_tx, _bundle_rx) = unbounded();
+        let (packet_tx, packet_rx) = unbounded();
+        let (verified_packet_tx, verified_packet_rx) = unbounded();
+        let exit = Arc::new(AtomicBool::new(false));
+        let block_engine_stage = BlockEngineStage::new(
+            BlockEngineConfig {
+                auth_service_endpoint: mock_block_engine.endpoint(),
+                backend_endpoint: mock_block_engine.endpoint(),
+                trust_packets: false,
+                packet_channel_capacity: 10,
+                bundle_channel_capacity: 10,
+                bundle_queue_capacity: DEFAULT_BUNDLE_QUEUE_CAPACITY,
+                simulate_bundles: true,
+                report_bundle_outcomes: false,
+                bundle_limits: BundleLimits::default(),
+                bundle_leader_lookahead_slots: DEFAULT_BUNDLE_LEADER_LOOKAHEAD_SLOTS,
+                bundle_ttl_slots: DEFAULT_BUNDLE_TTL_SLOTS,
+                bundle_filter: BundleFilter::default(),
+                bundle_execution_threads: DEFAULT_BUNDLE_EXECUTION_THREADS,
+                max_bundle_execution_duration: DEFAULT_MAX_BUNDLE_EXECUTION_DURATION,
+                max_bundle_contention_retries: DEFAULT_MAX_BUNDLE_CONTENTION_RETRIES,
+                max_bundle_block_cu_ratio: DEFAULT_MAX_BUNDLE_BLOCK_CU_RATIO,
+                protected_accounts: ProtectedAccountsConfig::default(),
+                default_block_builder_fee_info: Some(default_block_builder_fee_info),
+            },
+            bundle_tx,
+            cluster_info,
+            DropOldestSender::new(packet_tx, packet_rx, 10),
+            DropOldestSender::new(verified_packet_tx, verified_packet_rx, 10),
+            exit.clone(),
+            &block_builder_fee_info,
+            Arc::default(),
+            Arc::default(),
+            poh_recorder,
+        );
+
+        // fetched once the bundle stream is up
+        assert!(wait_for(
+            || *bundle_stage_fee_info.lock().unwrap()
+                == BlockBuilderFeeInfo {
+                    block_builder,
+                    block_builder_commission: 5,
+                },
+            CONNECT_TIMEOUT
+        ));
+
+        // and again every time it reconnects
+        mock_block_engine.set_block_builder_fee_info(block_builder.to_string(), 7);
+        mock_block_engine.fail_bundle_streams();
+        assert!(wait_for(
+            || bundle_stage_fee_info
+                .lock()
+                .unwrap()
+                .block_builder_commission
+                == 7,
+            Duration::from_secs(5)
+        ));
+
+        // a commission over 100% is ignored
+        mock_block_engine.set_block_builder_fee_info(Pubkey::new_unique().to_string(), 101);
+        mock_block_engine.fail_bundle_streams();
+        assert!(wait_for(
+            || mock_block_engine.num_bundle_subscriptions() == 3,
+            Duration::from_secs(5)
+        ));
+        // the fee info is fetched right after subscribing
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(
+            *bundle_stage_fee_info.lock().unwrap(),
+            BlockBuilderFeeInfo {
+                block_builder,
+                block_builder_commission: 7,
+            }
+        );
+
+        exit.store(true, Ordering::Relaxed);
+        block_engine_stage.join().unwrap();
+        poh_exit.store(true, Ordering::Relaxed);
+        poh_service.join().unwrap();
+    }
+
+    #[test]
+    fn test_forward_bundles_drops_newest_when_full() {
+        let (bundle_sender, bundle_receiver) = bounded(1);
+        let bundle_queue_stats = BundleQueueStats::default();
+        let mut block_engine_stats = BlockEngineStageStats::default();
+
+        let new_bundles = |num_bundles: usize| -> Vec<PacketBundle> {
+            (0..num_bundles)
+                .map(|_| PacketBundle {
+                    batch: PacketBatch::new(vec![Default::default()]),
+                    uuid: Uuid::new_v4(),
+                    received_at: Instant::now(),
+                    expiry_slot: Slot::MAX,
+                })
+                .collect()
+        };
+
+        let first_bundles = new_bundles(2);
+        let first_uuids: Vec<_> = first_bundles.iter().map(|b| b.uuid).collect();
+        BlockEngineStage::forward_bundles(
+            first_bundles,
+            &bundle_sender,
+            &bundle_queue_stats,
+            &mut block_engine_stats,
+        )
+        .unwrap();
+
+        // BundleStage isn't reading, so the next messages are dropped instead of blocking
+        for _ in 0..5 {
+            BlockEngineStage::forward_bundles(
+                new_bundles(3),
+                &bundle_sender,
+                &bundle_queue_stats,
+                &mut block_engine_stats,
+            )
+            .unwrap();
+        }
+        assert_eq!(bundle_receiver.len(), 1);
+        assert_eq!(block_engine_stats.num_bundles, 17);
+        assert_eq!(block_engine_stats.num_bundles_dropped, 15);
+        // only the bundles that made it into the channel are counted as queued
+        assert_eq!(bundle_queue_stats.channel_depth(), 2);
+
+        let received_uuids: Vec<_> = bundle_receiver
+            .try_recv()
+            .unwrap()
+            .iter()
+            .map(|b| b.uuid)
+            .collect();
+        assert_eq!(received_uuids, first_uuids);
+    }
+
+    #[test]
+    fn test_update_block_builder_fee_info() {
+        let default_block_builder = Pubkey::new_unique();
+        let block_builder_fee_info = Arc::new(Mutex::new(BlockBuilderFeeInfo {
+            block_builder: default_block_builder,
+            block_builder_commission: 0,
+        }));
+        // BundleStage holds a clone of the same handle
+        let bundle_stage_fee_info = block_builder_fee_info.clone();
+
+        let block_builder = Pubkey::new_unique();
+        BlockEngineStage::update_block_builder_fee_info(
+            &block_builder_fee_info,
+            &block_builder.to_string(),
+            5,
+        );
+        assert_eq!(
+            *bundle_stage_fee_info.lock().unwrap(),
+            BlockBuilderFeeInfo {
+                block_builder,
+                block_builder_commission: 5,
+            }
+        );
+
+        // a bad pubkey doesn't apply the commission without the matching fee account
+        BlockEngineStage::update_block_builder_fee_info(
+            &block_builder_fee_info,
+            "not-a-pubkey",
+            50,
+        );
+        assert_eq!(
+            *bundle_stage_fee_info.lock().unwrap(),
+            BlockBuilderFeeInfo {
+                block_builder,
+                block_builder_commission: 5,
+            }
+        );
+
+        // the tip payment program can't pay out more than 100%
+        BlockEngineStage::update_block_builder_fee_info(
+            &block_builder_fee_info,
+            &Pubkey::new_unique().to_string(),
+            101,
+        );
+        assert_eq!(
+            *bundle_stage_fee_info.lock().unwrap(),
+            BlockBuilderFeeInfo {
+                block_builder,
+                block_builder_commission: 5,
+            }
+        );
+    }
+}
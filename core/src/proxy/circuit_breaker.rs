@@ -0,0 +1,171 @@
+//! Drops the relayer packet stream when it delivers mostly malformed packets.
+//!
+//! Malformed packets are cheap to drop one at a time, but a relayer sending little else wastes
+//! the leader window converting and discarding them. Once the share of malformed packets over a
+//! sliding window passes the threshold the stream is dropped and the relayer isn't reconnected
+//! to until a cooldown has passed, leaving the heartbeat loss policy to decide what happens to
+//! the validator's own TPU packets in the meantime.
+
+use {
+    jito_protos::proto::packet::Packet as ProtoPacket,
+    solana_sdk::packet::PACKET_DATA_SIZE,
+    std::{collections::VecDeque, time::Duration},
+};
+
+/// When to trip the relayer circuit breaker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RelayerCircuitBreakerConfig {
+    /// Number of most recent packets the malformed share is computed over.
+    pub window_size: usize,
+    /// Share of malformed packets in a full window, in percent, above which the stream is dropped.
+    pub max_malformed_percent: u64,
+    /// How long to wait before reconnecting to a relayer that tripped the breaker.
+    pub cooldown: Duration,
+}
+
+impl Default for RelayerCircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            window_size: 10_000,
+            max_malformed_percent: 50,
+            cooldown: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Returns true if the packet's meta doesn't describe its data.
+pub(crate) fn is_malformed_packet(packet: &ProtoPacket) -> bool {
+    match &packet.meta {
+        None => true,
+        Some(meta) => {
+            let size = meta.size as usize;
+            size == 0 || size > PACKET_DATA_SIZE || size > packet.data.len()
+        }
+    }
+}
+
+pub(crate) struct RelayerCircuitBreaker {
+    config: RelayerCircuitBreakerConfig,
+    // true for every malformed packet in the window
+    window: VecDeque<bool>,
+    num_malformed: usize,
+}
+
+impl RelayerCircuitBreaker {
+    pub(crate) fn new(config: RelayerCircuitBreakerConfig) -> Self {
+        let config = RelayerCircuitBreakerConfig {
+            window_size: config.window_size.max(1),
+            ..config
+        };
+        Self {
+            config,
+            window: VecDeque::with_capacity(config.window_size),
+            num_malformed: 0,
+        }
+    }
+
+    pub(crate) fn cooldown(&self) -> Duration {
+        self.config.cooldown
+    }
+
+    /// Forgets the packets seen on the previous connection.
+    pub(crate) fn reset(&mut self) {
+        self.window.clear();
+        self.num_malformed = 0;
+    }
+
+    /// Records the next packet and returns true if the breaker trips.
+    pub(crate) fn record(&mut self, is_malformed: bool) -> bool {
+        if self.window.len() == self.config.window_size
+            && self.window.pop_front().unwrap_or_default()
+        {
+            self.num_malformed -= 1;
+        }
+        self.window.push_back(is_malformed);
+        if is_malformed {
+            self.num_malformed += 1;
+        }
+
+        self.window.len() == self.config.window_size
+            && (self.num_malformed as u64).saturating_mul(100)
+                > self
+                    .config
+                    .max_malformed_percent
+                    .saturating_mul(self.config.window_size as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, jito_protos::proto::packet::Meta};
+
+    fn new_circuit_breaker(
+        window_size: usize,
+        max_malformed_percent: u64,
+    ) -> RelayerCircuitBreaker {
+        RelayerCircuitBreaker::new(RelayerCircuitBreakerConfig {
+            window_size,
+            max_malformed_percent,
+            cooldown: Duration::from_secs(1),
+        })
+    }
+
+    #[test]
+    fn test_is_malformed_packet() {
+        let packet = |size: u64, data_len: usize| ProtoPacket {
+            data: vec![1; data_len],
+            meta: Some(Meta {
+                size,
+                ..Meta::default()
+            }),
+        };
+        assert!(!is_malformed_packet(&packet(100, 100)));
+        assert!(!is_malformed_packet(&packet(
+            PACKET_DATA_SIZE as u64,
+            PACKET_DATA_SIZE
+        )));
+        assert!(is_malformed_packet(&packet(0, 100)));
+        assert!(is_malformed_packet(&packet(101, 100)));
+        assert!(is_malformed_packet(&packet(
+            PACKET_DATA_SIZE as u64 + 1,
+            PACKET_DATA_SIZE + 1
+        )));
+        assert!(is_malformed_packet(&ProtoPacket {
+            data: vec![1; 100],
+            meta: None,
+        }));
+    }
+
+    #[test]
+    fn test_circuit_breaker_waits_for_full_window() {
+        let mut circuit_breaker = new_circuit_breaker(10, 50);
+        for _ in 0..9 {
+            assert!(!circuit_breaker.record(true));
+        }
+        assert!(circuit_breaker.record(true));
+    }
+
+    #[test]
+    fn test_circuit_breaker_threshold() {
+        let mut circuit_breaker = new_circuit_breaker(10, 50);
+        // exactly half malformed doesn't trip
+        for i in 0..10 {
+            assert!(!circuit_breaker.record(i >= 5));
+        }
+        // the window slides past a valid packet, so the next malformed one pushes it over
+        assert!(circuit_breaker.record(true));
+    }
+
+    #[test]
+    fn test_circuit_breaker_reset() {
+        let mut circuit_breaker = new_circuit_breaker(4, 50);
+        for _ in 0..3 {
+            circuit_breaker.record(true);
+        }
+        circuit_breaker.reset();
+        for _ in 0..3 {
+            assert!(!circuit_breaker.record(false));
+        }
+        assert!(!circuit_breaker.record(true));
+    }
+}
@@ -0,0 +1,218 @@
+//! Records why the relayer packet stream disconnected.
+//!
+//! The relayer stage keeps the most recent disconnects in [`MevStatus`](crate::proxy::MevStatus)
+//! so operators can tell a relayer dropping heartbeats apart from an expired token or an address
+//! change without digging through logs.
+
+use {
+    crate::proxy::ProxyError,
+    std::{
+        collections::VecDeque,
+        fmt::{self, Display},
+        time::{Duration, SystemTime},
+    },
+    tonic::{Code, Status},
+};
+
+/// Number of disconnects kept in [`DisconnectHistory`].
+pub const MAX_DISCONNECT_EVENTS: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The relayer stopped sending heartbeats.
+    HeartbeatTimeout,
+    /// The stream or one of the calls setting it up failed with this status code.
+    StreamError(Code),
+    /// The relayer rejected the access token.
+    AuthExpired,
+    /// The circuit breaker tripped on too many malformed packets.
+    MalformedPackets,
+    /// The HTTP proxy couldn't be reached or refused to tunnel to the relayer.
+    HttpProxyError,
+    /// The validator disconnected to switch to a new relayer address.
+    ConfigChange,
+    /// The validator is shutting down.
+    Shutdown,
+}
+
+impl DisconnectReason {
+    /// Classifies the result of consuming a relayer packet stream. The stream only ends without
+    /// an error when the validator exits or is told to connect to a new relayer address.
+    pub fn from_result(result: &crate::proxy::Result<()>, exiting: bool) -> Self {
+        match result {
+            Ok(()) if exiting => Self::Shutdown,
+            Ok(()) => Self::ConfigChange,
+            Err(e) => Self::from_error(e),
+        }
+    }
+
+    pub fn from_error(e: &ProxyError) -> Self {
+        match e {
+            ProxyError::HeartbeatExpired => Self::HeartbeatTimeout,
+            ProxyError::MalformedPackets => Self::MalformedPackets,
+            ProxyError::HttpProxyConnectError(_) => Self::HttpProxyError,
+            ProxyError::GrpcError(status) => Self::from_status(status),
+            // the relayer ended the stream or went away
+            ProxyError::GrpcStreamDisconnected
+            | ProxyError::ConnectionError(_)
+            | ProxyError::ResolveError(_) => Self::StreamError(Code::Unavailable),
+            ProxyError::ConnectionTimeout | ProxyError::RpcTimeout(_) => {
+                Self::StreamError(Code::DeadlineExceeded)
+            }
+            ProxyError::MissingTpuSocket(_)
+            | ProxyError::InvalidSocketAddress(_)
+            | ProxyError::InvalidData(_)
+            | ProxyError::InvalidRelayerAddress(_)
+            | ProxyError::InvalidHttpProxy(_) => Self::StreamError(Code::InvalidArgument),
+            ProxyError::HeartbeatChannelError | ProxyError::PacketForwardError => {
+                Self::StreamError(Code::Internal)
+            }
+        }
+    }
+
+    pub fn from_status(status: &Status) -> Self {
+        match status.code() {
+            Code::Unauthenticated => Self::AuthExpired,
+            code => Self::StreamError(code),
+        }
+    }
+}
+
+impl Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::HeartbeatTimeout => write!(f, "heartbeat_timeout"),
+            Self::StreamError(code) => write!(f, "stream_error({:?})", code),
+            Self::AuthExpired => write!(f, "auth_expired"),
+            Self::MalformedPackets => write!(f, "malformed_packets"),
+            Self::HttpProxyError => write!(f, "http_proxy_error"),
+            Self::ConfigChange => write!(f, "config_change"),
+            Self::Shutdown => write!(f, "shutdown"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisconnectEvent {
+    pub timestamp: SystemTime,
+    pub endpoint: String,
+    pub reason: DisconnectReason,
+    /// How long the connection was up before it dropped.
+    pub uptime: Duration,
+}
+
+/// The last [`MAX_DISCONNECT_EVENTS`] disconnects, oldest first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DisconnectHistory {
+    events: VecDeque<DisconnectEvent>,
+}
+
+impl DisconnectHistory {
+    pub fn record(&mut self, event: DisconnectEvent) {
+        if self.events.len() == MAX_DISCONNECT_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DisconnectEvent> {
+        self.events.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disconnect_reason_from_status() {
+        assert_eq!(
+            DisconnectReason::from_error(&ProxyError::GrpcError(Status::unauthenticated(
+                "token expired"
+            ))),
+            DisconnectReason::AuthExpired
+        );
+        assert_eq!(
+            DisconnectReason::from_error(&ProxyError::GrpcError(Status::unavailable("restart"))),
+            DisconnectReason::StreamError(Code::Unavailable)
+        );
+        assert_eq!(
+            DisconnectReason::from_error(&ProxyError::GrpcError(Status::permission_denied(
+                "not whitelisted"
+            ))),
+            DisconnectReason::StreamError(Code::PermissionDenied)
+        );
+        assert_eq!(
+            DisconnectReason::from_error(&ProxyError::GrpcError(Status::internal("oops"))),
+            DisconnectReason::StreamError(Code::Internal)
+        );
+    }
+
+    #[test]
+    fn test_disconnect_reason_from_internal_errors() {
+        assert_eq!(
+            DisconnectReason::from_error(&ProxyError::HeartbeatExpired),
+            DisconnectReason::HeartbeatTimeout
+        );
+        assert_eq!(
+            DisconnectReason::from_error(&ProxyError::RpcTimeout("subscribe_packets")),
+            DisconnectReason::StreamError(Code::DeadlineExceeded)
+        );
+        assert_eq!(
+            DisconnectReason::from_error(&ProxyError::GrpcStreamDisconnected),
+            DisconnectReason::StreamError(Code::Unavailable)
+        );
+        assert_eq!(
+            DisconnectReason::from_error(&ProxyError::MissingTpuSocket("tpu".into())),
+            DisconnectReason::StreamError(Code::InvalidArgument)
+        );
+        assert_eq!(
+            DisconnectReason::from_error(&ProxyError::HttpProxyConnectError("407".into())),
+            DisconnectReason::HttpProxyError
+        );
+    }
+
+    #[test]
+    fn test_disconnect_reason_from_result() {
+        assert_eq!(
+            DisconnectReason::from_result(&Ok(()), true),
+            DisconnectReason::Shutdown
+        );
+        assert_eq!(
+            DisconnectReason::from_result(&Ok(()), false),
+            DisconnectReason::ConfigChange
+        );
+        // an error while exiting is still reported as the error
+        assert_eq!(
+            DisconnectReason::from_result(&Err(ProxyError::HeartbeatExpired), true),
+            DisconnectReason::HeartbeatTimeout
+        );
+    }
+
+    #[test]
+    fn test_disconnect_history_is_bounded() {
+        let mut history = DisconnectHistory::default();
+        for i in 0..MAX_DISCONNECT_EVENTS + 10 {
+            history.record(DisconnectEvent {
+                timestamp: SystemTime::now(),
+                endpoint: format!("http://relayer-{}", i),
+                reason: DisconnectReason::HeartbeatTimeout,
+                uptime: Duration::from_secs(i as u64),
+            });
+        }
+        assert_eq!(history.len(), MAX_DISCONNECT_EVENTS);
+        assert_eq!(history.iter().next().unwrap().endpoint, "http://relayer-10");
+        assert_eq!(
+            history.iter().last().unwrap().endpoint,
+            format!("http://relayer-{}", MAX_DISCONNECT_EVENTS + 9)
+        );
+    }
+}
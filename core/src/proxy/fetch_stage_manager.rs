@@ -1,13 +1,13 @@
 use {
-    crate::proxy::{HeartbeatEvent, ProxyError},
-    crossbeam_channel::{select, tick, Receiver, Sender},
+    crate::proxy::{HeartbeatEvent, MevConfigUpdate, MevStatus, ProxyError, EXIT_CHECK_INTERVAL},
+    crossbeam_channel::{never, select, tick, Receiver, Sender},
     solana_gossip::cluster_info::ClusterInfo,
-    solana_perf::packet::PacketBatch,
+    solana_perf::{packet::PacketBatch, sigverify::check_for_simple_vote_packet},
     std::{
         net::SocketAddr,
         sync::{
             atomic::{AtomicBool, Ordering},
-            Arc,
+            Arc, RwLock,
         },
         thread::{self, Builder, JoinHandle},
         time::{Duration, Instant},
@@ -18,6 +18,23 @@ const HEARTBEAT_TIMEOUT: Duration = Duration::from_millis(1500); // Empirically
 const DISCONNECT_DELAY: Duration = Duration::from_secs(60);
 const METRICS_CADENCE: Duration = Duration::from_secs(1);
 
+/// Determines what happens to packets arriving on the validator's own TPU ports while the
+/// relayer isn't sending heartbeats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeartbeatLossPolicy {
+    /// Fall back to processing packets received on the validator's TPU ports.
+    FailOpen,
+    /// Keep dropping packets received on the validator's TPU ports; only the relayer is trusted
+    /// as a packet source. Packets are discarded rather than blocking FetchStage.
+    FailClosed,
+}
+
+impl Default for HeartbeatLossPolicy {
+    fn default() -> Self {
+        Self::FailOpen
+    }
+}
+
 /// Manages switching between the validator's tpu ports and that of the proxy's.
 /// Switch-overs are triggered by late and missed heartbeats.    
 pub struct FetchStageManager {
@@ -25,6 +42,7 @@ pub struct FetchStageManager {
 }
 
 impl FetchStageManager {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         // ClusterInfo is used to switch between advertising the proxy's TPU ports and that of this validator's.
         cluster_info: Arc<ClusterInfo>,
@@ -34,6 +52,16 @@ impl FetchStageManager {
         packet_intercept_rx: Receiver<PacketBatch>,
         // Intercepted packets get piped through here.
         packet_tx: Sender<PacketBatch>,
+        // Policy applied to intercepted packets while heartbeats are missing.
+        heartbeat_loss_policy: HeartbeatLossPolicy,
+        // Simple vote packets are processed locally even when other intercepted packets are dropped.
+        vote_bypass: bool,
+        // Channel that runtime configuration changes are received from.
+        config_update_rx: Receiver<MevConfigUpdate>,
+        // Config changes for the relayer connection are forwarded to the RelayerStage through here.
+        relayer_config_update_tx: Sender<MevConfigUpdate>,
+        // Used to detect when the relayer has given up reconnecting.
+        mev_status: Arc<RwLock<MevStatus>>,
         exit: Arc<AtomicBool>,
     ) -> Self {
         let t_hdl = Self::start(
@@ -41,6 +69,12 @@ impl FetchStageManager {
             heartbeat_rx,
             packet_intercept_rx,
             packet_tx,
+            heartbeat_loss_policy,
+            vote_bypass,
+            config_update_rx,
+            relayer_config_update_tx,
+            mev_status,
+            DISCONNECT_DELAY,
             exit,
         );
 
@@ -51,19 +85,34 @@ impl FetchStageManager {
     /// Starts connected
     /// When connected and a packet is received, forward it
     /// When disconnected, packet is dropped
+    /// When connected and the heartbeat loss policy is FailClosed, packet is dropped unless the
+    ///      relayer is degraded
+    /// When a packet is dropped and vote bypass is enabled, simple votes in it are still forwarded
     /// When receiving heartbeat while connected and not pending disconnect
     ///      Sets pending_disconnect to true and records time
     /// When receiving heartbeat while connected, and pending for > DISCONNECT_DELAY_SEC
     ///      Sets fetch_connected to false, pending_disconnect to false
     ///      Advertises TPU ports sent in heartbeat
+    /// When receiving heartbeat while disconnected with TPU ports that differ from the advertised
+    ///      ones, e.g. after a relayer restart, advertises the new TPU ports
     /// When tick is received without heartbeat_received
     ///      Sets fetch_connected to true, pending_disconnect to false
     ///      Advertises saved contact info
+    /// When the relayer address is updated
+    ///      Sets fetch_connected to true, pending_disconnect to false
+    ///      Advertises saved contact info before the old relayer connection is torn down
+    #[allow(clippy::too_many_arguments)]
     fn start(
         cluster_info: Arc<ClusterInfo>,
         heartbeat_rx: Receiver<HeartbeatEvent>,
         packet_intercept_rx: Receiver<PacketBatch>,
         packet_tx: Sender<PacketBatch>,
+        mut heartbeat_loss_policy: HeartbeatLossPolicy,
+        vote_bypass: bool,
+        mut config_update_rx: Receiver<MevConfigUpdate>,
+        relayer_config_update_tx: Sender<MevConfigUpdate>,
+        mev_status: Arc<RwLock<MevStatus>>,
+        disconnect_delay: Duration,
         exit: Arc<AtomicBool>,
     ) -> JoinHandle<()> {
         Builder::new().name("fetch-stage-manager".into()).spawn(move || {
@@ -77,19 +126,43 @@ impl FetchStageManager {
 
             let heartbeat_tick = tick(HEARTBEAT_TIMEOUT);
             let metrics_tick = tick(METRICS_CADENCE);
+            let exit_tick = tick(EXIT_CHECK_INTERVAL);
             let mut packets_forwarded = 0;
+            let mut fail_closed_packets_dropped = 0;
+            let mut vote_packets_bypassed = 0;
             let mut heartbeats_received = 0;
+            let mut relayer_degraded = false;
             loop {
                 select! {
                     recv(packet_intercept_rx) -> pkt => {
                         match pkt {
-                            Ok(pkt) => {
-                                if fetch_connected {
+                            Ok(mut pkt) => {
+                                let forward = fetch_connected && match heartbeat_loss_policy {
+                                    // a degraded relayer isn't coming back soon, so local packets are processed regardless of policy
+                                    HeartbeatLossPolicy::FailClosed => relayer_degraded,
+                                    HeartbeatLossPolicy::FailOpen => true,
+                                };
+                                if forward {
                                     if packet_tx.send(pkt).is_err() {
                                         error!("{:?}", ProxyError::PacketForwardError);
                                         return;
                                     }
                                     packets_forwarded += 1;
+                                } else {
+                                    let mut num_packets_dropped = pkt.len();
+                                    if vote_bypass {
+                                        if let Some(votes) = Self::take_simple_vote_packets(&mut pkt) {
+                                            num_packets_dropped -= votes.len();
+                                            vote_packets_bypassed += votes.len();
+                                            if packet_tx.send(votes).is_err() {
+                                                error!("{:?}", ProxyError::PacketForwardError);
+                                                return;
+                                            }
+                                        }
+                                    }
+                                    if fetch_connected {
+                                        fail_closed_packets_dropped += num_packets_dropped;
+                                    }
                                 }
                             }
                             Err(_) => {
@@ -102,6 +175,7 @@ impl FetchStageManager {
                         if exit.load(Ordering::Relaxed) {
                             break;
                         }
+                        relayer_degraded = mev_status.read().unwrap().relayer.degraded;
                         if !heartbeat_received && (!fetch_connected || pending_disconnect) {
                             warn!("heartbeat late, reconnecting fetch stage");
                             fetch_connected = true;
@@ -120,42 +194,575 @@ impl FetchStageManager {
                                 pending_disconnect_ts = Instant::now();
                                 pending_disconnect = true;
                             }
-                            if fetch_connected && pending_disconnect && pending_disconnect_ts.elapsed() > DISCONNECT_DELAY {
+                            if fetch_connected && pending_disconnect && pending_disconnect_ts.elapsed() > disconnect_delay {
                                 info!("disconnecting fetch stage");
                                 fetch_connected = false;
                                 pending_disconnect = false;
                                 Self::set_tpu_addresses(&cluster_info, tpu_addr, tpu_forward_addr);
+                            } else if !fetch_connected && Self::set_tpu_addresses(&cluster_info, tpu_addr, tpu_forward_addr) {
+                                info!("relayer tpu addresses changed to {:?}", (tpu_addr, tpu_forward_addr));
                             }
                         } else {
                             // see comment on heartbeat_sender clone in new()
                             unreachable!();
                         }
                     }
+                    recv(config_update_rx) -> maybe_update => {
+                        match maybe_update {
+                            Ok(MevConfigUpdate::HeartbeatLossPolicy(policy)) => {
+                                info!("heartbeat loss policy updated from {:?} to {:?}", heartbeat_loss_policy, policy);
+                                heartbeat_loss_policy = policy;
+                            }
+                            Ok(MevConfigUpdate::RelayerAddress(address)) => {
+                                info!("relayer address updated to {}, reconnecting fetch stage", address);
+                                // Start accepting packets on our own TPU ports before the old relayer
+                                // goes away so nothing sent during the swap gets lost.
+                                fetch_connected = true;
+                                pending_disconnect = false;
+                                heartbeat_received = false;
+                                Self::set_tpu_addresses(&cluster_info, my_fallback_contact_info.tpu, my_fallback_contact_info.tpu_forwards);
+                                heartbeats_received = 0;
+                                if relayer_config_update_tx.send(MevConfigUpdate::RelayerAddress(address)).is_err() {
+                                    error!("relayer stage disconnected, unable to update relayer address");
+                                }
+                            }
+                            Ok(update @ MevConfigUpdate::RelayerPacketRateLimits(_)) => {
+                                if relayer_config_update_tx.send(update).is_err() {
+                                    error!("relayer stage disconnected, unable to update relayer packet rate limits");
+                                }
+                            }
+                            Err(_) => {
+                                // no more updates can be sent, stop selecting on this channel
+                                config_update_rx = never();
+                            }
+                        }
+                    }
+                    recv(exit_tick) -> _ => {
+                        if exit.load(Ordering::Relaxed) {
+                            break;
+                        }
+                    }
                     recv(metrics_tick) -> _ => {
                         datapoint_info!(
                             "relayer-heartbeat",
                             ("fetch_stage_packets_forwarded", packets_forwarded, i64),
+                            ("fail_closed_packets_dropped", fail_closed_packets_dropped, i64),
+                            ("vote_packets_bypassed", vote_packets_bypassed, i64),
                             ("heartbeats_received", heartbeats_received, i64),
+                            ("relayer_degraded", relayer_degraded, bool),
                         );
-
                     }
                 }
             }
         }).unwrap()
     }
 
+    /// Returns a batch with copies of the simple vote packets in `packet_batch`, if there are any.
+    fn take_simple_vote_packets(packet_batch: &mut PacketBatch) -> Option<PacketBatch> {
+        let votes: Vec<_> = packet_batch
+            .iter_mut()
+            .filter(|packet| !packet.meta.discard())
+            .filter_map(|packet| check_for_simple_vote_packet(packet).then(|| packet.clone()))
+            .collect();
+        (!votes.is_empty()).then(|| PacketBatch::new(votes))
+    }
+
+    /// Advertises the given TPU addresses. Returns false without touching the contact info if
+    /// they're already advertised.
     fn set_tpu_addresses(
         cluster_info: &Arc<ClusterInfo>,
         tpu_address: SocketAddr,
         tpu_forward_address: SocketAddr,
-    ) {
+    ) -> bool {
         let mut new_contact_info = cluster_info.my_contact_info();
+        if new_contact_info.tpu == tpu_address
+            && new_contact_info.tpu_forwards == tpu_forward_address
+        {
+            return false;
+        }
         new_contact_info.tpu = tpu_address;
         new_contact_info.tpu_forwards = tpu_forward_address;
         cluster_info.set_my_contact_info(new_contact_info);
+        true
     }
 
     pub fn join(self) -> thread::Result<()> {
         self.t_hdl.join()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::proxy::{
+            circuit_breaker::RelayerCircuitBreakerConfig,
+            rate_limiter::RelayerPacketRateLimits,
+            relayer_resolver::SystemResolver,
+            relayer_stage::{
+                RelayerAddressUpdate, RelayerConfig, RelayerConnectionConfig, RelayerStage,
+                DEFAULT_BACKOFF_RESET_GRACE_PERIOD,
+            },
+            test_utils::{MockRelayer, MockRelayerRequest, MOCK_ACCESS_TOKEN},
+            DropOldestSender,
+        },
+        crossbeam_channel::{unbounded, RecvTimeoutError},
+        solana_gossip::contact_info::ContactInfo,
+        solana_perf::packet::Packet,
+        solana_sdk::{
+            hash::Hash,
+            signature::{Keypair, Signer},
+            system_transaction,
+        },
+        solana_streamer::socket::SocketAddrSpace,
+        solana_vote_program::vote_transaction,
+        std::thread::sleep,
+    };
+
+    /// Polls `condition` until it's true, returning false if `timeout` elapses first.
+    fn wait_for(condition: impl Fn() -> bool, timeout: Duration) -> bool {
+        let start = Instant::now();
+        while !condition() {
+            if start.elapsed() > timeout {
+                return false;
+            }
+            sleep(Duration::from_millis(10));
+        }
+        true
+    }
+
+    fn new_test_cluster_info() -> Arc<ClusterInfo> {
+        let keypair = Arc::new(Keypair::new());
+        let contact_info = ContactInfo::new_localhost(&keypair.pubkey(), 0);
+        Arc::new(ClusterInfo::new(
+            contact_info,
+            keypair,
+            SocketAddrSpace::Unspecified,
+        ))
+    }
+
+    #[test]
+    fn test_fail_closed_drops_packets_during_outage() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let (_heartbeat_tx, heartbeat_rx) = unbounded();
+        let (packet_intercept_tx, packet_intercept_rx) = unbounded();
+        let (packet_tx, packet_rx) = unbounded();
+        let (config_update_tx, config_update_rx) = unbounded();
+
+        let (relayer_config_update_tx, _relayer_config_update_rx) = unbounded();
+
+        let fetch_stage_manager = FetchStageManager::new(
+            new_test_cluster_info(),
+            heartbeat_rx,
+            packet_intercept_rx,
+            packet_tx,
+            HeartbeatLossPolicy::FailClosed,
+            true,
+            config_update_rx,
+            relayer_config_update_tx,
+            Arc::default(),
+            exit.clone(),
+        );
+
+        // no heartbeats are ever sent, so the relayer is considered down the entire time
+        for _ in 0..10 {
+            packet_intercept_tx
+                .send(PacketBatch::new(vec![Packet::default()]))
+                .unwrap();
+        }
+        assert!(matches!(
+            packet_rx.recv_timeout(HEARTBEAT_TIMEOUT * 2),
+            Err(RecvTimeoutError::Timeout)
+        ));
+
+        // flipping the policy at runtime lets local packets through again
+        config_update_tx
+            .send(MevConfigUpdate::HeartbeatLossPolicy(
+                HeartbeatLossPolicy::FailOpen,
+            ))
+            .unwrap();
+        sleep(Duration::from_millis(100));
+        packet_intercept_tx
+            .send(PacketBatch::new(vec![Packet::default()]))
+            .unwrap();
+        assert_eq!(
+            packet_rx
+                .recv_timeout(Duration::from_secs(1))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(packet_rx.try_recv().is_err());
+
+        exit.store(true, Ordering::Relaxed);
+        fetch_stage_manager.join().unwrap();
+    }
+
+    #[test]
+    fn test_relayer_address_update_keeps_packets_flowing() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let cluster_info = new_test_cluster_info();
+        let relayer_a = MockRelayer::start(Duration::from_millis(100));
+        let relayer_b = MockRelayer::start(Duration::from_millis(100));
+        relayer_b.set_tpu_addresses((
+            SocketAddr::from(([127, 0, 0, 1], 12_222)),
+            SocketAddr::from(([127, 0, 0, 1], 12_223)),
+        ));
+
+        let (heartbeat_tx, heartbeat_rx) = unbounded();
+        let (relayer_packet_tx, relayer_packet_rx) = unbounded();
+        let (verified_packet_tx, verified_packet_rx) = unbounded();
+        let (relayer_config_update_tx, relayer_config_update_rx) = unbounded();
+        let relayer_stage = RelayerStage::new(
+            RelayerConfig {
+                auth_service_endpoint: relayer_a.endpoint(),
+                backend_address: relayer_a.endpoint().into(),
+                expected_heartbeat_interval: Duration::from_millis(100),
+                oldest_allowed_heartbeat: Duration::from_millis(500),
+                trust_packets: false,
+                heartbeat_loss_policy: HeartbeatLossPolicy::FailOpen,
+                vote_bypass: true,
+                packet_channel_capacity: 10,
+                packet_dedup_capacity: 10,
+                packet_rate_limits: RelayerPacketRateLimits::default(),
+                heartbeats_to_recover: 1,
+                connection_config: RelayerConnectionConfig::default(),
+                circuit_breaker: RelayerCircuitBreakerConfig::default(),
+                resolver: Arc::new(SystemResolver),
+                max_consecutive_connect_failures: 50,
+                degraded_retry_interval: Duration::from_secs(60),
+                backoff_reset_grace_period: DEFAULT_BACKOFF_RESET_GRACE_PERIOD,
+                require_relayer: false,
+                packet_priority_percent: None,
+            },
+            cluster_info.clone(),
+            heartbeat_tx,
+            DropOldestSender::new(relayer_packet_tx, relayer_packet_rx.clone(), 10),
+            DropOldestSender::new(verified_packet_tx, verified_packet_rx, 10),
+            relayer_config_update_rx,
+            Arc::default(),
+            exit.clone(),
+        );
+
+        let (packet_intercept_tx, packet_intercept_rx) = unbounded();
+        let (packet_tx, packet_rx) = unbounded();
+        let (config_update_tx, config_update_rx) = unbounded();
+        let fetch_stage_manager = FetchStageManager {
+            t_hdl: FetchStageManager::start(
+                cluster_info.clone(),
+                heartbeat_rx,
+                packet_intercept_rx,
+                packet_tx,
+                HeartbeatLossPolicy::FailOpen,
+                true,
+                config_update_rx,
+                relayer_config_update_tx,
+                Arc::default(),
+                Duration::from_millis(200),
+                exit.clone(),
+            ),
+        };
+        let advertised = || {
+            let contact_info = cluster_info.my_contact_info();
+            (contact_info.tpu, contact_info.tpu_forwards)
+        };
+        let proto_packet_batch = |signature_bytes: &[u8]| jito_protos::proto::packet::PacketBatch {
+            packets: signature_bytes
+                .iter()
+                .map(|b| {
+                    let mut data = vec![1u8];
+                    data.extend_from_slice(&[*b; 64]);
+                    data.extend_from_slice(&[0u8; 100]);
+                    jito_protos::proto::packet::Packet {
+                        meta: Some(jito_protos::proto::packet::Meta {
+                            size: data.len() as u64,
+                            ..Default::default()
+                        }),
+                        data,
+                    }
+                })
+                .collect(),
+        };
+
+        // the auth loop takes a few seconds to hand out the first token
+        assert!(wait_for(
+            || advertised() == relayer_a.tpu_addresses(),
+            Duration::from_secs(30)
+        ));
+        relayer_a.send_packets(proto_packet_batch(&[1, 2]));
+        assert_eq!(
+            relayer_packet_rx
+                .recv_timeout(Duration::from_secs(1))
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // switch to relayer b, which hands out its own tokens
+        config_update_tx
+            .send(MevConfigUpdate::RelayerAddress(RelayerAddressUpdate {
+                backend_address: relayer_b.endpoint().into(),
+                auth_service_endpoint: Some(relayer_b.endpoint()),
+            }))
+            .unwrap();
+
+        // local packets keep flowing while the relayer is swapped out underneath
+        for _ in 0..10 {
+            packet_intercept_tx
+                .send(PacketBatch::new(vec![Packet::default()]))
+                .unwrap();
+        }
+        for _ in 0..10 {
+            packet_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        }
+
+        assert!(wait_for(
+            || advertised() == relayer_b.tpu_addresses(),
+            Duration::from_secs(30)
+        ));
+        relayer_b.send_packets(proto_packet_batch(&[3, 4]));
+        assert_eq!(
+            relayer_packet_rx
+                .recv_timeout(Duration::from_secs(1))
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // the token for relayer b came from relayer b's auth service
+        let requests = relayer_b.requests();
+        assert!(requests
+            .iter()
+            .any(|request| matches!(request, MockRelayerRequest::GenerateAuthTokens { .. })));
+        assert!(requests.contains(&MockRelayerRequest::SubscribePackets {
+            authorization: Some(format!("Bearer {}", MOCK_ACCESS_TOKEN)),
+        }));
+        assert_eq!(relayer_a.num_connections(), 1);
+
+        exit.store(true, Ordering::Relaxed);
+        relayer_stage.join().unwrap();
+        fetch_stage_manager.join().unwrap();
+    }
+
+    #[test]
+    fn test_degraded_relayer_overrides_fail_closed() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let (_heartbeat_tx, heartbeat_rx) = unbounded();
+        let (packet_intercept_tx, packet_intercept_rx) = unbounded();
+        let (packet_tx, packet_rx) = unbounded();
+        let (_config_update_tx, config_update_rx) = unbounded();
+        let (relayer_config_update_tx, _relayer_config_update_rx) = unbounded();
+        let mev_status = Arc::new(RwLock::new(MevStatus::default()));
+
+        let fetch_stage_manager = FetchStageManager::new(
+            new_test_cluster_info(),
+            heartbeat_rx,
+            packet_intercept_rx,
+            packet_tx,
+            HeartbeatLossPolicy::FailClosed,
+            true,
+            config_update_rx,
+            relayer_config_update_tx,
+            mev_status.clone(),
+            exit.clone(),
+        );
+
+        // the relayer stage exhausts its retry budget
+        mev_status.write().unwrap().relayer.degraded = true;
+        sleep(HEARTBEAT_TIMEOUT * 2);
+        for _ in 0..10 {
+            packet_intercept_tx
+                .send(PacketBatch::new(vec![Packet::default()]))
+                .unwrap();
+        }
+        for _ in 0..10 {
+            packet_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        }
+
+        // once the relayer connects again the policy applies
+        mev_status.write().unwrap().relayer.degraded = false;
+        sleep(HEARTBEAT_TIMEOUT * 2);
+        packet_intercept_tx
+            .send(PacketBatch::new(vec![Packet::default()]))
+            .unwrap();
+        assert!(matches!(
+            packet_rx.recv_timeout(HEARTBEAT_TIMEOUT),
+            Err(RecvTimeoutError::Timeout)
+        ));
+
+        exit.store(true, Ordering::Relaxed);
+        fetch_stage_manager.join().unwrap();
+    }
+
+    #[test]
+    fn test_vote_packets_bypass_relayer() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let (_heartbeat_tx, heartbeat_rx) = unbounded();
+        let (packet_intercept_tx, packet_intercept_rx) = unbounded();
+        let (packet_tx, packet_rx) = unbounded();
+        let (_config_update_tx, config_update_rx) = unbounded();
+        let (relayer_config_update_tx, _relayer_config_update_rx) = unbounded();
+
+        let fetch_stage_manager = FetchStageManager::new(
+            new_test_cluster_info(),
+            heartbeat_rx,
+            packet_intercept_rx,
+            packet_tx,
+            HeartbeatLossPolicy::FailClosed,
+            true,
+            config_update_rx,
+            relayer_config_update_tx,
+            Arc::default(),
+            exit.clone(),
+        );
+
+        let keypair = Keypair::new();
+        let vote_packet = || {
+            let vote_tx = vote_transaction::new_vote_transaction(
+                vec![1],
+                Hash::new_unique(),
+                Hash::new_unique(),
+                &keypair,
+                &keypair,
+                &keypair,
+                None,
+            );
+            Packet::from_data(None, vote_tx).unwrap()
+        };
+        let transfer_packet = || {
+            let transfer_tx = system_transaction::transfer(
+                &keypair,
+                &Keypair::new().pubkey(),
+                1,
+                Hash::new_unique(),
+            );
+            Packet::from_data(None, transfer_tx).unwrap()
+        };
+
+        // non-vote packets are dropped under FailClosed, votes are processed locally
+        packet_intercept_tx
+            .send(PacketBatch::new(vec![
+                transfer_packet(),
+                vote_packet(),
+                transfer_packet(),
+                vote_packet(),
+                transfer_packet(),
+            ]))
+            .unwrap();
+        let votes = packet_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(votes.len(), 2);
+        assert!(votes.iter().all(|packet| packet.meta.is_simple_vote_tx()));
+
+        packet_intercept_tx
+            .send(PacketBatch::new(vec![transfer_packet(), transfer_packet()]))
+            .unwrap();
+        assert!(matches!(
+            packet_rx.recv_timeout(HEARTBEAT_TIMEOUT),
+            Err(RecvTimeoutError::Timeout)
+        ));
+
+        exit.store(true, Ordering::Relaxed);
+        fetch_stage_manager.join().unwrap();
+    }
+
+    #[test]
+    fn test_tpu_advertisement_follows_relayer_heartbeats() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let cluster_info = new_test_cluster_info();
+        let fallback_contact_info = cluster_info.my_contact_info();
+        let mock_relayer = MockRelayer::start(Duration::from_millis(100));
+
+        let (heartbeat_tx, heartbeat_rx) = unbounded();
+        let (relayer_packet_tx, relayer_packet_rx) = unbounded();
+        let (verified_packet_tx, verified_packet_rx) = unbounded();
+        let (relayer_config_update_tx, relayer_config_update_rx) = unbounded();
+        let relayer_stage = RelayerStage::new(
+            RelayerConfig {
+                auth_service_endpoint: mock_relayer.endpoint(),
+                backend_address: mock_relayer.endpoint().into(),
+                expected_heartbeat_interval: Duration::from_millis(100),
+                oldest_allowed_heartbeat: Duration::from_millis(500),
+                trust_packets: false,
+                heartbeat_loss_policy: HeartbeatLossPolicy::FailOpen,
+                vote_bypass: true,
+                packet_channel_capacity: 10,
+                packet_dedup_capacity: 10,
+                packet_rate_limits: RelayerPacketRateLimits::default(),
+                heartbeats_to_recover: 1,
+                connection_config: RelayerConnectionConfig::default(),
+                circuit_breaker: RelayerCircuitBreakerConfig::default(),
+                resolver: Arc::new(SystemResolver),
+                max_consecutive_connect_failures: 50,
+                degraded_retry_interval: Duration::from_secs(60),
+                backoff_reset_grace_period: DEFAULT_BACKOFF_RESET_GRACE_PERIOD,
+                require_relayer: false,
+                packet_priority_percent: None,
+            },
+            cluster_info.clone(),
+            heartbeat_tx,
+            DropOldestSender::new(relayer_packet_tx, relayer_packet_rx, 10),
+            DropOldestSender::new(verified_packet_tx, verified_packet_rx, 10),
+            relayer_config_update_rx,
+            Arc::default(),
+            exit.clone(),
+        );
+
+        let (_packet_intercept_tx, packet_intercept_rx) = unbounded();
+        let (packet_tx, _packet_rx) = unbounded();
+        let (_config_update_tx, config_update_rx) = unbounded();
+        let fetch_stage_manager = FetchStageManager {
+            t_hdl: FetchStageManager::start(
+                cluster_info.clone(),
+                heartbeat_rx,
+                packet_intercept_rx,
+                packet_tx,
+                HeartbeatLossPolicy::FailOpen,
+                true,
+                config_update_rx,
+                relayer_config_update_tx,
+                Arc::default(),
+                Duration::from_millis(200),
+                exit.clone(),
+            ),
+        };
+        let advertised = || {
+            let contact_info = cluster_info.my_contact_info();
+            (contact_info.tpu, contact_info.tpu_forwards)
+        };
+
+        // the relayer's TPU ports are advertised once it has been heartbeating for a while; the
+        // auth loop takes a few seconds to hand out the first token
+        let relayer_tpu_addresses = mock_relayer.tpu_addresses();
+        assert!(wait_for(
+            || advertised() == relayer_tpu_addresses,
+            Duration::from_secs(30)
+        ));
+
+        // the relayer restarts on a different port
+        let new_relayer_tpu_addresses = (
+            SocketAddr::from(([127, 0, 0, 1], 12_222)),
+            SocketAddr::from(([127, 0, 0, 1], 12_223)),
+        );
+        mock_relayer.set_tpu_addresses(new_relayer_tpu_addresses);
+        mock_relayer.drop_connections();
+        assert!(wait_for(
+            || advertised() == new_relayer_tpu_addresses,
+            Duration::from_secs(5)
+        ));
+
+        // the validator's own ports are advertised again once heartbeats stop
+        mock_relayer.set_heartbeat_interval(None);
+        assert!(wait_for(
+            || advertised()
+                == (
+                    fallback_contact_info.tpu,
+                    fallback_contact_info.tpu_forwards
+                ),
+            HEARTBEAT_TIMEOUT * 3
+        ));
+
+        exit.store(true, Ordering::Relaxed);
+        relayer_stage.join().unwrap();
+        fetch_stage_manager.join().unwrap();
+    }
+}
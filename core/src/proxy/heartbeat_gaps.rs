@@ -0,0 +1,172 @@
+//! Tracks the gaps between relayer heartbeats.
+//!
+//! The relayer is only disconnected once its heartbeats stop entirely. Heartbeats arriving late
+//! usually come first, so the largest gap, the number of late gaps and the time since the last
+//! heartbeat are reported every metrics interval for alerting on a degrading relayer.
+
+use std::time::{Duration, Instant};
+
+/// Gaps longer than the expected interval times this ratio (as a fraction) count as late.
+const LATE_GAP_RATIO: (u32, u32) = (3, 2);
+
+/// Heartbeat gaps observed during one reporting interval.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct HeartbeatGapStats {
+    /// The interval the gaps were measured against.
+    pub(crate) expected_interval: Duration,
+    /// Longest time between two consecutive heartbeats.
+    pub(crate) max_gap: Duration,
+    /// Number of gaps longer than 1.5x the expected interval.
+    pub(crate) num_late_gaps: u64,
+    /// Time since the last heartbeat, or since the stage started if none was received yet.
+    pub(crate) time_since_last_heartbeat: Duration,
+}
+
+impl HeartbeatGapStats {
+    pub(crate) fn report(&self) {
+        datapoint_info!(
+            "relayer_stage-heartbeat",
+            (
+                "expected_interval_ms",
+                self.expected_interval.as_millis() as i64,
+                i64
+            ),
+            ("max_gap_ms", self.max_gap.as_millis() as i64, i64),
+            ("num_late_gaps", self.num_late_gaps, i64),
+            (
+                "time_since_last_heartbeat_ms",
+                self.time_since_last_heartbeat.as_millis() as i64,
+                i64
+            ),
+        );
+    }
+}
+
+pub(crate) struct HeartbeatGapTracker {
+    started_at: Instant,
+    // used until the relayer advertises the interval it sends heartbeats at
+    fallback_interval: Duration,
+    advertised_interval: Option<Duration>,
+    last_heartbeat: Option<Instant>,
+    max_gap: Duration,
+    num_late_gaps: u64,
+}
+
+impl HeartbeatGapTracker {
+    pub(crate) fn new(fallback_interval: Duration, now: Instant) -> Self {
+        Self {
+            started_at: now,
+            fallback_interval,
+            advertised_interval: None,
+            last_heartbeat: None,
+            max_gap: Duration::ZERO,
+            num_late_gaps: 0,
+        }
+    }
+
+    pub(crate) fn expected_interval(&self) -> Duration {
+        self.advertised_interval.unwrap_or(self.fallback_interval)
+    }
+
+    /// Records a heartbeat received at `now`. `advertised_interval` is the interval the relayer
+    /// says it sends heartbeats at, if the heartbeat carries one.
+    pub(crate) fn on_heartbeat(&mut self, now: Instant, advertised_interval: Option<Duration>) {
+        if let Some(interval) = advertised_interval.filter(|interval| !interval.is_zero()) {
+            self.advertised_interval = Some(interval);
+        }
+        if let Some(last_heartbeat) = self.last_heartbeat {
+            let gap = now.saturating_duration_since(last_heartbeat);
+            self.max_gap = self.max_gap.max(gap);
+            let (numerator, denominator) = LATE_GAP_RATIO;
+            if gap * denominator > self.expected_interval() * numerator {
+                self.num_late_gaps += 1;
+            }
+        }
+        self.last_heartbeat = Some(now);
+    }
+
+    /// Returns the gaps since the last call and starts a new reporting interval.
+    pub(crate) fn take_stats(&mut self, now: Instant) -> HeartbeatGapStats {
+        let stats = HeartbeatGapStats {
+            expected_interval: self.expected_interval(),
+            max_gap: self.max_gap,
+            num_late_gaps: self.num_late_gaps,
+            time_since_last_heartbeat: now
+                .saturating_duration_since(self.last_heartbeat.unwrap_or(self.started_at)),
+        };
+        self.max_gap = Duration::ZERO;
+        self.num_late_gaps = 0;
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(500);
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_heartbeat_gap_stats() {
+        let start = Instant::now();
+        let mut tracker = HeartbeatGapTracker::new(INTERVAL, start);
+
+        // nothing received yet
+        assert_eq!(
+            tracker.take_stats(start + ms(2_000)),
+            HeartbeatGapStats {
+                expected_interval: INTERVAL,
+                max_gap: Duration::ZERO,
+                num_late_gaps: 0,
+                time_since_last_heartbeat: ms(2_000),
+            }
+        );
+
+        // a gap of exactly 1.5x the interval isn't late
+        for offset in [1_000, 1_500, 2_250, 3_200, 3_700] {
+            tracker.on_heartbeat(start + ms(offset), None);
+        }
+        assert_eq!(
+            tracker.take_stats(start + ms(4_000)),
+            HeartbeatGapStats {
+                expected_interval: INTERVAL,
+                max_gap: ms(950),
+                num_late_gaps: 1,
+                time_since_last_heartbeat: ms(300),
+            }
+        );
+
+        // the next interval starts over, measuring from the last heartbeat of the previous one
+        tracker.on_heartbeat(start + ms(4_200), None);
+        assert_eq!(
+            tracker.take_stats(start + ms(5_000)),
+            HeartbeatGapStats {
+                expected_interval: INTERVAL,
+                max_gap: ms(500),
+                num_late_gaps: 0,
+                time_since_last_heartbeat: ms(800),
+            }
+        );
+    }
+
+    #[test]
+    fn test_heartbeat_gap_advertised_interval() {
+        let start = Instant::now();
+        let mut tracker = HeartbeatGapTracker::new(INTERVAL, start);
+        tracker.on_heartbeat(start, Some(ms(1_000)));
+        // late against the fallback but not against the advertised interval
+        tracker.on_heartbeat(start + ms(1_200), None);
+        // an unset interval keeps the advertised one
+        tracker.on_heartbeat(start + ms(2_800), Some(Duration::ZERO));
+
+        let stats = tracker.take_stats(start + ms(2_800));
+        assert_eq!(stats.expected_interval, ms(1_000));
+        assert_eq!(stats.max_gap, ms(1_600));
+        assert_eq!(stats.num_late_gaps, 1);
+        assert_eq!(stats.time_since_last_heartbeat, Duration::ZERO);
+    }
+}
@@ -0,0 +1,270 @@
+//! Tunnels the relayer connection through an HTTP proxy.
+//!
+//! Some validators can only reach the internet through an HTTP proxy. The connector asks the
+//! proxy for a tunnel to the relayer with a CONNECT request and hands the tunneled stream to
+//! tonic, which runs TLS and HTTP/2 over it the same way it would over a direct connection. The
+//! relayer hostname is resolved by the proxy.
+
+use {
+    crate::proxy::{ProxyError, Result},
+    std::{error::Error, fmt, io},
+    thiserror::Error,
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    },
+    tonic::codegen::{http::Uri, Context, Future, Pin, Poll, Service},
+};
+
+/// Max size of the proxy's response to the CONNECT request.
+const MAX_CONNECT_RESPONSE_SIZE: usize = 8 * 1024;
+
+/// Basic auth credentials for the proxy.
+#[derive(Clone, PartialEq, Eq)]
+pub struct HttpProxyCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for HttpProxyCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HttpProxyCredentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// HTTP proxy the relayer connection is tunneled through.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpProxyConfig {
+    /// `http://host:port` of the proxy.
+    pub proxy_url: Uri,
+    pub credentials: Option<HttpProxyCredentials>,
+}
+
+impl HttpProxyConfig {
+    /// Parses an `http://host:port` proxy url and optional `username:password` credentials.
+    pub fn parse(proxy_url: &str, credentials: Option<&str>) -> Result<Self> {
+        let invalid =
+            |e: &dyn fmt::Display| ProxyError::InvalidHttpProxy(format!("{}: {}", proxy_url, e));
+        let proxy_url: Uri = proxy_url.parse().map_err(|e| invalid(&e))?;
+        if proxy_url.scheme_str() != Some("http") {
+            return Err(invalid(&"expected an http:// proxy url"));
+        }
+        if proxy_url.host().is_none() {
+            return Err(invalid(&"missing proxy host"));
+        }
+
+        let credentials = credentials
+            .map(|credentials| {
+                let (username, password) = credentials.split_once(':').ok_or_else(|| {
+                    ProxyError::InvalidHttpProxy(
+                        "credentials must be formatted as username:password".to_string(),
+                    )
+                })?;
+                Ok(HttpProxyCredentials {
+                    username: username.to_string(),
+                    password: password.to_string(),
+                })
+            })
+            .transpose()?;
+        Ok(Self {
+            proxy_url,
+            credentials,
+        })
+    }
+
+    fn proxy_authority(&self) -> (String, u16) {
+        let host = self
+            .proxy_url
+            .host()
+            .unwrap_or_default()
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        (host, self.proxy_url.port_u16().unwrap_or(80))
+    }
+
+    /// Opens a tunnel to the host and port of `target` through the proxy.
+    async fn connect(
+        &self,
+        target: &Uri,
+        tcp_nodelay: bool,
+    ) -> std::result::Result<TcpStream, HttpProxyConnectError> {
+        let target_authority = target_authority(target)?;
+        let (proxy_host, proxy_port) = self.proxy_authority();
+        let mut stream = TcpStream::connect((proxy_host.as_str(), proxy_port))
+            .await
+            .map_err(|e| {
+                HttpProxyConnectError(format!(
+                    "failed to connect to proxy {}: {}",
+                    self.proxy_url, e
+                ))
+            })?;
+        stream
+            .set_nodelay(tcp_nodelay)
+            .map_err(|e| HttpProxyConnectError(e.to_string()))?;
+
+        let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", target_authority);
+        if let Some(HttpProxyCredentials { username, password }) = &self.credentials {
+            request.push_str(&format!(
+                "Proxy-Authorization: Basic {}\r\n",
+                base64::encode(format!("{}:{}", username, password))
+            ));
+        }
+        request.push_str("\r\n");
+
+        let io_error = |e: io::Error| {
+            HttpProxyConnectError(format!("proxy {} CONNECT failed: {}", self.proxy_url, e))
+        };
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(io_error)?;
+        let response = read_connect_response(&mut stream).await.map_err(io_error)?;
+
+        let status_line = response.lines().next().unwrap_or_default();
+        match status_line.split_whitespace().nth(1) {
+            Some(status) if status.starts_with('2') => Ok(stream),
+            Some("407") => Err(HttpProxyConnectError(format!(
+                "proxy {} requires authentication: {}",
+                self.proxy_url, status_line
+            ))),
+            _ => Err(HttpProxyConnectError(format!(
+                "proxy {} refused to tunnel to {}: {}",
+                self.proxy_url, target_authority, status_line
+            ))),
+        }
+    }
+}
+
+/// The proxy couldn't be reached or wouldn't open a tunnel to the relayer.
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub(crate) struct HttpProxyConnectError(String);
+
+impl HttpProxyConnectError {
+    /// Finds the proxy error in the source chain of a connection error.
+    pub(crate) fn find(error: &(dyn Error + 'static)) -> Option<&Self> {
+        let mut source = Some(error);
+        while let Some(error) = source {
+            if let Some(proxy_error) = error.downcast_ref::<Self>() {
+                return Some(proxy_error);
+            }
+            source = error.source();
+        }
+        None
+    }
+}
+
+/// `host:port` to tunnel to, with the default port for the scheme if the uri has none.
+fn target_authority(target: &Uri) -> std::result::Result<String, HttpProxyConnectError> {
+    let host = target
+        .host()
+        .ok_or_else(|| HttpProxyConnectError(format!("{} has no host", target)))?;
+    let port = target
+        .port_u16()
+        .unwrap_or(if target.scheme_str() == Some("https") {
+            443
+        } else {
+            80
+        });
+    Ok(format!("{}:{}", host, port))
+}
+
+/// Reads the response head one byte at a time so nothing sent through the tunnel afterwards is
+/// consumed.
+async fn read_connect_response(stream: &mut TcpStream) -> io::Result<String> {
+    let mut response = Vec::with_capacity(128);
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_CONNECT_RESPONSE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "response too large",
+            ));
+        }
+        let byte = stream.read_u8().await?;
+        response.push(byte);
+    }
+    Ok(String::from_utf8_lossy(&response).into_owned())
+}
+
+/// Connects to the endpoint through an HTTP proxy. The endpoint uri is left untouched so the
+/// hostname is still used for TLS and the HTTP/2 authority.
+#[derive(Clone, Debug)]
+pub(crate) struct HttpProxyConnector {
+    pub(crate) proxy: HttpProxyConfig,
+    pub(crate) tcp_nodelay: bool,
+}
+
+impl Service<Uri> for HttpProxyConnector {
+    type Response = TcpStream;
+    type Error = HttpProxyConnectError;
+    type Future =
+        Pin<Box<dyn Future<Output = std::result::Result<TcpStream, HttpProxyConnectError>> + Send>>;
+
+    fn poll_ready(
+        &mut self,
+        _cx: &mut Context<'_>,
+    ) -> Poll<std::result::Result<(), HttpProxyConnectError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let proxy = self.proxy.clone();
+        let tcp_nodelay = self.tcp_nodelay;
+        Box::pin(async move { proxy.connect(&uri, tcp_nodelay).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_http_proxy() {
+        let proxy = HttpProxyConfig::parse("http://proxy.example.com:3128", None).unwrap();
+        assert_eq!(
+            proxy.proxy_authority(),
+            ("proxy.example.com".to_string(), 3128)
+        );
+        assert_eq!(proxy.credentials, None);
+
+        let proxy = HttpProxyConfig::parse("http://[::1]", Some("validator:pass:word")).unwrap();
+        assert_eq!(proxy.proxy_authority(), ("::1".to_string(), 80));
+        assert_eq!(
+            proxy.credentials,
+            Some(HttpProxyCredentials {
+                username: "validator".to_string(),
+                password: "pass:word".to_string(),
+            })
+        );
+        assert!(!format!("{:?}", proxy).contains("pass:word"));
+
+        for (proxy_url, credentials) in [
+            ("https://proxy.example.com:3128", None),
+            ("proxy.example.com:3128", None),
+            ("http://proxy.example.com:3128", Some("no-password")),
+        ] {
+            assert!(matches!(
+                HttpProxyConfig::parse(proxy_url, credentials),
+                Err(ProxyError::InvalidHttpProxy(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_target_authority() {
+        let authority = |uri: &'static str| target_authority(&Uri::from_static(uri)).unwrap();
+        assert_eq!(
+            authority("https://relayer.example.com"),
+            "relayer.example.com:443"
+        );
+        assert_eq!(
+            authority("http://relayer.example.com:11226"),
+            "relayer.example.com:11226"
+        );
+        assert_eq!(authority("http://[::1]:11226"), "[::1]:11226");
+    }
+}
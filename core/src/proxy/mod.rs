@@ -12,21 +12,289 @@
 
 mod auth;
 pub mod block_engine_stage;
+pub mod circuit_breaker;
+pub mod disconnect_history;
 pub mod fetch_stage_manager;
+mod heartbeat_gaps;
+pub mod http_proxy;
+pub mod rate_limiter;
+pub mod relayer_health;
+pub mod relayer_resolver;
 pub mod relayer_stage;
+pub mod retry_budget;
+#[cfg(test)]
+pub mod test_utils;
 
 use {
+    crate::{
+        proxy::{
+            disconnect_history::DisconnectHistory, fetch_stage_manager::HeartbeatLossPolicy,
+            rate_limiter::RelayerPacketRateLimits, relayer_stage::RelayerAddressUpdate,
+        },
+        tip_manager::TipManagerError,
+    },
+    crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender, TryRecvError, TrySendError},
     std::{
+        collections::VecDeque,
+        future::Future,
         net::{AddrParseError, SocketAddr},
         result,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread::{self, Builder, JoinHandle},
+        time::Duration,
     },
     thiserror::Error,
     tonic::Status,
 };
 
 type Result<T> = result::Result<T, ProxyError>;
+
+/// Deadline for unary calls and stream establishment against the relayer, block engine and auth
+/// service, so a hung server can't stall the proxy stage threads.
+pub const DEFAULT_RPC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the proxy stage loops check the exit flag while waiting on something else, so
+/// shutdown isn't held up by a quiet stream or a long sleep.
+const EXIT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Sleeps for `duration`, waking up early once `exit` is set.
+async fn sleep_unless_exit(duration: Duration, exit: &AtomicBool) {
+    let deadline = tokio::time::Instant::now() + duration;
+    while !exit.load(Ordering::Relaxed) {
+        let now = tokio::time::Instant::now();
+        if now >= deadline {
+            return;
+        }
+        tokio::time::sleep(EXIT_CHECK_INTERVAL.min(deadline - now)).await;
+    }
+}
+
+/// Completes once `exit` is set.
+async fn wait_for_exit(exit: &AtomicBool) {
+    while !exit.load(Ordering::Relaxed) {
+        tokio::time::sleep(EXIT_CHECK_INTERVAL).await;
+    }
+}
+
+/// Awaits a gRPC call, failing with [`ProxyError::RpcTimeout`] if it takes longer than `timeout`.
+async fn with_rpc_timeout<T>(
+    rpc_name: &'static str,
+    timeout: Duration,
+    rpc: impl Future<Output = result::Result<T, Status>>,
+) -> Result<T> {
+    tokio::time::timeout(timeout, rpc)
+        .await
+        .map_err(|_| ProxyError::RpcTimeout(rpc_name))?
+        .map_err(ProxyError::from)
+}
 type HeartbeatEvent = (SocketAddr, SocketAddr);
 
+/// Configuration changes that can be applied to the proxy stages while the validator is running.
+#[derive(Clone, Debug)]
+pub enum MevConfigUpdate {
+    /// Changes how packets from the local TPU ports are handled while the relayer is unhealthy.
+    HeartbeatLossPolicy(HeartbeatLossPolicy),
+    /// Tears down the current relayer connection and reconnects to the given address.
+    RelayerAddress(RelayerAddressUpdate),
+    /// Changes the rate limits applied to packets received from the relayer.
+    RelayerPacketRateLimits(RelayerPacketRateLimits),
+}
+
+/// Status of the relayer connection.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RelayerStatus {
+    pub connected: bool,
+    pub last_heartbeat_age_ms: Option<u64>,
+    pub active_endpoint: Option<String>,
+    pub packets_forwarded_last_minute: u64,
+    /// Set once the relayer exhausts its connection retry budget. The validator processes packets
+    /// from its own TPU ports until the relayer connects again.
+    pub degraded: bool,
+    /// Why the most recent relayer connections ended.
+    pub recent_disconnects: DisconnectHistory,
+    /// Set while the relayer is kept disconnected after sending too many malformed packets.
+    pub circuit_breaker_open: bool,
+    pub num_circuit_breaker_trips: u64,
+}
+
+/// Health of one of the block engine streams.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockEngineStreamStatus {
+    pub connected: bool,
+    /// Failed connection attempts and stream errors since the stream was last subscribed.
+    pub consecutive_failures: usize,
+}
+
+/// Status of the block engine connections. The packet and bundle streams connect independently,
+/// so one can be down while the other keeps working.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockEngineStatus {
+    pub packet_stream: BlockEngineStreamStatus,
+    pub bundle_stream: BlockEngineStreamStatus,
+    pub active_endpoint: Option<String>,
+    pub bundles_received_last_minute: u64,
+}
+
+impl BlockEngineStatus {
+    /// Whether both the packet and bundle streams are up.
+    pub fn connected(&self) -> bool {
+        self.packet_stream.connected && self.bundle_stream.connected
+    }
+}
+
+/// Snapshot of the proxy connections used for health checks. The proxy stages refresh it at
+/// heartbeat or metrics cadence, never per packet.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MevStatus {
+    pub relayer: RelayerStatus,
+    pub block_engine: BlockEngineStatus,
+    /// Set when the tip payment program failed validation at startup.
+    pub tip_program_error: Option<TipManagerError>,
+}
+
+/// Sums counts recorded once per second over the last minute.
+#[derive(Default)]
+struct LastMinuteCounter {
+    counts: VecDeque<u64>,
+    total: u64,
+}
+
+impl LastMinuteCounter {
+    const NUM_SECONDS: usize = 60;
+
+    /// Records the count for the last second and returns the total over the last minute.
+    fn record(&mut self, count: u64) -> u64 {
+        if self.counts.len() == Self::NUM_SECONDS {
+            let oldest = self.counts.pop_front().unwrap_or_default();
+            self.total = self.total.saturating_sub(oldest);
+        }
+        self.counts.push_back(count);
+        self.total = self.total.saturating_add(count);
+        self.total
+    }
+}
+
+/// Default max number of packet batches a proxy stage lets queue up for sigverify or banking stage.
+pub const DEFAULT_PACKET_CHANNEL_CAPACITY: usize = 10_000;
+
+/// Default max number of bundle messages the block engine stage lets queue up for BundleStage.
+pub const DEFAULT_BUNDLE_CHANNEL_CAPACITY: usize = 1_000;
+
+/// Sending half of a channel that holds at most `capacity` items. Instead of blocking the gRPC
+/// stream when downstream stalls, the oldest queued items are evicted to make room. The channel
+/// must belong to the proxy stage alone, see [`DropOldestForwarder`], or other producers' items
+/// get evicted too.
+#[derive(Clone)]
+pub struct DropOldestSender<T> {
+    sender: Sender<T>,
+    // Used only to evict the oldest item in the channel.
+    receiver: Receiver<T>,
+    capacity: usize,
+}
+
+impl<T> DropOldestSender<T> {
+    pub fn new(sender: Sender<T>, receiver: Receiver<T>, capacity: usize) -> Self {
+        Self {
+            sender,
+            receiver,
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Returns the number of items evicted to make room for `item`.
+    pub fn send(&self, item: T) -> Result<usize> {
+        let mut num_dropped = 0;
+        while self.sender.len() >= self.capacity {
+            match self.receiver.try_recv() {
+                Ok(_) => num_dropped += 1,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(ProxyError::PacketForwardError),
+            }
+        }
+        // another sender can fill a bounded channel between the check above and this send
+        let mut item = item;
+        loop {
+            match self.sender.try_send(item) {
+                Ok(()) => return Ok(num_dropped),
+                Err(TrySendError::Full(returned)) => {
+                    item = returned;
+                    match self.receiver.try_recv() {
+                        Ok(_) => num_dropped += 1,
+                        Err(TryRecvError::Empty) => {}
+                        Err(TryRecvError::Disconnected) => {
+                            return Err(ProxyError::PacketForwardError)
+                        }
+                    }
+                }
+                Err(TrySendError::Disconnected(_)) => return Err(ProxyError::PacketForwardError),
+            }
+        }
+    }
+}
+
+/// Moves items from a proxy stage's own bounded channel to a channel it shares with other
+/// producers, such as FetchStage's packets or TransactionSigVerifier's verified packets.
+///
+/// Items are only moved while the shared channel holds fewer than `capacity` items. When its
+/// consumer stalls, the proxy stage's items back up in its own channel, where
+/// [`DropOldestSender`] evicts them, and the other producers' items are never dropped.
+pub struct DropOldestForwarder {
+    t_hdl: JoinHandle<()>,
+}
+
+impl DropOldestForwarder {
+    /// How long to wait for the shared channel to drain below capacity before checking again.
+    const DOWNSTREAM_FULL_BACKOFF: Duration = Duration::from_millis(1);
+
+    /// Returns the sender for the proxy stage and the forwarder moving what it sends to
+    /// `downstream`.
+    pub fn new<T: Send + 'static>(
+        name: &str,
+        downstream: Sender<T>,
+        capacity: usize,
+        exit: Arc<AtomicBool>,
+    ) -> (DropOldestSender<T>, Self) {
+        let capacity = capacity.max(1);
+        let (sender, receiver) = bounded(capacity);
+        let drop_oldest_sender = DropOldestSender::new(sender, receiver.clone(), capacity);
+        let t_hdl = Builder::new()
+            .name(name.to_string())
+            .spawn(move || Self::forward(receiver, downstream, capacity, &exit))
+            .unwrap();
+        (drop_oldest_sender, Self { t_hdl })
+    }
+
+    fn forward<T>(
+        receiver: Receiver<T>,
+        downstream: Sender<T>,
+        capacity: usize,
+        exit: &AtomicBool,
+    ) {
+        while !exit.load(Ordering::Relaxed) {
+            if downstream.len() >= capacity {
+                thread::sleep(Self::DOWNSTREAM_FULL_BACKOFF);
+                continue;
+            }
+            match receiver.recv_timeout(EXIT_CHECK_INTERVAL) {
+                Ok(item) => {
+                    if downstream.send(item).is_err() {
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        self.t_hdl.join()
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ProxyError {
     #[error("grpc error: {0}")]
@@ -35,6 +303,27 @@ pub enum ProxyError {
     #[error("stream disconnected")]
     GrpcStreamDisconnected,
 
+    #[error("connection error: {0}")]
+    ConnectionError(#[from] tonic::transport::Error),
+
+    #[error("connection timed out")]
+    ConnectionTimeout,
+
+    #[error("{0} timed out")]
+    RpcTimeout(&'static str),
+
+    #[error("failed to resolve relayer address: {0}")]
+    ResolveError(String),
+
+    #[error("invalid relayer address: {0}")]
+    InvalidRelayerAddress(String),
+
+    #[error("invalid http proxy: {0}")]
+    InvalidHttpProxy(String),
+
+    #[error("http proxy error: {0}")]
+    HttpProxyConnectError(String),
+
     #[error("heartbeat error")]
     HeartbeatChannelError,
 
@@ -52,4 +341,103 @@ pub enum ProxyError {
 
     #[error("invalid gRPC data: {0:?}")]
     InvalidData(String),
+
+    #[error("too many malformed packets")]
+    MalformedPackets,
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crossbeam_channel::unbounded};
+
+    #[test]
+    fn test_last_minute_counter() {
+        let mut counter = LastMinuteCounter::default();
+        for _ in 0..LastMinuteCounter::NUM_SECONDS {
+            counter.record(10);
+        }
+        assert_eq!(counter.record(0), 590);
+
+        // counts older than a minute age out
+        for _ in 0..LastMinuteCounter::NUM_SECONDS {
+            counter.record(1);
+        }
+        assert_eq!(counter.record(1), 60);
+    }
+
+    #[test]
+    fn test_drop_oldest_sender_bounds_stalled_channel() {
+        let (sender, receiver) = unbounded();
+        let drop_oldest_sender = DropOldestSender::new(sender, receiver.clone(), 10);
+
+        // nobody reads from the receiver, the channel never grows past capacity
+        let num_dropped: usize = (0..100).map(|i| drop_oldest_sender.send(i).unwrap()).sum();
+        assert_eq!(num_dropped, 90);
+        assert_eq!(receiver.len(), 10);
+
+        // only the newest items are kept
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            (90..100).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_drop_oldest_forwarder_only_drops_proxy_items() {
+        let exit = Arc::new(AtomicBool::new(false));
+        // shared with a local producer, nobody reads from it
+        let (local_sender, receiver) = unbounded();
+        for i in 0..50 {
+            local_sender.send(i).unwrap();
+        }
+        let (proxy_sender, forwarder) =
+            DropOldestForwarder::new("test-forwarder", local_sender, 10, exit);
+        let num_dropped: usize = (1000..1100).map(|i| proxy_sender.send(i).unwrap()).sum();
+        assert_eq!(num_dropped, 90);
+        std::thread::sleep(Duration::from_millis(100));
+
+        // every local item is still queued, the proxy's newest items wait behind them
+        assert_eq!(receiver.len(), 50);
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            (0..50).collect::<Vec<_>>()
+        );
+        let mut forwarded = vec![];
+        while forwarded.len() < 10 {
+            forwarded.push(receiver.recv_timeout(Duration::from_secs(5)).unwrap());
+        }
+        assert_eq!(forwarded, (1090..1100).collect::<Vec<_>>());
+
+        // the forwarder exits once the proxy stage drops its sender
+        drop(proxy_sender);
+        forwarder.join().unwrap();
+    }
+
+    #[test]
+    fn test_with_rpc_timeout() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            // a server that takes longer than the deadline to respond
+            let slow_rpc = async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok::<_, Status>(())
+            };
+            let start = std::time::Instant::now();
+            let result = with_rpc_timeout("slow_rpc", Duration::from_millis(100), slow_rpc).await;
+            assert!(matches!(result, Err(ProxyError::RpcTimeout("slow_rpc"))));
+            assert!(start.elapsed() < Duration::from_secs(5));
+
+            let result = with_rpc_timeout("fast_rpc", Duration::from_millis(100), async {
+                Ok::<_, Status>(1)
+            })
+            .await;
+            assert!(matches!(result, Ok(1)));
+
+            let result = with_rpc_timeout("failed_rpc", Duration::from_millis(100), async {
+                Err::<(), _>(Status::unavailable("refused"))
+            })
+            .await;
+            assert!(matches!(result, Err(ProxyError::GrpcError(_))));
+        });
+    }
 }
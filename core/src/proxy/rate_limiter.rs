@@ -0,0 +1,155 @@
+//! Token-bucket rate limiting for packets streamed from the relayer.
+//!
+//! Relayer packets bypass the QUIC per-connection limits, so without this a misbehaving relayer
+//! could push packets faster than sigverify can shed them.
+
+use std::time::Instant;
+
+/// Rate limits applied to packets received from the relayer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RelayerPacketRateLimits {
+    /// Sustained rate of non-vote packets accepted.
+    pub packets_per_second: u64,
+    /// Max number of non-vote packets accepted in a burst.
+    pub packet_burst: u64,
+    /// Sustained rate of packets flagged as simple vote transactions accepted.
+    pub vote_packets_per_second: u64,
+    /// Max number of simple vote packets accepted in a burst.
+    pub vote_packet_burst: u64,
+}
+
+impl Default for RelayerPacketRateLimits {
+    fn default() -> Self {
+        Self {
+            packets_per_second: 100_000,
+            packet_burst: 20_000,
+            vote_packets_per_second: 200_000,
+            vote_packet_burst: 40_000,
+        }
+    }
+}
+
+struct TokenBucket {
+    tokens_per_second: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(tokens_per_second: u64, capacity: u64, now: Instant) -> Self {
+        Self {
+            tokens_per_second: tokens_per_second as f64,
+            capacity: capacity as f64,
+            tokens: capacity as f64,
+            last_refill: now,
+        }
+    }
+
+    fn set_limits(&mut self, tokens_per_second: u64, capacity: u64) {
+        self.tokens_per_second = tokens_per_second as f64;
+        self.capacity = capacity as f64;
+        self.tokens = self.tokens.min(self.capacity);
+    }
+
+    fn try_consume(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.tokens_per_second).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Keeps separate buckets for vote and non-vote packets so a flood of one can't starve the other.
+pub(crate) struct RelayerPacketRateLimiter {
+    packets: TokenBucket,
+    vote_packets: TokenBucket,
+}
+
+impl RelayerPacketRateLimiter {
+    pub(crate) fn new(limits: RelayerPacketRateLimits) -> Self {
+        let now = Instant::now();
+        Self {
+            packets: TokenBucket::new(limits.packets_per_second, limits.packet_burst, now),
+            vote_packets: TokenBucket::new(
+                limits.vote_packets_per_second,
+                limits.vote_packet_burst,
+                now,
+            ),
+        }
+    }
+
+    pub(crate) fn set_limits(&mut self, limits: RelayerPacketRateLimits) {
+        self.packets
+            .set_limits(limits.packets_per_second, limits.packet_burst);
+        self.vote_packets
+            .set_limits(limits.vote_packets_per_second, limits.vote_packet_burst);
+    }
+
+    /// Returns true if the packet fits within the rate limit and should be forwarded.
+    pub(crate) fn try_accept(&mut self, is_simple_vote: bool, now: Instant) -> bool {
+        if is_simple_vote {
+            self.vote_packets.try_consume(now)
+        } else {
+            self.packets.try_consume(now)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::time::Duration};
+
+    #[test]
+    fn test_rate_limiter_caps_flood() {
+        let limits = RelayerPacketRateLimits {
+            packets_per_second: 1_000,
+            packet_burst: 100,
+            vote_packets_per_second: 2_000,
+            vote_packet_burst: 200,
+        };
+        let mut rate_limiter = RelayerPacketRateLimiter::new(limits);
+        let start = Instant::now();
+
+        // ten seconds of traffic at 10x the limit, one packet every 100us
+        let num_forwarded = (0..100_000u32)
+            .filter(|i| {
+                rate_limiter.try_accept(false, start + Duration::from_micros(*i as u64 * 100))
+            })
+            .count();
+
+        // the initial burst plus one second worth of tokens per second
+        assert!(num_forwarded <= 100 + 10 * 1_000);
+        assert!(num_forwarded >= 10 * 1_000);
+
+        // vote packets have their own bucket that wasn't touched by the flood
+        let now = start + Duration::from_secs(10);
+        let num_votes_forwarded = (0..1_000)
+            .filter(|_| rate_limiter.try_accept(true, now))
+            .count();
+        assert_eq!(num_votes_forwarded, 200);
+    }
+
+    #[test]
+    fn test_rate_limiter_set_limits() {
+        let mut rate_limiter = RelayerPacketRateLimiter::new(RelayerPacketRateLimits::default());
+        let now = Instant::now();
+
+        rate_limiter.set_limits(RelayerPacketRateLimits {
+            packets_per_second: 10,
+            packet_burst: 5,
+            ..RelayerPacketRateLimits::default()
+        });
+        let num_forwarded = (0..100)
+            .filter(|_| rate_limiter.try_accept(false, now))
+            .count();
+        assert_eq!(num_forwarded, 5);
+    }
+}
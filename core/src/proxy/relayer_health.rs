@@ -0,0 +1,169 @@
+//! Tracks whether the relayer is healthy based on the heartbeats it sends.
+//!
+//! Packets from an unhealthy relayer are dropped. Once heartbeats stop for longer than the
+//! timeout the relayer is unhealthy until it sends enough consecutive heartbeats again, which
+//! keeps a flapping relayer from injecting stale packets.
+
+use std::time::{Duration, Instant};
+
+/// Default number of consecutive heartbeats needed before packets from the relayer are accepted.
+pub const DEFAULT_HEARTBEATS_TO_RECOVER: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HealthState {
+    Healthy,
+    Unhealthy { consecutive_heartbeats: usize },
+}
+
+pub(crate) struct RelayerHealth {
+    state: HealthState,
+    last_heartbeat: Option<Instant>,
+    // Heartbeats further apart than this break the streak and mark the relayer unhealthy.
+    heartbeat_timeout: Duration,
+    heartbeats_to_recover: usize,
+}
+
+impl RelayerHealth {
+    /// Starts out unhealthy; the relayer has to prove itself with heartbeats first.
+    pub(crate) fn new(heartbeat_timeout: Duration, heartbeats_to_recover: usize) -> Self {
+        Self {
+            state: HealthState::Unhealthy {
+                consecutive_heartbeats: 0,
+            },
+            last_heartbeat: None,
+            heartbeat_timeout,
+            heartbeats_to_recover: heartbeats_to_recover.max(1),
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.state = HealthState::Unhealthy {
+            consecutive_heartbeats: 0,
+        };
+        self.last_heartbeat = None;
+    }
+
+    pub(crate) fn on_heartbeat(&mut self, now: Instant) {
+        let on_time = self.is_last_heartbeat_recent(now);
+        self.last_heartbeat = Some(now);
+
+        self.state = match self.state {
+            HealthState::Healthy if on_time => HealthState::Healthy,
+            HealthState::Unhealthy {
+                consecutive_heartbeats,
+            } if on_time => HealthState::Unhealthy {
+                consecutive_heartbeats: consecutive_heartbeats.saturating_add(1),
+            },
+            _ => HealthState::Unhealthy {
+                consecutive_heartbeats: 1,
+            },
+        };
+        if let HealthState::Unhealthy {
+            consecutive_heartbeats,
+        } = self.state
+        {
+            if consecutive_heartbeats >= self.heartbeats_to_recover {
+                info!(
+                    "relayer healthy after {} heartbeats",
+                    consecutive_heartbeats
+                );
+                self.state = HealthState::Healthy;
+            }
+        }
+    }
+
+    pub(crate) fn is_healthy(&mut self, now: Instant) -> bool {
+        if self.state == HealthState::Healthy && !self.is_last_heartbeat_recent(now) {
+            warn!("relayer heartbeat late, dropping relayer packets until it recovers");
+            self.state = HealthState::Unhealthy {
+                consecutive_heartbeats: 0,
+            };
+        }
+        self.state == HealthState::Healthy
+    }
+
+    fn is_last_heartbeat_recent(&self, now: Instant) -> bool {
+        self.last_heartbeat
+            .map(|last_heartbeat| {
+                now.saturating_duration_since(last_heartbeat) <= self.heartbeat_timeout
+            })
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_millis(1500);
+    const INTERVAL: Duration = Duration::from_millis(500);
+
+    #[test]
+    fn test_relayer_health_recovers_after_consecutive_heartbeats() {
+        let mut health = RelayerHealth::new(TIMEOUT, 3);
+        let mut now = Instant::now();
+        assert!(!health.is_healthy(now));
+
+        health.on_heartbeat(now);
+        assert!(!health.is_healthy(now));
+        now += INTERVAL;
+        health.on_heartbeat(now);
+        assert!(!health.is_healthy(now));
+        now += INTERVAL;
+        health.on_heartbeat(now);
+        assert!(health.is_healthy(now));
+
+        // stays healthy until heartbeats stop for longer than the timeout
+        assert!(health.is_healthy(now + TIMEOUT));
+        assert!(!health.is_healthy(now + TIMEOUT + INTERVAL));
+    }
+
+    #[test]
+    fn test_relayer_health_flapping() {
+        let mut health = RelayerHealth::new(TIMEOUT, 3);
+        let mut now = Instant::now();
+
+        for _ in 0..5 {
+            // two heartbeats isn't enough to recover
+            health.on_heartbeat(now);
+            now += INTERVAL;
+            health.on_heartbeat(now);
+            assert!(!health.is_healthy(now));
+
+            // heartbeats stop, breaking the streak
+            now += TIMEOUT * 2;
+            assert!(!health.is_healthy(now));
+        }
+
+        // a late heartbeat starts a new streak instead of continuing the old one
+        health.on_heartbeat(now);
+        now += INTERVAL;
+        health.on_heartbeat(now);
+        now += INTERVAL;
+        health.on_heartbeat(now);
+        assert!(health.is_healthy(now));
+
+        // going unhealthy again requires a full streak to recover
+        now += TIMEOUT * 2;
+        assert!(!health.is_healthy(now));
+        health.on_heartbeat(now);
+        assert!(!health.is_healthy(now));
+        now += INTERVAL;
+        health.on_heartbeat(now);
+        assert!(!health.is_healthy(now));
+        now += INTERVAL;
+        health.on_heartbeat(now);
+        assert!(health.is_healthy(now));
+    }
+
+    #[test]
+    fn test_relayer_health_reset() {
+        let mut health = RelayerHealth::new(TIMEOUT, 1);
+        let now = Instant::now();
+        health.on_heartbeat(now);
+        assert!(health.is_healthy(now));
+
+        health.reset();
+        assert!(!health.is_healthy(now));
+    }
+}
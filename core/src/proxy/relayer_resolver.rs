@@ -0,0 +1,219 @@
+//! Resolves the relayer hostname on every connection attempt.
+//!
+//! Relayers run behind DNS-based failover, so the addresses behind the hostname change while the
+//! validator is running. Each attempt looks the hostname up again instead of reusing the address
+//! from the first connection, and consecutive attempts rotate through all returned addresses.
+//! Relayers on the same host reached over a Unix domain socket skip resolution entirely.
+
+use {
+    crate::proxy::{ProxyError, Result},
+    std::{
+        fmt, io,
+        net::{SocketAddr, ToSocketAddrs},
+        sync::Arc,
+    },
+    tokio::net::TcpStream,
+    tonic::{
+        codegen::{http::Uri, Context, Future, Pin, Poll, Service},
+        transport::Endpoint,
+    },
+};
+#[cfg(unix)]
+use {std::path::PathBuf, tokio::net::UnixStream};
+
+/// Looks up the addresses the relayer hostname currently points at.
+pub trait RelayerResolver: fmt::Debug + Send + Sync {
+    /// Returns every address `host` resolves to.
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>>;
+}
+
+/// Resolves hostnames with the operating system resolver.
+#[derive(Debug, Default)]
+pub struct SystemResolver;
+
+impl RelayerResolver for SystemResolver {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        Ok((host, port).to_socket_addrs()?.collect())
+    }
+}
+
+/// Picks the address used for each connection attempt.
+pub(crate) struct RelayerAddressRotation {
+    resolver: Arc<dyn RelayerResolver>,
+    num_attempts: usize,
+}
+
+impl RelayerAddressRotation {
+    pub(crate) fn new(resolver: Arc<dyn RelayerResolver>) -> Self {
+        Self {
+            resolver,
+            num_attempts: 0,
+        }
+    }
+
+    /// Resolves the endpoint's hostname and returns the next address to connect to.
+    pub(crate) async fn next_addr(&mut self, endpoint: &Endpoint) -> Result<SocketAddr> {
+        let uri = endpoint.uri();
+        let host = uri
+            .host()
+            .ok_or_else(|| ProxyError::ResolveError(format!("{} has no host", uri)))?
+            // IPv6 literals keep their brackets in the uri
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let port = uri
+            .port_u16()
+            .unwrap_or(if uri.scheme_str() == Some("https") {
+                443
+            } else {
+                80
+            });
+
+        let resolver = self.resolver.clone();
+        let resolve_host = host.clone();
+        let mut addrs = tokio::task::spawn_blocking(move || resolver.resolve(&resolve_host, port))
+            .await
+            .map_err(|e| ProxyError::ResolveError(e.to_string()))?
+            .map_err(|e| ProxyError::ResolveError(format!("{}: {}", host, e)))?;
+        // keep the order stable so the rotation doesn't depend on the order records are returned
+        addrs.sort_unstable();
+        addrs.dedup();
+        if addrs.is_empty() {
+            return Err(ProxyError::ResolveError(format!(
+                "{} has no addresses",
+                host
+            )));
+        }
+
+        let addr = addrs[self.num_attempts % addrs.len()];
+        self.num_attempts = self.num_attempts.wrapping_add(1);
+        Ok(addr)
+    }
+}
+
+/// Connects to an already resolved address. The endpoint uri is left untouched so the hostname
+/// is still used for TLS and the HTTP/2 authority.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ResolvedConnector {
+    pub(crate) addr: SocketAddr,
+    pub(crate) tcp_nodelay: bool,
+}
+
+impl Service<Uri> for ResolvedConnector {
+    type Response = TcpStream;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<TcpStream>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _uri: Uri) -> Self::Future {
+        let Self { addr, tcp_nodelay } = *self;
+        Box::pin(async move {
+            let stream = TcpStream::connect(addr).await?;
+            stream.set_nodelay(tcp_nodelay)?;
+            Ok(stream)
+        })
+    }
+}
+
+/// Connects to a relayer listening on a Unix domain socket. The endpoint uri is a placeholder
+/// only used for the HTTP/2 authority.
+#[cfg(unix)]
+#[derive(Clone, Debug)]
+pub(crate) struct UnixConnector {
+    pub(crate) path: PathBuf,
+}
+
+#[cfg(unix)]
+impl Service<Uri> for UnixConnector {
+    type Response = UnixStream;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<UnixStream>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _uri: Uri) -> Self::Future {
+        let path = self.path.clone();
+        Box::pin(async move { UnixStream::connect(path).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::sync::Mutex};
+
+    #[derive(Debug, Default)]
+    struct TestResolver {
+        addrs: Mutex<Vec<SocketAddr>>,
+        lookups: Mutex<Vec<(String, u16)>>,
+    }
+
+    impl RelayerResolver for TestResolver {
+        fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+            self.lookups.lock().unwrap().push((host.to_string(), port));
+            Ok(self.addrs.lock().unwrap().clone())
+        }
+    }
+
+    #[test]
+    fn test_address_rotation_resolves_every_attempt() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let resolver = Arc::new(TestResolver::default());
+        let addr_a: SocketAddr = "10.0.0.1:11226".parse().unwrap();
+        let addr_b: SocketAddr = "10.0.0.2:11226".parse().unwrap();
+        let addr_c: SocketAddr = "[2001:db8::1]:11226".parse().unwrap();
+        *resolver.addrs.lock().unwrap() = vec![addr_b, addr_a];
+
+        let mut rotation = RelayerAddressRotation::new(resolver.clone());
+        let endpoint = Endpoint::from_static("http://relayer.example.com:11226");
+        rt.block_on(async {
+            assert_eq!(rotation.next_addr(&endpoint).await.unwrap(), addr_a);
+            assert_eq!(rotation.next_addr(&endpoint).await.unwrap(), addr_b);
+            assert_eq!(rotation.next_addr(&endpoint).await.unwrap(), addr_a);
+
+            // a changed record is picked up on the next attempt
+            *resolver.addrs.lock().unwrap() = vec![addr_c];
+            assert_eq!(rotation.next_addr(&endpoint).await.unwrap(), addr_c);
+
+            resolver.addrs.lock().unwrap().clear();
+            assert!(matches!(
+                rotation.next_addr(&endpoint).await,
+                Err(ProxyError::ResolveError(_))
+            ));
+        });
+        assert_eq!(
+            *resolver.lookups.lock().unwrap(),
+            vec![("relayer.example.com".to_string(), 11226); 5]
+        );
+    }
+
+    #[test]
+    fn test_address_rotation_default_port() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let resolver = Arc::new(TestResolver::default());
+        *resolver.addrs.lock().unwrap() = vec!["10.0.0.1:443".parse().unwrap()];
+
+        let mut rotation = RelayerAddressRotation::new(resolver.clone());
+        rt.block_on(async {
+            rotation
+                .next_addr(&Endpoint::from_static("https://relayer.example.com"))
+                .await
+                .unwrap();
+            rotation
+                .next_addr(&Endpoint::from_static("http://[::1]"))
+                .await
+                .unwrap();
+        });
+        assert_eq!(
+            *resolver.lookups.lock().unwrap(),
+            vec![
+                ("relayer.example.com".to_string(), 443),
+                ("::1".to_string(), 80)
+            ]
+        );
+    }
+}
@@ -8,66 +8,511 @@
 //! - Expected to send heartbeat to validator as watchdog. If watchdog times out, the validator
 //!   disconnects and reverts the TPU and TPU forward settings.
 
+#[cfg(unix)]
+use crate::proxy::relayer_resolver::UnixConnector;
 use {
     crate::{
         backoff::BackoffStrategy,
-        proto_packet_to_packet,
         proxy::{
-            auth::{token_manager::auth_tokens_update_loop, AuthInterceptor},
-            HeartbeatEvent, ProxyError,
+            auth::{
+                token_manager::{auth_tokens_update_loop, generate_auth_tokens},
+                AuthInterceptor,
+            },
+            circuit_breaker::{
+                is_malformed_packet, RelayerCircuitBreaker, RelayerCircuitBreakerConfig,
+            },
+            disconnect_history::{DisconnectEvent, DisconnectReason},
+            fetch_stage_manager::HeartbeatLossPolicy,
+            heartbeat_gaps::HeartbeatGapTracker,
+            http_proxy::{HttpProxyConfig, HttpProxyConnectError, HttpProxyConnector},
+            rate_limiter::{RelayerPacketRateLimiter, RelayerPacketRateLimits},
+            relayer_health::RelayerHealth,
+            relayer_resolver::{RelayerAddressRotation, RelayerResolver, ResolvedConnector},
+            retry_budget::RelayerRetryBudget,
+            sleep_unless_exit, wait_for_exit, with_rpc_timeout, DropOldestSender, HeartbeatEvent,
+            LastMinuteCounter, MevConfigUpdate, MevStatus, ProxyError, DEFAULT_RPC_TIMEOUT,
+            EXIT_CHECK_INTERVAL,
         },
+        relayer_proto_packet_to_packet,
         sigverify::SigverifyTracerPacketStats,
     },
-    crossbeam_channel::Sender,
+    crossbeam_channel::{Receiver, Sender},
     jito_protos::proto::{
-        auth::Token,
+        auth::{auth_service_client::AuthServiceClient, Token},
         relayer::{self, relayer_client::RelayerClient},
     },
+    lru::LruCache,
     solana_gossip::cluster_info::ClusterInfo,
     solana_perf::packet::PacketBatch,
-    solana_sdk::saturating_add_assign,
+    solana_sdk::signature::{Keypair, Signature, SIGNATURE_BYTES},
     std::{
+        fmt::{self, Display},
+        future::Future,
         net::{IpAddr, Ipv4Addr, SocketAddr},
+        path::{Path, PathBuf},
         sync::{
-            atomic::{AtomicBool, Ordering},
-            Arc, Mutex,
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc, Mutex, RwLock,
         },
         thread::{self, Builder, JoinHandle},
-        time::{Duration, Instant},
+        time::{Duration, Instant, SystemTime},
     },
-    tokio::time::{interval, sleep},
+    thiserror::Error,
+    tokio::time::{interval, timeout},
     tonic::{
         codegen::InterceptedService,
-        transport::{Channel, Endpoint},
+        transport::{Channel, ClientTlsConfig, Endpoint},
         Streaming,
     },
 };
 
-#[derive(Default)]
+const METRICS_CADENCE: Duration = Duration::from_secs(1);
+
+// How often a degraded relayer stage checks for exit and config updates between connection attempts.
+const DEGRADED_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Unix socket connections ignore the uri; tonic still needs one for the HTTP/2 authority.
+const UNIX_SOCKET_PLACEHOLDER_URI: &str = "http://localhost";
+const UNIX_SOCKETS_UNSUPPORTED: &str = "unix sockets aren't supported on this platform";
+
+/// Default number of recently seen relayer packet signatures remembered for deduplication.
+pub const DEFAULT_PACKET_DEDUP_CAPACITY: usize = 100_000;
+
+/// Default time a relayer connection has to stay up before the reconnect backoff is reset.
+pub const DEFAULT_BACKOFF_RESET_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
 struct RelayerStageStats {
+    num_empty_messages: AtomicU64,
+    num_packets_received: AtomicU64,
+    num_packets_forwarded: AtomicU64,
+    num_packets_dropped: AtomicU64,
+    num_duplicate_packets: AtomicU64,
+    num_rate_limited_packets: AtomicU64,
+    num_malformed_packets: AtomicU64,
+    num_unhealthy_packets_dropped: AtomicU64,
+    num_heartbeats: AtomicU64,
+    num_connect_attempts: AtomicU64,
+    num_connect_errors: AtomicU64,
+    num_stream_errors: AtomicU64,
+    heartbeat_gaps: Mutex<HeartbeatGapTracker>,
+}
+
+/// Counts accumulated since the last report.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct RelayerStageStatsDeltas {
     num_empty_messages: u64,
-    num_packets: u64,
+    num_packets_received: u64,
+    num_packets_forwarded: u64,
+    num_packets_dropped: u64,
+    num_duplicate_packets: u64,
+    num_rate_limited_packets: u64,
+    num_malformed_packets: u64,
+    num_unhealthy_packets_dropped: u64,
     num_heartbeats: u64,
+    num_connect_attempts: u64,
+    num_connect_errors: u64,
+    num_stream_errors: u64,
 }
 
 impl RelayerStageStats {
-    pub(crate) fn report(&self) {
+    fn new(expected_heartbeat_interval: Duration) -> Self {
+        Self {
+            num_empty_messages: AtomicU64::default(),
+            num_packets_received: AtomicU64::default(),
+            num_packets_forwarded: AtomicU64::default(),
+            num_packets_dropped: AtomicU64::default(),
+            num_duplicate_packets: AtomicU64::default(),
+            num_rate_limited_packets: AtomicU64::default(),
+            num_malformed_packets: AtomicU64::default(),
+            num_unhealthy_packets_dropped: AtomicU64::default(),
+            num_heartbeats: AtomicU64::default(),
+            num_connect_attempts: AtomicU64::default(),
+            num_connect_errors: AtomicU64::default(),
+            num_stream_errors: AtomicU64::default(),
+            heartbeat_gaps: Mutex::new(HeartbeatGapTracker::new(
+                expected_heartbeat_interval,
+                Instant::now(),
+            )),
+        }
+    }
+
+    fn take_deltas(&self) -> RelayerStageStatsDeltas {
+        RelayerStageStatsDeltas {
+            num_empty_messages: self.num_empty_messages.swap(0, Ordering::Relaxed),
+            num_packets_received: self.num_packets_received.swap(0, Ordering::Relaxed),
+            num_packets_forwarded: self.num_packets_forwarded.swap(0, Ordering::Relaxed),
+            num_packets_dropped: self.num_packets_dropped.swap(0, Ordering::Relaxed),
+            num_duplicate_packets: self.num_duplicate_packets.swap(0, Ordering::Relaxed),
+            num_rate_limited_packets: self.num_rate_limited_packets.swap(0, Ordering::Relaxed),
+            num_malformed_packets: self.num_malformed_packets.swap(0, Ordering::Relaxed),
+            num_unhealthy_packets_dropped: self
+                .num_unhealthy_packets_dropped
+                .swap(0, Ordering::Relaxed),
+            num_heartbeats: self.num_heartbeats.swap(0, Ordering::Relaxed),
+            num_connect_attempts: self.num_connect_attempts.swap(0, Ordering::Relaxed),
+            num_connect_errors: self.num_connect_errors.swap(0, Ordering::Relaxed),
+            num_stream_errors: self.num_stream_errors.swap(0, Ordering::Relaxed),
+        }
+    }
+}
+
+impl RelayerStageStatsDeltas {
+    fn report(&self) {
         datapoint_info!(
             "relayer_stage-stats",
             ("num_empty_messages", self.num_empty_messages, i64),
-            ("num_packets", self.num_packets, i64),
+            ("num_packets_received", self.num_packets_received, i64),
+            ("num_packets_forwarded", self.num_packets_forwarded, i64),
+            ("num_packets_dropped", self.num_packets_dropped, i64),
+            ("num_duplicate_packets", self.num_duplicate_packets, i64),
+            (
+                "num_rate_limited_packets",
+                self.num_rate_limited_packets,
+                i64
+            ),
+            ("num_malformed_packets", self.num_malformed_packets, i64),
+            (
+                "num_unhealthy_packets_dropped",
+                self.num_unhealthy_packets_dropped,
+                i64
+            ),
             ("num_heartbeats", self.num_heartbeats, i64),
+            ("num_connect_attempts", self.num_connect_attempts, i64),
+            ("num_connect_errors", self.num_connect_errors, i64),
+            ("num_stream_errors", self.num_stream_errors, i64),
         );
     }
 }
 
+/// Remembers the first signature of recently received relayer packets so the same transaction
+/// isn't forwarded twice, e.g. when the relayer echoes back packets after a fail-open.
+struct RelayerPacketDeduper {
+    signatures: LruCache<Signature, ()>,
+}
+
+impl RelayerPacketDeduper {
+    fn new(capacity: usize) -> Self {
+        Self {
+            signatures: LruCache::new(capacity.max(1)),
+        }
+    }
+
+    /// Returns true if a packet with the same first signature was recorded recently. Packets too
+    /// short to contain a signature are never considered duplicates.
+    fn is_duplicate(&self, packet_data: &[u8]) -> bool {
+        Self::first_signature(packet_data)
+            .map(|signature| self.signatures.contains(&signature))
+            .unwrap_or_default()
+    }
+
+    /// Remembers the packet's first signature. Only forwarded packets are recorded, so a packet
+    /// dropped by the rate limiter is accepted if the relayer sends it again.
+    fn record(&mut self, packet_data: &[u8]) {
+        if let Some(signature) = Self::first_signature(packet_data) {
+            self.signatures.put(signature, ());
+        }
+    }
+
+    fn first_signature(packet_data: &[u8]) -> Option<Signature> {
+        // the first byte is the compact-u16 signature count, which always fits in a single byte
+        // for transactions that fit in a packet
+        packet_data.get(1..1 + SIGNATURE_BYTES).map(Signature::new)
+    }
+}
+
+/// Checks applied to relayer packets before they're forwarded.
+struct RelayerPacketFilter {
+    health: RelayerHealth,
+    deduper: RelayerPacketDeduper,
+    rate_limiter: RelayerPacketRateLimiter,
+    circuit_breaker: RelayerCircuitBreaker,
+}
+
+/// Resets the reconnect backoff once a connection has proven itself, so a relayer that accepts
+/// connections and drops them right away keeps climbing the backoff curve instead of being
+/// retried at the minimum delay.
+struct BackoffResetTracker {
+    connected_at: Instant,
+    grace_period: Duration,
+    is_reset: bool,
+}
+
+impl BackoffResetTracker {
+    fn new(connected_at: Instant, grace_period: Duration) -> Self {
+        Self {
+            connected_at,
+            grace_period,
+            is_reset: false,
+        }
+    }
+
+    /// Resets `backoff` the first time the connection has been up for the grace period and a
+    /// heartbeat has been received since connecting. Returns true if the backoff was reset.
+    fn maybe_reset(
+        &mut self,
+        backoff: &mut BackoffStrategy,
+        last_heartbeat: Instant,
+        now: Instant,
+    ) -> bool {
+        if self.is_reset
+            || last_heartbeat <= self.connected_at
+            || now.saturating_duration_since(self.connected_at) < self.grace_period
+        {
+            return false;
+        }
+        backoff.reset();
+        self.is_reset = true;
+        true
+    }
+}
+
+/// gRPC channel settings for the relayer connection.
+///
+/// HTTP/2 keepalive pings let the validator notice a dead connection within a few seconds
+/// instead of waiting on the OS TCP timeouts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelayerConnectionConfig {
+    /// Interval between HTTP/2 keepalive pings.
+    pub keep_alive_interval: Duration,
+    /// How long to wait for a keepalive ping to be acknowledged before closing the connection.
+    pub keep_alive_timeout: Duration,
+    /// Max time spent establishing a connection before retrying.
+    pub connect_timeout: Duration,
+    pub tcp_nodelay: bool,
+    /// Whether relayers reached over a Unix domain socket go through the auth challenge. The
+    /// socket is already protected by filesystem permissions, so it is skipped by default.
+    pub unix_socket_auth: bool,
+    /// HTTP proxy TCP connections to the relayer are tunneled through, if any.
+    pub http_proxy: Option<HttpProxyConfig>,
+}
+
+impl Default for RelayerConnectionConfig {
+    fn default() -> Self {
+        Self {
+            keep_alive_interval: Duration::from_secs(5),
+            keep_alive_timeout: Duration::from_secs(10),
+            connect_timeout: Duration::from_secs(10),
+            tcp_nodelay: true,
+            unix_socket_auth: false,
+            http_proxy: None,
+        }
+    }
+}
+
+impl RelayerConnectionConfig {
+    /// Parses a relayer address. `http://` and `https://` addresses are reached over TCP, with
+    /// TLS for `https`. `unix:///path/to/socket` addresses are reached over a Unix domain socket
+    /// without TLS, and are rejected on platforms without Unix domain sockets.
+    pub fn parse(address: &str) -> crate::proxy::Result<RelayerAddress> {
+        let invalid =
+            |e: &dyn Display| ProxyError::InvalidRelayerAddress(format!("{}: {}", address, e));
+
+        if let Some(path) = address.strip_prefix("unix://") {
+            if cfg!(not(unix)) {
+                return Err(invalid(&UNIX_SOCKETS_UNSUPPORTED));
+            }
+            if !path.starts_with('/') {
+                return Err(invalid(&"unix socket path must be absolute"));
+            }
+            return Ok(RelayerAddress {
+                endpoint: Endpoint::from_static(UNIX_SOCKET_PLACEHOLDER_URI),
+                transport: RelayerTransport::Unix(PathBuf::from(path)),
+            });
+        }
+
+        let mut endpoint = Endpoint::from_shared(address.to_string()).map_err(|e| invalid(&e))?;
+        match endpoint.uri().scheme_str() {
+            Some("http") => {}
+            Some("https") => {
+                endpoint = endpoint
+                    .tls_config(ClientTlsConfig::new())
+                    .map_err(|e| invalid(&e))?;
+            }
+            _ => return Err(invalid(&"expected an http, https or unix address")),
+        }
+        Ok(RelayerAddress::from(endpoint))
+    }
+
+    /// Parses an auth service address. `https://` addresses are reached with TLS.
+    pub fn parse_auth_service(address: &str) -> crate::proxy::Result<Endpoint> {
+        let invalid =
+            |e: &dyn Display| ProxyError::InvalidRelayerAddress(format!("{}: {}", address, e));
+
+        let endpoint = Endpoint::from_shared(address.to_string()).map_err(|e| invalid(&e))?;
+        match endpoint.uri().scheme_str() {
+            Some("http") => Ok(endpoint),
+            Some("https") => endpoint
+                .tls_config(ClientTlsConfig::new())
+                .map_err(|e| invalid(&e)),
+            _ => Err(invalid(&"expected an http or https address")),
+        }
+    }
+
+    /// Whether the access token has to be fetched before connecting over `transport`.
+    fn requires_auth(&self, transport: &RelayerTransport) -> bool {
+        match transport {
+            RelayerTransport::Tcp => true,
+            RelayerTransport::Unix(_) => self.unix_socket_auth,
+        }
+    }
+
+    fn apply(&self, endpoint: Endpoint) -> Endpoint {
+        endpoint
+            .http2_keep_alive_interval(self.keep_alive_interval)
+            .keep_alive_timeout(self.keep_alive_timeout)
+            .tcp_nodelay(self.tcp_nodelay)
+    }
+
+    async fn connect_tcp(
+        &self,
+        endpoint: &Endpoint,
+        addr: SocketAddr,
+    ) -> crate::proxy::Result<Channel> {
+        let connector = ResolvedConnector {
+            addr,
+            tcp_nodelay: self.tcp_nodelay,
+        };
+        self.with_connect_timeout(endpoint.connect_with_connector(connector))
+            .await
+    }
+
+    async fn connect_http_proxy(
+        &self,
+        endpoint: &Endpoint,
+        http_proxy: &HttpProxyConfig,
+    ) -> crate::proxy::Result<Channel> {
+        let connector = HttpProxyConnector {
+            proxy: http_proxy.clone(),
+            tcp_nodelay: self.tcp_nodelay,
+        };
+        self.with_connect_timeout(endpoint.connect_with_connector(connector))
+            .await
+    }
+
+    /// Connects to the auth service, through the HTTP proxy if there is one. Networks that
+    /// require the proxy for relayer traffic don't let the auth service be dialed directly either.
+    async fn connect_auth_service(&self, endpoint: &Endpoint) -> crate::proxy::Result<Channel> {
+        match &self.http_proxy {
+            Some(http_proxy) => self.connect_http_proxy(endpoint, http_proxy).await,
+            None => self.with_connect_timeout(endpoint.connect()).await,
+        }
+    }
+
+    fn http_proxy_connector(&self) -> Option<HttpProxyConnector> {
+        self.http_proxy
+            .as_ref()
+            .map(|http_proxy| HttpProxyConnector {
+                proxy: http_proxy.clone(),
+                tcp_nodelay: self.tcp_nodelay,
+            })
+    }
+
+    #[cfg(unix)]
+    async fn connect_unix(
+        &self,
+        endpoint: &Endpoint,
+        path: &Path,
+    ) -> crate::proxy::Result<Channel> {
+        let connector = UnixConnector {
+            path: path.to_path_buf(),
+        };
+        self.with_connect_timeout(endpoint.connect_with_connector(connector))
+            .await
+    }
+
+    /// Unix socket addresses are rejected when parsed, so this is never reached.
+    #[cfg(not(unix))]
+    async fn connect_unix(
+        &self,
+        _endpoint: &Endpoint,
+        path: &Path,
+    ) -> crate::proxy::Result<Channel> {
+        Err(ProxyError::InvalidRelayerAddress(format!(
+            "unix://{}: {}",
+            path.display(),
+            UNIX_SOCKETS_UNSUPPORTED
+        )))
+    }
+
+    async fn with_connect_timeout(
+        &self,
+        connect: impl Future<Output = Result<Channel, tonic::transport::Error>>,
+    ) -> crate::proxy::Result<Channel> {
+        timeout(self.connect_timeout, connect)
+            .await
+            .map_err(|_| ProxyError::ConnectionTimeout)?
+            .map_err(|e| match HttpProxyConnectError::find(&e) {
+                Some(proxy_error) => ProxyError::HttpProxyConnectError(proxy_error.to_string()),
+                None => ProxyError::from(e),
+            })
+    }
+}
+
+/// How the validator reaches the relayer backend.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RelayerTransport {
+    /// TCP to the endpoint's host.
+    Tcp,
+    /// A Unix domain socket at this path, for a relayer running on the same host.
+    Unix(PathBuf),
+}
+
+/// Relayer backend address, built with [`RelayerConnectionConfig::parse`].
+#[derive(Clone, Debug)]
+pub struct RelayerAddress {
+    pub endpoint: Endpoint,
+    pub transport: RelayerTransport,
+}
+
+impl From<Endpoint> for RelayerAddress {
+    fn from(endpoint: Endpoint) -> Self {
+        Self {
+            endpoint,
+            transport: RelayerTransport::Tcp,
+        }
+    }
+}
+
+/// A relayer address change received while the validator is running.
+#[derive(Clone, Debug)]
+pub struct RelayerAddressUpdate {
+    pub backend_address: RelayerAddress,
+    /// Auth service that hands out tokens for the new relayer. Tokens from another relayer's
+    /// auth service are rejected, so it has to be set unless both share one. The current auth
+    /// service is kept if it isn't.
+    pub auth_service_endpoint: Option<Endpoint>,
+}
+
+impl Display for RelayerAddressUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.auth_service_endpoint {
+            Some(auth_service_endpoint) => write!(
+                f,
+                "{} (auth service {})",
+                self.backend_address,
+                auth_service_endpoint.uri()
+            ),
+            None => write!(f, "{}", self.backend_address),
+        }
+    }
+}
+
+impl Display for RelayerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.transport {
+            RelayerTransport::Tcp => write!(f, "{}", self.endpoint.uri()),
+            RelayerTransport::Unix(path) => write!(f, "unix://{}", path.display()),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct RelayerConfig {
     /// Address to the external auth-service responsible for generating access tokens.
     pub auth_service_endpoint: Endpoint,
 
-    /// Primary backend endpoint.
-    pub backend_endpoint: Endpoint,
+    /// Primary backend address.
+    pub backend_address: RelayerAddress,
 
     /// Interval at which heartbeats are expected.
     pub expected_heartbeat_interval: Duration,
@@ -77,6 +522,84 @@ pub struct RelayerConfig {
 
     /// If set then it will be assumed the backend verified packets so signature verification will be bypassed in the validator.
     pub trust_packets: bool,
+
+    /// Determines whether packets from the validator's TPU ports are processed while the relayer is unhealthy.
+    pub heartbeat_loss_policy: HeartbeatLossPolicy,
+
+    /// If set, simple vote transactions received on the validator's TPU ports are always processed
+    /// locally instead of being dropped in favor of the relayer.
+    pub vote_bypass: bool,
+
+    /// Max number of packet batches queued downstream before the oldest ones are dropped.
+    pub packet_channel_capacity: usize,
+
+    /// Number of recently seen packet signatures used to drop duplicate relayer packets.
+    pub packet_dedup_capacity: usize,
+
+    /// Rate limits applied to packets received from the relayer.
+    pub packet_rate_limits: RelayerPacketRateLimits,
+
+    /// Number of consecutive heartbeats needed before packets from the relayer are accepted.
+    pub heartbeats_to_recover: usize,
+
+    /// Keepalive and timeout settings for the relayer connection.
+    pub connection_config: RelayerConnectionConfig,
+
+    /// When to drop a relayer stream that sends mostly malformed packets.
+    pub circuit_breaker: RelayerCircuitBreakerConfig,
+
+    /// Looks up the backend hostname before every connection attempt.
+    pub resolver: Arc<dyn RelayerResolver>,
+
+    /// Number of consecutive failed connection attempts before falling back to the validator's
+    /// TPU ports and retrying the relayer at `degraded_retry_interval`.
+    pub max_consecutive_connect_failures: usize,
+
+    /// Time between connection attempts once the retry budget is exhausted.
+    pub degraded_retry_interval: Duration,
+
+    /// How long a connection has to stay up, with at least one heartbeat received, before the
+    /// reconnect backoff is reset.
+    pub backoff_reset_grace_period: Duration,
+
+    /// If set, the validator refuses to start when the relayer preflight check fails instead of
+    /// retrying the relayer in the background.
+    pub require_relayer: bool,
+
+    /// Scales the banking stage priority of relayer packets, in percent. `None` prioritizes them
+    /// the same as packets from the validator's TPU ports.
+    pub packet_priority_percent: Option<u64>,
+}
+
+/// What the relayer preflight check found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelayerPreflightReport {
+    pub address: String,
+    /// Address the relayer hostname resolved to, `None` for Unix domain sockets and relayers
+    /// reached through an HTTP proxy.
+    pub resolved_addr: Option<SocketAddr>,
+    /// Whether the auth handshake ran. It's skipped for Unix domain sockets unless
+    /// `unix_socket_auth` is set.
+    pub authenticated: bool,
+    pub tpu_addr: SocketAddr,
+    pub tpu_forward_addr: SocketAddr,
+    pub elapsed: Duration,
+}
+
+/// The step of the relayer preflight check that failed.
+#[derive(Error, Debug)]
+pub enum RelayerPreflightError {
+    #[error("failed to resolve relayer address {address}: {error}, check --relayer-address")]
+    Resolve { address: String, error: ProxyError },
+
+    #[error("failed to connect to relayer at {address}: {error}, check --relayer-address and that the relayer is reachable")]
+    Connect { address: String, error: ProxyError },
+
+    #[error("auth handshake with {endpoint} failed: {error}, check --relayer-auth-service-address and that the validator identity is allowed to connect")]
+    Auth { endpoint: String, error: ProxyError },
+
+    #[error("failed to fetch tpu configs from relayer at {address}: {error}")]
+    TpuConfigs { address: String, error: ProxyError },
 }
 
 pub struct RelayerStage {
@@ -84,6 +607,7 @@ pub struct RelayerStage {
 }
 
 impl RelayerStage {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         relayer_config: RelayerConfig,
         // The keypair stored here is used to sign auth challenges.
@@ -91,20 +615,73 @@ impl RelayerStage {
         // Channel that server-sent heartbeats are piped through.
         heartbeat_tx: Sender<HeartbeatEvent>,
         // Channel that non-trusted streamed packets are piped through.
-        packet_tx: Sender<PacketBatch>,
+        packet_tx: DropOldestSender<PacketBatch>,
         // Channel that trusted streamed packets are piped through.
-        verified_packet_tx: Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
+        verified_packet_tx: DropOldestSender<(
+            Vec<PacketBatch>,
+            Option<SigverifyTracerPacketStats>,
+        )>,
+        // Channel that relayer config changes are received from.
+        config_update_rx: Receiver<MevConfigUpdate>,
+        // Connection status reported to health checks.
+        mev_status: Arc<RwLock<MevStatus>>,
         exit: Arc<AtomicBool>,
     ) -> Self {
         let RelayerConfig {
             auth_service_endpoint,
-            backend_endpoint,
+            backend_address,
             expected_heartbeat_interval,
             oldest_allowed_heartbeat,
             trust_packets,
+            packet_dedup_capacity,
+            packet_rate_limits,
+            heartbeats_to_recover,
+            connection_config,
+            circuit_breaker,
+            resolver,
+            max_consecutive_connect_failures,
+            degraded_retry_interval,
+            backoff_reset_grace_period,
+            ..
         } = relayer_config;
 
         let access_token = Arc::new(Mutex::new(Token::default()));
+        let relayer_stats = Arc::new(RelayerStageStats::new(expected_heartbeat_interval));
+
+        let stats_thread = {
+            let relayer_stats = relayer_stats.clone();
+            let mev_status = mev_status.clone();
+            let exit = exit.clone();
+            Builder::new()
+                .name("relayer-stage-stats".into())
+                .spawn(move || {
+                    let mut packets_forwarded = LastMinuteCounter::default();
+                    let mut last_report = Instant::now();
+                    while !exit.load(Ordering::Relaxed) {
+                        thread::sleep(EXIT_CHECK_INTERVAL);
+                        if last_report.elapsed() < METRICS_CADENCE {
+                            continue;
+                        }
+                        last_report = Instant::now();
+                        let deltas = relayer_stats.take_deltas();
+                        deltas.report();
+                        relayer_stats
+                            .heartbeat_gaps
+                            .lock()
+                            .unwrap()
+                            .take_stats(last_report)
+                            .report();
+                        mev_status
+                            .write()
+                            .unwrap()
+                            .relayer
+                            .packets_forwarded_last_minute =
+                            packets_forwarded.record(deltas.num_packets_forwarded);
+                    }
+                })
+                .unwrap()
+        };
+
         let thread = Builder::new()
             .name("relayer-stage".into())
             .spawn(move || {
@@ -113,28 +690,38 @@ impl RelayerStage {
                     .build()
                     .unwrap();
 
-                rt.spawn(auth_tokens_update_loop(
-                    auth_service_endpoint,
-                    access_token.clone(),
-                    cluster_info.clone(),
-                    exit.clone(),
-                ));
                 rt.block_on(Self::start(
+                    auth_service_endpoint,
+                    cluster_info,
                     access_token,
                     heartbeat_tx,
                     expected_heartbeat_interval,
                     oldest_allowed_heartbeat,
                     packet_tx,
-                    backend_endpoint,
+                    backend_address,
                     verified_packet_tx,
                     trust_packets,
+                    connection_config,
+                    RelayerAddressRotation::new(resolver),
+                    RelayerRetryBudget::new(max_consecutive_connect_failures),
+                    degraded_retry_interval,
+                    backoff_reset_grace_period,
+                    config_update_rx,
+                    relayer_stats,
+                    mev_status,
+                    RelayerPacketFilter {
+                        health: RelayerHealth::new(oldest_allowed_heartbeat, heartbeats_to_recover),
+                        deduper: RelayerPacketDeduper::new(packet_dedup_capacity),
+                        rate_limiter: RelayerPacketRateLimiter::new(packet_rate_limits),
+                        circuit_breaker: RelayerCircuitBreaker::new(circuit_breaker),
+                    },
                     exit,
                 ));
             })
             .unwrap();
 
         Self {
-            t_hdls: vec![thread],
+            t_hdls: vec![thread, stats_thread],
         }
     }
 
@@ -145,41 +732,215 @@ impl RelayerStage {
         Ok(())
     }
 
+    /// Checks that the relayer in `relayer_config` can be reached before the stage starts: the
+    /// address resolves, a connection can be established, `keypair` passes the auth handshake and
+    /// the relayer returns its TPU configs. Runs on its own runtime, so it must not be called from
+    /// within one.
+    pub fn preflight(
+        relayer_config: &RelayerConfig,
+        keypair: &Keypair,
+    ) -> Result<RelayerPreflightReport, RelayerPreflightError> {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(Self::run_preflight(relayer_config, keypair))
+    }
+
+    async fn run_preflight(
+        relayer_config: &RelayerConfig,
+        keypair: &Keypair,
+    ) -> Result<RelayerPreflightReport, RelayerPreflightError> {
+        let RelayerConfig {
+            auth_service_endpoint,
+            backend_address,
+            connection_config,
+            resolver,
+            ..
+        } = relayer_config;
+        let start = Instant::now();
+        let address = backend_address.to_string();
+        let endpoint = connection_config.apply(backend_address.endpoint.clone());
+
+        let (channel, resolved_addr) =
+            match (&backend_address.transport, &connection_config.http_proxy) {
+                // the proxy resolves the relayer hostname
+                (RelayerTransport::Tcp, Some(http_proxy)) => (
+                    connection_config
+                        .connect_http_proxy(&endpoint, http_proxy)
+                        .await,
+                    None,
+                ),
+                (RelayerTransport::Tcp, None) => {
+                    let addr = RelayerAddressRotation::new(resolver.clone())
+                        .next_addr(&endpoint)
+                        .await
+                        .map_err(|error| RelayerPreflightError::Resolve {
+                            address: address.clone(),
+                            error,
+                        })?;
+                    let channel = connection_config.connect_tcp(&endpoint, addr).await;
+                    (channel, Some(addr))
+                }
+                (RelayerTransport::Unix(path), _) => {
+                    (connection_config.connect_unix(&endpoint, path).await, None)
+                }
+            };
+        let channel = channel.map_err(|error| RelayerPreflightError::Connect {
+            address: address.clone(),
+            error,
+        })?;
+
+        let authenticated = connection_config.requires_auth(&backend_address.transport);
+        let access_token = if authenticated {
+            let auth_error = |error| RelayerPreflightError::Auth {
+                endpoint: auth_service_endpoint.uri().to_string(),
+                error,
+            };
+            let auth_channel = connection_config
+                .connect_auth_service(auth_service_endpoint)
+                .await
+                .map_err(auth_error)?;
+            let (access_token, _refresh_token) =
+                generate_auth_tokens(&mut AuthServiceClient::new(auth_channel), keypair)
+                    .await
+                    .map_err(auth_error)?;
+            access_token
+        } else {
+            Token::default()
+        };
+
+        let mut client = RelayerClient::with_interceptor(
+            channel,
+            AuthInterceptor::new(Arc::new(Mutex::new(access_token))),
+        );
+        let (tpu_addr, tpu_forward_addr) =
+            Self::fetch_tpu_addresses(&mut client)
+                .await
+                .map_err(|error| RelayerPreflightError::TpuConfigs {
+                    address: address.clone(),
+                    error,
+                })?;
+
+        Ok(RelayerPreflightReport {
+            address,
+            resolved_addr,
+            authenticated,
+            tpu_addr,
+            tpu_forward_addr,
+            elapsed: start.elapsed(),
+        })
+    }
+
     #[allow(clippy::too_many_arguments)]
     async fn start(
+        auth_service_endpoint: Endpoint,
+        cluster_info: Arc<ClusterInfo>,
         access_token: Arc<Mutex<Token>>,
         heartbeat_tx: Sender<HeartbeatEvent>,
         expected_heartbeat_interval: Duration,
         oldest_allowed_heartbeat: Duration,
-        packet_tx: Sender<PacketBatch>,
-        relayer_endpoint: Endpoint,
-        verified_packet_tx: Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
+        packet_tx: DropOldestSender<PacketBatch>,
+        mut relayer_address: RelayerAddress,
+        verified_packet_tx: DropOldestSender<(
+            Vec<PacketBatch>,
+            Option<SigverifyTracerPacketStats>,
+        )>,
         trust_packets: bool,
+        connection_config: RelayerConnectionConfig,
+        mut address_rotation: RelayerAddressRotation,
+        mut retry_budget: RelayerRetryBudget,
+        degraded_retry_interval: Duration,
+        backoff_reset_grace_period: Duration,
+        config_update_rx: Receiver<MevConfigUpdate>,
+        relayer_stats: Arc<RelayerStageStats>,
+        mev_status: Arc<RwLock<MevStatus>>,
+        mut packet_filter: RelayerPacketFilter,
         exit: Arc<AtomicBool>,
     ) {
-        const WAIT_FOR_FIRST_AUTH: Duration = Duration::from_secs(5);
+        // short enough that an address change reaches a stage stuck waiting for a token quickly
+        const WAIT_FOR_AUTH_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
         let mut wait_count: usize = 0;
         let mut stream_error_count: usize = 0;
         let mut connect_error_count: usize = 0;
-        while access_token.lock().unwrap().value.is_empty() {
-            if exit.load(Ordering::Relaxed) {
-                return;
-            }
-            wait_count += 1;
-            datapoint_info!(
-                "relayer_stage-wait_for_auth",
-                ("wait_count", wait_count, i64)
-            );
-            sleep(WAIT_FOR_FIRST_AUTH).await;
-        }
 
+        let mut relayer_endpoint = connection_config.apply(relayer_address.endpoint.clone());
+        let mut circuit_breaker_tripped = false;
+        let mut new_relayer_address = None;
         let mut backoff = BackoffStrategy::new();
+        let spawn_auth_tokens_update_loop = |auth_service_endpoint: Endpoint| {
+            tokio::spawn(auth_tokens_update_loop(
+                auth_service_endpoint,
+                connection_config.http_proxy_connector(),
+                access_token.clone(),
+                cluster_info.clone(),
+                exit.clone(),
+            ))
+        };
+        let mut auth_tokens_task = spawn_auth_tokens_update_loop(auth_service_endpoint);
         while !exit.load(Ordering::Relaxed) {
-            match relayer_endpoint.connect().await {
+            Self::apply_config_updates(
+                &config_update_rx,
+                &mut packet_filter,
+                &mut new_relayer_address,
+            );
+            if let Some(update) = new_relayer_address.take() {
+                info!("connecting to new relayer address: {}", update);
+                if let Some(endpoint) = update.auth_service_endpoint {
+                    // the old relayer's tokens aren't accepted by the new one
+                    auth_tokens_task.abort();
+                    *access_token.lock().unwrap() = Token::default();
+                    auth_tokens_task = spawn_auth_tokens_update_loop(endpoint);
+                }
+                relayer_endpoint = connection_config.apply(update.backend_address.endpoint.clone());
+                relayer_address = update.backend_address;
+                backoff = BackoffStrategy::new();
+            }
+
+            // unix socket relayers may be reached without an access token. Config updates are
+            // applied while waiting so an unreachable auth service can be replaced.
+            if connection_config.requires_auth(&relayer_address.transport)
+                && access_token.lock().unwrap().value.is_empty()
+            {
+                wait_count += 1;
+                datapoint_info!(
+                    "relayer_stage-wait_for_auth",
+                    ("wait_count", wait_count, i64)
+                );
+                sleep_unless_exit(WAIT_FOR_AUTH_POLL_INTERVAL, &exit).await;
+                continue;
+            }
+
+            relayer_stats
+                .num_connect_attempts
+                .fetch_add(1, Ordering::Relaxed);
+            mev_status.write().unwrap().relayer.active_endpoint = Some(relayer_address.to_string());
+            let connect_result = tokio::select! {
+                result = async {
+                    match (&relayer_address.transport, &connection_config.http_proxy) {
+                        (RelayerTransport::Tcp, Some(http_proxy)) => {
+                            connection_config.connect_http_proxy(&relayer_endpoint, http_proxy).await
+                        }
+                        (RelayerTransport::Tcp, None) => {
+                            // resolve again on every attempt so dns changes are picked up
+                            let addr = address_rotation.next_addr(&relayer_endpoint).await?;
+                            connection_config.connect_tcp(&relayer_endpoint, addr).await
+                        }
+                        (RelayerTransport::Unix(path), _) => {
+                            connection_config.connect_unix(&relayer_endpoint, path).await
+                        }
+                    }
+                } => result,
+                _ = wait_for_exit(&exit) => break,
+            };
+            match connect_result {
                 Ok(channel) => {
-                    match Self::start_consuming_relayer_packets(
+                    let connected_at = Instant::now();
+                    let result = Self::start_consuming_relayer_packets(
                         &mut backoff,
+                        backoff_reset_grace_period,
+                        &mut retry_budget,
                         RelayerClient::with_interceptor(
                             channel,
                             AuthInterceptor::new(access_token.clone()),
@@ -190,13 +951,34 @@ impl RelayerStage {
                         &packet_tx,
                         &verified_packet_tx,
                         trust_packets,
+                        &config_update_rx,
+                        &relayer_stats,
+                        &mev_status,
+                        &mut packet_filter,
+                        &mut new_relayer_address,
                         &exit,
                     )
-                    .await
+                    .await;
                     {
+                        let mut mev_status = mev_status.write().unwrap();
+                        mev_status.relayer.connected = false;
+                        mev_status.relayer.last_heartbeat_age_ms = None;
+                    }
+                    Self::record_disconnect(
+                        &mev_status,
+                        &relayer_address,
+                        DisconnectReason::from_result(&result, exit.load(Ordering::Relaxed)),
+                        connected_at.elapsed(),
+                    );
+                    circuit_breaker_tripped = matches!(result, Err(ProxyError::MalformedPackets));
+                    match result {
                         Ok(_) => {}
                         Err(e) => {
+                            Self::on_connect_failure(&mut retry_budget, &mev_status);
                             stream_error_count += 1;
+                            relayer_stats
+                                .num_stream_errors
+                                .fetch_add(1, Ordering::Relaxed);
                             datapoint_error!(
                                 "relayer_stage-stream_error",
                                 ("count", stream_error_count, i64),
@@ -206,7 +988,11 @@ impl RelayerStage {
                     }
                 }
                 Err(e) => {
+                    Self::on_connect_failure(&mut retry_budget, &mev_status);
                     connect_error_count += 1;
+                    relayer_stats
+                        .num_connect_errors
+                        .fetch_add(1, Ordering::Relaxed);
                     datapoint_error!(
                         "relayer_stage-connect_error",
                         ("count", connect_error_count, i64),
@@ -214,50 +1000,207 @@ impl RelayerStage {
                     );
                 }
             }
-            sleep(Duration::from_millis(backoff.next_wait())).await;
+            Self::apply_config_updates(
+                &config_update_rx,
+                &mut packet_filter,
+                &mut new_relayer_address,
+            );
+            if new_relayer_address.is_some() {
+                // reconnect to the new address right away
+                continue;
+            }
+            if std::mem::take(&mut circuit_breaker_tripped) {
+                Self::wait_out_circuit_breaker(
+                    &config_update_rx,
+                    &mut packet_filter,
+                    &mut new_relayer_address,
+                    &mev_status,
+                    &exit,
+                )
+                .await;
+            } else if retry_budget.is_degraded() {
+                Self::wait_while_degraded(
+                    &mut retry_budget,
+                    degraded_retry_interval,
+                    &config_update_rx,
+                    &mut packet_filter,
+                    &mut new_relayer_address,
+                    &exit,
+                )
+                .await;
+            } else {
+                sleep_unless_exit(Duration::from_millis(backoff.next_wait()), &exit).await;
+            }
+        }
+    }
+
+    fn record_disconnect(
+        mev_status: &RwLock<MevStatus>,
+        relayer_address: &RelayerAddress,
+        reason: DisconnectReason,
+        uptime: Duration,
+    ) {
+        let endpoint = relayer_address.to_string();
+        datapoint_info!(
+            "relayer_stage-disconnect",
+            ("endpoint", endpoint, String),
+            ("reason", reason.to_string(), String),
+            ("uptime_ms", uptime.as_millis() as u64, i64),
+        );
+        mev_status
+            .write()
+            .unwrap()
+            .relayer
+            .recent_disconnects
+            .record(DisconnectEvent {
+                timestamp: SystemTime::now(),
+                endpoint,
+                reason,
+                uptime,
+            });
+    }
+
+    fn on_connect_failure(retry_budget: &mut RelayerRetryBudget, mev_status: &RwLock<MevStatus>) {
+        if retry_budget.on_failure() {
+            error!(
+                "relayer failed {} consecutive connection attempts, falling back to local tpu",
+                retry_budget.consecutive_failures()
+            );
+            datapoint_error!(
+                "relayer_stage-degraded",
+                (
+                    "consecutive_failures",
+                    retry_budget.consecutive_failures(),
+                    i64
+                ),
+            );
+            mev_status.write().unwrap().relayer.degraded = true;
+        }
+    }
+
+    fn on_connected(retry_budget: &mut RelayerRetryBudget, mev_status: &RwLock<MevStatus>) {
+        if retry_budget.on_connected() {
+            info!("relayer connection recovered, leaving degraded mode");
+            mev_status.write().unwrap().relayer.degraded = false;
+        }
+    }
+
+    /// Waits out `retry_interval` before the next connection attempt, warning once a minute.
+    /// Returns early if a new relayer address is received.
+    async fn wait_while_degraded(
+        retry_budget: &mut RelayerRetryBudget,
+        retry_interval: Duration,
+        config_update_rx: &Receiver<MevConfigUpdate>,
+        packet_filter: &mut RelayerPacketFilter,
+        new_relayer_address: &mut Option<RelayerAddressUpdate>,
+        exit: &AtomicBool,
+    ) {
+        let retry_at = Instant::now() + retry_interval;
+        while !exit.load(Ordering::Relaxed) {
+            let now = Instant::now();
+            if now >= retry_at {
+                return;
+            }
+            if retry_budget.should_warn(now) {
+                warn!(
+                    "relayer degraded after {} consecutive failed connection attempts, using local tpu",
+                    retry_budget.consecutive_failures()
+                );
+                datapoint_error!(
+                    "relayer_stage-degraded_warning",
+                    (
+                        "consecutive_failures",
+                        retry_budget.consecutive_failures(),
+                        i64
+                    ),
+                );
+            }
+            sleep_unless_exit(DEGRADED_POLL_INTERVAL.min(retry_at - now), exit).await;
+
+            Self::apply_config_updates(config_update_rx, packet_filter, new_relayer_address);
+            if new_relayer_address.is_some() {
+                return;
+            }
         }
     }
 
+    /// Keeps the relayer disconnected for the circuit breaker cooldown. Returns early if a new
+    /// relayer address is received.
+    async fn wait_out_circuit_breaker(
+        config_update_rx: &Receiver<MevConfigUpdate>,
+        packet_filter: &mut RelayerPacketFilter,
+        new_relayer_address: &mut Option<RelayerAddressUpdate>,
+        mev_status: &RwLock<MevStatus>,
+        exit: &AtomicBool,
+    ) {
+        let cooldown = packet_filter.circuit_breaker.cooldown();
+        error!(
+            "relayer sent too many malformed packets, reconnecting in {:?}",
+            cooldown
+        );
+        datapoint_error!(
+            "relayer_stage-circuit_breaker_open",
+            ("cooldown_ms", cooldown.as_millis() as u64, i64),
+        );
+        {
+            let mut mev_status = mev_status.write().unwrap();
+            mev_status.relayer.circuit_breaker_open = true;
+            mev_status.relayer.num_circuit_breaker_trips += 1;
+        }
+
+        let retry_at = Instant::now() + cooldown;
+        while !exit.load(Ordering::Relaxed) {
+            let now = Instant::now();
+            if now >= retry_at {
+                break;
+            }
+            sleep_unless_exit(DEGRADED_POLL_INTERVAL.min(retry_at - now), exit).await;
+
+            Self::apply_config_updates(config_update_rx, packet_filter, new_relayer_address);
+            if new_relayer_address.is_some() {
+                break;
+            }
+        }
+        mev_status.write().unwrap().relayer.circuit_breaker_open = false;
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn start_consuming_relayer_packets(
         backoff: &mut BackoffStrategy,
+        backoff_reset_grace_period: Duration,
+        retry_budget: &mut RelayerRetryBudget,
         mut client: RelayerClient<InterceptedService<Channel, AuthInterceptor>>,
         heartbeat_tx: &Sender<HeartbeatEvent>,
         expected_heartbeat_interval: Duration,
         oldest_allowed_heartbeat: Duration,
-        packet_tx: &Sender<PacketBatch>,
-        verified_packet_tx: &Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
+        packet_tx: &DropOldestSender<PacketBatch>,
+        verified_packet_tx: &DropOldestSender<(
+            Vec<PacketBatch>,
+            Option<SigverifyTracerPacketStats>,
+        )>,
         trust_packets: bool,
+        config_update_rx: &Receiver<MevConfigUpdate>,
+        relayer_stats: &RelayerStageStats,
+        mev_status: &RwLock<MevStatus>,
+        packet_filter: &mut RelayerPacketFilter,
+        new_relayer_address: &mut Option<RelayerAddressUpdate>,
         exit: &Arc<AtomicBool>,
     ) -> crate::proxy::Result<()> {
-        let heartbeat_event: HeartbeatEvent = {
-            let tpu_config = client
-                .get_tpu_configs(relayer::GetTpuConfigsRequest {})
-                .await?
-                .into_inner();
-            let tpu_addr = tpu_config
-                .tpu
-                .ok_or_else(|| ProxyError::MissingTpuSocket("tpu".into()))?;
-            let tpu_forward_addr = tpu_config
-                .tpu_forward
-                .ok_or_else(|| ProxyError::MissingTpuSocket("tpu_fwd".into()))?;
-
-            let tpu_ip = IpAddr::from(tpu_addr.ip.parse::<Ipv4Addr>()?);
-            let tpu_forward_ip = IpAddr::from(tpu_forward_addr.ip.parse::<Ipv4Addr>()?);
-
-            let tpu_socket = SocketAddr::new(tpu_ip, tpu_addr.port as u16);
-            let tpu_forward_socket = SocketAddr::new(tpu_forward_ip, tpu_forward_addr.port as u16);
-            (tpu_socket, tpu_forward_socket)
-        };
+        let heartbeat_event = Self::fetch_tpu_addresses(&mut client).await?;
 
-        let packet_stream = client
-            .subscribe_packets(relayer::SubscribePacketsRequest {})
-            .await?
-            .into_inner();
+        let packet_stream = with_rpc_timeout(
+            "subscribe_packets",
+            DEFAULT_RPC_TIMEOUT,
+            client.subscribe_packets(relayer::SubscribePacketsRequest {}),
+        )
+        .await?
+        .into_inner();
 
-        // assume it's all good here
-        backoff.reset();
+        Self::on_connected(retry_budget, mev_status);
 
         Self::consume_packet_stream(
+            backoff,
+            backoff_reset_grace_period,
             heartbeat_event,
             heartbeat_tx,
             expected_heartbeat_interval,
@@ -266,29 +1209,72 @@ impl RelayerStage {
             packet_tx,
             trust_packets,
             verified_packet_tx,
+            config_update_rx,
+            relayer_stats,
+            mev_status,
+            packet_filter,
+            new_relayer_address,
             exit,
         )
         .await
     }
 
+    /// Returns the relayer's TPU and TPU forward addresses.
+    async fn fetch_tpu_addresses(
+        client: &mut RelayerClient<InterceptedService<Channel, AuthInterceptor>>,
+    ) -> crate::proxy::Result<HeartbeatEvent> {
+        let tpu_config = with_rpc_timeout(
+            "get_tpu_configs",
+            DEFAULT_RPC_TIMEOUT,
+            client.get_tpu_configs(relayer::GetTpuConfigsRequest {}),
+        )
+        .await?
+        .into_inner();
+        let tpu_addr = tpu_config
+            .tpu
+            .ok_or_else(|| ProxyError::MissingTpuSocket("tpu".into()))?;
+        let tpu_forward_addr = tpu_config
+            .tpu_forward
+            .ok_or_else(|| ProxyError::MissingTpuSocket("tpu_fwd".into()))?;
+
+        let tpu_ip = IpAddr::from(tpu_addr.ip.parse::<Ipv4Addr>()?);
+        let tpu_forward_ip = IpAddr::from(tpu_forward_addr.ip.parse::<Ipv4Addr>()?);
+
+        let tpu_socket = SocketAddr::new(tpu_ip, tpu_addr.port as u16);
+        let tpu_forward_socket = SocketAddr::new(tpu_forward_ip, tpu_forward_addr.port as u16);
+        Ok((tpu_socket, tpu_forward_socket))
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn consume_packet_stream(
+        backoff: &mut BackoffStrategy,
+        backoff_reset_grace_period: Duration,
         heartbeat_event: HeartbeatEvent,
         heartbeat_tx: &Sender<HeartbeatEvent>,
         expected_heartbeat_interval: Duration,
         oldest_allowed_heartbeat: Duration,
         mut packet_stream: Streaming<relayer::SubscribePacketsResponse>,
-        packet_tx: &Sender<PacketBatch>,
+        packet_tx: &DropOldestSender<PacketBatch>,
         trust_packets: bool,
-        verified_packet_tx: &Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
+        verified_packet_tx: &DropOldestSender<(
+            Vec<PacketBatch>,
+            Option<SigverifyTracerPacketStats>,
+        )>,
+        config_update_rx: &Receiver<MevConfigUpdate>,
+        relayer_stats: &RelayerStageStats,
+        mev_status: &RwLock<MevStatus>,
+        packet_filter: &mut RelayerPacketFilter,
+        new_relayer_address: &mut Option<RelayerAddressUpdate>,
         exit: &Arc<AtomicBool>,
     ) -> crate::proxy::Result<()> {
-        const METRICS_TICK: Duration = Duration::from_secs(1);
-
-        let mut relayer_stats = RelayerStageStats::default();
-        let mut metrics_tick = interval(METRICS_TICK);
-
         let mut heartbeat_check_interval = interval(expected_heartbeat_interval);
+        let mut exit_check_interval = interval(EXIT_CHECK_INTERVAL);
         let mut last_heartbeat_ts = Instant::now();
+        let mut backoff_reset_tracker =
+            BackoffResetTracker::new(last_heartbeat_ts, backoff_reset_grace_period);
+        packet_filter.health.reset();
+        packet_filter.circuit_breaker.reset();
+        mev_status.write().unwrap().relayer.connected = true;
 
         info!("connected to packet stream");
 
@@ -296,62 +1282,167 @@ impl RelayerStage {
             tokio::select! {
                 maybe_msg = packet_stream.message() => {
                     let resp = maybe_msg?.ok_or(ProxyError::GrpcStreamDisconnected)?;
-                    Self::handle_relayer_packets(resp, heartbeat_event, heartbeat_tx, &mut last_heartbeat_ts, packet_tx, trust_packets, verified_packet_tx, &mut relayer_stats)?;
+                    Self::handle_relayer_packets(resp, heartbeat_event, heartbeat_tx, &mut last_heartbeat_ts, packet_tx, trust_packets, verified_packet_tx, relayer_stats, packet_filter)?;
                 }
                 _ = heartbeat_check_interval.tick() => {
+                    mev_status.write().unwrap().relayer.last_heartbeat_age_ms =
+                        Some(last_heartbeat_ts.elapsed().as_millis() as u64);
                     if last_heartbeat_ts.elapsed() > oldest_allowed_heartbeat {
                         return Err(ProxyError::HeartbeatExpired);
                     }
+                    if backoff_reset_tracker.maybe_reset(backoff, last_heartbeat_ts, Instant::now()) {
+                        debug!("relayer connection stable, reset backoff");
+                    }
+                    Self::apply_config_updates(config_update_rx, packet_filter, new_relayer_address);
+                    if new_relayer_address.is_some() {
+                        info!("relayer address updated, disconnecting from packet stream");
+                        return Ok(());
+                    }
                 }
-                _ = metrics_tick.tick() => {
-                    relayer_stats.report();
-                    relayer_stats = RelayerStageStats::default();
-                }
+                _ = exit_check_interval.tick() => {}
             }
         }
 
+        // cancel the subscription instead of leaving it open until the runtime shuts down
+        info!("exiting, closing relayer packet stream");
+        drop(packet_stream);
         Ok(())
     }
 
+    /// Applies queued runtime config changes. A new relayer address is stored in
+    /// `new_relayer_address` so the caller can reconnect.
+    fn apply_config_updates(
+        config_update_rx: &Receiver<MevConfigUpdate>,
+        packet_filter: &mut RelayerPacketFilter,
+        new_relayer_address: &mut Option<RelayerAddressUpdate>,
+    ) {
+        for update in config_update_rx.try_iter() {
+            match update {
+                MevConfigUpdate::RelayerAddress(address) => {
+                    *new_relayer_address = Some(address);
+                }
+                MevConfigUpdate::RelayerPacketRateLimits(limits) => {
+                    info!("relayer packet rate limits updated to {:?}", limits);
+                    packet_filter.rate_limiter.set_limits(limits);
+                }
+                // handled by FetchStageManager
+                MevConfigUpdate::HeartbeatLossPolicy(_) => {}
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn handle_relayer_packets(
         subscribe_packets_resp: relayer::SubscribePacketsResponse,
         heartbeat_event: HeartbeatEvent,
         heartbeat_tx: &Sender<HeartbeatEvent>,
         last_heartbeat_ts: &mut Instant,
-        packet_tx: &Sender<PacketBatch>,
+        packet_tx: &DropOldestSender<PacketBatch>,
         trust_packets: bool,
-        verified_packet_tx: &Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
-        relayer_stats: &mut RelayerStageStats,
+        verified_packet_tx: &DropOldestSender<(
+            Vec<PacketBatch>,
+            Option<SigverifyTracerPacketStats>,
+        )>,
+        relayer_stats: &RelayerStageStats,
+        packet_filter: &mut RelayerPacketFilter,
     ) -> crate::proxy::Result<()> {
         match subscribe_packets_resp.msg {
             None => {
-                saturating_add_assign!(relayer_stats.num_empty_messages, 1);
+                relayer_stats
+                    .num_empty_messages
+                    .fetch_add(1, Ordering::Relaxed);
             }
             Some(relayer::subscribe_packets_response::Msg::Batch(proto_batch)) => {
+                relayer_stats
+                    .num_packets_received
+                    .fetch_add(proto_batch.packets.len() as u64, Ordering::Relaxed);
+
+                let now = Instant::now();
+                if !packet_filter.health.is_healthy(now) {
+                    relayer_stats
+                        .num_unhealthy_packets_dropped
+                        .fetch_add(proto_batch.packets.len() as u64, Ordering::Relaxed);
+                    return Ok(());
+                }
+
+                let mut num_rate_limited_packets = 0;
+                let mut num_duplicate_packets = 0;
+                let mut num_malformed_packets = 0;
+                let mut circuit_breaker_tripped = false;
                 let packet_batch = PacketBatch::new(
                     proto_batch
                         .packets
                         .into_iter()
-                        .map(proto_packet_to_packet)
+                        .filter(|packet| {
+                            let is_malformed = is_malformed_packet(packet);
+                            circuit_breaker_tripped |=
+                                packet_filter.circuit_breaker.record(is_malformed);
+                            if is_malformed {
+                                num_malformed_packets += 1;
+                                return false;
+                            }
+
+                            let is_simple_vote = packet
+                                .meta
+                                .as_ref()
+                                .and_then(|meta| meta.flags.as_ref())
+                                .map(|flags| flags.simple_vote_tx)
+                                .unwrap_or_default();
+                            // duplicates are dropped first so they don't use up the rate limit
+                            if packet_filter.deduper.is_duplicate(&packet.data) {
+                                num_duplicate_packets += 1;
+                                false
+                            } else if !packet_filter.rate_limiter.try_accept(is_simple_vote, now) {
+                                num_rate_limited_packets += 1;
+                                false
+                            } else {
+                                packet_filter.deduper.record(&packet.data);
+                                true
+                            }
+                        })
+                        .map(relayer_proto_packet_to_packet)
                         .collect(),
                 );
+                let num_packets = packet_batch.len() as u64;
+                relayer_stats
+                    .num_rate_limited_packets
+                    .fetch_add(num_rate_limited_packets, Ordering::Relaxed);
+                relayer_stats
+                    .num_duplicate_packets
+                    .fetch_add(num_duplicate_packets, Ordering::Relaxed);
+                relayer_stats
+                    .num_malformed_packets
+                    .fetch_add(num_malformed_packets, Ordering::Relaxed);
+                if circuit_breaker_tripped {
+                    return Err(ProxyError::MalformedPackets);
+                }
+                if packet_batch.is_empty() {
+                    return Ok(());
+                }
 
-                saturating_add_assign!(relayer_stats.num_packets, packet_batch.len() as u64);
-
-                if trust_packets {
-                    verified_packet_tx
-                        .send((vec![packet_batch], None))
-                        .map_err(|_| ProxyError::PacketForwardError)?;
+                let num_batches_dropped = if trust_packets {
+                    verified_packet_tx.send((vec![packet_batch], None))?
                 } else {
-                    packet_tx
-                        .send(packet_batch)
-                        .map_err(|_| ProxyError::PacketForwardError)?;
-                }
+                    packet_tx.send(packet_batch)?
+                };
+                relayer_stats
+                    .num_packets_dropped
+                    .fetch_add(num_batches_dropped as u64, Ordering::Relaxed);
+                relayer_stats
+                    .num_packets_forwarded
+                    .fetch_add(num_packets, Ordering::Relaxed);
             }
             Some(relayer::subscribe_packets_response::Msg::Heartbeat(_)) => {
-                saturating_add_assign!(relayer_stats.num_heartbeats, 1);
+                relayer_stats.num_heartbeats.fetch_add(1, Ordering::Relaxed);
 
                 *last_heartbeat_ts = Instant::now();
+                // relayers don't advertise their heartbeat interval yet, the configured one is used
+                relayer_stats
+                    .heartbeat_gaps
+                    .lock()
+                    .unwrap()
+                    .on_heartbeat(*last_heartbeat_ts, None);
+                packet_filter.health.on_heartbeat(*last_heartbeat_ts);
                 heartbeat_tx
                     .send(heartbeat_event)
                     .map_err(|_| ProxyError::HeartbeatChannelError)?;
@@ -360,3 +1451,1100 @@ impl RelayerStage {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::proxy::{
+            fetch_stage_manager::FetchStageManager,
+            relayer_resolver::SystemResolver,
+            test_utils::{
+                MockConnectRequest, MockHttpProxy, MockRelayer, MockRelayerRequest,
+                MOCK_ACCESS_TOKEN,
+            },
+        },
+        crossbeam_channel::{unbounded, RecvTimeoutError},
+        solana_gossip::contact_info::ContactInfo,
+        solana_perf::packet::Packet,
+        solana_sdk::{
+            pubkey::Pubkey,
+            signature::{Keypair, Signer},
+        },
+        solana_streamer::socket::SocketAddrSpace,
+    };
+
+    // The auth loop waits a few seconds before fetching the first token, so connecting to the
+    // relayer takes a while.
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+    struct TestRelayerStage {
+        relayer_stage: RelayerStage,
+        cluster_info: Arc<ClusterInfo>,
+        heartbeat_rx: Receiver<HeartbeatEvent>,
+        packet_rx: Receiver<PacketBatch>,
+        config_update_tx: Sender<MevConfigUpdate>,
+        mev_status: Arc<RwLock<MevStatus>>,
+        exit: Arc<AtomicBool>,
+    }
+
+    impl TestRelayerStage {
+        fn start(relayer_config: RelayerConfig) -> Self {
+            let keypair = Arc::new(Keypair::new());
+            let cluster_info = Arc::new(ClusterInfo::new(
+                ContactInfo::new_localhost(&keypair.pubkey(), 0),
+                keypair,
+                SocketAddrSpace::Unspecified,
+            ));
+            let (heartbeat_tx, heartbeat_rx) = unbounded();
+            let (packet_tx, packet_rx) = unbounded();
+            let (verified_packet_tx, verified_packet_rx) = unbounded();
+            let (config_update_tx, config_update_rx) = unbounded();
+            let mev_status = Arc::new(RwLock::new(MevStatus::default()));
+            let exit = Arc::new(AtomicBool::new(false));
+
+            let relayer_stage = RelayerStage::new(
+                relayer_config,
+                cluster_info.clone(),
+                heartbeat_tx,
+                DropOldestSender::new(packet_tx, packet_rx.clone(), 10),
+                DropOldestSender::new(verified_packet_tx, verified_packet_rx, 10),
+                config_update_rx,
+                mev_status.clone(),
+                exit.clone(),
+            );
+            Self {
+                relayer_stage,
+                cluster_info,
+                heartbeat_rx,
+                packet_rx,
+                config_update_tx,
+                mev_status,
+                exit,
+            }
+        }
+
+        /// Asserts the stage's threads are joined once `exit` is set, rather than left waiting
+        /// on the relayer.
+        fn exit_and_join(self) {
+            self.exit.store(true, Ordering::Relaxed);
+            let (joined_tx, joined_rx) = unbounded();
+            let relayer_stage = self.relayer_stage;
+            thread::spawn(move || {
+                let _ = joined_tx.send(relayer_stage.join().is_ok());
+            });
+            assert_eq!(joined_rx.recv_timeout(Duration::from_secs(10)), Ok(true));
+        }
+    }
+
+    fn new_test_relayer_config(endpoint: Endpoint) -> RelayerConfig {
+        RelayerConfig {
+            auth_service_endpoint: endpoint.clone(),
+            backend_address: endpoint.into(),
+            expected_heartbeat_interval: Duration::from_millis(500),
+            oldest_allowed_heartbeat: Duration::from_millis(1500),
+            trust_packets: false,
+            heartbeat_loss_policy: HeartbeatLossPolicy::FailOpen,
+            vote_bypass: true,
+            packet_channel_capacity: 10,
+            packet_dedup_capacity: 10,
+            packet_rate_limits: RelayerPacketRateLimits::default(),
+            heartbeats_to_recover: 3,
+            connection_config: RelayerConnectionConfig::default(),
+            circuit_breaker: RelayerCircuitBreakerConfig::default(),
+            resolver: Arc::new(SystemResolver),
+            max_consecutive_connect_failures: 50,
+            degraded_retry_interval: Duration::from_secs(60),
+            backoff_reset_grace_period: DEFAULT_BACKOFF_RESET_GRACE_PERIOD,
+            require_relayer: false,
+            packet_priority_percent: None,
+        }
+    }
+
+    /// Polls `condition` until it's true, returning false if `timeout` elapses first.
+    fn wait_for(condition: impl Fn() -> bool, timeout: Duration) -> bool {
+        let start = Instant::now();
+        while !condition() {
+            if start.elapsed() > timeout {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        true
+    }
+
+    fn new_proto_packet(data: Vec<u8>) -> jito_protos::proto::packet::Packet {
+        jito_protos::proto::packet::Packet {
+            meta: Some(jito_protos::proto::packet::Meta {
+                size: data.len() as u64,
+                ..Default::default()
+            }),
+            data,
+        }
+    }
+
+    fn new_proto_packet_batch(signature_bytes: &[u8]) -> jito_protos::proto::packet::PacketBatch {
+        jito_protos::proto::packet::PacketBatch {
+            packets: signature_bytes
+                .iter()
+                .map(|b| new_proto_packet(packet_data_with_signature(*b)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_backoff_not_reset_while_flapping() {
+        let mut backoff = BackoffStrategy::new();
+        let mut now = Instant::now();
+
+        // the relayer accepts the connection, sends a heartbeat and drops it 100ms later
+        let mut waits = vec![];
+        for _ in 0..6 {
+            let mut tracker = BackoffResetTracker::new(now, DEFAULT_BACKOFF_RESET_GRACE_PERIOD);
+            let heartbeat = now + Duration::from_millis(50);
+            now += Duration::from_millis(100);
+            assert!(!tracker.maybe_reset(&mut backoff, heartbeat, now));
+
+            let wait = backoff.next_wait();
+            waits.push(wait);
+            now += Duration::from_millis(wait);
+        }
+        // the delay between connection attempts keeps growing instead of staying at the minimum
+        assert!(waits.windows(2).all(|w| w[0] < w[1]), "{:?}", waits);
+    }
+
+    #[test]
+    fn test_backoff_reset_after_grace_period() {
+        let mut backoff = BackoffStrategy::new();
+        let first_wait = backoff.next_wait();
+        backoff.next_wait();
+        backoff.next_wait();
+
+        let connected_at = Instant::now();
+        let grace_period = Duration::from_secs(5);
+        let mut tracker = BackoffResetTracker::new(connected_at, grace_period);
+
+        // up long enough but no heartbeat yet
+        assert!(!tracker.maybe_reset(&mut backoff, connected_at, connected_at + grace_period));
+
+        let heartbeat = connected_at + Duration::from_millis(500);
+        assert!(!tracker.maybe_reset(
+            &mut backoff,
+            heartbeat,
+            connected_at + Duration::from_secs(1)
+        ));
+        assert!(tracker.maybe_reset(&mut backoff, heartbeat, connected_at + grace_period));
+        // only resets once per connection
+        assert!(!tracker.maybe_reset(&mut backoff, heartbeat, connected_at + grace_period * 2));
+        assert_eq!(backoff.next_wait(), first_wait);
+    }
+
+    fn packet_data_with_signature(signature_byte: u8) -> Vec<u8> {
+        let mut data = vec![1u8];
+        data.extend_from_slice(&[signature_byte; SIGNATURE_BYTES]);
+        data.extend_from_slice(&[0u8; 100]);
+        data
+    }
+
+    #[test]
+    fn test_relayer_packet_deduper() {
+        let mut packet_deduper = RelayerPacketDeduper::new(10);
+        let mut check_and_record = |data: &[u8]| {
+            let is_duplicate = packet_deduper.is_duplicate(data);
+            packet_deduper.record(data);
+            is_duplicate
+        };
+
+        // a stream where every transaction is echoed back once
+        let stream: Vec<Vec<u8>> = (0..5)
+            .flat_map(|i| vec![packet_data_with_signature(i), packet_data_with_signature(i)])
+            .collect();
+        let num_duplicates = stream.iter().filter(|data| check_and_record(data)).count();
+        assert_eq!(num_duplicates, 5);
+
+        // packets without a full signature are never treated as duplicates
+        assert!(!check_and_record(&[]));
+        assert!(!check_and_record(&[1, 2, 3]));
+        assert!(!check_and_record(&[1, 2, 3]));
+
+        // the oldest signatures are forgotten once capacity is reached
+        for i in 5..15 {
+            assert!(!check_and_record(&packet_data_with_signature(i)));
+        }
+        assert!(!check_and_record(&packet_data_with_signature(0)));
+
+        // packets that are only checked aren't remembered
+        assert!(!packet_deduper.is_duplicate(&packet_data_with_signature(20)));
+        assert!(!packet_deduper.is_duplicate(&packet_data_with_signature(20)));
+    }
+
+    #[test]
+    fn test_handle_relayer_packets_filters_packets() {
+        let (heartbeat_tx, _heartbeat_rx) = crossbeam_channel::unbounded();
+        let (packet_tx, packet_rx) = crossbeam_channel::unbounded();
+        let (verified_packet_tx, verified_packet_rx) = crossbeam_channel::unbounded();
+        let packet_tx = DropOldestSender::new(packet_tx, packet_rx.clone(), 100);
+        let verified_packet_tx =
+            DropOldestSender::new(verified_packet_tx, verified_packet_rx.clone(), 100);
+        let relayer_stats = RelayerStageStats::new(Duration::from_millis(500));
+        let mut packet_filter = RelayerPacketFilter {
+            health: RelayerHealth::new(Duration::from_secs(60), 1),
+            deduper: RelayerPacketDeduper::new(100),
+            rate_limiter: RelayerPacketRateLimiter::new(RelayerPacketRateLimits::default()),
+            circuit_breaker: RelayerCircuitBreaker::new(RelayerCircuitBreakerConfig::default()),
+        };
+        let heartbeat_event = (
+            SocketAddr::from(([127, 0, 0, 1], 1)),
+            SocketAddr::from(([127, 0, 0, 1], 2)),
+        );
+
+        let new_response = |signature_bytes: &[u8]| relayer::SubscribePacketsResponse {
+            msg: Some(relayer::subscribe_packets_response::Msg::Batch(
+                jito_protos::proto::packet::PacketBatch {
+                    packets: signature_bytes
+                        .iter()
+                        .map(|b| new_proto_packet(packet_data_with_signature(*b)))
+                        .collect(),
+                },
+            )),
+            ..Default::default()
+        };
+
+        let heartbeat_response = relayer::SubscribePacketsResponse {
+            msg: Some(relayer::subscribe_packets_response::Msg::Heartbeat(
+                Default::default(),
+            )),
+            ..Default::default()
+        };
+
+        // packets are dropped until the relayer heartbeats, then the second message echoes two
+        // packets from the first one
+        for response in [
+            new_response(&[7, 8]),
+            heartbeat_response,
+            new_response(&[1, 2, 3]),
+            new_response(&[2, 3, 4]),
+        ] {
+            RelayerStage::handle_relayer_packets(
+                response,
+                heartbeat_event,
+                &heartbeat_tx,
+                &mut Instant::now(),
+                &packet_tx,
+                false,
+                &verified_packet_tx,
+                &relayer_stats,
+                &mut packet_filter,
+            )
+            .unwrap();
+        }
+
+        let forwarded_batches: Vec<_> = packet_rx.try_iter().collect();
+        let num_forwarded: usize = forwarded_batches.iter().map(|batch| batch.len()).sum();
+        assert_eq!(num_forwarded, 4);
+        assert!(forwarded_batches
+            .iter()
+            .flat_map(|batch| batch.iter())
+            .all(|packet| packet.meta.is_from_relayer()));
+        let deltas = relayer_stats.take_deltas();
+        assert_eq!(deltas.num_packets_received, 8);
+        assert_eq!(deltas.num_unhealthy_packets_dropped, 2);
+        assert_eq!(deltas.num_heartbeats, 1);
+        assert_eq!(deltas.num_duplicate_packets, 2);
+        assert_eq!(deltas.num_packets_forwarded, 4);
+    }
+
+    #[test]
+    fn test_handle_relayer_packets_duplicates_do_not_use_rate_limit() {
+        let (heartbeat_tx, _heartbeat_rx) = crossbeam_channel::unbounded();
+        let (packet_tx, packet_rx) = crossbeam_channel::unbounded();
+        let (verified_packet_tx, verified_packet_rx) = crossbeam_channel::unbounded();
+        let packet_tx = DropOldestSender::new(packet_tx, packet_rx.clone(), 100);
+        let verified_packet_tx =
+            DropOldestSender::new(verified_packet_tx, verified_packet_rx.clone(), 100);
+        let relayer_stats = RelayerStageStats::new(Duration::from_millis(500));
+        // five packets in total, never refilled
+        let mut packet_filter = RelayerPacketFilter {
+            health: RelayerHealth::new(Duration::from_secs(60), 1),
+            deduper: RelayerPacketDeduper::new(100),
+            rate_limiter: RelayerPacketRateLimiter::new(RelayerPacketRateLimits {
+                packets_per_second: 0,
+                packet_burst: 5,
+                ..RelayerPacketRateLimits::default()
+            }),
+            circuit_breaker: RelayerCircuitBreaker::new(RelayerCircuitBreakerConfig::default()),
+        };
+        let heartbeat_event = (
+            SocketAddr::from(([127, 0, 0, 1], 1)),
+            SocketAddr::from(([127, 0, 0, 1], 2)),
+        );
+
+        let new_response = |signature_bytes: &[u8]| relayer::SubscribePacketsResponse {
+            msg: Some(relayer::subscribe_packets_response::Msg::Batch(
+                jito_protos::proto::packet::PacketBatch {
+                    packets: signature_bytes
+                        .iter()
+                        .map(|b| new_proto_packet(packet_data_with_signature(*b)))
+                        .collect(),
+                },
+            )),
+            ..Default::default()
+        };
+        let heartbeat_response = relayer::SubscribePacketsResponse {
+            msg: Some(relayer::subscribe_packets_response::Msg::Heartbeat(
+                Default::default(),
+            )),
+            ..Default::default()
+        };
+
+        // a burst of echoes of the first three packets leaves room for the next two
+        for response in [
+            heartbeat_response,
+            new_response(&[1, 2, 3]),
+            new_response(&[1, 2, 3, 1, 2, 3, 1, 2, 3]),
+            new_response(&[4, 5]),
+        ] {
+            RelayerStage::handle_relayer_packets(
+                response,
+                heartbeat_event,
+                &heartbeat_tx,
+                &mut Instant::now(),
+                &packet_tx,
+                false,
+                &verified_packet_tx,
+                &relayer_stats,
+                &mut packet_filter,
+            )
+            .unwrap();
+        }
+
+        let num_forwarded: usize = packet_rx.try_iter().map(|batch| batch.len()).sum();
+        assert_eq!(num_forwarded, 5);
+        let deltas = relayer_stats.take_deltas();
+        assert_eq!(deltas.num_duplicate_packets, 9);
+        assert_eq!(deltas.num_rate_limited_packets, 0);
+        assert_eq!(deltas.num_packets_forwarded, 5);
+    }
+
+    #[test]
+    fn test_handle_relayer_packets_resent_rate_limited_packet() {
+        let (heartbeat_tx, _heartbeat_rx) = crossbeam_channel::unbounded();
+        let (packet_tx, packet_rx) = crossbeam_channel::unbounded();
+        let (verified_packet_tx, verified_packet_rx) = crossbeam_channel::unbounded();
+        let packet_tx = DropOldestSender::new(packet_tx, packet_rx.clone(), 100);
+        let verified_packet_tx =
+            DropOldestSender::new(verified_packet_tx, verified_packet_rx.clone(), 100);
+        let relayer_stats = RelayerStageStats::new(Duration::from_millis(500));
+        let rate_limits = RelayerPacketRateLimits {
+            packets_per_second: 0,
+            packet_burst: 2,
+            ..RelayerPacketRateLimits::default()
+        };
+        let mut packet_filter = RelayerPacketFilter {
+            health: RelayerHealth::new(Duration::from_secs(60), 1),
+            deduper: RelayerPacketDeduper::new(100),
+            rate_limiter: RelayerPacketRateLimiter::new(rate_limits),
+            circuit_breaker: RelayerCircuitBreaker::new(RelayerCircuitBreakerConfig::default()),
+        };
+        let heartbeat_event = (
+            SocketAddr::from(([127, 0, 0, 1], 1)),
+            SocketAddr::from(([127, 0, 0, 1], 2)),
+        );
+
+        let new_response = |signature_bytes: &[u8]| relayer::SubscribePacketsResponse {
+            msg: Some(relayer::subscribe_packets_response::Msg::Batch(
+                new_proto_packet_batch(signature_bytes),
+            )),
+            ..Default::default()
+        };
+        let heartbeat_response = relayer::SubscribePacketsResponse {
+            msg: Some(relayer::subscribe_packets_response::Msg::Heartbeat(
+                Default::default(),
+            )),
+            ..Default::default()
+        };
+        let handle = |response, packet_filter: &mut RelayerPacketFilter| {
+            RelayerStage::handle_relayer_packets(
+                response,
+                heartbeat_event,
+                &heartbeat_tx,
+                &mut Instant::now(),
+                &packet_tx,
+                false,
+                &verified_packet_tx,
+                &relayer_stats,
+                packet_filter,
+            )
+            .unwrap();
+        };
+
+        // the third packet doesn't fit in the burst
+        handle(heartbeat_response, &mut packet_filter);
+        handle(new_response(&[1, 2, 3]), &mut packet_filter);
+        let deltas = relayer_stats.take_deltas();
+        assert_eq!(deltas.num_rate_limited_packets, 1);
+        assert_eq!(deltas.num_packets_forwarded, 2);
+
+        // once there's room again, the resent packet is forwarded instead of dropped as a
+        // duplicate, while echoes of the forwarded ones still are
+        packet_filter.rate_limiter = RelayerPacketRateLimiter::new(rate_limits);
+        handle(new_response(&[1, 3]), &mut packet_filter);
+        let deltas = relayer_stats.take_deltas();
+        assert_eq!(deltas.num_duplicate_packets, 1);
+        assert_eq!(deltas.num_rate_limited_packets, 0);
+        assert_eq!(deltas.num_packets_forwarded, 1);
+
+        let forwarded_signature_bytes: Vec<u8> = packet_rx
+            .try_iter()
+            .flat_map(|batch| {
+                batch
+                    .iter()
+                    .map(|packet| *packet.data(1).unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(forwarded_signature_bytes, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_relayer_stage_stats_deltas() {
+        let relayer_stats = RelayerStageStats::new(Duration::from_millis(500));
+        relayer_stats
+            .num_packets_received
+            .fetch_add(10, Ordering::Relaxed);
+        relayer_stats
+            .num_packets_forwarded
+            .fetch_add(8, Ordering::Relaxed);
+        relayer_stats.num_heartbeats.fetch_add(3, Ordering::Relaxed);
+        relayer_stats
+            .num_connect_attempts
+            .fetch_add(2, Ordering::Relaxed);
+        relayer_stats
+            .num_stream_errors
+            .fetch_add(1, Ordering::Relaxed);
+
+        assert_eq!(
+            relayer_stats.take_deltas(),
+            RelayerStageStatsDeltas {
+                num_packets_received: 10,
+                num_packets_forwarded: 8,
+                num_heartbeats: 3,
+                num_connect_attempts: 2,
+                num_stream_errors: 1,
+                ..RelayerStageStatsDeltas::default()
+            }
+        );
+
+        // counters are reset after every report
+        relayer_stats.num_heartbeats.fetch_add(1, Ordering::Relaxed);
+        assert_eq!(
+            relayer_stats.take_deltas(),
+            RelayerStageStatsDeltas {
+                num_heartbeats: 1,
+                ..RelayerStageStatsDeltas::default()
+            }
+        );
+        assert_eq!(
+            relayer_stats.take_deltas(),
+            RelayerStageStatsDeltas::default()
+        );
+    }
+
+    #[test]
+    fn test_relayer_connect_timeout() {
+        // never accepts, so the connection can't complete within the timeout
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint =
+            Endpoint::from_shared(format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let connection_config = RelayerConnectionConfig {
+            connect_timeout: Duration::ZERO,
+            ..RelayerConnectionConfig::default()
+        };
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let result = rt.block_on(connection_config.connect_tcp(
+            &connection_config.apply(endpoint),
+            listener.local_addr().unwrap(),
+        ));
+        assert!(matches!(result, Err(ProxyError::ConnectionTimeout)));
+    }
+
+    #[test]
+    fn test_relayer_stage_exits_promptly() {
+        // accepts connections but never responds, for both auth and packets
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint =
+            Endpoint::from_shared(format!("http://{}", listener.local_addr().unwrap())).unwrap();
+
+        let test_relayer_stage = TestRelayerStage::start(new_test_relayer_config(endpoint));
+        thread::sleep(Duration::from_millis(500));
+        test_relayer_stage.exit_and_join();
+    }
+
+    #[test]
+    fn test_relayer_preflight() {
+        let relayer = MockRelayer::start(Duration::from_millis(100));
+        let keypair = Keypair::new();
+
+        let report =
+            RelayerStage::preflight(&new_test_relayer_config(relayer.endpoint()), &keypair)
+                .unwrap();
+        assert_eq!(report.resolved_addr, Some(relayer.local_addr()));
+        assert!(report.authenticated);
+        assert_eq!(
+            (report.tpu_addr, report.tpu_forward_addr),
+            relayer.tpu_addresses()
+        );
+        assert_eq!(
+            relayer.requests(),
+            vec![
+                MockRelayerRequest::GenerateAuthChallenge {
+                    pubkey: keypair.pubkey().to_bytes().to_vec()
+                },
+                MockRelayerRequest::GenerateAuthTokens {
+                    client_pubkey: keypair.pubkey().to_bytes().to_vec()
+                },
+                MockRelayerRequest::GetTpuConfigs {
+                    authorization: Some(format!("Bearer {}", MOCK_ACCESS_TOKEN))
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_relayer_preflight_unreachable() {
+        // nothing listens on the port once the listener is dropped
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let endpoint = Endpoint::from_shared(format!("http://{}", addr)).unwrap();
+
+        let result = RelayerStage::preflight(&new_test_relayer_config(endpoint), &Keypair::new());
+        assert!(matches!(result, Err(RelayerPreflightError::Connect { .. })));
+    }
+
+    #[test]
+    fn test_relayer_preflight_auth_failure() {
+        let relayer = MockRelayer::start(Duration::from_millis(100));
+        relayer.set_reject_auth(true);
+
+        let result = RelayerStage::preflight(
+            &new_test_relayer_config(relayer.endpoint()),
+            &Keypair::new(),
+        );
+        match result {
+            Err(RelayerPreflightError::Auth {
+                error: ProxyError::GrpcError(status),
+                ..
+            }) => assert_eq!(status.code(), tonic::Code::PermissionDenied),
+            result => panic!("unexpected preflight result: {:?}", result),
+        }
+        // the relayer isn't asked for its tpu configs without a token
+        assert!(!relayer
+            .requests()
+            .iter()
+            .any(|request| matches!(request, MockRelayerRequest::GetTpuConfigs { .. })));
+    }
+
+    #[test]
+    fn test_relayer_preflight_through_http_proxy() {
+        let relayer = MockRelayer::start(Duration::from_millis(100));
+        let proxy_authorization = format!("Basic {}", base64::encode("validator:secret"));
+        let proxy = MockHttpProxy::start(Some(proxy_authorization.clone()));
+
+        // the auth service can only be reached through the proxy, a direct connection fails
+        let auth_authority = format!("auth.relayer.test:{}", relayer.local_addr().port());
+        proxy.add_host(&auth_authority, relayer.local_addr());
+
+        let mut relayer_config = new_test_relayer_config(relayer.endpoint());
+        relayer_config.auth_service_endpoint =
+            Endpoint::from_shared(format!("http://{}", auth_authority)).unwrap();
+        relayer_config.connection_config.http_proxy =
+            Some(HttpProxyConfig::parse(&proxy.url(), Some("validator:secret")).unwrap());
+        let report = RelayerStage::preflight(&relayer_config, &Keypair::new()).unwrap();
+        assert!(report.authenticated);
+        assert_eq!(report.resolved_addr, None);
+        assert_eq!(
+            (report.tpu_addr, report.tpu_forward_addr),
+            relayer.tpu_addresses()
+        );
+        // the tpu configs and the auth tokens were fetched over the tunnel
+        assert_eq!(
+            proxy.requests(),
+            vec![
+                MockConnectRequest {
+                    target: relayer.local_addr().to_string(),
+                    proxy_authorization: Some(proxy_authorization.clone()),
+                },
+                MockConnectRequest {
+                    target: auth_authority,
+                    proxy_authorization: Some(proxy_authorization),
+                },
+            ]
+        );
+        assert!(relayer
+            .requests()
+            .iter()
+            .any(|request| matches!(request, MockRelayerRequest::GetTpuConfigs { .. })));
+    }
+
+    #[test]
+    fn test_relayer_http_proxy_rejects_credentials() {
+        let relayer = MockRelayer::start(Duration::from_millis(100));
+        let proxy = MockHttpProxy::start(Some(format!(
+            "Basic {}",
+            base64::encode("validator:secret")
+        )));
+
+        let mut relayer_config = new_test_relayer_config(relayer.endpoint());
+        relayer_config.connection_config.http_proxy =
+            Some(HttpProxyConfig::parse(&proxy.url(), Some("validator:wrong")).unwrap());
+        match RelayerStage::preflight(&relayer_config, &Keypair::new()) {
+            Err(RelayerPreflightError::Connect {
+                error: ProxyError::HttpProxyConnectError(error),
+                ..
+            }) => assert!(error.contains("407"), "{}", error),
+            result => panic!("unexpected preflight result: {:?}", result),
+        }
+        assert_eq!(proxy.requests().len(), 1);
+    }
+
+    /// Resolves the relayer hostname to whatever address the test points it at.
+    #[derive(Debug)]
+    struct TestResolver {
+        addr: Mutex<SocketAddr>,
+    }
+
+    impl RelayerResolver for TestResolver {
+        fn resolve(&self, host: &str, _port: u16) -> std::io::Result<Vec<SocketAddr>> {
+            assert_eq!(host, "relayer.test");
+            Ok(vec![*self.addr.lock().unwrap()])
+        }
+    }
+
+    #[test]
+    fn test_relayer_stage_follows_dns_change() {
+        let relayer_a = MockRelayer::start(Duration::from_millis(100));
+        let relayer_b = MockRelayer::start(Duration::from_millis(100));
+        let resolver = Arc::new(TestResolver {
+            addr: Mutex::new(relayer_a.local_addr()),
+        });
+        let test_relayer_stage = TestRelayerStage::start(RelayerConfig {
+            backend_address: Endpoint::from_static("http://relayer.test:11226").into(),
+            expected_heartbeat_interval: Duration::from_millis(100),
+            oldest_allowed_heartbeat: Duration::from_millis(500),
+            resolver: resolver.clone(),
+            ..new_test_relayer_config(relayer_a.endpoint())
+        });
+        assert!(wait_for(
+            || relayer_a.num_connections() == 1,
+            CONNECT_TIMEOUT
+        ));
+
+        // the hostname fails over to relayer b while the validator is connected to relayer a
+        *resolver.addr.lock().unwrap() = relayer_b.local_addr();
+        relayer_a.drop_connections();
+        assert!(wait_for(
+            || relayer_b.num_connections() == 1,
+            Duration::from_secs(5)
+        ));
+        assert_eq!(relayer_a.num_connections(), 1);
+
+        test_relayer_stage.exit_and_join();
+    }
+
+    #[test]
+    fn test_relayer_auth_follows_identity_change() {
+        let mock_relayer = MockRelayer::start(Duration::from_millis(100));
+        let test_relayer_stage =
+            TestRelayerStage::start(new_test_relayer_config(mock_relayer.endpoint()));
+        let generated_tokens_for = |identity: Pubkey| {
+            let client_pubkey = identity.to_bytes().to_vec();
+            mock_relayer.requests().into_iter().any(|request| {
+                request
+                    == MockRelayerRequest::GenerateAuthTokens {
+                        client_pubkey: client_pubkey.clone(),
+                    }
+            })
+        };
+
+        let old_identity = test_relayer_stage.cluster_info.id();
+        assert!(wait_for(
+            || generated_tokens_for(old_identity),
+            CONNECT_TIMEOUT
+        ));
+        test_relayer_stage
+            .heartbeat_rx
+            .recv_timeout(CONNECT_TIMEOUT)
+            .unwrap();
+
+        // set-identity swaps the keypair in cluster info while the validator is running
+        let new_keypair = Arc::new(Keypair::new());
+        let new_identity = new_keypair.pubkey();
+        test_relayer_stage.cluster_info.set_keypair(new_keypair);
+        assert!(wait_for(
+            || generated_tokens_for(new_identity),
+            Duration::from_secs(5)
+        ));
+
+        // the packet stream stays up across the rotation
+        assert_eq!(mock_relayer.num_connections(), 1);
+        while test_relayer_stage.heartbeat_rx.try_recv().is_ok() {}
+        test_relayer_stage
+            .heartbeat_rx
+            .recv_timeout(Duration::from_secs(1))
+            .unwrap();
+
+        test_relayer_stage.exit_and_join();
+    }
+
+    #[test]
+    fn test_relayer_address_update_while_waiting_for_auth() {
+        // never accepts, so no access token is ever generated for the initial address
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint =
+            Endpoint::from_shared(format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let test_relayer_stage = TestRelayerStage::start(new_test_relayer_config(endpoint));
+        thread::sleep(Duration::from_millis(500));
+        assert!(test_relayer_stage
+            .heartbeat_rx
+            .recv_timeout(Duration::from_millis(100))
+            .is_err());
+
+        let mock_relayer = MockRelayer::start(Duration::from_millis(100));
+        test_relayer_stage
+            .config_update_tx
+            .send(MevConfigUpdate::RelayerAddress(RelayerAddressUpdate {
+                backend_address: mock_relayer.endpoint().into(),
+                auth_service_endpoint: Some(mock_relayer.endpoint()),
+            }))
+            .unwrap();
+        test_relayer_stage
+            .heartbeat_rx
+            .recv_timeout(CONNECT_TIMEOUT)
+            .unwrap();
+
+        test_relayer_stage.exit_and_join();
+    }
+
+    #[test]
+    fn test_parse_relayer_address() {
+        let address = RelayerConnectionConfig::parse("http://relayer.example.com:11226").unwrap();
+        assert_eq!(address.transport, RelayerTransport::Tcp);
+        assert_eq!(address.endpoint.uri(), "http://relayer.example.com:11226");
+
+        let address = RelayerConnectionConfig::parse("https://relayer.example.com").unwrap();
+        assert_eq!(address.transport, RelayerTransport::Tcp);
+        assert_eq!(address.endpoint.uri().scheme_str(), Some("https"));
+
+        #[cfg(unix)]
+        {
+            let address =
+                RelayerConnectionConfig::parse("unix:///run/relayer/relayer.sock").unwrap();
+            assert_eq!(
+                address.transport,
+                RelayerTransport::Unix(PathBuf::from("/run/relayer/relayer.sock"))
+            );
+            assert_eq!(address.to_string(), "unix:///run/relayer/relayer.sock");
+        }
+        #[cfg(not(unix))]
+        assert!(matches!(
+            RelayerConnectionConfig::parse("unix:///run/relayer/relayer.sock"),
+            Err(ProxyError::InvalidRelayerAddress(_))
+        ));
+
+        for invalid in [
+            "",
+            "relayer.example.com:11226",
+            "ftp://relayer.example.com",
+            "unix://relayer.sock",
+        ] {
+            assert!(
+                matches!(
+                    RelayerConnectionConfig::parse(invalid),
+                    Err(ProxyError::InvalidRelayerAddress(_))
+                ),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_unix_socket_requires_auth() {
+        let unix = RelayerTransport::Unix(PathBuf::from("/run/relayer/relayer.sock"));
+        let connection_config = RelayerConnectionConfig::default();
+        assert!(connection_config.requires_auth(&RelayerTransport::Tcp));
+        assert!(!connection_config.requires_auth(&unix));
+
+        let connection_config = RelayerConnectionConfig {
+            unix_socket_auth: true,
+            ..RelayerConnectionConfig::default()
+        };
+        assert!(connection_config.requires_auth(&unix));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_relayer_stage_over_unix_socket() {
+        let socket_dir = tempfile::tempdir().unwrap();
+        let mock_relayer = MockRelayer::start_unix(
+            Duration::from_millis(100),
+            &socket_dir.path().join("relayer.sock"),
+        );
+        let test_relayer_stage = TestRelayerStage::start(RelayerConfig {
+            backend_address: RelayerConnectionConfig::parse(mock_relayer.address()).unwrap(),
+            expected_heartbeat_interval: Duration::from_millis(100),
+            oldest_allowed_heartbeat: Duration::from_millis(500),
+            heartbeats_to_recover: 1,
+            // the auth service is never reached, the unix socket connection skips auth
+            ..new_test_relayer_config(Endpoint::from_static("http://127.0.0.1:1"))
+        });
+
+        // connects right away instead of waiting for the first access token
+        assert_eq!(
+            test_relayer_stage
+                .heartbeat_rx
+                .recv_timeout(Duration::from_secs(5))
+                .unwrap(),
+            mock_relayer.tpu_addresses()
+        );
+        mock_relayer.send_packets(new_proto_packet_batch(&[1, 2]));
+        assert_eq!(
+            test_relayer_stage
+                .packet_rx
+                .recv_timeout(Duration::from_secs(1))
+                .unwrap()
+                .len(),
+            2
+        );
+
+        for request in mock_relayer.requests() {
+            match request {
+                MockRelayerRequest::GetTpuConfigs { authorization }
+                | MockRelayerRequest::SubscribePackets { authorization } => {
+                    assert_eq!(authorization, None)
+                }
+                request => panic!("unexpected auth request {:?}", request),
+            }
+        }
+
+        test_relayer_stage.exit_and_join();
+    }
+
+    #[test]
+    fn test_relayer_circuit_breaker_drops_malformed_stream() {
+        let mock_relayer = MockRelayer::start(Duration::from_millis(100));
+        let test_relayer_stage = TestRelayerStage::start(RelayerConfig {
+            expected_heartbeat_interval: Duration::from_millis(100),
+            oldest_allowed_heartbeat: Duration::from_millis(500),
+            heartbeats_to_recover: 1,
+            circuit_breaker: RelayerCircuitBreakerConfig {
+                window_size: 10,
+                max_malformed_percent: 50,
+                cooldown: Duration::from_secs(1),
+            },
+            ..new_test_relayer_config(mock_relayer.endpoint())
+        });
+        test_relayer_stage
+            .heartbeat_rx
+            .recv_timeout(CONNECT_TIMEOUT)
+            .unwrap();
+
+        // mostly packets without meta
+        let mut packet_batch = new_proto_packet_batch(&[1, 2]);
+        packet_batch
+            .packets
+            .extend((0..8).map(|_| jito_protos::proto::packet::Packet {
+                data: packet_data_with_signature(3),
+                meta: None,
+            }));
+        mock_relayer.send_packets(packet_batch);
+        let mev_status = &test_relayer_stage.mev_status;
+        assert!(wait_for(
+            || mev_status.read().unwrap().relayer.circuit_breaker_open,
+            Duration::from_secs(5)
+        ));
+        {
+            let mev_status = mev_status.read().unwrap();
+            assert!(!mev_status.relayer.connected);
+            assert_eq!(mev_status.relayer.num_circuit_breaker_trips, 1);
+            assert_eq!(
+                mev_status
+                    .relayer
+                    .recent_disconnects
+                    .iter()
+                    .last()
+                    .unwrap()
+                    .reason,
+                DisconnectReason::MalformedPackets
+            );
+        }
+        // nothing from the tripping batch is forwarded
+        assert!(test_relayer_stage.packet_rx.is_empty());
+
+        // reconnects once the cooldown is over
+        assert!(wait_for(
+            || mock_relayer.num_connections() == 2,
+            Duration::from_secs(5)
+        ));
+        assert!(!mev_status.read().unwrap().relayer.circuit_breaker_open);
+
+        test_relayer_stage.exit_and_join();
+    }
+
+    #[test]
+    fn test_relayer_stage_reconnects_after_drop() {
+        let mock_relayer = MockRelayer::start(Duration::from_millis(100));
+        let test_relayer_stage = TestRelayerStage::start(RelayerConfig {
+            expected_heartbeat_interval: Duration::from_millis(100),
+            oldest_allowed_heartbeat: Duration::from_millis(500),
+            heartbeats_to_recover: 1,
+            ..new_test_relayer_config(mock_relayer.endpoint())
+        });
+
+        assert_eq!(
+            test_relayer_stage
+                .heartbeat_rx
+                .recv_timeout(CONNECT_TIMEOUT)
+                .unwrap(),
+            mock_relayer.tpu_addresses()
+        );
+        mock_relayer.send_packets(new_proto_packet_batch(&[1, 2]));
+        assert_eq!(
+            test_relayer_stage
+                .packet_rx
+                .recv_timeout(Duration::from_secs(1))
+                .unwrap()
+                .len(),
+            2
+        );
+
+        mock_relayer.drop_connections();
+        assert!(wait_for(
+            || mock_relayer.num_connections() >= 2,
+            Duration::from_secs(5)
+        ));
+
+        // packets are accepted again once the new stream heartbeats
+        while test_relayer_stage.heartbeat_rx.try_recv().is_ok() {}
+        test_relayer_stage
+            .heartbeat_rx
+            .recv_timeout(Duration::from_secs(1))
+            .unwrap();
+        mock_relayer.send_packets(new_proto_packet_batch(&[3, 4]));
+        assert_eq!(
+            test_relayer_stage
+                .packet_rx
+                .recv_timeout(Duration::from_secs(1))
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // relayer requests carry the access token handed out by the auth service
+        let requests = mock_relayer.requests();
+        assert!(requests
+            .iter()
+            .any(|request| matches!(request, MockRelayerRequest::GenerateAuthTokens { .. })));
+        let expected_authorization = format!("Bearer {}", MOCK_ACCESS_TOKEN);
+        for request in requests {
+            if let MockRelayerRequest::GetTpuConfigs { authorization }
+            | MockRelayerRequest::SubscribePackets { authorization } = request
+            {
+                assert_eq!(authorization, Some(expected_authorization.clone()));
+            }
+        }
+
+        // shuts down promptly while streaming too
+        test_relayer_stage.exit_and_join();
+    }
+
+    #[test]
+    fn test_relayer_stage_heartbeat_loss_fail_open() {
+        let mock_relayer = MockRelayer::start(Duration::from_millis(100));
+        let TestRelayerStage {
+            relayer_stage,
+            heartbeat_rx,
+            packet_rx: relayer_packet_rx,
+            exit,
+            ..
+        } = TestRelayerStage::start(RelayerConfig {
+            expected_heartbeat_interval: Duration::from_millis(100),
+            oldest_allowed_heartbeat: Duration::from_millis(500),
+            heartbeats_to_recover: 1,
+            ..new_test_relayer_config(mock_relayer.endpoint())
+        });
+
+        let keypair = Arc::new(Keypair::new());
+        let cluster_info = Arc::new(ClusterInfo::new(
+            ContactInfo::new_localhost(&keypair.pubkey(), 0),
+            keypair,
+            SocketAddrSpace::Unspecified,
+        ));
+        let (packet_intercept_tx, packet_intercept_rx) = unbounded();
+        let (local_packet_tx, local_packet_rx) = unbounded();
+        let (_config_update_tx, config_update_rx) = unbounded();
+        let (relayer_config_update_tx, _relayer_config_update_rx) = unbounded();
+        let fetch_stage_manager = FetchStageManager::new(
+            cluster_info,
+            heartbeat_rx,
+            packet_intercept_rx,
+            local_packet_tx,
+            HeartbeatLossPolicy::FailOpen,
+            true,
+            config_update_rx,
+            relayer_config_update_tx,
+            Arc::default(),
+            exit.clone(),
+        );
+
+        assert!(wait_for(
+            || mock_relayer.num_connections() >= 1,
+            CONNECT_TIMEOUT
+        ));
+        thread::sleep(Duration::from_millis(300));
+
+        // the stream is torn down and re-established once heartbeats stop
+        mock_relayer.set_heartbeat_interval(None);
+        assert!(wait_for(
+            || mock_relayer.num_connections() >= 2,
+            Duration::from_secs(5)
+        ));
+
+        // packets from the relayer are dropped while it isn't heartbeating, but packets on the
+        // local TPU ports keep flowing
+        mock_relayer.send_packets(new_proto_packet_batch(&[5]));
+        assert!(matches!(
+            relayer_packet_rx.recv_timeout(Duration::from_millis(300)),
+            Err(RecvTimeoutError::Timeout)
+        ));
+        packet_intercept_tx
+            .send(PacketBatch::new(vec![Packet::default()]))
+            .unwrap();
+        assert_eq!(
+            local_packet_rx
+                .recv_timeout(Duration::from_secs(1))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // relayer packets are accepted again once heartbeats resume
+        mock_relayer.set_heartbeat_interval(Some(Duration::from_millis(100)));
+        thread::sleep(Duration::from_millis(300));
+        mock_relayer.send_packets(new_proto_packet_batch(&[6]));
+        assert_eq!(
+            relayer_packet_rx
+                .recv_timeout(Duration::from_secs(1))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        exit.store(true, Ordering::Relaxed);
+        relayer_stage.join().unwrap();
+        fetch_stage_manager.join().unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+//! Limits how long the relayer stage keeps retrying a relayer that never comes up.
+//!
+//! After too many consecutive connection failures the relayer is considered degraded: the
+//! validator processes packets from its own TPU ports regardless of the heartbeat loss policy and
+//! only re-attempts the relayer at a long interval.
+
+use std::time::{Duration, Instant};
+
+/// Default number of consecutive failed connection attempts before falling back to the local TPU.
+pub const DEFAULT_MAX_CONSECUTIVE_CONNECT_FAILURES: usize = 50;
+
+/// Default time between connection attempts while degraded.
+pub const DEFAULT_DEGRADED_RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+const DEGRADED_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+
+pub(crate) struct RelayerRetryBudget {
+    max_consecutive_failures: usize,
+    consecutive_failures: usize,
+    degraded: bool,
+    last_warning: Option<Instant>,
+}
+
+impl RelayerRetryBudget {
+    pub(crate) fn new(max_consecutive_failures: usize) -> Self {
+        Self {
+            max_consecutive_failures: max_consecutive_failures.max(1),
+            consecutive_failures: 0,
+            degraded: false,
+            last_warning: None,
+        }
+    }
+
+    /// Returns true if this success ends degraded mode.
+    pub(crate) fn on_connected(&mut self) -> bool {
+        let was_degraded = self.degraded;
+        self.consecutive_failures = 0;
+        self.degraded = false;
+        self.last_warning = None;
+        was_degraded
+    }
+
+    /// Returns true if this failure starts degraded mode.
+    pub(crate) fn on_failure(&mut self) -> bool {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if !self.degraded && self.consecutive_failures >= self.max_consecutive_failures {
+            self.degraded = true;
+            return true;
+        }
+        false
+    }
+
+    pub(crate) fn is_degraded(&self) -> bool {
+        self.degraded
+    }
+
+    pub(crate) fn consecutive_failures(&self) -> usize {
+        self.consecutive_failures
+    }
+
+    /// Rate limits the degraded mode warning to once a minute.
+    pub(crate) fn should_warn(&mut self, now: Instant) -> bool {
+        if !self.degraded {
+            return false;
+        }
+        let should_warn = self
+            .last_warning
+            .map(|last_warning| {
+                now.saturating_duration_since(last_warning) >= DEGRADED_WARNING_INTERVAL
+            })
+            .unwrap_or(true);
+        if should_warn {
+            self.last_warning = Some(now);
+        }
+        should_warn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_budget_always_failing() {
+        let mut retry_budget = RelayerRetryBudget::new(50);
+        for _ in 0..49 {
+            assert!(!retry_budget.on_failure());
+            assert!(!retry_budget.is_degraded());
+        }
+        assert!(retry_budget.on_failure());
+        assert!(retry_budget.is_degraded());
+
+        // stays degraded without re-announcing it
+        for _ in 0..100 {
+            assert!(!retry_budget.on_failure());
+            assert!(retry_budget.is_degraded());
+        }
+        assert_eq!(retry_budget.consecutive_failures(), 150);
+
+        assert!(retry_budget.on_connected());
+        assert!(!retry_budget.is_degraded());
+        assert!(!retry_budget.on_connected());
+    }
+
+    #[test]
+    fn test_retry_budget_success_resets_failures() {
+        let mut retry_budget = RelayerRetryBudget::new(3);
+        retry_budget.on_failure();
+        retry_budget.on_failure();
+        assert!(!retry_budget.on_connected());
+        retry_budget.on_failure();
+        retry_budget.on_failure();
+        assert!(!retry_budget.is_degraded());
+        assert!(retry_budget.on_failure());
+    }
+
+    #[test]
+    fn test_retry_budget_warns_once_per_minute() {
+        let mut retry_budget = RelayerRetryBudget::new(1);
+        let now = Instant::now();
+        assert!(!retry_budget.should_warn(now));
+
+        retry_budget.on_failure();
+        assert!(retry_budget.should_warn(now));
+        assert!(!retry_budget.should_warn(now + Duration::from_secs(30)));
+        assert!(retry_budget.should_warn(now + DEGRADED_WARNING_INTERVAL));
+    }
+}
@@ -0,0 +1,764 @@
+//! Mock relayer and block engine for testing the proxy connections without running the real
+//! services.
+//!
+//! [`MockRelayer`] serves the auth and relayer gRPC services on a local port or Unix domain
+//! socket. Tests script what the validator sees (packet batches, heartbeat cadence, dropped
+//! connections) and inspect every request the validator made. [`MockBlockEngine`] serves the auth
+//! and block engine services and lets tests end the packet and bundle streams separately.
+//! [`MockHttpProxy`] tunnels CONNECT requests for testing connections through an HTTP proxy.
+
+use {
+    chrono::Utc,
+    jito_protos::proto::{
+        auth::{
+            auth_service_server::{AuthService, AuthServiceServer},
+            GenerateAuthChallengeRequest, GenerateAuthChallengeResponse, GenerateAuthTokensRequest,
+            GenerateAuthTokensResponse, RefreshAccessTokenRequest, RefreshAccessTokenResponse,
+            Token,
+        },
+        block_engine::{
+            self,
+            block_engine_validator_server::{BlockEngineValidator, BlockEngineValidatorServer},
+            BlockBuilderFeeInfoRequest, BlockBuilderFeeInfoResponse, SubscribeBundlesRequest,
+            SubscribeBundlesResponse,
+        },
+        packet::PacketBatch as ProtoPacketBatch,
+        relayer::{
+            relayer_server::{Relayer, RelayerServer},
+            subscribe_packets_response::Msg,
+            GetTpuConfigsRequest, GetTpuConfigsResponse, SubscribePacketsRequest,
+            SubscribePacketsResponse,
+        },
+        shared::{Heartbeat, Socket},
+    },
+    std::{
+        collections::HashMap,
+        net::{SocketAddr, TcpListener},
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        thread::{Builder, JoinHandle},
+        time::Duration,
+    },
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        sync::{
+            mpsc::{self, error::TrySendError},
+            oneshot,
+        },
+        time::sleep,
+    },
+    tokio_stream::wrappers::ReceiverStream,
+    tonic::{
+        transport::{Endpoint, Server},
+        Request, Response, Status,
+    },
+};
+#[cfg(unix)]
+use {
+    std::{os::unix::net::UnixListener as StdUnixListener, path::Path},
+    tokio::net::UnixListener,
+    tokio_stream::wrappers::UnixListenerStream,
+};
+
+/// Access token handed out to the validator; relayer requests carry it as a bearer token.
+pub const MOCK_ACCESS_TOKEN: &str = "mock-relayer-access-token";
+const MOCK_REFRESH_TOKEN: &str = "mock-relayer-refresh-token";
+// Long enough that the validator never tries to refresh during a test.
+const TOKEN_TTL: Duration = Duration::from_secs(60 * 60);
+const SUBSCRIBER_CHANNEL_CAPACITY: usize = 1_000;
+// How often the heartbeat loop checks whether heartbeats were resumed.
+const HEARTBEAT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A request the validator sent to the mock relayer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MockRelayerRequest {
+    GenerateAuthChallenge { pubkey: Vec<u8> },
+    GenerateAuthTokens { client_pubkey: Vec<u8> },
+    RefreshAccessToken { refresh_token: String },
+    GetTpuConfigs { authorization: Option<String> },
+    SubscribePackets { authorization: Option<String> },
+}
+
+type SubscribePacketsSender = mpsc::Sender<Result<SubscribePacketsResponse, Status>>;
+
+struct MockRelayerState {
+    tpu_addresses: Mutex<(SocketAddr, SocketAddr)>,
+    heartbeat_interval: Mutex<Option<Duration>>,
+    subscribers: Mutex<Vec<SubscribePacketsSender>>,
+    requests: Mutex<Vec<MockRelayerRequest>>,
+    reject_auth: AtomicBool,
+    shutting_down: AtomicBool,
+}
+
+impl MockRelayerState {
+    fn new(heartbeat_interval: Option<Duration>) -> Self {
+        Self {
+            tpu_addresses: Mutex::new((
+                SocketAddr::from(([127, 0, 0, 1], 11_222)),
+                SocketAddr::from(([127, 0, 0, 1], 11_223)),
+            )),
+            heartbeat_interval: Mutex::new(heartbeat_interval),
+            subscribers: Mutex::default(),
+            requests: Mutex::default(),
+            reject_auth: AtomicBool::new(false),
+            shutting_down: AtomicBool::new(false),
+        }
+    }
+
+    fn record(&self, request: MockRelayerRequest) {
+        self.requests.lock().unwrap().push(request);
+    }
+
+    /// Sends `msg` to every open packet stream, forgetting streams the validator closed.
+    fn broadcast(&self, msg: Msg) {
+        self.subscribers.lock().unwrap().retain(|subscriber| {
+            let resp = SubscribePacketsResponse {
+                msg: Some(msg.clone()),
+                ..SubscribePacketsResponse::default()
+            };
+            !matches!(subscriber.try_send(Ok(resp)), Err(TrySendError::Closed(_)))
+        });
+    }
+}
+
+#[derive(Clone)]
+struct MockRelayerService {
+    state: Arc<MockRelayerState>,
+}
+
+#[tonic::async_trait]
+impl AuthService for MockRelayerService {
+    async fn generate_auth_challenge(
+        &self,
+        request: Request<GenerateAuthChallengeRequest>,
+    ) -> Result<Response<GenerateAuthChallengeResponse>, Status> {
+        self.state
+            .record(MockRelayerRequest::GenerateAuthChallenge {
+                pubkey: request.into_inner().pubkey,
+            });
+        if self.state.reject_auth.load(Ordering::Relaxed) {
+            return Err(Status::permission_denied("validator not whitelisted"));
+        }
+        Ok(Response::new(GenerateAuthChallengeResponse {
+            challenge: "mock-challenge".to_string(),
+        }))
+    }
+
+    async fn generate_auth_tokens(
+        &self,
+        request: Request<GenerateAuthTokensRequest>,
+    ) -> Result<Response<GenerateAuthTokensResponse>, Status> {
+        self.state.record(MockRelayerRequest::GenerateAuthTokens {
+            client_pubkey: request.into_inner().client_pubkey,
+        });
+        Ok(Response::new(GenerateAuthTokensResponse {
+            access_token: Some(new_token(MOCK_ACCESS_TOKEN)),
+            refresh_token: Some(new_token(MOCK_REFRESH_TOKEN)),
+        }))
+    }
+
+    async fn refresh_access_token(
+        &self,
+        request: Request<RefreshAccessTokenRequest>,
+    ) -> Result<Response<RefreshAccessTokenResponse>, Status> {
+        self.state.record(MockRelayerRequest::RefreshAccessToken {
+            refresh_token: request.into_inner().refresh_token,
+        });
+        Ok(Response::new(RefreshAccessTokenResponse {
+            access_token: Some(new_token(MOCK_ACCESS_TOKEN)),
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl Relayer for MockRelayerService {
+    async fn get_tpu_configs(
+        &self,
+        request: Request<GetTpuConfigsRequest>,
+    ) -> Result<Response<GetTpuConfigsResponse>, Status> {
+        self.state.record(MockRelayerRequest::GetTpuConfigs {
+            authorization: authorization(&request),
+        });
+        let (tpu, tpu_forward) = *self.state.tpu_addresses.lock().unwrap();
+        Ok(Response::new(GetTpuConfigsResponse {
+            tpu: Some(to_socket(tpu)),
+            tpu_forward: Some(to_socket(tpu_forward)),
+        }))
+    }
+
+    type SubscribePacketsStream = ReceiverStream<Result<SubscribePacketsResponse, Status>>;
+
+    async fn subscribe_packets(
+        &self,
+        request: Request<SubscribePacketsRequest>,
+    ) -> Result<Response<Self::SubscribePacketsStream>, Status> {
+        self.state.record(MockRelayerRequest::SubscribePackets {
+            authorization: authorization(&request),
+        });
+        if self.state.shutting_down.load(Ordering::Relaxed) {
+            return Err(Status::unavailable("mock relayer shutting down"));
+        }
+        let (sender, receiver) = mpsc::channel(SUBSCRIBER_CHANNEL_CAPACITY);
+        self.state.subscribers.lock().unwrap().push(sender);
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
+
+fn new_token(value: &str) -> Token {
+    Token {
+        value: value.to_string(),
+        expires_at_utc: Some(prost_types::Timestamp {
+            seconds: Utc::now().timestamp() + TOKEN_TTL.as_secs() as i64,
+            nanos: 0,
+        }),
+    }
+}
+
+fn authorization<T>(request: &Request<T>) -> Option<String> {
+    request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+fn to_socket(addr: SocketAddr) -> Socket {
+    Socket {
+        ip: addr.ip().to_string(),
+        port: addr.port().into(),
+    }
+}
+
+async fn heartbeat_loop(state: Arc<MockRelayerState>) {
+    loop {
+        let heartbeat_interval = *state.heartbeat_interval.lock().unwrap();
+        match heartbeat_interval {
+            Some(heartbeat_interval) => {
+                sleep(heartbeat_interval).await;
+                state.broadcast(Msg::Heartbeat(Heartbeat::default()));
+            }
+            None => sleep(HEARTBEAT_POLL_INTERVAL).await,
+        }
+    }
+}
+
+enum MockRelayerListener {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(StdUnixListener),
+}
+
+/// Serves the auth and relayer services on a local port or Unix domain socket until dropped.
+pub struct MockRelayer {
+    addr: Option<SocketAddr>,
+    address: String,
+    state: Arc<MockRelayerState>,
+    shutdown_tx: Option<oneshot::Sender<()>>,
+    server_thread: Option<JoinHandle<()>>,
+}
+
+impl MockRelayer {
+    /// Starts the mock relayer, sending heartbeats every `heartbeat_interval` on each open packet
+    /// stream.
+    pub fn start(heartbeat_interval: Duration) -> Self {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        Self::spawn(
+            heartbeat_interval,
+            MockRelayerListener::Tcp(addr),
+            format!("http://{}", addr),
+        )
+    }
+
+    /// Starts the mock relayer on a Unix domain socket at `path`. The socket is bound before this
+    /// returns.
+    #[cfg(unix)]
+    pub fn start_unix(heartbeat_interval: Duration, path: &Path) -> Self {
+        let listener = StdUnixListener::bind(path).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        Self::spawn(
+            heartbeat_interval,
+            MockRelayerListener::Unix(listener),
+            format!("unix://{}", path.display()),
+        )
+    }
+
+    fn spawn(heartbeat_interval: Duration, listener: MockRelayerListener, address: String) -> Self {
+        let addr = match &listener {
+            MockRelayerListener::Tcp(addr) => Some(*addr),
+            #[cfg(unix)]
+            MockRelayerListener::Unix(_) => None,
+        };
+        let state = Arc::new(MockRelayerState::new(Some(heartbeat_interval)));
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+
+        let server_thread = {
+            let service = MockRelayerService {
+                state: state.clone(),
+            };
+            Builder::new()
+                .name("mock-relayer".into())
+                .spawn(move || {
+                    let rt = tokio::runtime::Builder::new_multi_thread()
+                        .enable_all()
+                        .build()
+                        .unwrap();
+                    rt.spawn(heartbeat_loop(service.state.clone()));
+                    let router = Server::builder()
+                        .add_service(AuthServiceServer::new(service.clone()))
+                        .add_service(RelayerServer::new(service));
+                    let shutdown = async {
+                        let _ = shutdown_rx.await;
+                    };
+                    match listener {
+                        MockRelayerListener::Tcp(addr) => {
+                            rt.block_on(router.serve_with_shutdown(addr, shutdown))
+                        }
+                        #[cfg(unix)]
+                        MockRelayerListener::Unix(listener) => {
+                            let listener = {
+                                let _guard = rt.enter();
+                                UnixListener::from_std(listener).unwrap()
+                            };
+                            rt.block_on(router.serve_with_incoming_shutdown(
+                                UnixListenerStream::new(listener),
+                                shutdown,
+                            ))
+                        }
+                    }
+                    .unwrap();
+                })
+                .unwrap()
+        };
+
+        Self {
+            addr,
+            address,
+            state,
+            shutdown_tx: Some(shutdown_tx),
+            server_thread: Some(server_thread),
+        }
+    }
+
+    /// Endpoint serving both the auth and relayer services. Panics if the mock relayer listens on
+    /// a Unix domain socket; use [`Self::address`] instead.
+    pub fn endpoint(&self) -> Endpoint {
+        assert!(self.addr.is_some(), "mock relayer listens on a unix socket");
+        Endpoint::from_shared(self.address.clone()).unwrap()
+    }
+
+    /// Address of the mock relayer as it would be passed to `--relayer-address`.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Local address the mock relayer listens on. Panics if it listens on a Unix domain socket.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr.expect("mock relayer listens on a unix socket")
+    }
+
+    /// TPU and TPU forward addresses returned to the validator.
+    pub fn tpu_addresses(&self) -> (SocketAddr, SocketAddr) {
+        *self.state.tpu_addresses.lock().unwrap()
+    }
+
+    /// Changes the TPU addresses returned to the validator, which picks them up the next time it
+    /// connects. Only IPv4 addresses are supported.
+    pub fn set_tpu_addresses(&self, tpu_addresses: (SocketAddr, SocketAddr)) {
+        *self.state.tpu_addresses.lock().unwrap() = tpu_addresses;
+    }
+
+    /// Sends `packet_batch` on every open packet stream.
+    pub fn send_packets(&self, packet_batch: ProtoPacketBatch) {
+        self.state.broadcast(Msg::Batch(packet_batch));
+    }
+
+    /// Changes the heartbeat cadence; `None` stops sending heartbeats.
+    pub fn set_heartbeat_interval(&self, heartbeat_interval: Option<Duration>) {
+        *self.state.heartbeat_interval.lock().unwrap() = heartbeat_interval;
+    }
+
+    /// Makes the auth service reject every auth challenge request.
+    pub fn set_reject_auth(&self, reject_auth: bool) {
+        self.state.reject_auth.store(reject_auth, Ordering::Relaxed);
+    }
+
+    /// Ends every open packet stream. The validator sees the stream disconnect and reconnects.
+    pub fn drop_connections(&self) {
+        self.state.subscribers.lock().unwrap().clear();
+    }
+
+    /// Number of packet streams the validator has opened.
+    pub fn num_connections(&self) -> usize {
+        self.requests()
+            .iter()
+            .filter(|request| matches!(request, MockRelayerRequest::SubscribePackets { .. }))
+            .count()
+    }
+
+    /// Every request received so far, in order.
+    pub fn requests(&self) -> Vec<MockRelayerRequest> {
+        self.state.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockRelayer {
+    fn drop(&mut self) {
+        // open packet streams would otherwise keep the graceful shutdown waiting forever
+        self.state.shutting_down.store(true, Ordering::Relaxed);
+        self.drop_connections();
+        if let Some(shutdown_tx) = self.shutdown_tx.take() {
+            let _ = shutdown_tx.send(());
+        }
+        if let Some(server_thread) = self.server_thread.take() {
+            server_thread.join().unwrap();
+        }
+    }
+}
+
+type BlockEnginePacketSender = mpsc::Sender<Result<block_engine::SubscribePacketsResponse, Status>>;
+type BlockEngineBundleSender = mpsc::Sender<Result<SubscribeBundlesResponse, Status>>;
+
+#[derive(Default)]
+struct MockBlockEngineState {
+    packet_subscribers: Mutex<Vec<BlockEnginePacketSender>>,
+    bundle_subscribers: Mutex<Vec<BlockEngineBundleSender>>,
+    num_packet_subscriptions: AtomicUsize,
+    num_bundle_subscriptions: AtomicUsize,
+    block_builder_fee_info: Mutex<BlockBuilderFeeInfoResponse>,
+    shutting_down: AtomicBool,
+}
+
+#[derive(Clone)]
+struct MockBlockEngineService {
+    state: Arc<MockBlockEngineState>,
+}
+
+#[tonic::async_trait]
+impl BlockEngineValidator for MockBlockEngineService {
+    type SubscribePacketsStream =
+        ReceiverStream<Result<block_engine::SubscribePacketsResponse, Status>>;
+
+    async fn subscribe_packets(
+        &self,
+        _request: Request<block_engine::SubscribePacketsRequest>,
+    ) -> Result<Response<Self::SubscribePacketsStream>, Status> {
+        self.state
+            .num_packet_subscriptions
+            .fetch_add(1, Ordering::Relaxed);
+        if self.state.shutting_down.load(Ordering::Relaxed) {
+            return Err(Status::unavailable("mock block engine shutting down"));
+        }
+        let (sender, receiver) = mpsc::channel(SUBSCRIBER_CHANNEL_CAPACITY);
+        self.state.packet_subscribers.lock().unwrap().push(sender);
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    type SubscribeBundlesStream = ReceiverStream<Result<SubscribeBundlesResponse, Status>>;
+
+    async fn subscribe_bundles(
+        &self,
+        _request: Request<SubscribeBundlesRequest>,
+    ) -> Result<Response<Self::SubscribeBundlesStream>, Status> {
+        self.state
+            .num_bundle_subscriptions
+            .fetch_add(1, Ordering::Relaxed);
+        if self.state.shutting_down.load(Ordering::Relaxed) {
+            return Err(Status::unavailable("mock block engine shutting down"));
+        }
+        let (sender, receiver) = mpsc::channel(SUBSCRIBER_CHANNEL_CAPACITY);
+        self.state.bundle_subscribers.lock().unwrap().push(sender);
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn get_block_builder_fee_info(
+        &self,
+        _request: Request<BlockBuilderFeeInfoRequest>,
+    ) -> Result<Response<BlockBuilderFeeInfoResponse>, Status> {
+        Ok(Response::new(
+            self.state.block_builder_fee_info.lock().unwrap().clone(),
+        ))
+    }
+}
+
+/// Serves the auth and block engine services on a local port until dropped.
+pub struct MockBlockEngine {
+    addr: SocketAddr,
+    state: Arc<MockBlockEngineState>,
+    shutdown_tx: Option<oneshot::Sender<()>>,
+    server_thread: Option<JoinHandle<()>>,
+}
+
+impl MockBlockEngine {
+    pub fn start() -> Self {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let state = Arc::new(MockBlockEngineState::default());
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+
+        let server_thread = {
+            let service = MockBlockEngineService {
+                state: state.clone(),
+            };
+            // only the auth service is used, the relayer service isn't served
+            let auth_service = MockRelayerService {
+                state: Arc::new(MockRelayerState::new(None)),
+            };
+            Builder::new()
+                .name("mock-block-engine".into())
+                .spawn(move || {
+                    let rt = tokio::runtime::Builder::new_multi_thread()
+                        .enable_all()
+                        .build()
+                        .unwrap();
+                    rt.block_on(
+                        Server::builder()
+                            .add_service(AuthServiceServer::new(auth_service))
+                            .add_service(BlockEngineValidatorServer::new(service))
+                            .serve_with_shutdown(addr, async {
+                                let _ = shutdown_rx.await;
+                            }),
+                    )
+                    .unwrap();
+                })
+                .unwrap()
+        };
+
+        Self {
+            addr,
+            state,
+            shutdown_tx: Some(shutdown_tx),
+            server_thread: Some(server_thread),
+        }
+    }
+
+    /// Endpoint serving both the auth and block engine services.
+    pub fn endpoint(&self) -> Endpoint {
+        Endpoint::from_shared(format!("http://{}", self.addr)).unwrap()
+    }
+
+    /// Sends `packet_batch` on every open packet stream.
+    pub fn send_packets(&self, packet_batch: ProtoPacketBatch) {
+        self.state
+            .packet_subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| {
+                let resp = block_engine::SubscribePacketsResponse {
+                    batch: Some(packet_batch.clone()),
+                    ..block_engine::SubscribePacketsResponse::default()
+                };
+                !matches!(subscriber.try_send(Ok(resp)), Err(TrySendError::Closed(_)))
+            });
+    }
+
+    /// Fails every open bundle stream with a server-side error, leaving the packet streams open.
+    pub fn fail_bundle_streams(&self) {
+        for subscriber in self.state.bundle_subscribers.lock().unwrap().drain(..) {
+            let _ = subscriber.try_send(Err(Status::internal("mock bundle stream failure")));
+        }
+    }
+
+    /// Sets the block builder fee info returned from now on. The validator fetches it whenever
+    /// it opens a bundle stream.
+    pub fn set_block_builder_fee_info(&self, pubkey: String, commission: u64) {
+        *self.state.block_builder_fee_info.lock().unwrap() =
+            BlockBuilderFeeInfoResponse { pubkey, commission };
+    }
+
+    /// Number of packet streams the validator has opened.
+    pub fn num_packet_subscriptions(&self) -> usize {
+        self.state.num_packet_subscriptions.load(Ordering::Relaxed)
+    }
+
+    /// Number of bundle streams the validator has opened.
+    pub fn num_bundle_subscriptions(&self) -> usize {
+        self.state.num_bundle_subscriptions.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for MockBlockEngine {
+    fn drop(&mut self) {
+        // open streams would otherwise keep the graceful shutdown waiting forever
+        self.state.shutting_down.store(true, Ordering::Relaxed);
+        self.state.packet_subscribers.lock().unwrap().clear();
+        self.state.bundle_subscribers.lock().unwrap().clear();
+        if let Some(shutdown_tx) = self.shutdown_tx.take() {
+            let _ = shutdown_tx.send(());
+        }
+        if let Some(server_thread) = self.server_thread.take() {
+            server_thread.join().unwrap();
+        }
+    }
+}
+
+/// CONNECT request received by [`MockHttpProxy`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockConnectRequest {
+    /// `host:port` the client asked to tunnel to.
+    pub target: String,
+    pub proxy_authorization: Option<String>,
+}
+
+/// HTTP proxy that tunnels CONNECT requests to their target until dropped.
+pub struct MockHttpProxy {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<MockConnectRequest>>>,
+    hosts: Arc<Mutex<HashMap<String, SocketAddr>>>,
+    shutdown_tx: Option<oneshot::Sender<()>>,
+    server_thread: Option<JoinHandle<()>>,
+}
+
+impl MockHttpProxy {
+    /// Starts the proxy. If `proxy_authorization` is set, CONNECT requests without exactly that
+    /// `Proxy-Authorization` header are refused with a 407.
+    pub fn start(proxy_authorization: Option<String>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let hosts = Arc::new(Mutex::new(HashMap::new()));
+        let (shutdown_tx, mut shutdown_rx) = oneshot::channel();
+
+        let server_thread = {
+            let requests = requests.clone();
+            let hosts = hosts.clone();
+            Builder::new()
+                .name("mock-http-proxy".into())
+                .spawn(move || {
+                    let rt = tokio::runtime::Builder::new_multi_thread()
+                        .enable_all()
+                        .build()
+                        .unwrap();
+                    rt.block_on(async move {
+                        let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                        loop {
+                            tokio::select! {
+                                result = listener.accept() => {
+                                    let (stream, _) = result.unwrap();
+                                    tokio::spawn(mock_http_proxy_tunnel(
+                                        stream,
+                                        proxy_authorization.clone(),
+                                        requests.clone(),
+                                        hosts.clone(),
+                                    ));
+                                }
+                                _ = &mut shutdown_rx => break,
+                            }
+                        }
+                    });
+                })
+                .unwrap()
+        };
+
+        Self {
+            addr,
+            requests,
+            hosts,
+            shutdown_tx: Some(shutdown_tx),
+            server_thread: Some(server_thread),
+        }
+    }
+
+    /// Url of the proxy as it would be passed to `--relayer-http-proxy`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Every CONNECT request received so far, in order.
+    pub fn requests(&self) -> Vec<MockConnectRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Tunnels CONNECT requests for `authority` to `addr`. Only the proxy knows the host, so a
+    /// client that dials it directly fails to connect.
+    pub fn add_host(&self, authority: &str, addr: SocketAddr) {
+        self.hosts
+            .lock()
+            .unwrap()
+            .insert(authority.to_string(), addr);
+    }
+}
+
+impl Drop for MockHttpProxy {
+    fn drop(&mut self) {
+        if let Some(shutdown_tx) = self.shutdown_tx.take() {
+            let _ = shutdown_tx.send(());
+        }
+        if let Some(server_thread) = self.server_thread.take() {
+            server_thread.join().unwrap();
+        }
+    }
+}
+
+async fn mock_http_proxy_tunnel(
+    mut stream: tokio::net::TcpStream,
+    proxy_authorization: Option<String>,
+    requests: Arc<Mutex<Vec<MockConnectRequest>>>,
+    hosts: Arc<Mutex<HashMap<String, SocketAddr>>>,
+) {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        match stream.read_u8().await {
+            Ok(byte) => head.push(byte),
+            Err(_) => return,
+        }
+    }
+    let head = String::from_utf8_lossy(&head).into_owned();
+    let mut lines = head.lines();
+    let target = match lines
+        .next()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<_>>()[..]
+    {
+        ["CONNECT", target, _] => target.to_string(),
+        _ => {
+            let _ = stream
+                .write_all(b"HTTP/1.1 405 Method Not Allowed\r\n\r\n")
+                .await;
+            return;
+        }
+    };
+    let request = MockConnectRequest {
+        target,
+        proxy_authorization: lines.find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("proxy-authorization")
+                .then(|| value.trim().to_string())
+        }),
+    };
+    requests.lock().unwrap().push(request.clone());
+
+    if proxy_authorization.is_some() && request.proxy_authorization != proxy_authorization {
+        let _ = stream
+            .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+            .await;
+        return;
+    }
+    let host_addr = hosts.lock().unwrap().get(&request.target).copied();
+    let upstream = match host_addr {
+        Some(addr) => tokio::net::TcpStream::connect(addr).await,
+        None => tokio::net::TcpStream::connect(&request.target).await,
+    };
+    let mut upstream = match upstream {
+        Ok(upstream) => upstream,
+        Err(_) => {
+            let _ = stream.write_all(b"HTTP/1.1 502 Bad Gateway\r\n\r\n").await;
+            return;
+        }
+    };
+    if stream
+        .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+        .await
+        .is_ok()
+    {
+        let _ = tokio::io::copy_bidirectional(&mut stream, &mut upstream).await;
+    }
+}
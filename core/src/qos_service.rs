@@ -119,6 +119,18 @@ impl QosService {
         txs_costs
     }
 
+    /// Sums the cost model's estimate for the given transactions without counting it in the
+    /// service's metrics.
+    pub fn estimate_transactions_cost<'a>(
+        &self,
+        transactions: impl Iterator<Item = &'a SanitizedTransaction>,
+    ) -> u64 {
+        let cost_model = self.cost_model.read().unwrap();
+        transactions
+            .map(|tx| cost_model.calculate_cost(tx).sum())
+            .fold(0, u64::saturating_add)
+    }
+
     /// Given a list of transactions and their costs, this function returns a corresponding
     /// list of Results that indicate if a transaction is selected to be included in the current block,
     /// and a count of the number of transactions that would fit in the block
@@ -1,17 +1,22 @@
 use {
+    crate::proxy::block_engine_stage::BlockBuilderFeeInfo,
     anchor_lang::{
         solana_program::hash::Hash, AccountDeserialize, InstructionData, ToAccountMetas,
     },
     log::warn,
-    solana_runtime::bank::Bank,
+    solana_rpc_client_api::response::TipAccounts,
+    solana_runtime::{account_overrides::AccountOverrides, bank::Bank},
     solana_sdk::{
         account::ReadableAccount,
         bundle::error::TipPaymentError,
+        clock::Slot,
         instruction::Instruction,
         pubkey::Pubkey,
+        saturating_add_assign,
         signature::Keypair,
         signer::Signer,
         stake_history::Epoch,
+        system_instruction::SystemInstruction,
         system_program,
         transaction::{SanitizedTransaction, Transaction},
     },
@@ -19,6 +24,7 @@ use {
         collections::HashSet,
         sync::{Arc, Mutex, MutexGuard},
     },
+    thiserror::Error,
     tip_distribution::sdk::{
         derive_config_account_address, derive_tip_distribution_account_address,
         instruction::{
@@ -35,6 +41,22 @@ use {
 
 pub type Result<T> = std::result::Result<T, TipPaymentError>;
 
+/// Why the configured tip payment program can't be used to pay tips.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum TipManagerError {
+    #[error("tip payment program {0} doesn't exist")]
+    ProgramMissing(Pubkey),
+
+    #[error("tip payment program {0} isn't executable")]
+    ProgramNotExecutable(Pubkey),
+
+    #[error("tip payment program accounts aren't initialized, missing: {missing:?}, not owned by the program: {uninitialized:?}")]
+    AccountsNotInitialized {
+        missing: Vec<Pubkey>,
+        uninitialized: Vec<Pubkey>,
+    },
+}
+
 #[derive(Debug, Clone)]
 struct TipPaymentProgramInfo {
     program_id: Pubkey,
@@ -61,6 +83,22 @@ struct TipDistributionProgramInfo {
     config_pda_and_bump: (Pubkey, u8),
 }
 
+/// How BankingStage treats transactions that use the tip accounts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BankingStageTipAccountPolicy {
+    /// Drop transactions that use any of the tip accounts, tips can only be paid in bundles.
+    Deny,
+    /// Let transactions pay into the tip accounts outside of bundles. They contend with bundles
+    /// for the tip accounts' write locks, and still can't use the tip payment config account.
+    Allow,
+}
+
+impl Default for BankingStageTipAccountPolicy {
+    fn default() -> Self {
+        Self::Deny
+    }
+}
+
 /// This config is used on each invocation to the `init_tip_distribution_account` instruction.
 #[derive(Debug, Clone)]
 pub struct TipDistributionAccountConfig {
@@ -88,12 +126,84 @@ impl Default for TipDistributionAccountConfig {
     }
 }
 
+/// Tips collected from committed bundles, in the last slot a tip was collected in and over that
+/// slot's epoch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TipStats {
+    pub slot: Slot,
+    pub slot_tip_lamports: u64,
+    pub slot_num_tipping_bundles: u64,
+    pub slot_max_tip_lamports: u64,
+    pub epoch: Epoch,
+    pub epoch_tip_lamports: u64,
+    pub epoch_num_tipping_bundles: u64,
+}
+
+#[derive(Debug, Default)]
+struct TipStatsTracker {
+    stats: TipStats,
+    // set until the tips collected in stats.slot are reported
+    pending_report: bool,
+}
+
+impl TipStatsTracker {
+    fn record(&mut self, slot: Slot, epoch: Epoch, tip_lamports: u64) {
+        if slot != self.stats.slot {
+            self.report();
+            if epoch != self.stats.epoch {
+                self.stats.epoch = epoch;
+                self.stats.epoch_tip_lamports = 0;
+                self.stats.epoch_num_tipping_bundles = 0;
+            }
+            self.stats.slot = slot;
+            self.stats.slot_tip_lamports = 0;
+            self.stats.slot_num_tipping_bundles = 0;
+            self.stats.slot_max_tip_lamports = 0;
+        }
+        saturating_add_assign!(self.stats.slot_tip_lamports, tip_lamports);
+        saturating_add_assign!(self.stats.slot_num_tipping_bundles, 1);
+        self.stats.slot_max_tip_lamports = self.stats.slot_max_tip_lamports.max(tip_lamports);
+        saturating_add_assign!(self.stats.epoch_tip_lamports, tip_lamports);
+        saturating_add_assign!(self.stats.epoch_num_tipping_bundles, 1);
+        self.pending_report = true;
+    }
+
+    fn report(&mut self) {
+        if !self.pending_report {
+            return;
+        }
+        datapoint_info!(
+            "tip_manager-slot_tips",
+            ("slot", self.stats.slot, i64),
+            ("tip_lamports", self.stats.slot_tip_lamports, i64),
+            (
+                "num_tipping_bundles",
+                self.stats.slot_num_tipping_bundles,
+                i64
+            ),
+            ("max_tip_lamports", self.stats.slot_max_tip_lamports, i64),
+            ("epoch", self.stats.epoch, i64),
+            ("epoch_tip_lamports", self.stats.epoch_tip_lamports, i64),
+            (
+                "epoch_num_tipping_bundles",
+                self.stats.epoch_num_tipping_bundles,
+                i64
+            ),
+        );
+        self.pending_report = false;
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TipManager {
     tip_payment_program_info: TipPaymentProgramInfo,
     tip_distribution_program_info: TipDistributionProgramInfo,
     tip_distribution_account_config: TipDistributionAccountConfig,
+    min_bundle_tip_lamports: u64,
+    banking_stage_tip_account_policy: BankingStageTipAccountPolicy,
     lock: Arc<Mutex<()>>,
+    // shared by every clone of the TipManager
+    tip_stats: Arc<Mutex<TipStatsTracker>>,
 }
 
 #[derive(Clone)]
@@ -101,6 +211,9 @@ pub struct TipManagerConfig {
     pub tip_payment_program_id: Pubkey,
     pub tip_distribution_program_id: Pubkey,
     pub tip_distribution_account_config: TipDistributionAccountConfig,
+    /// Minimum lamports a bundle has to pay the tip accounts to be committed.
+    pub min_bundle_tip_lamports: u64,
+    pub banking_stage_tip_account_policy: BankingStageTipAccountPolicy,
 }
 
 impl Default for TipManagerConfig {
@@ -109,6 +222,8 @@ impl Default for TipManagerConfig {
             tip_payment_program_id: Pubkey::new_unique(),
             tip_distribution_program_id: Pubkey::new_unique(),
             tip_distribution_account_config: TipDistributionAccountConfig::default(),
+            min_bundle_tip_lamports: 0,
+            banking_stage_tip_account_policy: BankingStageTipAccountPolicy::default(),
         }
     }
 }
@@ -119,6 +234,8 @@ impl TipManager {
             tip_payment_program_id,
             tip_distribution_program_id,
             tip_distribution_account_config,
+            min_bundle_tip_lamports,
+            banking_stage_tip_account_policy,
         } = config;
 
         let config_pda_bump =
@@ -161,7 +278,10 @@ impl TipManager {
                 config_pda_and_bump,
             },
             tip_distribution_account_config,
+            min_bundle_tip_lamports,
+            banking_stage_tip_account_policy,
             lock: Arc::new(Mutex::new(())),
+            tip_stats: Arc::new(Mutex::new(TipStatsTracker::default())),
         }
     }
 
@@ -179,22 +299,231 @@ impl TipManager {
         self.tip_distribution_program_info.config_pda_and_bump.0
     }
 
+    /// Checks the tip payment program is deployed on `bank` and its config and tip accounts are
+    /// owned by it. BundleStage initializes the accounts in its first leader slot if they aren't,
+    /// so they're only expected to be missing on a new cluster.
+    pub fn validate(&self, bank: &Bank) -> std::result::Result<(), TipManagerError> {
+        let program_id = self.tip_payment_program_id();
+        match bank.get_account(&program_id) {
+            None => return Err(TipManagerError::ProgramMissing(program_id)),
+            Some(program) if !program.executable() => {
+                return Err(TipManagerError::ProgramNotExecutable(program_id))
+            }
+            Some(_) => {}
+        }
+
+        let info = &self.tip_payment_program_info;
+        let mut missing = vec![];
+        let mut uninitialized = vec![];
+        for pubkey in [
+            info.config_pda_bump.0,
+            info.tip_pda_0.0,
+            info.tip_pda_1.0,
+            info.tip_pda_2.0,
+            info.tip_pda_3.0,
+            info.tip_pda_4.0,
+            info.tip_pda_5.0,
+            info.tip_pda_6.0,
+            info.tip_pda_7.0,
+        ] {
+            match bank.get_account(&pubkey) {
+                None => missing.push(pubkey),
+                // anyone can send lamports to a PDA, only the program can take ownership of it
+                Some(account) if account.owner() != &program_id => uninitialized.push(pubkey),
+                Some(_) => {}
+            }
+        }
+        if missing.is_empty() && uninitialized.is_empty() {
+            Ok(())
+        } else {
+            Err(TipManagerError::AccountsNotInitialized {
+                missing,
+                uninitialized,
+            })
+        }
+    }
+
     /// Given a bank, returns the current `tip_receiver` configured with the tip-payment program.
     pub fn get_configured_tip_receiver(&self, bank: &Bank) -> Result<Pubkey> {
         Ok(self.get_tip_payment_config_account(bank)?.tip_receiver)
     }
 
     pub fn get_tip_accounts(&self) -> HashSet<Pubkey> {
-        HashSet::from([
-            self.tip_payment_program_info.tip_pda_0.0,
-            self.tip_payment_program_info.tip_pda_1.0,
-            self.tip_payment_program_info.tip_pda_2.0,
-            self.tip_payment_program_info.tip_pda_3.0,
-            self.tip_payment_program_info.tip_pda_4.0,
-            self.tip_payment_program_info.tip_pda_5.0,
-            self.tip_payment_program_info.tip_pda_6.0,
-            self.tip_payment_program_info.tip_pda_7.0,
-        ])
+        HashSet::from(self.tip_pdas())
+    }
+
+    /// Returns the tip accounts bundles are credited for paying into, along with the program
+    /// they're derived from. The accounts are derived once, when the TipManager is created.
+    pub fn tip_accounts(&self) -> TipAccounts {
+        TipAccounts {
+            tip_payment_program_id: self.tip_payment_program_id(),
+            tip_accounts: self.tip_pdas().to_vec(),
+        }
+    }
+
+    fn tip_pdas(&self) -> [Pubkey; 8] {
+        let info = &self.tip_payment_program_info;
+        [
+            info.tip_pda_0.0,
+            info.tip_pda_1.0,
+            info.tip_pda_2.0,
+            info.tip_pda_3.0,
+            info.tip_pda_4.0,
+            info.tip_pda_5.0,
+            info.tip_pda_6.0,
+            info.tip_pda_7.0,
+        ]
+    }
+
+    /// Accounts transactions in BankingStage can't use. The tip payment config account is always
+    /// included since changing the tip receiver mid-slot would take the tips already paid.
+    pub fn banking_stage_blacklisted_accounts(&self) -> HashSet<Pubkey> {
+        let mut blacklisted_accounts = HashSet::from([self.tip_payment_config_pubkey()]);
+        if self.banking_stage_tip_account_policy == BankingStageTipAccountPolicy::Deny {
+            blacklisted_accounts.extend(self.get_tip_accounts());
+        }
+        blacklisted_accounts
+    }
+
+    /// Lamports the transactions transfer to the tip accounts with system program transfers. This
+    /// is the tip a bundle declares, what it pays is only known once it has executed.
+    pub fn declared_tip_lamports(&self, transactions: &[SanitizedTransaction]) -> u64 {
+        let tip_accounts = self.get_tip_accounts();
+        transactions
+            .iter()
+            .map(|transaction| {
+                let message = transaction.message();
+                let account_keys = message.account_keys();
+                message
+                    .program_instructions_iter()
+                    .filter_map(|(program_id, instruction)| {
+                        if !system_program::check_id(program_id) {
+                            return None;
+                        }
+                        let to = account_keys.get(*instruction.accounts.get(1)? as usize)?;
+                        if !tip_accounts.contains(to) {
+                            return None;
+                        }
+                        match bincode::deserialize(&instruction.data) {
+                            Ok(SystemInstruction::Transfer { lamports }) => Some(lamports),
+                            _ => None,
+                        }
+                    })
+                    .fold(0, u64::saturating_add)
+            })
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Lamports a bundle moved into the tip accounts, given the accounts it wrote while executing
+    /// on `bank`.
+    pub fn paid_tip_lamports(&self, bank: &Bank, account_overrides: &AccountOverrides) -> u64 {
+        self.get_tip_accounts()
+            .iter()
+            .map(|tip_account| {
+                let pre_balance = bank.get_balance(tip_account);
+                let post_balance = account_overrides
+                    .get(tip_account)
+                    .map(|account| account.lamports())
+                    .unwrap_or(pre_balance);
+                post_balance.saturating_sub(pre_balance)
+            })
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Checks the tip accounts and the tip payment config are only written by system program
+    /// transfers paying into a tip account. Every instruction of the tip payment program moves
+    /// the tips somewhere else, so they're only used by the validator itself, never in bundles.
+    pub fn check_tip_account_writes(&self, transactions: &[SanitizedTransaction]) -> Result<()> {
+        let mut tip_payment_accounts = self.get_tip_accounts();
+        tip_payment_accounts.insert(self.tip_payment_config_pubkey());
+        for transaction in transactions {
+            let message = transaction.message();
+            let account_keys = message.account_keys();
+            for (program_id, instruction) in message.program_instructions_iter() {
+                let is_transfer = system_program::check_id(program_id)
+                    && matches!(
+                        bincode::deserialize(&instruction.data),
+                        Ok(SystemInstruction::Transfer { .. })
+                    );
+                for (position, index) in instruction.accounts.iter().enumerate() {
+                    let index = *index as usize;
+                    let account = match account_keys.get(index) {
+                        Some(account) if tip_payment_accounts.contains(account) => account,
+                        _ => continue,
+                    };
+                    // the recipient is the second account of a transfer
+                    if message.is_writable(index) && !(is_transfer && position == 1) {
+                        return Err(TipPaymentError::TipAccountViolation(*account));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a tip account that ended up with fewer lamports than it started with, given the
+    /// accounts a bundle wrote while executing on `bank`.
+    pub fn drained_tip_account(
+        &self,
+        bank: &Bank,
+        account_overrides: &AccountOverrides,
+    ) -> Option<Pubkey> {
+        self.get_tip_accounts().into_iter().find(|tip_account| {
+            account_overrides
+                .get(tip_account)
+                .map(|account| account.lamports() < bank.get_balance(tip_account))
+                .unwrap_or_default()
+        })
+    }
+
+    /// Checks an executed bundle paid at least the tip its transactions declare and at least the
+    /// configured minimum, without taking lamports from any of the tip accounts or writing to
+    /// them other than with a tip. Returns the lamports paid.
+    pub fn check_tip_paid(
+        &self,
+        transactions: &[SanitizedTransaction],
+        bank: &Bank,
+        account_overrides: &AccountOverrides,
+    ) -> Result<u64> {
+        self.check_tip_account_writes(transactions)?;
+        // a tip paid into one tip account doesn't make up for draining another
+        if let Some(tip_account) = self.drained_tip_account(bank, account_overrides) {
+            return Err(TipPaymentError::TipAccountViolation(tip_account));
+        }
+        let paid = self.paid_tip_lamports(bank, account_overrides);
+        let required = self
+            .declared_tip_lamports(transactions)
+            .max(self.min_bundle_tip_lamports);
+        if paid < required {
+            return Err(TipPaymentError::InsufficientTip { paid, required });
+        }
+        Ok(paid)
+    }
+
+    /// Adds the tip a bundle paid once the bundle is committed to `bank`. Bundles that weren't
+    /// committed mustn't be recorded, their tips were rolled back along with the rest of them.
+    pub fn record_committed_tip(&self, bank: &Bank, tip_lamports: u64) {
+        if tip_lamports == 0 {
+            return;
+        }
+        self.tip_stats
+            .lock()
+            .unwrap()
+            .record(bank.slot(), bank.epoch(), tip_lamports);
+    }
+
+    /// Reports the tips collected in the last slot a tip was collected in, once PoH has moved on
+    /// from that slot's bank. `working_slot` is the slot of the bank being built, if any.
+    pub fn maybe_report_slot_tips(&self, working_slot: Option<Slot>) {
+        let mut tip_stats = self.tip_stats.lock().unwrap();
+        if working_slot != Some(tip_stats.stats.slot) {
+            tip_stats.report();
+        }
+    }
+
+    /// Returns the tips collected so far in the last slot a tip was collected in and its epoch.
+    pub fn tip_stats(&self) -> TipStats {
+        self.tip_stats.lock().unwrap().stats
     }
 
     pub fn get_tip_payment_config_account(&self, bank: &Bank) -> Result<Config> {
@@ -368,6 +697,70 @@ impl TipManager {
         .unwrap()
     }
 
+    /// Builds the transactions initializing the tip payment program, the tip distribution config
+    /// and this epoch's tip distribution account, skipping the ones already initialized.
+    pub fn initialize_tip_accounts_txs(
+        &self,
+        bank: &Bank,
+        keypair: &Keypair,
+    ) -> Vec<SanitizedTransaction> {
+        let mut transactions = vec![];
+        if self.should_initialize_tip_payment_program(bank) {
+            transactions
+                .push(self.initialize_tip_payment_program_tx(bank.last_blockhash(), keypair));
+        }
+        if self.should_initialize_tip_distribution_config(bank) {
+            transactions
+                .push(self.initialize_tip_distribution_config_tx(bank.last_blockhash(), keypair));
+        }
+        if self.should_init_tip_distribution_account(bank) {
+            transactions
+                .push(self.init_tip_distribution_account_tx(bank.last_blockhash(), bank.epoch()));
+        }
+        transactions
+    }
+
+    /// Builds a transaction pointing the tip payment config at this validator's tip distribution
+    /// account for the bank's epoch and at the block builder's fee account and commission.
+    /// Returns None if it already does. Fails if the commission is over 100% or the fee account
+    /// doesn't exist, since the tip payment program can't pay the block builder its share then.
+    pub fn maybe_change_tip_receiver_tx(
+        &self,
+        bank: &Bank,
+        keypair: &Keypair,
+        block_builder_fee_info: &BlockBuilderFeeInfo,
+    ) -> Result<Option<SanitizedTransaction>> {
+        let BlockBuilderFeeInfo {
+            block_builder,
+            block_builder_commission,
+        } = block_builder_fee_info;
+        if *block_builder_commission > 100 {
+            return Err(TipPaymentError::InvalidBlockBuilderCommission(
+                *block_builder_commission,
+            ));
+        }
+        if bank.get_account(block_builder).is_none() {
+            return Err(TipPaymentError::BlockBuilderMissing(*block_builder));
+        }
+
+        let config = self.get_tip_payment_config_account(bank)?;
+        let my_tip_distribution_pda = self.get_my_tip_distribution_pda(bank.epoch());
+        if config.tip_receiver == my_tip_distribution_pda
+            && config.block_builder == *block_builder
+            && config.block_builder_commission_pct == *block_builder_commission
+        {
+            return Ok(None);
+        }
+        self.change_tip_receiver_and_block_builder_tx(
+            &my_tip_distribution_pda,
+            bank,
+            keypair,
+            block_builder,
+            *block_builder_commission,
+        )
+        .map(Some)
+    }
+
     /// Builds a transaction that changes the current tip receiver to new_tip_receiver.
     /// The on-chain program will transfer tips sitting in the tip accounts to the tip receiver
     /// before changing ownership.
@@ -471,3 +864,622 @@ impl TipManager {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        anchor_lang::AccountSerialize,
+        solana_ledger::genesis_utils::{create_genesis_config, GenesisConfigInfo},
+        solana_sdk::{
+            account::{AccountSharedData, WritableAccount},
+            bpf_loader,
+            instruction::AccountMeta,
+            system_transaction::transfer,
+        },
+    };
+
+    struct TipTest {
+        tip_manager: TipManager,
+        tip_account: Pubkey,
+        bank: Bank,
+        mint_keypair: Keypair,
+    }
+
+    impl TipTest {
+        fn new(min_bundle_tip_lamports: u64) -> Self {
+            let GenesisConfigInfo {
+                genesis_config,
+                mint_keypair,
+                ..
+            } = create_genesis_config(1_000_000);
+            let tip_manager = TipManager::new(TipManagerConfig {
+                min_bundle_tip_lamports,
+                ..TipManagerConfig::default()
+            });
+            let tip_account = *tip_manager.get_tip_accounts().iter().next().unwrap();
+            Self {
+                tip_manager,
+                tip_account,
+                bank: Bank::new_for_tests(&genesis_config),
+                mint_keypair,
+            }
+        }
+
+        fn transfer(&self, to: &Pubkey, lamports: u64) -> SanitizedTransaction {
+            SanitizedTransaction::from_transaction_for_tests(transfer(
+                &self.mint_keypair,
+                to,
+                lamports,
+                self.bank.last_blockhash(),
+            ))
+        }
+
+        /// Overrides as if the bundle moved `lamports` into `account`.
+        fn paid(&self, account: &Pubkey, lamports: u64) -> AccountOverrides {
+            let mut account_overrides = AccountOverrides::default();
+            account_overrides.set_account(
+                account,
+                Some(AccountSharedData::new(
+                    self.bank.get_balance(account) + lamports,
+                    0,
+                    &system_program::id(),
+                )),
+            );
+            account_overrides
+        }
+    }
+
+    #[test]
+    fn test_tip_accounts_derived_from_seeds() {
+        let tip_payment_program_id = Pubkey::new_from_array([7; 32]);
+        let tip_manager = TipManager::new(TipManagerConfig {
+            tip_payment_program_id,
+            ..TipManagerConfig::default()
+        });
+        // derived offline from the tip payment program's seeds, so a seed mixed up between accounts
+        // or a change to how they're derived shows up here
+        let expected_tip_pdas: Vec<(Pubkey, u8)> = [
+            ("FnN6xkxfzRLbzPAe1Jr3PbdCbJABD5y18GtW9LuNJ5iH", 255),
+            ("EmrYZovCsQCmfKn4XRFBmChfj7MteW5DraDh3BxZw1eg", 254),
+            ("HVXnNoQ2ef6fMFEmt4DJwjG1cHdUwFmMRrb1F8jxSnEL", 254),
+            ("4RZTNJ8pRop3sPtmQaMj2ULVkUiacYcipJtozqJXip8K", 253),
+            ("F6svdxDUdF9jRZfp4vRsJpVGyFnvDdEckehRqB61UUBn", 253),
+            ("FX3gtUBgeJECGBsDuzHP3JuN4BX6yQTNC6f42TL4fwky", 251),
+            ("5KgWWJCVFTqe3xcTpbCJknfUkv7BPnEyy6vaBpXkxPso", 255),
+            ("EdPBXPYAvHxZqdvAVwTduXCRHDuH2EmRcn1C1a6c8ppn", 255),
+        ]
+        .iter()
+        .map(|(tip_pda, bump)| (tip_pda.parse().unwrap(), *bump))
+        .collect();
+
+        let info = &tip_manager.tip_payment_program_info;
+        assert_eq!(
+            vec![
+                info.tip_pda_0,
+                info.tip_pda_1,
+                info.tip_pda_2,
+                info.tip_pda_3,
+                info.tip_pda_4,
+                info.tip_pda_5,
+                info.tip_pda_6,
+                info.tip_pda_7,
+            ],
+            expected_tip_pdas
+        );
+        assert_eq!(
+            tip_manager.get_tip_accounts(),
+            expected_tip_pdas
+                .iter()
+                .map(|(tip_pda, _bump)| *tip_pda)
+                .collect::<HashSet<_>>()
+        );
+        assert_eq!(
+            tip_manager.tip_payment_config_pubkey(),
+            "DhyjS275B57HCg8fF14Gq2Jizxz7MgRif6CzgCE1u3bR"
+                .parse::<Pubkey>()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_tip_accounts_credited() {
+        let test = TipTest::new(0);
+        let tip_accounts = test.tip_manager.tip_accounts();
+        assert_eq!(
+            tip_accounts.tip_payment_program_id,
+            test.tip_manager.tip_payment_program_id()
+        );
+        assert_eq!(tip_accounts.tip_accounts.len(), 8);
+        assert_eq!(
+            tip_accounts
+                .tip_accounts
+                .iter()
+                .copied()
+                .collect::<HashSet<_>>(),
+            test.tip_manager.get_tip_accounts()
+        );
+        // the same accounts are returned every time
+        assert_eq!(test.tip_manager.tip_accounts(), tip_accounts);
+
+        // a tip paid into any of the returned accounts is credited to the bundle
+        for tip_account in &tip_accounts.tip_accounts {
+            let transactions = vec![test.transfer(tip_account, 1_000)];
+            assert_eq!(test.tip_manager.declared_tip_lamports(&transactions), 1_000);
+            assert_eq!(
+                test.tip_manager.check_tip_paid(
+                    &transactions,
+                    &test.bank,
+                    &test.paid(tip_account, 1_000)
+                ),
+                Ok(1_000)
+            );
+        }
+    }
+
+    #[test]
+    fn test_banking_stage_blacklisted_accounts() {
+        let tip_manager = TipManager::new(TipManagerConfig::default());
+        let mut expected = tip_manager.get_tip_accounts();
+        expected.insert(tip_manager.tip_payment_config_pubkey());
+        assert_eq!(tip_manager.banking_stage_blacklisted_accounts(), expected);
+
+        let tip_manager = TipManager::new(TipManagerConfig {
+            banking_stage_tip_account_policy: BankingStageTipAccountPolicy::Allow,
+            ..TipManagerConfig::default()
+        });
+        assert_eq!(
+            tip_manager.banking_stage_blacklisted_accounts(),
+            HashSet::from([tip_manager.tip_payment_config_pubkey()])
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let test = TipTest::new(0);
+        let program_id = test.tip_manager.tip_payment_program_id();
+        assert_eq!(
+            test.tip_manager.validate(&test.bank),
+            Err(TipManagerError::ProgramMissing(program_id))
+        );
+
+        let mut program = AccountSharedData::new(1_000_000, 0, &bpf_loader::id());
+        test.bank.store_account(&program_id, &program);
+        assert_eq!(
+            test.tip_manager.validate(&test.bank),
+            Err(TipManagerError::ProgramNotExecutable(program_id))
+        );
+
+        // someone sent lamports to the config account before the program was initialized
+        program.set_executable(true);
+        test.bank.store_account(&program_id, &program);
+        let config_pubkey = test.tip_manager.tip_payment_config_pubkey();
+        test.bank.store_account(
+            &config_pubkey,
+            &AccountSharedData::new(1_000_000, 0, &system_program::id()),
+        );
+        let info = &test.tip_manager.tip_payment_program_info;
+        let tip_accounts = vec![
+            info.tip_pda_0.0,
+            info.tip_pda_1.0,
+            info.tip_pda_2.0,
+            info.tip_pda_3.0,
+            info.tip_pda_4.0,
+            info.tip_pda_5.0,
+            info.tip_pda_6.0,
+            info.tip_pda_7.0,
+        ];
+        assert_eq!(
+            test.tip_manager.validate(&test.bank),
+            Err(TipManagerError::AccountsNotInitialized {
+                missing: tip_accounts.clone(),
+                uninitialized: vec![config_pubkey],
+            })
+        );
+
+        for pubkey in tip_accounts.iter().chain([&config_pubkey]) {
+            test.bank
+                .store_account(pubkey, &AccountSharedData::new(1_000_000, 0, &program_id));
+        }
+        assert_eq!(test.tip_manager.validate(&test.bank), Ok(()));
+    }
+
+    /// Stores a tip payment config account owned by the program, as if it was initialized.
+    fn store_tip_payment_config(
+        test: &TipTest,
+        tip_receiver: Pubkey,
+        block_builder_fee_info: &BlockBuilderFeeInfo,
+    ) {
+        let mut data = vec![];
+        Config {
+            tip_receiver,
+            block_builder: block_builder_fee_info.block_builder,
+            block_builder_commission_pct: block_builder_fee_info.block_builder_commission,
+            ..Config::default()
+        }
+        .try_serialize(&mut data)
+        .unwrap();
+        let mut account = AccountSharedData::new(
+            1_000_000,
+            data.len(),
+            &test.tip_manager.tip_payment_program_id(),
+        );
+        account.set_data(data);
+        test.bank
+            .store_account(&test.tip_manager.tip_payment_config_pubkey(), &account);
+    }
+
+    #[test]
+    fn test_initialize_tip_accounts_txs() {
+        let test = TipTest::new(0);
+        let keypair = Keypair::new();
+        let block_builder_fee_info = BlockBuilderFeeInfo {
+            block_builder: test.mint_keypair.pubkey(),
+            block_builder_commission: 0,
+        };
+        assert_eq!(
+            test.tip_manager
+                .initialize_tip_accounts_txs(&test.bank, &keypair)
+                .len(),
+            3
+        );
+        assert_eq!(
+            test.tip_manager.maybe_change_tip_receiver_tx(
+                &test.bank,
+                &keypair,
+                &block_builder_fee_info
+            ),
+            Err(TipPaymentError::AccountMissing(
+                test.tip_manager.tip_payment_config_pubkey()
+            ))
+        );
+
+        // nothing is initialized twice
+        let tip_distribution_program_id = test.tip_manager.tip_distribution_program_info.program_id;
+        store_tip_payment_config(&test, Pubkey::new_unique(), &block_builder_fee_info);
+        for pubkey in [
+            test.tip_manager.tip_distribution_config_pubkey(),
+            test.tip_manager
+                .get_my_tip_distribution_pda(test.bank.epoch()),
+        ] {
+            test.bank.store_account(
+                &pubkey,
+                &AccountSharedData::new(1_000_000, 0, &tip_distribution_program_id),
+            );
+        }
+        assert!(test
+            .tip_manager
+            .initialize_tip_accounts_txs(&test.bank, &keypair)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_maybe_change_tip_receiver_tx() {
+        let test = TipTest::new(0);
+        let keypair = Keypair::new();
+        let block_builder_fee_info = BlockBuilderFeeInfo {
+            block_builder: test.mint_keypair.pubkey(),
+            block_builder_commission: 5,
+        };
+        let my_tip_distribution_pda = test
+            .tip_manager
+            .get_my_tip_distribution_pda(test.bank.epoch());
+
+        // the previous leader is still the tip receiver
+        let old_tip_receiver = Pubkey::new_unique();
+        store_tip_payment_config(&test, old_tip_receiver, &block_builder_fee_info);
+        let transaction = test
+            .tip_manager
+            .maybe_change_tip_receiver_tx(&test.bank, &keypair, &block_builder_fee_info)
+            .unwrap()
+            .unwrap();
+        assert_eq!(transaction.message().fee_payer(), &keypair.pubkey());
+        let account_keys = transaction.message().account_keys();
+        assert!(account_keys.iter().any(|key| key == &old_tip_receiver));
+        assert!(account_keys
+            .iter()
+            .any(|key| key == &my_tip_distribution_pda));
+
+        // a new block builder is enough to change over
+        store_tip_payment_config(
+            &test,
+            my_tip_distribution_pda,
+            &BlockBuilderFeeInfo {
+                block_builder: Pubkey::new_unique(),
+                ..block_builder_fee_info
+            },
+        );
+        assert!(test
+            .tip_manager
+            .maybe_change_tip_receiver_tx(&test.bank, &keypair, &block_builder_fee_info)
+            .unwrap()
+            .is_some());
+
+        // already pointing at this validator
+        store_tip_payment_config(&test, my_tip_distribution_pda, &block_builder_fee_info);
+        assert_eq!(
+            test.tip_manager.maybe_change_tip_receiver_tx(
+                &test.bank,
+                &keypair,
+                &block_builder_fee_info
+            ),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_maybe_change_tip_receiver_tx_block_builder_commission() {
+        let test = TipTest::new(0);
+        let keypair = Keypair::new();
+        let my_tip_distribution_pda = test
+            .tip_manager
+            .get_my_tip_distribution_pda(test.bank.epoch());
+        let fee_info = |block_builder_commission: u64| BlockBuilderFeeInfo {
+            block_builder: test.mint_keypair.pubkey(),
+            block_builder_commission,
+        };
+        store_tip_payment_config(&test, my_tip_distribution_pda, &fee_info(0));
+
+        // the whole range is accepted and a new commission alone changes the config over
+        assert_eq!(
+            test.tip_manager
+                .maybe_change_tip_receiver_tx(&test.bank, &keypair, &fee_info(0)),
+            Ok(None)
+        );
+        assert!(test
+            .tip_manager
+            .maybe_change_tip_receiver_tx(&test.bank, &keypair, &fee_info(100))
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            test.tip_manager
+                .maybe_change_tip_receiver_tx(&test.bank, &keypair, &fee_info(101)),
+            Err(TipPaymentError::InvalidBlockBuilderCommission(101))
+        );
+
+        // the fee account has to exist, even if the config already points at it
+        let missing_block_builder = Pubkey::new_unique();
+        let missing_fee_info = BlockBuilderFeeInfo {
+            block_builder: missing_block_builder,
+            block_builder_commission: 0,
+        };
+        store_tip_payment_config(&test, my_tip_distribution_pda, &missing_fee_info);
+        assert_eq!(
+            test.tip_manager
+                .maybe_change_tip_receiver_tx(&test.bank, &keypair, &missing_fee_info),
+            Err(TipPaymentError::BlockBuilderMissing(missing_block_builder))
+        );
+    }
+
+    #[test]
+    fn test_check_tip_paid() {
+        let test = TipTest::new(0);
+        let transactions = vec![
+            test.transfer(&Pubkey::new_unique(), 5_000),
+            test.transfer(&test.tip_account, 1_000),
+        ];
+        assert_eq!(test.tip_manager.declared_tip_lamports(&transactions), 1_000);
+        assert_eq!(
+            test.tip_manager.check_tip_paid(
+                &transactions,
+                &test.bank,
+                &test.paid(&test.tip_account, 1_000)
+            ),
+            Ok(1_000)
+        );
+    }
+
+    #[test]
+    fn test_check_tip_paid_partially() {
+        let test = TipTest::new(0);
+        let transactions = vec![test.transfer(&test.tip_account, 1_000)];
+        assert_eq!(
+            test.tip_manager.check_tip_paid(
+                &transactions,
+                &test.bank,
+                &test.paid(&test.tip_account, 400)
+            ),
+            Err(TipPaymentError::InsufficientTip {
+                paid: 400,
+                required: 1_000
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_tip_paid_to_wrong_account() {
+        let test = TipTest::new(1_000);
+        let wrong_account = Pubkey::new_unique();
+        let transactions = vec![test.transfer(&wrong_account, 1_000)];
+        assert_eq!(test.tip_manager.declared_tip_lamports(&transactions), 0);
+        assert_eq!(
+            test.tip_manager.check_tip_paid(
+                &transactions,
+                &test.bank,
+                &test.paid(&wrong_account, 1_000)
+            ),
+            Err(TipPaymentError::InsufficientTip {
+                paid: 0,
+                required: 1_000
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_tip_paid_then_drained() {
+        let test = TipTest::new(0);
+        let drained_account = *test
+            .tip_manager
+            .get_tip_accounts()
+            .iter()
+            .find(|tip_account| **tip_account != test.tip_account)
+            .unwrap();
+        test.bank.store_account(
+            &drained_account,
+            &AccountSharedData::new(5_000, 0, &test.tip_manager.tip_payment_program_id()),
+        );
+
+        // the bundle pays one tip account and takes as much back out of another
+        let mut account_overrides = test.paid(&test.tip_account, 1_000);
+        account_overrides.set_account(
+            &drained_account,
+            Some(AccountSharedData::new(
+                4_000,
+                0,
+                &test.tip_manager.tip_payment_program_id(),
+            )),
+        );
+        let transactions = vec![test.transfer(&test.tip_account, 1_000)];
+        assert_eq!(
+            test.tip_manager
+                .drained_tip_account(&test.bank, &account_overrides),
+            Some(drained_account)
+        );
+        assert_eq!(
+            test.tip_manager
+                .check_tip_paid(&transactions, &test.bank, &account_overrides),
+            Err(TipPaymentError::TipAccountViolation(drained_account))
+        );
+        assert_eq!(
+            test.tip_manager
+                .drained_tip_account(&test.bank, &test.paid(&test.tip_account, 1_000)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_check_tip_account_writes() {
+        let test = TipTest::new(0);
+        let new_transaction = |account_meta: AccountMeta| {
+            let instruction =
+                Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![account_meta]);
+            SanitizedTransaction::from_transaction_for_tests(Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&test.mint_keypair.pubkey()),
+                &[&test.mint_keypair],
+                test.bank.last_blockhash(),
+            ))
+        };
+
+        // paying a tip and reading a tip account are fine
+        assert_eq!(
+            test.tip_manager.check_tip_account_writes(&[
+                test.transfer(&test.tip_account, 1_000),
+                new_transaction(AccountMeta::new_readonly(test.tip_account, false)),
+            ]),
+            Ok(())
+        );
+
+        // any other program can't write to the tip accounts or the config
+        assert_eq!(
+            test.tip_manager
+                .check_tip_account_writes(&[new_transaction(AccountMeta::new(
+                    test.tip_account,
+                    false
+                ))]),
+            Err(TipPaymentError::TipAccountViolation(test.tip_account))
+        );
+        let config = test.tip_manager.tip_payment_config_pubkey();
+        assert_eq!(
+            test.tip_manager
+                .check_tip_account_writes(&[new_transaction(AccountMeta::new(config, false))]),
+            Err(TipPaymentError::TipAccountViolation(config))
+        );
+
+        // a bundle that pays a tip and then moves the tips to its own receiver
+        let block_builder_fee_info = BlockBuilderFeeInfo {
+            block_builder: test.mint_keypair.pubkey(),
+            block_builder_commission: 0,
+        };
+        store_tip_payment_config(&test, Pubkey::new_unique(), &block_builder_fee_info);
+        let change_tip_receiver_tx = test
+            .tip_manager
+            .change_tip_receiver_and_block_builder_tx(
+                &Pubkey::new_unique(),
+                &test.bank,
+                &test.mint_keypair,
+                &block_builder_fee_info.block_builder,
+                0,
+            )
+            .unwrap();
+        assert_matches!(
+            test.tip_manager.check_tip_account_writes(&[
+                test.transfer(&test.tip_account, 1_000),
+                change_tip_receiver_tx,
+            ]),
+            Err(TipPaymentError::TipAccountViolation(_))
+        );
+    }
+
+    #[test]
+    fn test_tip_stats() {
+        let test = TipTest::new(0);
+        let bank = Arc::new(test.bank);
+        let tip_manager = test.tip_manager;
+        assert_eq!(tip_manager.tip_stats(), TipStats::default());
+
+        tip_manager.record_committed_tip(&bank, 1_000);
+        tip_manager.record_committed_tip(&bank, 3_000);
+        // bundles that didn't tip aren't counted
+        tip_manager.record_committed_tip(&bank, 0);
+        // still building the slot's bank, nothing to report yet
+        tip_manager.maybe_report_slot_tips(Some(bank.slot()));
+        assert!(tip_manager.tip_stats.lock().unwrap().pending_report);
+        assert_eq!(
+            tip_manager.tip_stats(),
+            TipStats {
+                slot: bank.slot(),
+                slot_tip_lamports: 4_000,
+                slot_num_tipping_bundles: 2,
+                slot_max_tip_lamports: 3_000,
+                epoch: bank.epoch(),
+                epoch_tip_lamports: 4_000,
+                epoch_num_tipping_bundles: 2,
+            }
+        );
+        tip_manager.maybe_report_slot_tips(None);
+        assert!(!tip_manager.tip_stats.lock().unwrap().pending_report);
+
+        // the slot counts start over while the epoch keeps adding up
+        let next_slot_bank = Arc::new(Bank::new_from_parent(
+            &bank,
+            &Pubkey::default(),
+            bank.slot() + 1,
+        ));
+        assert_eq!(next_slot_bank.epoch(), bank.epoch());
+        tip_manager.record_committed_tip(&next_slot_bank, 2_000);
+        assert_eq!(
+            tip_manager.tip_stats(),
+            TipStats {
+                slot: next_slot_bank.slot(),
+                slot_tip_lamports: 2_000,
+                slot_num_tipping_bundles: 1,
+                slot_max_tip_lamports: 2_000,
+                epoch: bank.epoch(),
+                epoch_tip_lamports: 6_000,
+                epoch_num_tipping_bundles: 3,
+            }
+        );
+
+        // and both start over in the next epoch
+        let next_epoch_bank = Bank::new_from_parent(
+            &next_slot_bank,
+            &Pubkey::default(),
+            bank.epoch_schedule()
+                .get_first_slot_in_epoch(bank.epoch() + 1),
+        );
+        tip_manager.record_committed_tip(&next_epoch_bank, 500);
+        assert_eq!(
+            tip_manager.tip_stats(),
+            TipStats {
+                slot: next_epoch_bank.slot(),
+                slot_tip_lamports: 500,
+                slot_num_tipping_bundles: 1,
+                slot_max_tip_lamports: 500,
+                epoch: bank.epoch() + 1,
+                epoch_tip_lamports: 500,
+                epoch_num_tipping_bundles: 1,
+            }
+        );
+    }
+}
@@ -6,31 +6,36 @@ use {
         banking_stage::BankingStage,
         broadcast_stage::{BroadcastStage, BroadcastStageType, RetransmitSlotsReceiver},
         bundle_account_locker::BundleAccountLocker,
-        bundle_stage::BundleStage,
+        bundle_filter::{BundleFilter, ProtectedAccounts},
+        bundle_stage::{BundleStage, BundleStageConfig},
         cluster_info_vote_listener::{
             ClusterInfoVoteListener, GossipDuplicateConfirmedSlotsSender,
             GossipVerifiedVoteHashSender, VerifiedVoteSender, VoteTracker,
         },
         fetch_stage::FetchStage,
         find_packet_sender_stake_stage::FindPacketSenderStakeStage,
+        packet_bundle::BundleQueueStats,
         proxy::{
             block_engine_stage::{BlockBuilderFeeInfo, BlockEngineConfig, BlockEngineStage},
             fetch_stage_manager::FetchStageManager,
             relayer_stage::{RelayerConfig, RelayerStage},
+            DropOldestForwarder, MevConfigUpdate, MevStatus,
         },
         sigverify::TransactionSigVerifier,
         sigverify_stage::SigVerifyStage,
         staked_nodes_updater_service::StakedNodesUpdaterService,
         tip_manager::{TipManager, TipManagerConfig},
     },
-    crossbeam_channel::{unbounded, Receiver},
+    crossbeam_channel::{bounded, unbounded, Receiver, Sender},
     solana_gossip::cluster_info::ClusterInfo,
     solana_ledger::{blockstore::Blockstore, blockstore_processor::TransactionStatusSender},
+    solana_perf::packet::PacketBatch,
     solana_poh::poh_recorder::{PohRecorder, WorkingBankEntry},
     solana_rpc::{
         optimistically_confirmed_bank_tracker::BankNotificationSender,
         rpc_subscriptions::RpcSubscriptions,
     },
+    solana_rpc_client_api::response::TipAccounts,
     solana_runtime::{
         bank_forks::BankForks,
         cost_model::CostModel,
@@ -46,7 +51,7 @@ use {
     },
     solana_tpu_client::connection_cache::ConnectionCache,
     std::{
-        collections::{HashMap, HashSet},
+        collections::HashMap,
         net::{SocketAddr, UdpSocket},
         sync::{atomic::AtomicBool, Arc, Mutex, RwLock},
         thread,
@@ -74,6 +79,7 @@ pub struct Tpu {
     maybe_relayer_stage: Option<RelayerStage>,
     maybe_block_engine_stage: Option<BlockEngineStage>,
     maybe_fetch_stage_manager: Option<FetchStageManager>,
+    proxy_packet_forwarders: Vec<DropOldestForwarder>,
     banking_stage: BankingStage,
     cluster_info_vote_listener: ClusterInfoVoteListener,
     broadcast_stage: BroadcastStage,
@@ -83,6 +89,11 @@ pub struct Tpu {
     vote_find_packet_sender_stake_stage: FindPacketSenderStakeStage,
     staked_nodes_updater_service: StakedNodesUpdaterService,
     bundle_stage: BundleStage,
+    mev_config_sender: Sender<MevConfigUpdate>,
+    bundle_filter_sender: Sender<BundleFilter>,
+    bundles_enabled: Arc<AtomicBool>,
+    tip_accounts: Arc<TipAccounts>,
+    mev_status: Arc<RwLock<MevStatus>>,
 }
 
 impl Tpu {
@@ -111,6 +122,7 @@ impl Tpu {
         cost_model: &Arc<RwLock<CostModel>>,
         connection_cache: &Arc<ConnectionCache>,
         keypair: &Keypair,
+        vote_account: &Pubkey,
         log_messages_bytes_limit: Option<usize>,
         staked_nodes: &Arc<RwLock<StakedNodes>>,
         maybe_block_engine_config: Option<BlockEngineConfig>,
@@ -129,16 +141,10 @@ impl Tpu {
             transactions_forwards_quic: transactions_forwards_quic_sockets,
         } = sockets;
 
-        let (packet_intercept_sender, packet_intercept_receiver) = unbounded();
         let (packet_sender, packet_receiver) = unbounded();
 
-        // If there's a relayer, we need to redirect packets to the interceptor
-        // If not, they can flow straight through
-        let packet_send_channel = if maybe_relayer_config.is_some() {
-            packet_intercept_sender
-        } else {
-            packet_sender.clone()
-        };
+        let (packet_send_channel, maybe_packet_intercept_receiver) =
+            Self::tpu_packet_channel(maybe_relayer_config.is_some(), &packet_sender);
 
         let (vote_packet_sender, vote_packet_receiver) = unbounded();
         let (forwarded_packet_sender, forwarded_packet_receiver) = unbounded();
@@ -232,41 +238,107 @@ impl Tpu {
             )
         };
 
-        let block_builder_fee_info = Arc::new(Mutex::new(BlockBuilderFeeInfo {
-            block_builder: cluster_info.keypair().pubkey(),
-            block_builder_commission: 0,
-        }));
-
-        let (bundle_sender, bundle_receiver) = unbounded();
+        let block_builder_fee_info = Arc::new(Mutex::new(
+            maybe_block_engine_config
+                .as_ref()
+                .and_then(|block_engine_config| {
+                    block_engine_config.default_block_builder_fee_info.clone()
+                })
+                .unwrap_or_else(|| BlockBuilderFeeInfo {
+                    block_builder: cluster_info.keypair().pubkey(),
+                    block_builder_commission: 0,
+                }),
+        ));
+
+        let mev_status = Arc::new(RwLock::new(MevStatus::default()));
+        let bundle_queue_stats = Arc::new(BundleQueueStats::default());
+        let bundle_stage_config = maybe_block_engine_config
+            .as_ref()
+            .map(BundleStageConfig::from)
+            .unwrap_or_default();
+        let (bundle_sender, bundle_receiver) = bounded(bundle_stage_config.bundle_channel_capacity);
+        let (bundle_filter_sender, bundle_filter_receiver) = unbounded();
+        let bundles_enabled = Arc::new(AtomicBool::new(true));
+        // the proxy stages get their own bounded channels so a stalled sigverify or BankingStage
+        // only drops their packets, never the ones from the local TPU ports
+        let mut proxy_packet_forwarders = vec![];
         let maybe_block_engine_stage = maybe_block_engine_config.map(|block_engine_config| {
+            let capacity = block_engine_config.packet_channel_capacity;
+            let (block_engine_packet_sender, packet_forwarder) = DropOldestForwarder::new(
+                "block-engine-packet-forwarder",
+                packet_sender.clone(),
+                capacity,
+                exit.clone(),
+            );
+            let (block_engine_verified_sender, verified_forwarder) = DropOldestForwarder::new(
+                "block-engine-verified-forwarder",
+                verified_sender.clone(),
+                capacity,
+                exit.clone(),
+            );
+            proxy_packet_forwarders.push(packet_forwarder);
+            proxy_packet_forwarders.push(verified_forwarder);
             BlockEngineStage::new(
                 block_engine_config,
                 bundle_sender,
                 cluster_info.clone(),
-                packet_sender.clone(),
-                verified_sender.clone(),
+                block_engine_packet_sender,
+                block_engine_verified_sender,
                 exit.clone(),
                 &block_builder_fee_info,
+                mev_status.clone(),
+                bundle_queue_stats.clone(),
+                poh_recorder.clone(),
             )
         });
 
         let (heartbeat_tx, heartbeat_rx) = unbounded();
-        let maybe_fetch_stage_manager = maybe_relayer_config.as_ref().map(|_| {
-            FetchStageManager::new(
-                cluster_info.clone(),
-                heartbeat_rx,
-                packet_intercept_receiver,
-                packet_sender.clone(),
-                exit.clone(),
-            )
-        });
+        let (mev_config_sender, mev_config_receiver) = unbounded();
+        let (relayer_config_update_sender, relayer_config_update_receiver) = unbounded();
+        let relayer_priority_percent = maybe_relayer_config
+            .as_ref()
+            .and_then(|relayer_config| relayer_config.packet_priority_percent);
+        let maybe_fetch_stage_manager = maybe_relayer_config
+            .as_ref()
+            .zip(maybe_packet_intercept_receiver)
+            .map(|(relayer_config, packet_intercept_receiver)| {
+                FetchStageManager::new(
+                    cluster_info.clone(),
+                    heartbeat_rx,
+                    packet_intercept_receiver,
+                    packet_sender.clone(),
+                    relayer_config.heartbeat_loss_policy,
+                    relayer_config.vote_bypass,
+                    mev_config_receiver,
+                    relayer_config_update_sender,
+                    mev_status.clone(),
+                    exit.clone(),
+                )
+            });
         let maybe_relayer_stage = maybe_relayer_config.map(|relayer_config| {
+            let capacity = relayer_config.packet_channel_capacity;
+            let (relayer_packet_sender, packet_forwarder) = DropOldestForwarder::new(
+                "relayer-packet-forwarder",
+                packet_sender,
+                capacity,
+                exit.clone(),
+            );
+            let (relayer_verified_sender, verified_forwarder) = DropOldestForwarder::new(
+                "relayer-verified-forwarder",
+                verified_sender,
+                capacity,
+                exit.clone(),
+            );
+            proxy_packet_forwarders.push(packet_forwarder);
+            proxy_packet_forwarders.push(verified_forwarder);
             RelayerStage::new(
                 relayer_config,
                 cluster_info.clone(),
                 heartbeat_tx,
-                packet_sender,
-                verified_sender,
+                relayer_packet_sender,
+                relayer_verified_sender,
+                relayer_config_update_receiver,
+                mev_status.clone(),
                 exit.clone(),
             )
         });
@@ -290,14 +362,36 @@ impl Tpu {
         );
 
         let tip_manager = TipManager::new(tip_manager_config);
+        // a mistyped tip program would otherwise only show up as failing bundles once leader
+        if let Err(e) = tip_manager.validate(&bank_forks.read().unwrap().working_bank()) {
+            error!("tip payment program can't be used to pay tips: {}", e);
+            datapoint_error!(
+                "tip_manager-validate_error",
+                ("error", e.to_string(), String)
+            );
+            mev_status.write().unwrap().tip_program_error = Some(e);
+        }
+
+        let tip_accounts = Arc::new(tip_manager.tip_accounts());
+
+        // bundles could otherwise deauthorize or redirect the validator's own accounts while
+        // it's leader
+        let protected_accounts = ProtectedAccounts::new(
+            cluster_info.id(),
+            *vote_account,
+            &bundle_stage_config.protected_accounts,
+            &bank_forks.read().unwrap().working_bank(),
+        );
+        info!(
+            "{} accounts protected from bundles",
+            protected_accounts.len()
+        );
 
         let bundle_account_locker = BundleAccountLocker::default();
 
-        // tip accounts can't be used in BankingStage to avoid someone from stealing tips mid-slot.
-        // it also helps reduce surface area for potential account contention
-        let mut blacklisted_accounts = HashSet::new();
-        blacklisted_accounts.insert(tip_manager.tip_payment_config_pubkey());
-        blacklisted_accounts.extend(tip_manager.get_tip_accounts());
+        // the tip payment config can't be used in BankingStage to avoid someone from stealing tips
+        // mid-slot. keeping the tip accounts out of it too reduces contention with bundles
+        let blacklisted_accounts = tip_manager.banking_stage_blacklisted_accounts();
         let banking_stage = BankingStage::new(
             cluster_info,
             poh_recorder,
@@ -312,6 +406,7 @@ impl Tpu {
             bank_forks.clone(),
             blacklisted_accounts,
             bundle_account_locker.clone(),
+            relayer_priority_percent,
         );
 
         let bundle_stage = BundleStage::new(
@@ -325,6 +420,11 @@ impl Tpu {
             tip_manager,
             bundle_account_locker,
             &block_builder_fee_info,
+            bundle_queue_stats,
+            bundle_filter_receiver,
+            protected_accounts,
+            bundles_enabled.clone(),
+            bundle_stage_config,
         );
 
         let broadcast_stage = broadcast_type.new_broadcast_stage(
@@ -346,6 +446,7 @@ impl Tpu {
             maybe_block_engine_stage,
             maybe_relayer_stage,
             maybe_fetch_stage_manager,
+            proxy_packet_forwarders,
             banking_stage,
             cluster_info_vote_listener,
             broadcast_stage,
@@ -355,9 +456,56 @@ impl Tpu {
             vote_find_packet_sender_stake_stage,
             staked_nodes_updater_service,
             bundle_stage,
+            mev_config_sender,
+            bundle_filter_sender,
+            bundles_enabled,
+            tip_accounts,
+            mev_status,
+        }
+    }
+
+    /// Returns the channel FetchStage and the QUIC server send TPU packets to. With a relayer the
+    /// packets are intercepted by the FetchStageManager, which reads them from the returned
+    /// receiver. Without one they go straight to FindPacketSenderStakeStage with no extra hop.
+    fn tpu_packet_channel(
+        relayer_enabled: bool,
+        packet_sender: &Sender<PacketBatch>,
+    ) -> (Sender<PacketBatch>, Option<Receiver<PacketBatch>>) {
+        if relayer_enabled {
+            let (packet_intercept_sender, packet_intercept_receiver) = unbounded();
+            (packet_intercept_sender, Some(packet_intercept_receiver))
+        } else {
+            (packet_sender.clone(), None)
         }
     }
 
+    /// Returns a handle used to update the relayer configuration while the validator is running.
+    pub fn mev_config_sender(&self) -> Sender<MevConfigUpdate> {
+        self.mev_config_sender.clone()
+    }
+
+    /// Returns a handle used to replace BundleStage's bundle filter while the validator is
+    /// running.
+    pub fn bundle_filter_sender(&self) -> Sender<BundleFilter> {
+        self.bundle_filter_sender.clone()
+    }
+
+    /// Returns the switch that turns bundle execution off and back on while the validator is
+    /// running. Packets from the relayer and block engine keep flowing while it's off.
+    pub fn bundles_enabled(&self) -> Arc<AtomicBool> {
+        self.bundles_enabled.clone()
+    }
+
+    /// Returns the tip accounts BundleStage credits bundles for paying into, for the RPC service
+    /// to serve to searchers.
+    pub fn tip_accounts(&self) -> Arc<TipAccounts> {
+        self.tip_accounts.clone()
+    }
+
+    pub fn mev_status(&self) -> Arc<RwLock<MevStatus>> {
+        self.mev_status.clone()
+    }
+
     pub fn join(self) -> thread::Result<()> {
         let results = vec![
             self.fetch_stage.join(),
@@ -382,6 +530,9 @@ impl Tpu {
         if let Some(fetch_stage_manager) = self.maybe_fetch_stage_manager {
             fetch_stage_manager.join()?;
         }
+        for proxy_packet_forwarder in self.proxy_packet_forwarders {
+            proxy_packet_forwarder.join()?;
+        }
 
         let broadcast_result = self.broadcast_stage.join();
         for result in results {
@@ -391,3 +542,100 @@ impl Tpu {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::proxy::fetch_stage_manager::HeartbeatLossPolicy,
+        solana_gossip::contact_info::ContactInfo,
+        solana_perf::packet::Packet,
+        solana_streamer::socket::SocketAddrSpace,
+        std::{sync::atomic::Ordering, time::Duration},
+    };
+
+    /// Sends packets the way FetchStage does and returns what FindPacketSenderStakeStage outputs.
+    fn send_tpu_packets(relayer_enabled: bool, packet_batches: Vec<PacketBatch>) -> Vec<Packet> {
+        let exit = Arc::new(AtomicBool::new(false));
+        let (packet_sender, packet_receiver) = unbounded();
+        let (packet_send_channel, maybe_packet_intercept_receiver) =
+            Tpu::tpu_packet_channel(relayer_enabled, &packet_sender);
+        assert_eq!(maybe_packet_intercept_receiver.is_some(), relayer_enabled);
+        // no heartbeats are sent, but the channel has to stay open
+        let (_heartbeat_tx, heartbeat_rx) = unbounded();
+
+        let maybe_fetch_stage_manager =
+            maybe_packet_intercept_receiver.map(|packet_intercept_receiver| {
+                let keypair = Arc::new(Keypair::new());
+                let cluster_info = Arc::new(ClusterInfo::new(
+                    ContactInfo::new_localhost(&keypair.pubkey(), 0),
+                    keypair,
+                    SocketAddrSpace::Unspecified,
+                ));
+                let (_mev_config_sender, mev_config_receiver) = unbounded();
+                let (relayer_config_update_sender, _relayer_config_update_receiver) = unbounded();
+                FetchStageManager::new(
+                    cluster_info,
+                    heartbeat_rx,
+                    packet_intercept_receiver,
+                    packet_sender.clone(),
+                    HeartbeatLossPolicy::FailOpen,
+                    true,
+                    mev_config_receiver,
+                    relayer_config_update_sender,
+                    Arc::default(),
+                    exit.clone(),
+                )
+            });
+        drop(packet_sender);
+
+        let (find_packet_sender_stake_sender, find_packet_sender_stake_receiver) = unbounded();
+        let find_packet_sender_stake_stage = FindPacketSenderStakeStage::new(
+            packet_receiver,
+            find_packet_sender_stake_sender,
+            Arc::default(),
+            "Tpu",
+        );
+
+        let num_packets: usize = packet_batches.iter().map(|batch| batch.len()).sum();
+        for packet_batch in packet_batches {
+            packet_send_channel.send(packet_batch).unwrap();
+        }
+        let mut packets = vec![];
+        while packets.len() < num_packets {
+            let batches = find_packet_sender_stake_receiver
+                .recv_timeout(Duration::from_secs(1))
+                .unwrap();
+            packets.extend(batches.iter().flat_map(|batch| batch.iter().cloned()));
+        }
+
+        exit.store(true, Ordering::Relaxed);
+        if let Some(fetch_stage_manager) = maybe_fetch_stage_manager {
+            fetch_stage_manager.join().unwrap();
+        }
+        drop(packet_send_channel);
+        find_packet_sender_stake_stage.join().unwrap();
+        packets
+    }
+
+    #[test]
+    fn test_tpu_packets_with_and_without_relayer() {
+        let packet_batches: Vec<_> = (0..10u8)
+            .map(|i| {
+                let mut packet = Packet::default();
+                packet.buffer_mut()[0] = i;
+                packet.meta.size = 1;
+                PacketBatch::new(vec![packet])
+            })
+            .collect();
+
+        let without_relayer = send_tpu_packets(false, packet_batches.clone());
+        let with_relayer = send_tpu_packets(true, packet_batches.clone());
+        let expected: Vec<_> = packet_batches
+            .iter()
+            .flat_map(|batch| batch.iter().cloned())
+            .collect();
+        assert_eq!(without_relayer, expected);
+        assert_eq!(with_relayer, expected);
+    }
+}
@@ -5,13 +5,18 @@ use {
     crate::{
         accounts_hash_verifier::AccountsHashVerifier,
         broadcast_stage::BroadcastStageType,
+        bundle_filter::BundleFilter,
         cache_block_meta_service::{CacheBlockMetaSender, CacheBlockMetaService},
         cluster_info_vote_listener::VoteTracker,
         completed_data_sets_service::CompletedDataSetsService,
         consensus::{reconcile_blockstore_roots_with_external_source, ExternalRootSource, Tower},
         ledger_metric_report_service::LedgerMetricReportService,
         poh_timing_report_service::PohTimingReportService,
-        proxy::{block_engine_stage::BlockEngineConfig, relayer_stage::RelayerConfig},
+        proxy::{
+            block_engine_stage::BlockEngineConfig,
+            relayer_stage::{RelayerConfig, RelayerStage},
+            MevConfigUpdate, MevStatus,
+        },
         rewards_recorder_service::{RewardsRecorderSender, RewardsRecorderService},
         sample_performance_service::SamplePerformanceService,
         serve_repair::ServeRepair,
@@ -20,12 +25,12 @@ use {
         snapshot_packager_service::SnapshotPackagerService,
         stats_reporter_service::StatsReporterService,
         system_monitor_service::{verify_net_stats_access, SystemMonitorService},
-        tip_manager::TipManagerConfig,
+        tip_manager::{TipManager, TipManagerConfig},
         tower_storage::TowerStorage,
         tpu::{Tpu, TpuSockets, DEFAULT_TPU_COALESCE_MS},
         tvu::{Tvu, TvuConfig, TvuSockets},
     },
-    crossbeam_channel::{bounded, unbounded, Receiver},
+    crossbeam_channel::{bounded, unbounded, Receiver, Sender},
     rand::{thread_rng, Rng},
     solana_entry::poh::compute_hash_time_ns,
     solana_geyser_plugin_manager::geyser_plugin_service::GeyserPluginService,
@@ -67,6 +72,7 @@ use {
         transaction_notifier_interface::TransactionNotifierLock,
         transaction_status_service::TransactionStatusService,
     },
+    solana_rpc_client_api::response::TipAccounts,
     solana_runtime::{
         accounts_background_service::{
             AbsRequestHandlers, AbsRequestSender, AccountsBackgroundService, DroppedSlotsReceiver,
@@ -410,6 +416,19 @@ impl Validator {
             }
         }
 
+        if let Some(relayer_config) = &config.maybe_relayer_config {
+            match RelayerStage::preflight(relayer_config, &identity_keypair) {
+                Ok(report) => info!("relayer preflight check passed: {:?}", report),
+                Err(e) if relayer_config.require_relayer => {
+                    return Err(format!("Relayer preflight check failed: {}", e));
+                }
+                Err(e) => error!(
+                    "Relayer preflight check failed: {}. Starting anyway, the relayer will be retried in the background. Pass --require-relayer to refuse to start instead.",
+                    e
+                ),
+            }
+        }
+
         let mut bank_notification_senders = Vec::new();
 
         let geyser_plugin_service =
@@ -802,9 +821,15 @@ impl Validator {
                 None
             };
 
+            // derived the same way Tpu's TipManager derives them, which isn't created until later
+            let tip_accounts =
+                Arc::new(TipManager::new(config.tip_manager_config.clone()).tip_accounts());
             let json_rpc_service = JsonRpcService::new(
                 rpc_addr,
-                config.rpc_config.clone(),
+                JsonRpcConfig {
+                    tip_accounts: Some(tip_accounts),
+                    ..config.rpc_config.clone()
+                },
                 config.snapshot_config.clone(),
                 bank_forks.clone(),
                 block_commitment_cache.clone(),
@@ -1037,6 +1062,7 @@ impl Validator {
             &cost_model,
             &connection_cache,
             &identity_keypair,
+            vote_account,
             config.runtime_config.log_messages_bytes_limit,
             &staked_nodes,
             config.maybe_block_engine_config.clone(),
@@ -1103,6 +1129,31 @@ impl Validator {
         self.join();
     }
 
+    /// Returns a handle used to update the relayer configuration while the validator is running.
+    pub fn mev_config_sender(&self) -> Sender<MevConfigUpdate> {
+        self.tpu.mev_config_sender()
+    }
+
+    /// Returns a handle used to replace the bundle filter while the validator is running.
+    pub fn bundle_filter_sender(&self) -> Sender<BundleFilter> {
+        self.tpu.bundle_filter_sender()
+    }
+
+    /// Returns the switch used to turn bundle execution off while the validator is running.
+    pub fn bundles_enabled(&self) -> Arc<AtomicBool> {
+        self.tpu.bundles_enabled()
+    }
+
+    /// Returns the tip accounts bundles pay into.
+    pub fn tip_accounts(&self) -> Arc<TipAccounts> {
+        self.tpu.tip_accounts()
+    }
+
+    /// Returns the relayer and block engine connection status.
+    pub fn mev_status(&self) -> Arc<RwLock<MevStatus>> {
+        self.tpu.mev_status()
+    }
+
     fn print_node_info(node: &Node) {
         info!("{:?}", node.info);
         info!(
@@ -71,6 +71,8 @@ use {
 pub struct BlockCostCapacityMeter {
     pub capacity: u64,
     pub accumulated_cost: u64,
+    // the slot whose block the cost is accumulated for, set by the first accumulation
+    pub slot: Option<Slot>,
 }
 
 impl Default for BlockCostCapacityMeter {
@@ -84,12 +86,30 @@ impl BlockCostCapacityMeter {
         Self {
             capacity: capacity_limit,
             accumulated_cost: 0_u64,
+            slot: None,
         }
     }
 
     // return the remaining capacity
-    pub fn accumulate(&mut self, cost: u64) -> u64 {
-        self.accumulated_cost += cost;
+    pub fn accumulate(&mut self, slot: Slot, cost: u64) -> u64 {
+        match self.slot {
+            None => self.slot = Some(slot),
+            Some(meter_slot) => {
+                if meter_slot != slot {
+                    datapoint_error!(
+                        "block_cost_capacity_meter-wrong_slot",
+                        ("meter_slot", meter_slot, i64),
+                        ("slot", slot, i64),
+                    );
+                }
+                debug_assert_eq!(
+                    meter_slot, slot,
+                    "cost of slot {} accumulated against the meter of slot {}",
+                    slot, meter_slot
+                );
+            }
+        }
+        self.accumulated_cost = self.accumulated_cost.saturating_add(cost);
         self.capacity.saturating_sub(self.accumulated_cost)
     }
 }
@@ -123,26 +143,33 @@ fn first_err(results: &[Result<()>]) -> Result<()> {
     Ok(())
 }
 
-// Includes transaction signature for unit-testing
+// Includes transaction signature and index for unit-testing. `transaction_indexes` holds each
+// transaction's index in the slot, which is what's reported rather than its position in the
+// batch, since replay can shuffle the transactions within an entry.
 fn get_first_error(
     batch: &TransactionBatch,
+    transaction_indexes: &[usize],
     fee_collection_results: Vec<Result<()>>,
-) -> Option<(Result<()>, Signature)> {
+) -> Option<(Result<()>, Signature, usize)> {
     let mut first_err = None;
-    for (result, transaction) in fee_collection_results
+    let slot = batch.bank().slot();
+    for ((result, transaction), index) in fee_collection_results
         .iter()
         .zip(batch.sanitized_transactions())
+        .zip(transaction_indexes)
     {
         if let Err(ref err) = result {
             if first_err.is_none() {
-                first_err = Some((result.clone(), *transaction.signature()));
+                first_err = Some((result.clone(), *transaction.signature(), *index));
             }
             warn!(
-                "Unexpected validator error: {:?}, transaction: {:?}",
-                err, transaction
+                "Unexpected validator error in slot {}: {:?}, transaction {}: {:?}",
+                slot, err, index, transaction
             );
             datapoint_error!(
                 "validator_process_entry_error",
+                ("slot", slot, i64),
+                ("index", *index, i64),
                 (
                     "error",
                     format!("error: {:?}, transaction: {:?}", err, transaction),
@@ -208,11 +235,23 @@ fn execute_batch(
         .feature_set
         .is_active(&feature_set::gate_large_block::id())
     {
-        let execution_cost_units = aggregate_total_execution_units(timings) - pre_process_units;
+        // the aggregate is an average per program, so it can go down when timings that already
+        // hold executions are passed in. wrapping around would fail the block for exceeding the
+        // block cost limit.
+        let post_process_units = aggregate_total_execution_units(timings);
+        if post_process_units < pre_process_units {
+            datapoint_error!(
+                "validator_process_entry_error-execution_units_underflow",
+                ("slot", bank.slot(), i64),
+                ("pre_process_units", pre_process_units, i64),
+                ("post_process_units", post_process_units, i64),
+            );
+        }
+        let execution_cost_units = post_process_units.saturating_sub(pre_process_units);
         let remaining_block_cost_cap = cost_capacity_meter
             .write()
             .unwrap()
-            .accumulate(execution_cost_units + tx_cost);
+            .accumulate(bank.slot(), execution_cost_units.saturating_add(tx_cost));
 
         debug!(
             "bank {} executed a batch, number of transactions {}, total execute cu {}, total additional cu {}, remaining block cost cap {}",
@@ -263,8 +302,8 @@ fn execute_batch(
         );
     }
 
-    let first_err = get_first_error(batch, fee_collection_results);
-    first_err.map(|(result, _)| result).unwrap_or(Ok(()))
+    let first_err = get_first_error(batch, transaction_indexes, fee_collection_results);
+    first_err.map(|(result, _, _)| result).unwrap_or(Ok(()))
 }
 
 #[derive(Default)]
@@ -1881,7 +1920,7 @@ pub mod tests {
             pubkey::Pubkey,
             signature::{Keypair, Signer},
             system_instruction::SystemError,
-            system_transaction,
+            system_program, system_transaction,
             transaction::{Transaction, TransactionError},
         },
         solana_vote_program::{
@@ -3793,9 +3832,107 @@ pub mod tests {
             &mut ExecuteTimings::default(),
             None,
         );
-        let (err, signature) = get_first_error(&batch, fee_collection_results).unwrap();
+        let (err, signature, index) =
+            get_first_error(&batch, &[0, 1], fee_collection_results).unwrap();
         assert_eq!(err.unwrap_err(), TransactionError::AccountNotFound);
         assert_eq!(signature, account_not_found_sig);
+        assert_eq!(index, 0);
+    }
+
+    #[test]
+    fn test_get_first_error_reports_index_in_slot() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000_000);
+        let bank = Arc::new(Bank::new_for_tests(&genesis_config));
+
+        let ok_tx = system_transaction::transfer(
+            &mint_keypair,
+            &solana_sdk::pubkey::new_rand(),
+            42,
+            bank.last_blockhash(),
+        );
+        let invalid_blockhash_tx = system_transaction::transfer(
+            &Keypair::new(),
+            &solana_sdk::pubkey::new_rand(),
+            42,
+            Hash::default(),
+        );
+        let invalid_blockhash_sig = invalid_blockhash_tx.signatures[0];
+        let batch = bank.prepare_batch_for_tests(vec![ok_tx, invalid_blockhash_tx]);
+        let (
+            TransactionResults {
+                fee_collection_results,
+                ..
+            },
+            _balances,
+        ) = batch.bank().load_execute_and_commit_transactions(
+            &batch,
+            MAX_PROCESSING_AGE,
+            false,
+            false,
+            false,
+            false,
+            &mut ExecuteTimings::default(),
+            None,
+        );
+
+        // the failing transaction is second in the batch but was shuffled there from index 3
+        let (err, signature, index) =
+            get_first_error(&batch, &[7, 3], fee_collection_results).unwrap();
+        assert_eq!(err.unwrap_err(), TransactionError::BlockhashNotFound);
+        assert_eq!(signature, invalid_blockhash_sig);
+        assert_eq!(index, 3);
+    }
+
+    #[test]
+    fn test_execute_batch_execution_units_decrease() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1_000_000_000);
+        let bank = Arc::new(Bank::new_for_tests(&genesis_config));
+        assert!(bank
+            .feature_set
+            .is_active(&feature_set::gate_large_block::id()));
+
+        // timings that already hold a much more expensive run of the system program, so its
+        // average goes down once the transfer is executed
+        let mut timings = ExecuteTimings::default();
+        timings
+            .details
+            .accumulate_program(&system_program::id(), 0, 1_000_000, false);
+
+        let tx = system_transaction::transfer(
+            &mint_keypair,
+            &solana_sdk::pubkey::new_rand(),
+            42,
+            bank.last_blockhash(),
+        );
+        let batch = TransactionBatchWithIndexes {
+            batch: bank.prepare_batch_for_tests(vec![tx]),
+            transaction_indexes: vec![0],
+        };
+        let cost_capacity_meter = Arc::new(RwLock::new(BlockCostCapacityMeter::default()));
+        assert_eq!(
+            execute_batch(
+                &batch,
+                &bank,
+                None,
+                None,
+                &mut timings,
+                cost_capacity_meter.clone(),
+                0,
+                None,
+            ),
+            Ok(())
+        );
+        let cost_capacity_meter = cost_capacity_meter.read().unwrap();
+        assert_eq!(cost_capacity_meter.slot, Some(bank.slot()));
+        assert_eq!(cost_capacity_meter.accumulated_cost, 0);
     }
 
     #[test]
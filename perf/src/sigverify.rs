@@ -340,6 +340,14 @@ pub fn check_for_tracer_packet(packet: &mut Packet) -> bool {
     }
 }
 
+/// Sets the simple vote flag on a packet that hasn't been through sigverify yet.
+pub fn check_for_simple_vote_packet(packet: &mut Packet) -> bool {
+    if let Ok(packet_offsets) = do_get_packet_offsets(packet, 0) {
+        check_for_simple_vote_transaction(packet, &packet_offsets, 0).ok();
+    }
+    packet.meta.is_simple_vote_tx()
+}
+
 fn get_packet_offsets(
     packet: &mut Packet,
     current_offset: usize,
@@ -8,6 +8,7 @@ use {
         fee_calculator::{FeeCalculator, FeeRateGovernor},
         hash::Hash,
         inflation::Inflation,
+        pubkey::Pubkey,
         transaction::{Result, TransactionError},
     },
     solana_transaction_status::{
@@ -575,3 +576,12 @@ pub struct RpcPrioritizationFee {
     pub slot: Slot,
     pub prioritization_fee: u64,
 }
+
+/// The accounts bundles pay tips into, for searchers building the tip payment transactions in
+/// their bundles.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TipAccounts {
+    pub tip_payment_program_id: Pubkey,
+    /// Derived from the tip payment program's seeds, in seed order.
+    pub tip_accounts: Vec<Pubkey>,
+}
@@ -157,6 +157,8 @@ pub struct JsonRpcConfig {
     pub obsolete_v1_7_api: bool,
     pub rpc_scan_and_fix_roots: bool,
     pub max_request_body_size: Option<usize>,
+    /// The accounts bundles pay tips into, set by the validator once they're derived.
+    pub tip_accounts: Option<Arc<TipAccounts>>,
 }
 
 impl JsonRpcConfig {
@@ -638,6 +640,11 @@ impl JsonRpcRequestProcessor {
         *bank.epoch_schedule()
     }
 
+    /// Returns the accounts bundles pay tips into, if the validator provided them.
+    pub fn get_tip_accounts(&self) -> Option<Arc<TipAccounts>> {
+        self.config.tip_accounts.clone()
+    }
+
     pub fn get_balance(
         &self,
         pubkey: &Pubkey,
@@ -1,8 +1,12 @@
 #![cfg(feature = "full")]
 
 use {
-    anchor_lang::error::Error, serde::Deserialize, solana_program::pubkey::Pubkey,
-    solana_sdk::transaction::TransactionError, std::time::Duration, thiserror::Error,
+    anchor_lang::error::Error,
+    serde::Deserialize,
+    solana_program::pubkey::Pubkey,
+    solana_sdk::{clock::Slot, signature::Signature, transaction::TransactionError},
+    std::time::Duration,
+    thiserror::Error,
 };
 
 #[derive(Error, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -16,6 +20,9 @@ pub enum BundleExecutionError {
     #[error("The bundle exceeds the cost model")]
     ExceedsCostModel,
 
+    #[error("The bundle doesn't fit in the rest of the block")]
+    ExceedsBlockSpace,
+
     #[error("Tip error {0}")]
     TipError(#[from] TipPaymentError),
 
@@ -27,6 +34,15 @@ pub enum BundleExecutionError {
 
     #[error("Error locking bundle because the transaction is malformed")]
     LockError,
+
+    #[error("The bundle expired at slot {0}")]
+    Expired(Slot),
+
+    #[error("Transaction {0} in the bundle was already processed")]
+    DuplicateTransaction(Signature),
+
+    #[error("Executing the bundle took longer than the allowed time {0:?}")]
+    ExecutionTimeout(Duration),
 }
 
 #[derive(Error, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -39,6 +55,18 @@ pub enum TipPaymentError {
 
     #[error("Anchor error: {0}")]
     AnchorError(String),
+
+    #[error("bundle paid {paid} lamports in tips, {required} required")]
+    InsufficientTip { paid: u64, required: u64 },
+
+    #[error("block builder fee account is missing from bank: {0}")]
+    BlockBuilderMissing(Pubkey),
+
+    #[error("block builder commission {0}% is over 100%")]
+    InvalidBlockBuilderCommission(u64),
+
+    #[error("bundle took lamports from or wrote to tip account {0} other than to pay a tip")]
+    TipAccountViolation(Pubkey),
 }
 
 impl From<anchor_lang::error::Error> for TipPaymentError {
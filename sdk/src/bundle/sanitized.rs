@@ -1,8 +1,10 @@
 #![cfg(feature = "full")]
 
-use solana_sdk::transaction::SanitizedTransaction;
+use solana_sdk::{clock::Slot, transaction::SanitizedTransaction};
 
 #[derive(Clone, Debug)]
 pub struct SanitizedBundle {
     pub transactions: Vec<SanitizedTransaction>,
+    /// Last slot the bundle may be executed in.
+    pub expiry_slot: Slot,
 }
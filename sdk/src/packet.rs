@@ -25,6 +25,8 @@ bitflags! {
         const REPAIR         = 0b0000_0100;
         const SIMPLE_VOTE_TX = 0b0000_1000;
         const TRACER_PACKET  = 0b0001_0000;
+        /// Received from the relayer instead of the validator's own TPU ports.
+        const FROM_RELAYER   = 0b0010_0000;
     }
 }
 
@@ -174,6 +176,11 @@ impl Meta {
         self.flags.set(PacketFlags::SIMPLE_VOTE_TX, is_simple_vote);
     }
 
+    #[inline]
+    pub fn set_from_relayer(&mut self, from_relayer: bool) {
+        self.flags.set(PacketFlags::FROM_RELAYER, from_relayer);
+    }
+
     #[inline]
     pub fn forwarded(&self) -> bool {
         self.flags.contains(PacketFlags::FORWARDED)
@@ -193,6 +200,11 @@ impl Meta {
     pub fn is_tracer_packet(&self) -> bool {
         self.flags.contains(PacketFlags::TRACER_PACKET)
     }
+
+    #[inline]
+    pub fn is_from_relayer(&self) -> bool {
+        self.flags.contains(PacketFlags::FROM_RELAYER)
+    }
 }
 
 impl Default for Meta {
@@ -1,10 +1,13 @@
 #![allow(clippy::integer_arithmetic)]
 
 use {
+    crossbeam_channel::Sender,
     log::*,
     solana_cli_output::CliAccount,
-    solana_client::rpc_request::MAX_MULTIPLE_ACCOUNTS,
+    solana_client::{rpc_request::MAX_MULTIPLE_ACCOUNTS, rpc_response::TipAccounts},
     solana_core::{
+        bundle_filter::BundleFilter,
+        proxy::{MevConfigUpdate, MevStatus},
         tower_storage::TowerStorage,
         validator::{Validator, ValidatorConfig, ValidatorStartProgress},
     },
@@ -56,7 +59,7 @@ use {
         net::{IpAddr, Ipv4Addr, SocketAddr},
         path::{Path, PathBuf},
         str::FromStr,
-        sync::{Arc, RwLock},
+        sync::{atomic::AtomicBool, Arc, RwLock},
         time::Duration,
     },
     tokio::time::sleep,
@@ -977,6 +980,26 @@ impl TestValidator {
     pub fn bank_forks(&self) -> Arc<RwLock<BankForks>> {
         self.validator.as_ref().unwrap().bank_forks.clone()
     }
+
+    pub fn mev_config_sender(&self) -> Sender<MevConfigUpdate> {
+        self.validator.as_ref().unwrap().mev_config_sender()
+    }
+
+    pub fn bundle_filter_sender(&self) -> Sender<BundleFilter> {
+        self.validator.as_ref().unwrap().bundle_filter_sender()
+    }
+
+    pub fn bundles_enabled(&self) -> Arc<AtomicBool> {
+        self.validator.as_ref().unwrap().bundles_enabled()
+    }
+
+    pub fn tip_accounts(&self) -> Arc<TipAccounts> {
+        self.validator.as_ref().unwrap().tip_accounts()
+    }
+
+    pub fn mev_status(&self) -> Arc<RwLock<MevStatus>> {
+        self.validator.as_ref().unwrap().mev_status()
+    }
 }
 
 impl Drop for TestValidator {
@@ -1,4 +1,6 @@
 use {
+    chrono::{DateTime, Utc},
+    crossbeam_channel::Sender,
     jsonrpc_core::{MetaIoHandler, Metadata, Result},
     jsonrpc_core_client::{transports::ipc, RpcError},
     jsonrpc_derive::rpc,
@@ -7,7 +9,14 @@ use {
     log::*,
     serde::{de::Deserializer, Deserialize, Serialize},
     solana_core::{
-        consensus::Tower, tower_storage::TowerStorage, validator::ValidatorStartProgress,
+        bundle_filter::BundleFilter,
+        consensus::Tower,
+        proxy::{
+            relayer_stage::{RelayerAddressUpdate, RelayerConnectionConfig},
+            MevConfigUpdate, MevStatus,
+        },
+        tower_storage::TowerStorage,
+        validator::ValidatorStartProgress,
     },
     solana_gossip::{cluster_info::ClusterInfo, contact_info::ContactInfo},
     solana_runtime::bank_forks::BankForks,
@@ -22,7 +31,11 @@ use {
         fmt::{self, Display},
         net::SocketAddr,
         path::{Path, PathBuf},
-        sync::{Arc, RwLock},
+        str::FromStr,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, RwLock,
+        },
         thread::{self, Builder},
         time::{Duration, SystemTime},
     },
@@ -33,6 +46,10 @@ pub struct AdminRpcRequestMetadataPostInit {
     pub cluster_info: Arc<ClusterInfo>,
     pub bank_forks: Arc<RwLock<BankForks>>,
     pub vote_account: Pubkey,
+    pub mev_config_sender: Sender<MevConfigUpdate>,
+    pub bundle_filter_sender: Sender<BundleFilter>,
+    pub bundles_enabled: Arc<AtomicBool>,
+    pub mev_status: Arc<RwLock<MevStatus>>,
 }
 
 #[derive(Clone)]
@@ -133,6 +150,169 @@ impl Display for AdminRpcContactInfo {
     }
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AdminRpcMevStatus {
+    pub relayer_connected: bool,
+    pub relayer_last_heartbeat_age_ms: Option<u64>,
+    pub relayer_endpoint: Option<String>,
+    pub relayer_packets_forwarded_last_minute: u64,
+    pub relayer_degraded: bool,
+    pub relayer_recent_disconnects: Vec<AdminRpcRelayerDisconnect>,
+    pub relayer_circuit_breaker_open: bool,
+    pub relayer_circuit_breaker_trips: u64,
+    pub block_engine_connected: bool,
+    pub block_engine_packet_stream_connected: bool,
+    pub block_engine_packet_stream_failures: usize,
+    pub block_engine_bundle_stream_connected: bool,
+    pub block_engine_bundle_stream_failures: usize,
+    pub block_engine_endpoint: Option<String>,
+    pub block_engine_bundles_received_last_minute: u64,
+    pub tip_program_error: Option<String>,
+    pub bundles_enabled: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AdminRpcRelayerDisconnect {
+    pub timestamp: SystemTime,
+    pub endpoint: String,
+    pub reason: String,
+    pub uptime_ms: u64,
+}
+
+impl AdminRpcMevStatus {
+    fn new(mev_status: MevStatus, bundles_enabled: bool) -> Self {
+        let MevStatus {
+            relayer,
+            block_engine,
+            tip_program_error,
+        } = mev_status;
+        Self {
+            relayer_connected: relayer.connected,
+            relayer_last_heartbeat_age_ms: relayer.last_heartbeat_age_ms,
+            relayer_endpoint: relayer.active_endpoint,
+            relayer_packets_forwarded_last_minute: relayer.packets_forwarded_last_minute,
+            relayer_degraded: relayer.degraded,
+            relayer_recent_disconnects: relayer
+                .recent_disconnects
+                .iter()
+                .map(|event| AdminRpcRelayerDisconnect {
+                    timestamp: event.timestamp,
+                    endpoint: event.endpoint.clone(),
+                    reason: event.reason.to_string(),
+                    uptime_ms: event.uptime.as_millis() as u64,
+                })
+                .collect(),
+            relayer_circuit_breaker_open: relayer.circuit_breaker_open,
+            relayer_circuit_breaker_trips: relayer.num_circuit_breaker_trips,
+            block_engine_connected: block_engine.connected(),
+            block_engine_packet_stream_connected: block_engine.packet_stream.connected,
+            block_engine_packet_stream_failures: block_engine.packet_stream.consecutive_failures,
+            block_engine_bundle_stream_connected: block_engine.bundle_stream.connected,
+            block_engine_bundle_stream_failures: block_engine.bundle_stream.consecutive_failures,
+            block_engine_endpoint: block_engine.active_endpoint,
+            block_engine_bundles_received_last_minute: block_engine.bundles_received_last_minute,
+            tip_program_error: tip_program_error.map(|e| e.to_string()),
+            bundles_enabled,
+        }
+    }
+}
+
+impl Display for AdminRpcMevStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Relayer Connected: {}", self.relayer_connected)?;
+        if self.relayer_degraded {
+            writeln!(
+                f,
+                "Relayer Degraded: retry budget exhausted, processing packets from local TPU"
+            )?;
+        }
+        if self.relayer_circuit_breaker_open {
+            writeln!(
+                f,
+                "Relayer Circuit Breaker: open, too many malformed packets"
+            )?;
+        }
+        if let Some(relayer_endpoint) = &self.relayer_endpoint {
+            writeln!(f, "Relayer Endpoint: {}", relayer_endpoint)?;
+        }
+        if let Some(age_ms) = self.relayer_last_heartbeat_age_ms {
+            writeln!(f, "Relayer Last Heartbeat: {}ms ago", age_ms)?;
+        }
+        writeln!(
+            f,
+            "Relayer Packets Forwarded Last Minute: {}",
+            self.relayer_packets_forwarded_last_minute
+        )?;
+        writeln!(
+            f,
+            "Relayer Circuit Breaker Trips: {}",
+            self.relayer_circuit_breaker_trips
+        )?;
+        if !self.relayer_recent_disconnects.is_empty() {
+            writeln!(f, "Relayer Recent Disconnects:")?;
+            for disconnect in &self.relayer_recent_disconnects {
+                writeln!(
+                    f,
+                    "  {} {} {} after {}ms",
+                    DateTime::<Utc>::from(disconnect.timestamp).to_rfc3339(),
+                    disconnect.endpoint,
+                    disconnect.reason,
+                    disconnect.uptime_ms
+                )?;
+            }
+        }
+        writeln!(f, "Block Engine Connected: {}", self.block_engine_connected)?;
+        writeln!(
+            f,
+            "Block Engine Packet Stream: {}",
+            stream_health(
+                self.block_engine_packet_stream_connected,
+                self.block_engine_packet_stream_failures
+            )
+        )?;
+        writeln!(
+            f,
+            "Block Engine Bundle Stream: {}",
+            stream_health(
+                self.block_engine_bundle_stream_connected,
+                self.block_engine_bundle_stream_failures
+            )
+        )?;
+        if let Some(block_engine_endpoint) = &self.block_engine_endpoint {
+            writeln!(f, "Block Engine Endpoint: {}", block_engine_endpoint)?;
+        }
+        writeln!(
+            f,
+            "Block Engine Bundles Received Last Minute: {}",
+            self.block_engine_bundles_received_last_minute
+        )?;
+        match &self.tip_program_error {
+            Some(tip_program_error) => writeln!(f, "Tip Program: {}", tip_program_error),
+            None => writeln!(f, "Tip Program: ok")?,
+        }
+        writeln!(
+            f,
+            "Bundle Execution: {}",
+            if self.bundles_enabled {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        )
+    }
+}
+
+fn stream_health(connected: bool, consecutive_failures: usize) -> String {
+    if connected {
+        "connected".to_string()
+    } else {
+        format!(
+            "disconnected ({} consecutive failures)",
+            consecutive_failures
+        )
+    }
+}
+
 #[rpc]
 pub trait AdminRpc {
     type Metadata;
@@ -183,6 +363,29 @@ pub trait AdminRpc {
 
     #[rpc(meta, name = "contactInfo")]
     fn contact_info(&self, meta: Self::Metadata) -> Result<AdminRpcContactInfo>;
+
+    #[rpc(meta, name = "setRelayerAddress")]
+    fn set_relayer_address(
+        &self,
+        meta: Self::Metadata,
+        address: String,
+        auth_service_address: Option<String>,
+    ) -> Result<()>;
+
+    #[rpc(meta, name = "setBundleFilter")]
+    fn set_bundle_filter(
+        &self,
+        meta: Self::Metadata,
+        denied_accounts: Vec<String>,
+        denied_programs: Vec<String>,
+        min_tip_lamports: u64,
+    ) -> Result<()>;
+
+    #[rpc(meta, name = "setBundlesEnabled")]
+    fn set_bundles_enabled(&self, meta: Self::Metadata, enabled: bool) -> Result<()>;
+
+    #[rpc(meta, name = "mevStatus")]
+    fn mev_status(&self, meta: Self::Metadata) -> Result<AdminRpcMevStatus>;
 }
 
 pub struct AdminRpcImpl;
@@ -324,6 +527,101 @@ impl AdminRpc for AdminRpcImpl {
     fn contact_info(&self, meta: Self::Metadata) -> Result<AdminRpcContactInfo> {
         meta.with_post_init(|post_init| Ok(post_init.cluster_info.my_contact_info().into()))
     }
+
+    fn set_relayer_address(
+        &self,
+        meta: Self::Metadata,
+        address: String,
+        auth_service_address: Option<String>,
+    ) -> Result<()> {
+        debug!("set_relayer_address request received");
+
+        let backend_address = RelayerConnectionConfig::parse(&address)
+            .map_err(|err| jsonrpc_core::error::Error::invalid_params(err.to_string()))?;
+        let auth_service_endpoint = auth_service_address
+            .as_deref()
+            .map(RelayerConnectionConfig::parse_auth_service)
+            .transpose()
+            .map_err(|err| jsonrpc_core::error::Error::invalid_params(err.to_string()))?;
+        let relayer_address = RelayerAddressUpdate {
+            backend_address,
+            auth_service_endpoint,
+        };
+
+        meta.with_post_init(|post_init| {
+            post_init
+                .mev_config_sender
+                .send(MevConfigUpdate::RelayerAddress(relayer_address))
+                .map_err(|_| {
+                    jsonrpc_core::error::Error::invalid_params(
+                        "Relayer is not configured on this validator",
+                    )
+                })?;
+            info!("Relayer address updated to {}", address);
+            Ok(())
+        })
+    }
+
+    fn set_bundle_filter(
+        &self,
+        meta: Self::Metadata,
+        denied_accounts: Vec<String>,
+        denied_programs: Vec<String>,
+        min_tip_lamports: u64,
+    ) -> Result<()> {
+        debug!("set_bundle_filter request received");
+
+        let parse_pubkeys = |pubkeys: Vec<String>| {
+            pubkeys
+                .iter()
+                .map(|pubkey| {
+                    Pubkey::from_str(pubkey).map_err(|err| {
+                        jsonrpc_core::error::Error::invalid_params(format!(
+                            "invalid pubkey {}: {}",
+                            pubkey, err
+                        ))
+                    })
+                })
+                .collect::<Result<_>>()
+        };
+        let bundle_filter = BundleFilter {
+            denied_accounts: parse_pubkeys(denied_accounts)?,
+            denied_programs: parse_pubkeys(denied_programs)?,
+            min_tip_lamports,
+        };
+
+        meta.with_post_init(|post_init| {
+            post_init
+                .bundle_filter_sender
+                .send(bundle_filter)
+                .map_err(|_| jsonrpc_core::error::Error::internal_error())?;
+            info!("Bundle filter updated");
+            Ok(())
+        })
+    }
+
+    fn set_bundles_enabled(&self, meta: Self::Metadata, enabled: bool) -> Result<()> {
+        debug!("set_bundles_enabled request received");
+
+        meta.with_post_init(|post_init| {
+            post_init.bundles_enabled.store(enabled, Ordering::Relaxed);
+            if enabled {
+                info!("Bundle execution enabled");
+            } else {
+                warn!("Bundle execution disabled");
+            }
+            Ok(())
+        })
+    }
+
+    fn mev_status(&self, meta: Self::Metadata) -> Result<AdminRpcMevStatus> {
+        meta.with_post_init(|post_init| {
+            Ok(AdminRpcMevStatus::new(
+                post_init.mev_status.read().unwrap().clone(),
+                post_init.bundles_enabled.load(Ordering::Relaxed),
+            ))
+        })
+    }
 }
 
 impl AdminRpcImpl {
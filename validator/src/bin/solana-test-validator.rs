@@ -858,6 +858,10 @@ fn main() {
                     bank_forks: test_validator.bank_forks(),
                     cluster_info: test_validator.cluster_info(),
                     vote_account: test_validator.vote_account_address(),
+                    mev_config_sender: test_validator.mev_config_sender(),
+                    bundle_filter_sender: test_validator.bundle_filter_sender(),
+                    bundles_enabled: test_validator.bundles_enabled(),
+                    mev_status: test_validator.mev_status(),
                 });
             if let Some(dashboard) = dashboard {
                 dashboard.run(Duration::from_millis(250));
@@ -20,10 +20,38 @@ use {
         keypair::SKIP_SEED_PHRASE_VALIDATION_ARG,
     },
     solana_core::{
+        bundle_filter::{BundleFilter, ProtectedAccountsConfig},
+        bundle_queue::DEFAULT_BUNDLE_QUEUE_CAPACITY,
+        bundle_sanitizer::{BundleLimits, MAX_BUNDLE_SIZE_BYTES, MAX_BUNDLE_TRANSACTIONS},
+        bundle_stage::{
+            DEFAULT_BUNDLE_EXECUTION_THREADS, DEFAULT_BUNDLE_LEADER_LOOKAHEAD_SLOTS,
+            DEFAULT_MAX_BUNDLE_BLOCK_CU_RATIO, DEFAULT_MAX_BUNDLE_CONTENTION_RETRIES,
+            DEFAULT_MAX_BUNDLE_EXECUTION_DURATION,
+        },
         ledger_cleanup_service::{DEFAULT_MAX_LEDGER_SHREDS, DEFAULT_MIN_MAX_LEDGER_SHREDS},
-        proxy::{block_engine_stage::BlockEngineConfig, relayer_stage::RelayerConfig},
+        proxy::{
+            block_engine_stage::{
+                BlockBuilderFeeInfo, BlockEngineConfig, DEFAULT_BUNDLE_TTL_SLOTS,
+            },
+            circuit_breaker::RelayerCircuitBreakerConfig,
+            fetch_stage_manager::HeartbeatLossPolicy,
+            http_proxy::HttpProxyConfig,
+            rate_limiter::RelayerPacketRateLimits,
+            relayer_health::DEFAULT_HEARTBEATS_TO_RECOVER,
+            relayer_resolver::SystemResolver,
+            relayer_stage::{
+                RelayerConfig, RelayerConnectionConfig, DEFAULT_BACKOFF_RESET_GRACE_PERIOD,
+                DEFAULT_PACKET_DEDUP_CAPACITY,
+            },
+            retry_budget::{
+                DEFAULT_DEGRADED_RETRY_INTERVAL, DEFAULT_MAX_CONSECUTIVE_CONNECT_FAILURES,
+            },
+            DEFAULT_BUNDLE_CHANNEL_CAPACITY, DEFAULT_PACKET_CHANNEL_CAPACITY,
+        },
         system_monitor_service::SystemMonitorService,
-        tip_manager::{TipDistributionAccountConfig, TipManagerConfig},
+        tip_manager::{
+            BankingStageTipAccountPolicy, TipDistributionAccountConfig, TipManagerConfig,
+        },
         tower_storage,
         tpu::DEFAULT_TPU_COALESCE_MS,
         validator::{is_snapshot_config_valid, Validator, ValidatorConfig, ValidatorStartProgress},
@@ -1786,7 +1814,7 @@ pub fn main() {
                 .long("relayer-address")
                 .value_name("relayer_address")
                 .takes_value(true)
-                .help("Address of the relayer")
+                .help("Address of the relayer. unix:///path/to/socket connects to a relayer on the same host over a Unix domain socket. An empty address disables the relayer and packets received on this validator's TPU ports are processed as usual")
         )
         .arg(
             Arg::with_name("block_engine_address")
@@ -1827,12 +1855,352 @@ pub fn main() {
                 .takes_value(true)
                 .help("Maximum number of heartbeats the Relayer can miss before falling back to the normal TPU pipeline.")
         )
+        .arg(
+            Arg::with_name("relayer_heartbeat_loss_policy")
+                .long("relayer-heartbeat-loss-policy")
+                .takes_value(true)
+                .possible_values(&["fail-open", "fail-closed"])
+                .help("What to do with packets arriving on this validator's TPU ports while Relayer heartbeats are missing. \
+                       fail-open processes them, fail-closed drops them so the Relayer is the only packet source. [default: fail-open]")
+        )
+        .arg(
+            Arg::with_name("relayer_packet_channel_capacity")
+                .long("relayer-packet-channel-capacity")
+                .value_name("NUM_BATCHES")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Maximum number of Relayer packet batches queued for processing. The oldest batches are dropped once full.")
+        )
+        .arg(
+            Arg::with_name("relayer_packet_dedup_capacity")
+                .long("relayer-packet-dedup-capacity")
+                .value_name("NUM_SIGNATURES")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Number of recently seen Relayer packet signatures remembered to drop duplicate packets.")
+        )
+        .arg(
+            Arg::with_name("relayer_packets_per_second")
+                .long("relayer-packets-per-second")
+                .value_name("PACKETS_PER_SECOND")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Sustained rate of non-vote packets accepted from the Relayer. Excess packets are dropped.")
+        )
+        .arg(
+            Arg::with_name("relayer_packet_burst")
+                .long("relayer-packet-burst")
+                .value_name("NUM_PACKETS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Maximum number of non-vote packets accepted from the Relayer in a burst.")
+        )
+        .arg(
+            Arg::with_name("relayer_vote_packets_per_second")
+                .long("relayer-vote-packets-per-second")
+                .value_name("PACKETS_PER_SECOND")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Sustained rate of simple vote packets accepted from the Relayer. Excess packets are dropped.")
+        )
+        .arg(
+            Arg::with_name("relayer_vote_packet_burst")
+                .long("relayer-vote-packet-burst")
+                .value_name("NUM_PACKETS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Maximum number of simple vote packets accepted from the Relayer in a burst.")
+        )
+        .arg(
+            Arg::with_name("relayer_heartbeats_to_recover")
+                .long("relayer-heartbeats-to-recover")
+                .value_name("NUM_HEARTBEATS")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Number of consecutive heartbeats the Relayer must send before its packets are accepted again after missing heartbeats.")
+        )
+        .arg(
+            Arg::with_name("no_relayer_vote_bypass")
+                .long("no-relayer-vote-bypass")
+                .takes_value(false)
+                .help("Drop simple vote transactions received on this validator's TPU ports along with other transactions while the Relayer is handling TPU traffic, instead of processing them locally.")
+        )
+        .arg(
+            Arg::with_name("relayer_max_consecutive_connect_failures")
+                .long("relayer-max-consecutive-connect-failures")
+                .value_name("NUM_FAILURES")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Number of consecutive failed Relayer connection attempts before the validator falls back to its own TPU ports and only retries the Relayer every --relayer-degraded-retry-interval-secs. [default: 50]")
+        )
+        .arg(
+            Arg::with_name("relayer_degraded_retry_interval_secs")
+                .long("relayer-degraded-retry-interval-secs")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Time between Relayer connection attempts after --relayer-max-consecutive-connect-failures is reached. [default: 300]")
+        )
+        .arg(
+            Arg::with_name("relayer_keep_alive_interval_ms")
+                .long("relayer-keep-alive-interval-ms")
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Interval between HTTP/2 keepalive pings sent to the Relayer. [default: 5000]")
+        )
+        .arg(
+            Arg::with_name("relayer_keep_alive_timeout_ms")
+                .long("relayer-keep-alive-timeout-ms")
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Time to wait for the Relayer to acknowledge a keepalive ping before the connection is closed. [default: 10000]")
+        )
+        .arg(
+            Arg::with_name("relayer_connect_timeout_ms")
+                .long("relayer-connect-timeout-ms")
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Max time spent connecting to the Relayer before retrying. [default: 10000]")
+        )
+        .arg(
+            Arg::with_name("relayer_disable_tcp_nodelay")
+                .long("relayer-disable-tcp-nodelay")
+                .takes_value(false)
+                .help("Disable TCP_NODELAY on the Relayer connection.")
+        )
+        .arg(
+            Arg::with_name("relayer_unix_socket_auth")
+                .long("relayer-unix-socket-auth")
+                .takes_value(false)
+                .help("Authenticate with the relayer auth service when the relayer is reached over a Unix domain socket. By default the auth challenge is skipped for Unix domain sockets.")
+        )
+        .arg(
+            Arg::with_name("relayer_http_proxy")
+                .long("relayer-http-proxy")
+                .value_name("URL")
+                .takes_value(true)
+                .help("Tunnel the Relayer connection through the HTTP proxy at this http://host:port url with CONNECT requests. The proxy resolves the Relayer hostname.")
+        )
+        .arg(
+            Arg::with_name("relayer_http_proxy_auth")
+                .long("relayer-http-proxy-auth")
+                .value_name("USERNAME:PASSWORD")
+                .takes_value(true)
+                .requires("relayer_http_proxy")
+                .help("Basic auth credentials for the Relayer HTTP proxy.")
+        )
+        .arg(
+            Arg::with_name("relayer_packet_priority_percent")
+                .long("relayer-packet-priority-percent")
+                .value_name("PERCENT")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Order packets from the Relayer in banking stage by this percent of their priority, e.g. 50 to favor the validator's own TPU packets or 200 to favor the Relayer's. By default they're ordered like any other packet.")
+        )
+        .arg(
+            Arg::with_name("require_relayer")
+                .long("require-relayer")
+                .takes_value(false)
+                .help("Refuse to start if the Relayer can't be reached and authenticated with at startup. By default the failure is logged and the Relayer is retried in the background.")
+        )
+        .arg(
+            Arg::with_name("relayer_backoff_reset_grace_period_ms")
+                .long("relayer-backoff-reset-grace-period-ms")
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("How long a Relayer connection has to stay up and receive a heartbeat before the reconnect backoff is reset. [default: 5000]")
+        )
+        .arg(
+            Arg::with_name("relayer_malformed_packet_window")
+                .long("relayer-malformed-packet-window")
+                .value_name("NUM_PACKETS")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Number of most recent Relayer packets the malformed packet share is computed over. [default: 10000]")
+        )
+        .arg(
+            Arg::with_name("relayer_max_malformed_packet_percent")
+                .long("relayer-max-malformed-packet-percent")
+                .value_name("PERCENT")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Share of malformed packets above which the Relayer stream is dropped. [default: 50]")
+        )
+        .arg(
+            Arg::with_name("relayer_malformed_packet_cooldown_secs")
+                .long("relayer-malformed-packet-cooldown-secs")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("How long to wait before reconnecting to a Relayer that sent too many malformed packets. [default: 60]")
+        )
         .arg(
             Arg::with_name("trust_block_engine_packets")
                 .long("trust-block-engine-packets")
                 .takes_value(false)
                 .help("Skip signature verification on block engine packets. Not recommended unless the block engine is trusted.")
         )
+        .arg(
+            Arg::with_name("block_engine_packet_channel_capacity")
+                .long("block-engine-packet-channel-capacity")
+                .value_name("NUM_BATCHES")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Maximum number of block engine packet batches queued for processing. The oldest batches are dropped once full.")
+        )
+        .arg(
+            Arg::with_name("block_engine_bundle_channel_capacity")
+                .long("block-engine-bundle-channel-capacity")
+                .value_name("NUM_MESSAGES")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Maximum number of block engine bundle messages queued for processing. New bundles are dropped once full.")
+        )
+        .arg(
+            Arg::with_name("block_engine_bundle_queue_capacity")
+                .long("block-engine-bundle-queue-capacity")
+                .value_name("NUM_BUNDLES")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Maximum number of bundles held for execution. Once full, expired bundles are dropped first, then the bundles with the lowest tips. [default: 1024]")
+        )
+        .arg(
+            Arg::with_name("block_engine_disable_bundle_simulation")
+                .long("block-engine-disable-bundle-simulation")
+                .takes_value(false)
+                .help("Execute bundles without simulating them first. Saves the simulation time per bundle, but bundles that fail still take account locks and leader time.")
+        )
+        .arg(
+            Arg::with_name("block_engine_report_bundle_outcomes")
+                .long("block-engine-report-bundle-outcomes")
+                .takes_value(false)
+                .help("Report a metrics datapoint for every bundle executed or dropped while leader, including the time it spent queued, locking, executing, recording and committing, on top of the per slot totals.")
+        )
+        .arg(
+            Arg::with_name("block_engine_max_bundle_transactions")
+                .long("block-engine-max-bundle-transactions")
+                .value_name("NUM_TRANSACTIONS")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Maximum number of transactions in a block engine bundle. Bigger bundles are dropped. [default: 5]")
+        )
+        .arg(
+            Arg::with_name("block_engine_max_bundle_size_bytes")
+                .long("block-engine-max-bundle-size-bytes")
+                .value_name("BYTES")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Maximum combined size of the serialized transactions in a block engine bundle. Bigger bundles are dropped. [default: 6160]")
+        )
+        .arg(
+            Arg::with_name("block_engine_leader_lookahead_slots")
+                .long("block-engine-leader-lookahead-slots")
+                .value_name("NUM_SLOTS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Number of slots before its leader slots the validator starts holding on to block engine bundles. Bundles received earlier are dropped. [default: 4]")
+        )
+        .arg(
+            Arg::with_name("block_engine_bundle_ttl_slots")
+                .long("block-engine-bundle-ttl-slots")
+                .value_name("NUM_SLOTS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Number of slots after the current slot a block engine bundle may still be executed in. Bundles still queued after that are dropped.")
+        )
+        .arg(
+            Arg::with_name("block_engine_deny_account")
+                .long("block-engine-deny-account")
+                .validator(is_pubkey)
+                .value_name("ADDRESS")
+                .multiple(true)
+                .takes_value(true)
+                .help("Drop block engine bundles that use this account, including through an address lookup table. May be specified multiple times.")
+        )
+        .arg(
+            Arg::with_name("block_engine_deny_program")
+                .long("block-engine-deny-program")
+                .validator(is_pubkey)
+                .value_name("PROGRAM_ID")
+                .multiple(true)
+                .takes_value(true)
+                .help("Drop block engine bundles that invoke this program. May be specified multiple times.")
+        )
+        .arg(
+            Arg::with_name("block_engine_min_tip_lamports")
+                .long("block-engine-min-tip-lamports")
+                .value_name("LAMPORTS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Drop block engine bundles that transfer fewer lamports than this to the tip accounts, before they're executed. Can be changed at runtime with set-bundle-filter.")
+        )
+        .arg(
+            Arg::with_name("block_engine_bundle_execution_threads")
+                .long("block-engine-bundle-execution-threads")
+                .value_name("NUM_THREADS")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Number of threads executing block engine bundles that don't use any of the same accounts. Bundles that do are still executed one after the other. [default: 2]")
+        )
+        .arg(
+            Arg::with_name("block_engine_max_bundle_execution_ms")
+                .long("block-engine-max-bundle-execution-ms")
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Time a block engine bundle's transactions may spend executing before the bundle is abandoned. It's checked between transactions, so a slow transaction always runs to completion. [default: 30]")
+        )
+        .arg(
+            Arg::with_name("block_engine_max_bundle_contention_retries")
+                .long("block-engine-max-bundle-contention-retries")
+                .value_name("NUM_RETRIES")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Number of times a block engine bundle whose accounts stayed in use by other transactions is queued again while leader. [default: 3]")
+        )
+        .arg(
+            Arg::with_name("block_engine_max_bundle_block_cu_ratio")
+                .long("block-engine-max-bundle-block-cu-ratio")
+                .value_name("RATIO")
+                .takes_value(true)
+                .validator(is_parsable::<f64>)
+                .help("Share of each block's compute units, between 0 and 1, block engine bundles may use. Bundles beyond it are held back until the next leader slot. [default: 1]")
+        )
+        .arg(
+            Arg::with_name("block_engine_protect_account")
+                .long("block-engine-protect-account")
+                .validator(is_pubkey)
+                .value_name("ADDRESS")
+                .multiple(true)
+                .takes_value(true)
+                .help("Drop block engine bundles that write to this account, in addition to the validator's identity and vote account. May be specified multiple times.")
+        )
+        .arg(
+            Arg::with_name("block_engine_protect_delegated_stake_accounts")
+                .long("block-engine-protect-delegated-stake-accounts")
+                .takes_value(false)
+                .help("Drop block engine bundles that write to the stake accounts delegated to the validator's vote account when the validator starts.")
+        )
+        .arg(
+            Arg::with_name("block_engine_default_block_builder")
+                .long("block-engine-default-block-builder")
+                .validator(is_pubkey)
+                .value_name("ADDRESS")
+                .takes_value(true)
+                .help("Block builder fee account used until the block engine sends its own. [default: the validator identity]")
+        )
+        .arg(
+            Arg::with_name("block_engine_default_block_builder_commission")
+                .long("block-engine-default-block-builder-commission")
+                .validator(is_valid_percentage)
+                .value_name("PERCENTAGE")
+                .takes_value(true)
+                .help("Block builder commission used until the block engine sends its own. [default: 0]")
+        )
         .arg(
             Arg::with_name("tip_payment_program_pubkey")
                 .long("tip-payment-program-pubkey")
@@ -1868,6 +2236,28 @@ pub fn main() {
                 .takes_value(true)
                 .help("The commission validator takes from tips expressed in basis points.")
         )
+        .arg(
+            Arg::with_name("min_bundle_tip_lamports")
+                .long("min-bundle-tip-lamports")
+                .value_name("LAMPORTS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .default_value("0")
+                .help("Minimum lamports a bundle has to pay the tip accounts. Bundles paying less, or less than they transfer to the tip accounts, are discarded.")
+        )
+        .arg(
+            Arg::with_name("banking_stage_tip_accounts")
+                .long("banking-stage-tip-accounts")
+                .value_name("POLICY")
+                .takes_value(true)
+                .possible_values(&["deny", "allow"])
+                .default_value("deny")
+                .help("How transactions outside of bundles that use the tip accounts are handled. \
+                       Possible values are: \
+                       'deny': drops them, tips can only be paid by bundles. \
+                       'allow': lets them pay tips, contending with bundles for the tip accounts. \
+                       The tip payment config account can't be used by them either way."),
+        )
         .arg(
             Arg::with_name("shred_receiver_address")
                 .long("shred-receiver-address")
@@ -1971,6 +2361,18 @@ pub fn main() {
                     .help("Output display mode")
             )
         )
+        .subcommand(
+            SubCommand::with_name("mev-status")
+            .about("Display the relayer and block engine connection status")
+            .arg(
+                Arg::with_name("output")
+                    .long("output")
+                    .takes_value(true)
+                    .value_name("MODE")
+                    .possible_values(&["json", "json-compact"])
+                    .help("Output display mode")
+            )
+        )
         .subcommand(
             SubCommand::with_name("init")
             .about("Initialize the ledger directory then exit")
@@ -2029,6 +2431,73 @@ pub fn main() {
             .after_help("Note: the new staked nodes overrides only applies to the \
                          currently running validator instance")
         )
+        .subcommand(
+            SubCommand::with_name("set-relayer-address")
+            .about("Reconnect to a different relayer without restarting the validator")
+            .arg(
+                Arg::with_name("relayer_address")
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .index(1)
+                    .help("Address of the relayer to connect to"),
+            )
+            .arg(
+                Arg::with_name("auth_service_address")
+                    .long("auth-service-address")
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Address of the new relayer's authentication service. \
+                           Keeps the current one if not set"),
+            )
+            .after_help("Note: the new relayer address only applies to the currently running validator instance")
+        )
+        .subcommand(
+            SubCommand::with_name("set-bundle-filter")
+            .about("Replace the accounts and programs block engine bundles may not use, and the smallest tip they may pay")
+            .arg(
+                Arg::with_name("deny_account")
+                    .long("deny-account")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .multiple(true)
+                    .takes_value(true)
+                    .help("Drop bundles that use this account"),
+            )
+            .arg(
+                Arg::with_name("deny_program")
+                    .long("deny-program")
+                    .validator(is_pubkey)
+                    .value_name("PROGRAM_ID")
+                    .multiple(true)
+                    .takes_value(true)
+                    .help("Drop bundles that invoke this program"),
+            )
+            .arg(
+                Arg::with_name("min_tip_lamports")
+                    .long("min-tip-lamports")
+                    .value_name("LAMPORTS")
+                    .takes_value(true)
+                    .validator(is_parsable::<u64>)
+                    .default_value("0")
+                    .help("Drop bundles that transfer fewer lamports than this to the tip accounts"),
+            )
+            .after_help("Note: the new bundle filter replaces the previous one and only applies to the currently running validator instance")
+        )
+        .subcommand(
+            SubCommand::with_name("set-bundles-enabled")
+            .about("Turn block engine bundle execution off or back on, without restarting the validator")
+            .arg(
+                Arg::with_name("enabled")
+                    .value_name("ENABLED")
+                    .takes_value(true)
+                    .required(true)
+                    .index(1)
+                    .possible_values(&["true", "false"])
+                    .help("Whether bundles are executed. While disabled, received bundles are dropped and the tip accounts aren't updated"),
+            )
+            .after_help("Note: only applies to the currently running validator instance, bundles are enabled again on restart")
+        )
         .subcommand(
             SubCommand::with_name("wait-for-restart-window")
             .about("Monitor the validator for a good time to restart")
@@ -2173,6 +2642,26 @@ pub fn main() {
             }
             return;
         }
+        ("mev-status", Some(subcommand_matches)) => {
+            let output_mode = subcommand_matches.value_of("output");
+            let admin_client = admin_rpc_service::connect(&ledger_path);
+            let mev_status = admin_rpc_service::runtime()
+                .block_on(async move { admin_client.await?.mev_status().await })
+                .unwrap_or_else(|err| {
+                    eprintln!("MEV status query failed: {}", err);
+                    exit(1);
+                });
+            if let Some(mode) = output_mode {
+                match mode {
+                    "json" => println!("{}", serde_json::to_string_pretty(&mev_status).unwrap()),
+                    "json-compact" => print!("{}", serde_json::to_string(&mev_status).unwrap()),
+                    _ => unreachable!(),
+                }
+            } else {
+                print!("{}", mev_status);
+            }
+            return;
+        }
         ("init", _) => Operation::Initialize,
         ("exit", Some(subcommand_matches)) => {
             let min_idle_time = value_t_or_exit!(subcommand_matches, "min_idle_time", usize);
@@ -2238,6 +2727,58 @@ pub fn main() {
                 });
             return;
         }
+        ("set-relayer-address", Some(subcommand_matches)) => {
+            let address = value_t_or_exit!(subcommand_matches, "relayer_address", String);
+            let auth_service_address =
+                value_t!(subcommand_matches, "auth_service_address", String).ok();
+
+            let admin_client = admin_rpc_service::connect(&ledger_path);
+            admin_rpc_service::runtime()
+                .block_on(async move {
+                    admin_client
+                        .await?
+                        .set_relayer_address(address, auth_service_address)
+                        .await
+                })
+                .unwrap_or_else(|err| {
+                    println!("setRelayerAddress request failed: {}", err);
+                    exit(1);
+                });
+            return;
+        }
+        ("set-bundle-filter", Some(subcommand_matches)) => {
+            let denied_accounts =
+                values_t!(subcommand_matches, "deny_account", String).unwrap_or_default();
+            let denied_programs =
+                values_t!(subcommand_matches, "deny_program", String).unwrap_or_default();
+            let min_tip_lamports = value_t_or_exit!(subcommand_matches, "min_tip_lamports", u64);
+
+            let admin_client = admin_rpc_service::connect(&ledger_path);
+            admin_rpc_service::runtime()
+                .block_on(async move {
+                    admin_client
+                        .await?
+                        .set_bundle_filter(denied_accounts, denied_programs, min_tip_lamports)
+                        .await
+                })
+                .unwrap_or_else(|err| {
+                    println!("setBundleFilter request failed: {}", err);
+                    exit(1);
+                });
+            return;
+        }
+        ("set-bundles-enabled", Some(subcommand_matches)) => {
+            let enabled = value_t_or_exit!(subcommand_matches, "enabled", bool);
+
+            let admin_client = admin_rpc_service::connect(&ledger_path);
+            admin_rpc_service::runtime()
+                .block_on(async move { admin_client.await?.set_bundles_enabled(enabled).await })
+                .unwrap_or_else(|err| {
+                    println!("setBundlesEnabled request failed: {}", err);
+                    exit(1);
+                });
+            return;
+        }
         ("set-identity", Some(subcommand_matches)) => {
             let require_tower = subcommand_matches.is_present("require_tower");
 
@@ -2681,7 +3222,27 @@ pub fn main() {
 
     let is_block_engine_enabled = matches.is_present("block_engine_address")
         || matches.is_present("block_engine_auth_service_address")
-        || matches.is_present("trust_block_engine_packets");
+        || matches.is_present("trust_block_engine_packets")
+        || matches.is_present("block_engine_packet_channel_capacity")
+        || matches.is_present("block_engine_bundle_channel_capacity")
+        || matches.is_present("block_engine_bundle_queue_capacity")
+        || matches.is_present("block_engine_disable_bundle_simulation")
+        || matches.is_present("block_engine_report_bundle_outcomes")
+        || matches.is_present("block_engine_max_bundle_transactions")
+        || matches.is_present("block_engine_max_bundle_size_bytes")
+        || matches.is_present("block_engine_leader_lookahead_slots")
+        || matches.is_present("block_engine_bundle_ttl_slots")
+        || matches.is_present("block_engine_deny_account")
+        || matches.is_present("block_engine_min_tip_lamports")
+        || matches.is_present("block_engine_deny_program")
+        || matches.is_present("block_engine_bundle_execution_threads")
+        || matches.is_present("block_engine_max_bundle_execution_ms")
+        || matches.is_present("block_engine_max_bundle_contention_retries")
+        || matches.is_present("block_engine_max_bundle_block_cu_ratio")
+        || matches.is_present("block_engine_protect_account")
+        || matches.is_present("block_engine_protect_delegated_stake_accounts")
+        || matches.is_present("block_engine_default_block_builder")
+        || matches.is_present("block_engine_default_block_builder_commission");
     let maybe_block_engine_config = is_block_engine_enabled.then(|| {
         let addr: String = value_of(&matches, "block_engine_auth_service_address")
             .expect("missing block-engine-auth-service-address");
@@ -2708,14 +3269,109 @@ pub fn main() {
             auth_service_endpoint,
             backend_endpoint,
             trust_packets: matches.is_present("trust_block_engine_packets"),
+            packet_channel_capacity: value_of(&matches, "block_engine_packet_channel_capacity")
+                .unwrap_or(DEFAULT_PACKET_CHANNEL_CAPACITY),
+            bundle_channel_capacity: value_of(&matches, "block_engine_bundle_channel_capacity")
+                .unwrap_or(DEFAULT_BUNDLE_CHANNEL_CAPACITY),
+            bundle_queue_capacity: value_of(&matches, "block_engine_bundle_queue_capacity")
+                .unwrap_or(DEFAULT_BUNDLE_QUEUE_CAPACITY),
+            simulate_bundles: !matches.is_present("block_engine_disable_bundle_simulation"),
+            report_bundle_outcomes: matches.is_present("block_engine_report_bundle_outcomes"),
+            bundle_limits: BundleLimits {
+                max_transactions: value_of(&matches, "block_engine_max_bundle_transactions")
+                    .unwrap_or(MAX_BUNDLE_TRANSACTIONS),
+                max_size_bytes: value_of(&matches, "block_engine_max_bundle_size_bytes")
+                    .unwrap_or(MAX_BUNDLE_SIZE_BYTES),
+            },
+            bundle_leader_lookahead_slots: value_of(
+                &matches,
+                "block_engine_leader_lookahead_slots",
+            )
+            .unwrap_or(DEFAULT_BUNDLE_LEADER_LOOKAHEAD_SLOTS),
+            bundle_ttl_slots: value_of(&matches, "block_engine_bundle_ttl_slots")
+                .unwrap_or(DEFAULT_BUNDLE_TTL_SLOTS),
+            bundle_filter: BundleFilter {
+                denied_accounts: values_t!(matches, "block_engine_deny_account", Pubkey)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect(),
+                denied_programs: values_t!(matches, "block_engine_deny_program", Pubkey)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect(),
+                min_tip_lamports: value_of(&matches, "block_engine_min_tip_lamports")
+                    .unwrap_or_default(),
+            },
+            bundle_execution_threads: value_of(&matches, "block_engine_bundle_execution_threads")
+                .unwrap_or(DEFAULT_BUNDLE_EXECUTION_THREADS),
+            max_bundle_execution_duration: value_of(
+                &matches,
+                "block_engine_max_bundle_execution_ms",
+            )
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_MAX_BUNDLE_EXECUTION_DURATION),
+            max_bundle_contention_retries: value_of(
+                &matches,
+                "block_engine_max_bundle_contention_retries",
+            )
+            .unwrap_or(DEFAULT_MAX_BUNDLE_CONTENTION_RETRIES),
+            max_bundle_block_cu_ratio: value_of(&matches, "block_engine_max_bundle_block_cu_ratio")
+                .unwrap_or(DEFAULT_MAX_BUNDLE_BLOCK_CU_RATIO),
+            protected_accounts: ProtectedAccountsConfig {
+                extra_accounts: values_t!(matches, "block_engine_protect_account", Pubkey)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect(),
+                protect_delegated_stake_accounts: matches
+                    .is_present("block_engine_protect_delegated_stake_accounts"),
+            },
+            default_block_builder_fee_info: (matches
+                .is_present("block_engine_default_block_builder")
+                || matches.is_present("block_engine_default_block_builder_commission"))
+            .then(|| BlockBuilderFeeInfo {
+                block_builder: value_of(&matches, "block_engine_default_block_builder")
+                    .unwrap_or_else(|| identity_keypair.pubkey()),
+                block_builder_commission: value_of(
+                    &matches,
+                    "block_engine_default_block_builder_commission",
+                )
+                .unwrap_or_default(),
+            }),
         }
     });
 
-    let is_relayer_enabled = matches.is_present("relayer_auth_service_address")
-        || matches.is_present("relayer_address")
-        || matches.is_present("trust_relayer_packets")
-        || matches.is_present("relayer_expected_heartbeat_interval_ms")
-        || matches.is_present("relayer_max_failed_heartbeats");
+    // an empty relayer address keeps the standard TPU packet path even if other relayer
+    // arguments are set
+    let is_relayer_disabled = matches.value_of("relayer_address") == Some("");
+    let is_relayer_enabled = !is_relayer_disabled
+        && (matches.is_present("relayer_auth_service_address")
+            || matches.is_present("relayer_address")
+            || matches.is_present("trust_relayer_packets")
+            || matches.is_present("relayer_expected_heartbeat_interval_ms")
+            || matches.is_present("relayer_max_failed_heartbeats")
+            || matches.is_present("relayer_heartbeat_loss_policy")
+            || matches.is_present("relayer_packet_channel_capacity")
+            || matches.is_present("relayer_packet_dedup_capacity")
+            || matches.is_present("relayer_packets_per_second")
+            || matches.is_present("relayer_packet_burst")
+            || matches.is_present("relayer_vote_packets_per_second")
+            || matches.is_present("relayer_vote_packet_burst")
+            || matches.is_present("relayer_heartbeats_to_recover")
+            || matches.is_present("no_relayer_vote_bypass")
+            || matches.is_present("relayer_max_consecutive_connect_failures")
+            || matches.is_present("relayer_degraded_retry_interval_secs")
+            || matches.is_present("relayer_keep_alive_interval_ms")
+            || matches.is_present("relayer_keep_alive_timeout_ms")
+            || matches.is_present("relayer_connect_timeout_ms")
+            || matches.is_present("relayer_disable_tcp_nodelay")
+            || matches.is_present("relayer_unix_socket_auth")
+            || matches.is_present("relayer_http_proxy")
+            || matches.is_present("relayer_packet_priority_percent")
+            || matches.is_present("require_relayer")
+            || matches.is_present("relayer_malformed_packet_window")
+            || matches.is_present("relayer_max_malformed_packet_percent")
+            || matches.is_present("relayer_malformed_packet_cooldown_secs")
+            || matches.is_present("relayer_backoff_reset_grace_period_ms"));
     let maybe_relayer_config = is_relayer_enabled.then(|| {
         let addr: String = value_of(&matches, "relayer_auth_service_address")
             .expect("missing relayer-auth-service-address");
@@ -2728,13 +3384,8 @@ pub fn main() {
         }
 
         let addr: String = value_of(&matches, "relayer_address").expect("missing relayer-address");
-        let mut backend_endpoint =
-            Endpoint::from_shared(addr.clone()).expect("invalid relayer-address value");
-        if addr.contains("https") {
-            backend_endpoint = backend_endpoint
-                .tls_config(tonic::transport::ClientTlsConfig::new())
-                .expect("failed to set tls_config");
-        }
+        let backend_address =
+            RelayerConnectionConfig::parse(&addr).expect("invalid relayer-address value");
 
         let expected_heartbeat_interval_ms =
             value_of(&matches, "relayer_expected_heartbeat_interval_ms").unwrap_or(500);
@@ -2749,12 +3400,84 @@ pub fn main() {
         let oldest_allowed_heartbeat =
             Duration::from_millis(max_failed_heartbeats * expected_heartbeat_interval_ms);
 
+        let heartbeat_loss_policy = match matches.value_of("relayer_heartbeat_loss_policy") {
+            Some("fail-closed") => HeartbeatLossPolicy::FailClosed,
+            _ => HeartbeatLossPolicy::FailOpen,
+        };
+
+        let default_rate_limits = RelayerPacketRateLimits::default();
+        let packet_rate_limits = RelayerPacketRateLimits {
+            packets_per_second: value_of(&matches, "relayer_packets_per_second")
+                .unwrap_or(default_rate_limits.packets_per_second),
+            packet_burst: value_of(&matches, "relayer_packet_burst")
+                .unwrap_or(default_rate_limits.packet_burst),
+            vote_packets_per_second: value_of(&matches, "relayer_vote_packets_per_second")
+                .unwrap_or(default_rate_limits.vote_packets_per_second),
+            vote_packet_burst: value_of(&matches, "relayer_vote_packet_burst")
+                .unwrap_or(default_rate_limits.vote_packet_burst),
+        };
+
+        let default_connection_config = RelayerConnectionConfig::default();
+        let connection_config = RelayerConnectionConfig {
+            keep_alive_interval: value_of(&matches, "relayer_keep_alive_interval_ms")
+                .map(Duration::from_millis)
+                .unwrap_or(default_connection_config.keep_alive_interval),
+            keep_alive_timeout: value_of(&matches, "relayer_keep_alive_timeout_ms")
+                .map(Duration::from_millis)
+                .unwrap_or(default_connection_config.keep_alive_timeout),
+            connect_timeout: value_of(&matches, "relayer_connect_timeout_ms")
+                .map(Duration::from_millis)
+                .unwrap_or(default_connection_config.connect_timeout),
+            tcp_nodelay: !matches.is_present("relayer_disable_tcp_nodelay"),
+            unix_socket_auth: matches.is_present("relayer_unix_socket_auth"),
+            http_proxy: matches.value_of("relayer_http_proxy").map(|proxy_url| {
+                HttpProxyConfig::parse(proxy_url, matches.value_of("relayer_http_proxy_auth"))
+                    .expect("invalid relayer-http-proxy value")
+            }),
+        };
+
+        let default_circuit_breaker = RelayerCircuitBreakerConfig::default();
+        let circuit_breaker = RelayerCircuitBreakerConfig {
+            window_size: value_of(&matches, "relayer_malformed_packet_window")
+                .unwrap_or(default_circuit_breaker.window_size),
+            max_malformed_percent: value_of(&matches, "relayer_max_malformed_packet_percent")
+                .unwrap_or(default_circuit_breaker.max_malformed_percent),
+            cooldown: value_of(&matches, "relayer_malformed_packet_cooldown_secs")
+                .map(Duration::from_secs)
+                .unwrap_or(default_circuit_breaker.cooldown),
+        };
+
         RelayerConfig {
             auth_service_endpoint,
-            backend_endpoint,
+            backend_address,
             expected_heartbeat_interval,
             oldest_allowed_heartbeat,
             trust_packets: matches.is_present("trust_relayer_packets"),
+            heartbeat_loss_policy,
+            vote_bypass: !matches.is_present("no_relayer_vote_bypass"),
+            packet_channel_capacity: value_of(&matches, "relayer_packet_channel_capacity")
+                .unwrap_or(DEFAULT_PACKET_CHANNEL_CAPACITY),
+            packet_dedup_capacity: value_of(&matches, "relayer_packet_dedup_capacity")
+                .unwrap_or(DEFAULT_PACKET_DEDUP_CAPACITY),
+            packet_rate_limits,
+            heartbeats_to_recover: value_of(&matches, "relayer_heartbeats_to_recover")
+                .unwrap_or(DEFAULT_HEARTBEATS_TO_RECOVER),
+            connection_config,
+            circuit_breaker,
+            resolver: Arc::new(SystemResolver),
+            max_consecutive_connect_failures: value_of(
+                &matches,
+                "relayer_max_consecutive_connect_failures",
+            )
+            .unwrap_or(DEFAULT_MAX_CONSECUTIVE_CONNECT_FAILURES),
+            degraded_retry_interval: value_of(&matches, "relayer_degraded_retry_interval_secs")
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_DEGRADED_RETRY_INTERVAL),
+            backoff_reset_grace_period: value_of(&matches, "relayer_backoff_reset_grace_period_ms")
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_BACKOFF_RESET_GRACE_PERIOD),
+            require_relayer: matches.is_present("require_relayer"),
+            packet_priority_percent: value_of(&matches, "relayer_packet_priority_percent"),
         }
     });
 
@@ -2799,6 +3522,7 @@ pub fn main() {
                 "rpc_max_request_body_size",
                 usize
             )),
+            tip_accounts: None,
         },
         geyser_plugin_config_files,
         rpc_addrs: value_t!(matches, "rpc_port", u16).ok().map(|rpc_port| {
@@ -3343,6 +4067,10 @@ pub fn main() {
             bank_forks: validator.bank_forks.clone(),
             cluster_info: validator.cluster_info.clone(),
             vote_account,
+            mev_config_sender: validator.mev_config_sender(),
+            bundle_filter_sender: validator.bundle_filter_sender(),
+            bundles_enabled: validator.bundles_enabled(),
+            mev_status: validator.mev_status(),
         });
 
     if let Some(filename) = init_complete_file {
@@ -3579,5 +4307,10 @@ fn tip_manager_config_from_matches(
                 0
             }),
         },
+        min_bundle_tip_lamports: value_t_or_exit!(matches, "min_bundle_tip_lamports", u64),
+        banking_stage_tip_account_policy: match matches.value_of("banking_stage_tip_accounts") {
+            Some("allow") => BankingStageTipAccountPolicy::Allow,
+            _ => BankingStageTipAccountPolicy::Deny,
+        },
     }
 }